@@ -0,0 +1,110 @@
+//! This crate provides the attribute macros for the
+//! [pushd](https://docs.rs/pushd) crate. Don't use it directly. Instead,
+//! enable the `macros` feature of `pushd` and use the macros that it
+//! re-exports.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Expr, Ident, ItemFn};
+
+/// Runs the annotated function with the current directory changed to the
+/// given path.
+///
+/// The argument can be any expression that can be passed to `Pushd::new`.
+/// The function body is wrapped so that a `Pushd` guard is created before the
+/// body runs and dropped after it finishes, even if it returns early. If the
+/// directory cannot be changed, the function panics.
+#[proc_macro_attribute]
+pub fn in_dir(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(attr as Expr);
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = parse_macro_input!(item as ItemFn);
+
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            let _pushd = ::pushd::Pushd::new(#path).unwrap_or_else(|e| {
+                ::std::panic!("Could not change to the directory for #[in_dir]: {e}")
+            });
+            #block
+        }
+    }
+    .into()
+}
+
+/// Runs the annotated function with the current directory changed to a new
+/// temporary directory. The directory is deleted after the function finishes.
+///
+/// If this is called as `#[in_temp_dir(serial)]`, the function also holds a
+/// process-wide lock while it runs, so that no two functions annotated this
+/// way change the current directory at the same time. If the temporary
+/// directory cannot be created or changed to, the function panics.
+#[proc_macro_attribute]
+pub fn in_temp_dir(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let serial = if attr.is_empty() {
+        None
+    } else {
+        let arg = parse_macro_input!(attr as Ident);
+        if arg != "serial" {
+            return syn::Error::new(arg.span(), "expected `serial` or no argument")
+                .to_compile_error()
+                .into();
+        }
+        Some(quote! {
+            let _serial = ::pushd::__private::serial_lock();
+        })
+    };
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = parse_macro_input!(item as ItemFn);
+
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            #serial
+            let _pushd = ::pushd::Pushd::new_temp().unwrap_or_else(|e| {
+                ::std::panic!("Could not change to a temp dir for #[in_temp_dir]: {e}")
+            });
+            #block
+        }
+    }
+    .into()
+}
+
+/// Runs the annotated function with the current directory changed to the
+/// given path, while holding a process-wide lock.
+///
+/// This is the same as `#[in_dir]`, except that the lock is acquired before
+/// the directory is changed and released after it is changed back. The lock
+/// is the same one that `#[in_temp_dir(serial)]` uses, so no two functions
+/// annotated with either of these change the current directory at the same
+/// time. If the directory cannot be changed, the function panics.
+#[proc_macro_attribute]
+pub fn serial(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let path = parse_macro_input!(attr as Expr);
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = parse_macro_input!(item as ItemFn);
+
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            let _serial = ::pushd::__private::serial_lock();
+            let _pushd = ::pushd::Pushd::new(#path).unwrap_or_else(|e| {
+                ::std::panic!("Could not change to the directory for #[serial]: {e}")
+            });
+            #block
+        }
+    }
+    .into()
+}
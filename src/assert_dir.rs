@@ -0,0 +1,127 @@
+// This module contains the `assert_fs` integration, which is only compiled
+// when the `assert_fs` feature is enabled.
+
+use crate::{DropBehavior, Panicking, Pushd, PushdBuilder, PushdError};
+use std::path::Path;
+
+/// An `AssertPushd` is a [`Pushd`] for an `assert_fs` fixture, like an
+/// [`assert_fs::TempDir`] or an [`assert_fs::fixture::ChildPath`]. It borrows
+/// the fixture, so the fixture cannot be dropped, and a `TempDir` cannot be
+/// deleted, until the guard has changed back to the original directory.
+///
+/// Create one with [`Pushd::into_assert_dir`]. This requires the `assert_fs`
+/// feature.
+#[derive(Debug)]
+pub struct AssertPushd<'a, B: DropBehavior = Panicking> {
+    pushd: Pushd<B>,
+    fixture: &'a Path,
+}
+
+impl Pushd<Panicking> {
+    /// Changes to the directory of an `assert_fs` fixture and returns an
+    /// [`AssertPushd`] that changes back when it is dropped. The fixture can
+    /// be anything that refers to a directory, like an
+    /// [`assert_fs::TempDir`] or an [`assert_fs::fixture::ChildPath`].
+    ///
+    /// Other than borrowing the fixture, this behaves exactly like
+    /// [`Pushd::new`]. This requires the `assert_fs` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use assert_fs::prelude::*;
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let temp = assert_fs::TempDir::new()?;
+    /// let child = temp.child("child");
+    /// child.create_dir_all()?;
+    /// {
+    ///     let _pd = Pushd::into_assert_dir(&child)?;
+    ///     std::fs::write("file", "data")?;
+    /// }
+    /// child.child("file").assert("data");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn into_assert_dir<D>(fixture: &D) -> Result<AssertPushd<'_, Panicking>, PushdError>
+    where
+        D: AsRef<Path> + ?Sized,
+    {
+        let fixture = fixture.as_ref();
+        let pushd = PushdBuilder::new(fixture.to_owned()).push()?;
+        Ok(AssertPushd { pushd, fixture })
+    }
+}
+
+impl<B: DropBehavior> AssertPushd<'_, B> {
+    /// Returns the path of the fixture.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        self.fixture
+    }
+
+    /// Returns the [`Pushd`] guard for the fixture.
+    #[must_use]
+    pub fn guard(&self) -> &Pushd<B> {
+        &self.pushd
+    }
+
+    /// Returns a mutable reference to the [`Pushd`] guard for the fixture.
+    /// You can use this to call [`Pushd::pop`] while keeping the fixture
+    /// borrowed.
+    #[must_use]
+    pub fn guard_mut(&mut self) -> &mut Pushd<B> {
+        &mut self.pushd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{fixture::PathChild, TempDir};
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+
+    #[test]
+    #[serial]
+    fn into_assert_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let temp = TempDir::new()?;
+        {
+            let pd = Pushd::into_assert_dir(&temp)?;
+            assert_eq!(pd.path(), temp.path());
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(temp.path())?,
+            );
+            fs::create_dir("child")?;
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        let child = temp.child("child");
+        {
+            let mut pd = Pushd::into_assert_dir(&child)?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(child.path())?,
+            );
+            pd.guard_mut().pop()?;
+            assert_eq!(env::current_dir()?, cwd);
+        }
+
+        assert!(matches!(
+            Pushd::into_assert_dir(&temp.child("missing")),
+            Err(PushdError::TargetNotFound { .. }),
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+// This module contains `AnyAsyncPushd`, which works with any async runtime,
+// and the hook for spawning its blocking work. It is only compiled when the
+// `async` feature is enabled.
+
+use crate::{
+    DropBehavior, NonPanicking, Panicking, Pushd, PushdBuilder, PushdError, SendablePushd,
+};
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe, Location},
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+/// Runs the blocking work for an [`AnyAsyncPushd`]. Set one with
+/// [`set_blocking_spawner`].
+///
+/// This lets the directory changes run on an async runtime's blocking thread
+/// pool, like `async_std::task::spawn_blocking` or `smol::unblock`. Without
+/// one, each directory change runs on a new thread.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::BlockingSpawner;
+///
+/// struct NewThread;
+///
+/// impl BlockingSpawner for NewThread {
+///     fn spawn_blocking(&self, work: Box<dyn FnOnce() + Send>) {
+///         std::thread::spawn(work);
+///     }
+/// }
+///
+/// pushd::set_blocking_spawner(Box::new(NewThread));
+/// ```
+pub trait BlockingSpawner: Send + Sync + 'static {
+    /// Runs `work` somewhere that is allowed to block. If `work` is dropped
+    /// without being run, the future waiting for it returns a
+    /// [`PushdError::BlockingTaskDropped`] error.
+    fn spawn_blocking(&self, work: Box<dyn FnOnce() + Send>);
+}
+
+static SPAWNER: Mutex<Option<Arc<dyn BlockingSpawner>>> = Mutex::new(None);
+
+fn spawner() -> MutexGuard<'static, Option<Arc<dyn BlockingSpawner>>> {
+    SPAWNER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Makes every [`AnyAsyncPushd`] run its directory changes with `spawner`.
+/// This replaces any spawner that was set before.
+pub fn set_blocking_spawner(spawner: Box<dyn BlockingSpawner>) {
+    *self::spawner() = Some(Arc::from(spawner));
+}
+
+/// Removes the spawner set with [`set_blocking_spawner`], so that each
+/// directory change runs on a new thread again.
+pub fn clear_blocking_spawner() {
+    *spawner() = None;
+}
+
+/// An `AnyAsyncPushd` is like an [`AsyncPushd`](crate::AsyncPushd), but it
+/// works with any async runtime, not just tokio. It changes the current
+/// directory when it's created and returns to the original current directory
+/// when [`AnyAsyncPushd::pop`] is awaited.
+///
+/// All calls to [`std::env::set_current_dir`] and [`std::env::current_dir`]
+/// are made with the [`BlockingSpawner`] set with [`set_blocking_spawner`],
+/// or on a new thread if there isn't one, so they never block the async
+/// executor. This requires the `async` feature.
+///
+/// # Dropping without popping
+///
+/// Rust has no async drop, so you should always explicitly await
+/// [`AnyAsyncPushd::pop`] before the guard goes out of scope. If an
+/// `AnyAsyncPushd` is dropped without being popped, it falls back to the
+/// behavior of dropping a [`Pushd`], and restores the original directory
+/// synchronously on whatever thread drops the guard.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::AnyAsyncPushd;
+///
+/// async fn in_temp() -> Result<(), pushd::PushdError> {
+///     let mut pd = AnyAsyncPushd::new(std::env::temp_dir()).await?;
+///     // ...
+///     pd.pop().await
+/// }
+/// ```
+#[derive(Debug)]
+pub struct AnyAsyncPushd<B: DropBehavior = Panicking> {
+    inner: Option<SendablePushd<B>>,
+}
+
+impl AnyAsyncPushd<Panicking> {
+    /// Constructs a new `AnyAsyncPushd` struct.
+    ///
+    /// This accepts any type that implements [`AsRef<Path>`].
+    ///
+    /// The `AnyAsyncPushd` returned by this constructor will panic if it is
+    /// dropped without being popped and cannot change back to its original
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed, or if the blocking spawner drops
+    /// the work without running it.
+    //
+    // This isn't an `async fn` so that `#[track_caller]` works.
+    #[track_caller]
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+    ) -> impl Future<Output = Result<AnyAsyncPushd<Panicking>, PushdError>> {
+        let path = path.as_ref().to_owned();
+        let caller = Location::caller();
+        Self::spawn(move || Pushd::push(PushdBuilder::new(path), caller))
+    }
+}
+
+impl AnyAsyncPushd<NonPanicking> {
+    /// Constructs a new `AnyAsyncPushd` struct that will never panic.
+    ///
+    /// This accepts any type that implements [`AsRef<Path>`].
+    ///
+    /// If the `AnyAsyncPushd` created by this constructor is dropped without
+    /// being popped and cannot change back to the original directory, then it
+    /// logs a warning instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed, or if the blocking spawner drops
+    /// the work without running it.
+    #[track_caller]
+    pub fn new_no_panic<P: AsRef<Path>>(
+        path: P,
+    ) -> impl Future<Output = Result<AnyAsyncPushd<NonPanicking>, PushdError>> {
+        let path = path.as_ref().to_owned();
+        let caller = Location::caller();
+        Self::spawn(move || Pushd::push(PushdBuilder::new(path).no_panic(), caller))
+    }
+}
+
+impl<B: DropBehavior> AnyAsyncPushd<B> {
+    async fn spawn<F>(f: F) -> Result<AnyAsyncPushd<B>, PushdError>
+    where
+        F: FnOnce() -> Result<Pushd<B>, PushdError> + Send + 'static,
+    {
+        let pd = run_blocking(move || f().map(Pushd::into_sendable)).await??;
+        Ok(AnyAsyncPushd { inner: Some(pd) })
+    }
+
+    /// Changes back to the original directory the first time it is called. If
+    /// this method is called repeatedly it will not do anything on subsequent
+    /// calls.
+    ///
+    /// If this fails, the guard is not considered popped. It can be popped
+    /// again, and it will still try to restore the original directory when it
+    /// is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method return an error if the current directory cannot be changed, or if the blocking
+    /// spawner drops the work without running it.
+    pub async fn pop(&mut self) -> Result<(), PushdError> {
+        let Some(mut pd) = self.inner.take() else {
+            return Ok(());
+        };
+
+        // If the work is dropped without being run, the guard is dropped with
+        // it, which restores the original directory if it can.
+        let (pd, res) = run_blocking(move || {
+            let res = pd.pop();
+            (pd, res)
+        })
+        .await?;
+
+        if res.is_err() {
+            self.inner = Some(pd);
+        }
+        res
+    }
+}
+
+// Runs `f` with the blocking spawner and waits for it to finish. If `f`
+// panics, the panic is resumed in the caller.
+async fn run_blocking<T, F>(f: F) -> Result<T, PushdError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let shared = Arc::new(Mutex::new(Shared {
+        result: None,
+        done: false,
+        waker: None,
+    }));
+    let sender = Sender(Arc::clone(&shared));
+    let work = Box::new(move || {
+        let res = panic::catch_unwind(AssertUnwindSafe(f));
+        sender.send(res);
+    });
+    let spawner = spawner().clone();
+    match spawner {
+        Some(spawner) => spawner.spawn_blocking(work),
+        None => {
+            thread::spawn(work);
+        }
+    }
+    match (Receiver(shared)).await {
+        Some(Ok(v)) => Ok(v),
+        Some(Err(panic)) => panic::resume_unwind(panic),
+        None => Err(PushdError::BlockingTaskDropped),
+    }
+}
+
+// The state shared by the blocking work and the future waiting for it.
+struct Shared<T> {
+    result: Option<thread::Result<T>>,
+    // True once the work has finished or been dropped.
+    done: bool,
+    waker: Option<Waker>,
+}
+
+struct Sender<T>(Arc<Mutex<Shared<T>>>);
+
+impl<T> Sender<T> {
+    fn send(self, res: thread::Result<T>) {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).result = Some(res);
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        shared.done = true;
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Receiver<T>(Arc<Mutex<Shared<T>>>);
+
+impl<T> Future for Receiver<T> {
+    type Output = Option<thread::Result<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shared = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if shared.done {
+            return Poll::Ready(shared.result.take());
+        }
+        shared.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs, task::Wake};
+    use tempfile::tempdir;
+
+    // A minimal executor, since this must work without any runtime.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        struct Unpark(thread::Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = std::pin::pin!(fut);
+        loop {
+            if let Poll::Ready(v) = fut.as_mut().poll(&mut cx) {
+                return v;
+            }
+            thread::park();
+        }
+    }
+
+    struct Dropper;
+
+    impl BlockingSpawner for Dropper {
+        fn spawn_blocking(&self, work: Box<dyn FnOnce() + Send>) {
+            drop(work);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn any_async_pop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = fs::canonicalize(env::current_dir()?)?;
+        let td = tempdir()?;
+        block_on(async {
+            let mut pd = AnyAsyncPushd::new(td.path()).await?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+            pd.pop().await?;
+            assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+            // A second pop is a no-op.
+            pd.pop().await?;
+            assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+            {
+                let _pd = AnyAsyncPushd::new_no_panic(td.path()).await?;
+            }
+            assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+            Ok::<_, Box<dyn StdError>>(())
+        })?;
+
+        set_blocking_spawner(Box::new(Dropper));
+        let res = block_on(AnyAsyncPushd::new(td.path()));
+        clear_blocking_spawner();
+        assert!(matches!(res, Err(PushdError::BlockingTaskDropped)));
+        assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+        Ok(())
+    }
+}
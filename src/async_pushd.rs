@@ -0,0 +1,189 @@
+use crate::{
+    DropBehavior, NonPanicking, Panicking, Pushd, PushdBuilder, PushdError, SendablePushd,
+};
+use std::{
+    future::Future,
+    panic::{self, Location},
+    path::Path,
+};
+use tokio::task::{self, JoinError};
+
+/// An `AsyncPushd` is the async equivalent of a [`Pushd`]. It changes the
+/// current directory when it's created and returns to the original current
+/// directory when [`AsyncPushd::pop`] is awaited.
+///
+/// All calls to [`std::env::set_current_dir`] and [`std::env::current_dir`]
+/// are made via [`tokio::task::spawn_blocking`], so they never block the
+/// async executor. This requires the `tokio` feature.
+///
+/// Note that the current directory is process-wide state, so it is shared by
+/// every task and thread in the process. This type doesn't change that. It
+/// just makes sure that changing directories plays nicely with the tokio
+/// runtime.
+///
+/// # Dropping without popping
+///
+/// Rust has no async drop, so you should always explicitly await
+/// [`AsyncPushd::pop`] before the guard goes out of scope. If an `AsyncPushd`
+/// is dropped without being popped, it falls back to the behavior of
+/// dropping a [`Pushd`]. The original directory is restored synchronously on
+/// whatever thread drops the guard. If that fails, a guard created with
+/// [`AsyncPushd::new`] will panic, while a guard created with
+/// [`AsyncPushd::new_no_panic`] will log a warning. Like [`Pushd`], the `B`
+/// type parameter records which of these two behaviors the guard has.
+#[derive(Debug)]
+pub struct AsyncPushd<B: DropBehavior = Panicking> {
+    inner: Option<SendablePushd<B>>,
+}
+
+impl AsyncPushd<Panicking> {
+    /// Constructs a new `AsyncPushd` struct.
+    ///
+    /// This accepts any type that implements [`AsRef<Path>`].
+    ///
+    /// The `AsyncPushd` returned by this constructor will panic if it is
+    /// dropped without being popped and cannot change back to its original
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed, or if the blocking task used to
+    /// change it is cancelled.
+    //
+    // This isn't an `async fn` so that `#[track_caller]` works.
+    #[track_caller]
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+    ) -> impl Future<Output = Result<AsyncPushd<Panicking>, PushdError>> {
+        let path = path.as_ref().to_owned();
+        let caller = Location::caller();
+        Self::spawn(move || Pushd::push(PushdBuilder::new(path), caller))
+    }
+}
+
+impl AsyncPushd<NonPanicking> {
+    /// Constructs a new `AsyncPushd` struct that will never panic.
+    ///
+    /// This accepts any type that implements [`AsRef<Path>`].
+    ///
+    /// If the `AsyncPushd` created by this constructor is dropped without
+    /// being popped and cannot change back to the original directory, then it
+    /// will simply call
+    /// [`log::warn!`](https://docs.rs/log/latest/log/macro.warn.html) instead
+    /// of panicking.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed, or if the blocking task used to
+    /// change it is cancelled.
+    #[track_caller]
+    pub fn new_no_panic<P: AsRef<Path>>(
+        path: P,
+    ) -> impl Future<Output = Result<AsyncPushd<NonPanicking>, PushdError>> {
+        let path = path.as_ref().to_owned();
+        let caller = Location::caller();
+        Self::spawn(move || Pushd::push(PushdBuilder::new(path).no_panic(), caller))
+    }
+}
+
+impl<B: DropBehavior> AsyncPushd<B> {
+    async fn spawn<F>(f: F) -> Result<AsyncPushd<B>, PushdError>
+    where
+        F: FnOnce() -> Result<Pushd<B>, PushdError> + Send + 'static,
+    {
+        let pd = task::spawn_blocking(move || f().map(Pushd::into_sendable))
+            .await
+            .map_err(join_error)??;
+        Ok(AsyncPushd { inner: Some(pd) })
+    }
+
+    /// Changes back to the original directory the first time it is called. If
+    /// this method is called repeatedly it will not do anything on subsequent
+    /// calls.
+    ///
+    /// If this fails, the guard is not considered popped. It can be popped
+    /// again, and it will still try to restore the original directory when it
+    /// is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method return an error if the current directory cannot be changed, or if the blocking
+    /// task used to change it is cancelled.
+    pub async fn pop(&mut self) -> Result<(), PushdError> {
+        let Some(mut pd) = self.inner.take() else {
+            return Ok(());
+        };
+
+        let (pd, res) = task::spawn_blocking(move || {
+            let res = pd.pop();
+            (pd, res)
+        })
+        .await
+        .map_err(join_error)?;
+
+        if res.is_err() {
+            self.inner = Some(pd);
+        }
+        res
+    }
+}
+
+fn join_error(e: JoinError) -> PushdError {
+    if e.is_panic() {
+        panic::resume_unwind(e.into_panic());
+    }
+    PushdError::BlockingTask { source: e }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    async fn async_pop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = fs::canonicalize(env::current_dir()?)?;
+
+        let td = tempdir()?;
+        let mut pd = AsyncPushd::new(td.path()).await?;
+        assert_eq!(
+            fs::canonicalize(env::current_dir()?)?,
+            fs::canonicalize(td.path())?,
+        );
+        pd.pop().await?;
+        assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+        // A second pop is a no-op.
+        pd.pop().await?;
+        assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn async_drop_without_pop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = fs::canonicalize(env::current_dir()?)?;
+
+        {
+            let td = tempdir()?;
+            let _pd = AsyncPushd::new_no_panic(td.path()).await?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+        }
+        assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+// This module keeps a log of the directory changes this crate has made, for
+// `pushd::history`.
+
+use crate::{ErrorRecord, PushdError};
+use std::{
+    collections::VecDeque,
+    panic::Location,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::SystemTime,
+};
+
+/// The number of entries kept by [`history`]. Once the log is full, the
+/// oldest entry is removed each time a new one is added.
+pub const HISTORY_LIMIT: usize = 1000;
+
+/// Whether a [`HistoryEntry`] records a change into a directory or a change
+/// back out of it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum HistoryAction {
+    /// A [`Pushd`](crate::Pushd) was created, re-entered, or
+    /// [swapped](crate::Pushd::swap), or
+    /// [`DirStack::pushd`](crate::DirStack::pushd) was called.
+    Push,
+    /// A [`Pushd`](crate::Pushd) was popped or dropped, or
+    /// [`DirStack::popd`](crate::DirStack::popd) was called.
+    Pop,
+}
+
+/// One directory change made by this crate, as returned by [`history`]. With
+/// the `serde` feature, this implements `Serialize` and `Deserialize`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct HistoryEntry {
+    /// When the change was made.
+    pub time: SystemTime,
+    /// Whether this was a push or a pop.
+    pub action: HistoryAction,
+    /// The current directory before the change.
+    pub from: PathBuf,
+    /// The directory that was changed to, or that the change tried to change
+    /// to if it failed. If a guard changed to one of its
+    /// [fallback directories](crate::PushdBuilder::fallback_dirs) when it was
+    /// popped, this is the fallback directory.
+    pub to: PathBuf,
+    /// The location in the source of the code that created the guard, or that
+    /// called the [`DirStack`](crate::DirStack) method, as `file:line:column`.
+    pub caller: String,
+    /// The error, if the change failed.
+    pub outcome: Result<(), ErrorRecord>,
+}
+
+static LOG: Mutex<VecDeque<HistoryEntry>> = Mutex::new(VecDeque::new());
+
+fn log() -> MutexGuard<'static, VecDeque<HistoryEntry>> {
+    LOG.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns the most recent directory changes made by this crate in this
+/// process, oldest first. At most [`HISTORY_LIMIT`] entries are kept.
+///
+/// This is useful for finding out which guard left the process in the wrong
+/// directory, for example at the end of a test.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{HistoryAction, Pushd};
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// drop(Pushd::new(std::env::temp_dir())?);
+/// let history = pushd::history();
+/// let last = history.last().unwrap();
+/// assert_eq!(last.action, HistoryAction::Pop);
+/// assert!(last.outcome.is_ok());
+/// # Ok(())
+/// # }
+/// ```
+#[must_use]
+pub fn history() -> Vec<HistoryEntry> {
+    log().iter().cloned().collect()
+}
+
+/// Removes all entries from the log returned by [`history`].
+pub fn clear_history() {
+    log().clear();
+}
+
+pub(crate) fn record(
+    action: HistoryAction,
+    from: &Path,
+    to: &Path,
+    caller: &'static Location<'static>,
+    outcome: Result<(), &PushdError>,
+) {
+    let entry = HistoryEntry {
+        time: SystemTime::now(),
+        action,
+        from: from.to_owned(),
+        to: to.to_owned(),
+        caller: caller.to_string(),
+        outcome: outcome.map_err(ErrorRecord::from),
+    };
+    let mut log = log();
+    if log.len() == HISTORY_LIMIT {
+        log.pop_front();
+    }
+    log.push_back(entry);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn records_changes() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        clear_history();
+        let line = line!() + 1;
+        let pd = Pushd::new("src")?;
+        drop(pd);
+        assert!(Pushd::new("no-such-dir").is_err());
+
+        let entries = history();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].action, HistoryAction::Push);
+        assert_eq!(entries[0].from, cwd);
+        assert_eq!(entries[0].to, cwd.join("src"));
+        assert!(entries[0].caller.contains(&format!("audit.rs:{line}:")));
+        assert_eq!(entries[1].action, HistoryAction::Pop);
+        assert_eq!(entries[1].from, cwd.join("src"));
+        assert_eq!(entries[1].to, cwd);
+        assert_eq!(entries[1].caller, entries[0].caller);
+        assert!(entries[2].outcome.is_err());
+
+        for _ in 0..HISTORY_LIMIT {
+            record(HistoryAction::Push, &cwd, &cwd, Location::caller(), Ok(()));
+        }
+        let entries = history();
+        assert_eq!(entries.len(), HISTORY_LIMIT);
+        assert!(entries.iter().all(|e| e.outcome.is_ok()));
+        clear_history();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+// This module contains the `CwdBackend` trait, which lets a guard get and
+// change some current directory other than the process's.
+
+use crate::{
+    builder::Options, chdir, current_dir, files::Anchor, Panicking, Pushd, PushdBuilder, PushdError,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The operations a [`Pushd`] uses to get and change the current directory.
+/// By default a guard uses [`OsBackend`], which calls [`std::env::current_dir`]
+/// and [`std::env::set_current_dir`]. Use [`Pushd::with_backend`] or
+/// [`PushdBuilder::backend`] to use something else, like a fake current
+/// directory in unit tests, or the current directory of a remote process.
+///
+/// A guard with a backend other than [`OsBackend`] only uses the backend to
+/// change directories. It doesn't keep a handle to its target, so the
+/// [file helpers](Pushd::open) and [`Pushd::status`] use the target's path
+/// with the real filesystem, and options that need to change to an open
+/// directory handle, like [`PushdBuilder::security`], are ignored.
+pub trait CwdBackend: Send + Sync {
+    /// Returns the current directory.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the current directory cannot be determined.
+    fn current_dir(&self) -> io::Result<PathBuf>;
+
+    /// Changes the current directory to `path`, which may be relative to the
+    /// current directory.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the current directory cannot be changed.
+    fn set_current_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// Returns true if `path`, which may be relative to the current directory,
+    /// is an existing directory. This is used by [`Pushd::new_if_exists`] and
+    /// [`PushdBuilder::push_if_exists`]. The default implementation checks the
+    /// path on the real filesystem, relative to [`CwdBackend::current_dir`].
+    fn is_dir(&self, path: &Path) -> bool {
+        self.current_dir().is_ok_and(|cwd| cwd.join(path).is_dir())
+    }
+}
+
+/// The [`CwdBackend`] for the process's current directory. This is what a
+/// [`Pushd`] uses by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsBackend;
+
+impl CwdBackend for OsBackend {
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        current_dir()
+    }
+
+    fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        chdir(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+}
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` that uses the given [`CwdBackend`] instead of
+    /// the process's current directory.
+    ///
+    /// # Errors
+    ///
+    /// This returns the same errors as [`Pushd::new`], except that they come
+    /// from the backend.
+    #[track_caller]
+    pub fn with_backend<C, P>(backend: C, path: P) -> Result<Pushd<Panicking>, PushdError>
+    where
+        C: CwdBackend + 'static,
+        P: AsRef<Path>,
+    {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .backend(backend)
+            .push()
+    }
+}
+
+impl<B: crate::DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`] use the given [`CwdBackend`] instead of
+    /// the process's current directory. See [`CwdBackend`] for the options
+    /// that this affects.
+    #[must_use]
+    pub fn backend<C: CwdBackend + 'static>(mut self, backend: C) -> Self {
+        self.opts.backend = Some(Arc::new(backend));
+        self
+    }
+}
+
+impl Options {
+    pub(crate) fn current_dir(&self) -> io::Result<PathBuf> {
+        match &self.backend {
+            Some(backend) => backend.current_dir(),
+            None => current_dir(),
+        }
+    }
+
+    pub(crate) fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        match &self.backend {
+            Some(backend) => backend.set_current_dir(path),
+            None => chdir(path),
+        }
+    }
+
+    pub(crate) fn is_dir(&self, path: &Path) -> bool {
+        match &self.backend {
+            Some(backend) => backend.is_dir(path),
+            None => path.is_dir(),
+        }
+    }
+
+    // A guard with a custom backend can't open a handle to a directory that
+    // may not be on the real filesystem.
+    pub(crate) fn anchor(&self, path: PathBuf) -> Anchor {
+        if self.backend.is_some() {
+            Anchor::unopened(path)
+        } else {
+            Anchor::here(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{
+        env,
+        error::Error as StdError,
+        sync::{Mutex, PoisonError},
+    };
+
+    // A current directory that only exists in memory.
+    #[derive(Clone, Default)]
+    struct FakeBackend {
+        cwd: Arc<Mutex<PathBuf>>,
+    }
+
+    impl CwdBackend for FakeBackend {
+        fn current_dir(&self) -> io::Result<PathBuf> {
+            Ok(self
+                .cwd
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone())
+        }
+
+        fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+            let mut cwd = self.cwd.lock().unwrap_or_else(PoisonError::into_inner);
+            let new = cwd.join(path);
+            if new.starts_with("/missing") {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            *cwd = new;
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn fake_backend() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let backend = FakeBackend::default();
+        *backend.cwd.lock().unwrap() = PathBuf::from("/virtual");
+
+        let mut pd = Pushd::with_backend(backend.clone(), "some/dir")?;
+        assert_eq!(backend.current_dir()?, Path::new("/virtual/some/dir"));
+        assert_eq!(pd.original(), Path::new("/virtual"));
+        assert_eq!(env::current_dir()?, cwd);
+        pd.pop()?;
+        assert_eq!(backend.current_dir()?, Path::new("/virtual"));
+
+        let res = Pushd::with_backend(backend.clone(), "/missing");
+        assert!(matches!(res, Err(PushdError::TargetNotFound { .. })));
+        assert_eq!(backend.current_dir()?, Path::new("/virtual"));
+
+        Ok(())
+    }
+}
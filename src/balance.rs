@@ -0,0 +1,337 @@
+// This module contains the checks for guards that are leaked by code that
+// should have dropped them, like a test, including the check that runs when
+// the process exits.
+
+use crate::{audit, compare, compare::shown, current_dir, nesting, ActiveGuard, PushdError};
+use std::{
+    fmt::Write as _,
+    io::{self, Write as _},
+    panic,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+// The check started by `install_exit_check`, and whether its handler has been
+// registered with `atexit`.
+#[derive(Default)]
+struct ExitCheck {
+    check: Option<LeakCheck>,
+    registered: bool,
+}
+
+static EXIT_CHECK: Mutex<ExitCheck> = Mutex::new(ExitCheck {
+    check: None,
+    registered: false,
+});
+
+fn exit_check() -> MutexGuard<'static, ExitCheck> {
+    EXIT_CHECK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Returns an error if any guard is in its target. Call this where every
+/// guard should have been dropped, like the end of a test, to find guards
+/// that were leaked with [`std::mem::forget`] or by storing them somewhere
+/// that outlives the code that created them.
+///
+/// This checks the same guards as [`active`](crate::active), so guards
+/// created with [`Pushd::new_thread_scoped`](crate::Pushd::new_thread_scoped)
+/// and guards that use a [`CwdBackend`](crate::CwdBackend) are not checked. To check only the guards created by one piece of code,
+/// and to also check that the current directory was changed back, use a
+/// [`LeakCheck`].
+///
+/// # Errors
+///
+/// This returns a [`PushdError::GuardsLeaked`] error that lists the guards if
+/// there are any.
+pub fn verify_balanced() -> Result<(), PushdError> {
+    leaked(nesting::active_after(None))
+}
+
+/// A check for code that leaves guards in their targets or leaves the
+/// current directory changed. Start one with [`LeakCheck::start`] before the
+/// code runs, for example in a test harness's setup, and call
+/// [`LeakCheck::check`] afterwards, for example in its teardown.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{LeakCheck, Pushd};
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let check = LeakCheck::start()?;
+/// {
+///     let _pd = Pushd::new(std::env::temp_dir())?;
+/// }
+/// check.check()?;
+///
+/// let leaked = Pushd::new(std::env::temp_dir())?;
+/// assert!(check.check().is_err());
+/// # drop(leaked);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LeakCheck {
+    cwd: PathBuf,
+    // The id of a guard that would be created when the check starts. Every
+    // guard created after that has a higher id.
+    started_at: u64,
+}
+
+impl LeakCheck {
+    /// Starts a check, recording the current directory. Guards that are
+    /// already in their targets are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the current directory cannot be determined.
+    pub fn start() -> Result<LeakCheck, PushdError> {
+        Ok(LeakCheck {
+            cwd: current_dir()?,
+            started_at: nesting::next_id(),
+        })
+    }
+
+    /// Returns the current directory from when the check was started.
+    #[must_use]
+    pub fn original(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// Returns an error if a guard created since the check was started is in
+    /// its target, or if the current directory isn't the one from when the
+    /// check was started. This can be called more than once.
+    ///
+    /// # Errors
+    ///
+    /// This returns a [`PushdError::GuardsLeaked`] error that lists the
+    /// guards if there are any, and a [`PushdError::CwdLeaked`] error if the
+    /// current directory has changed. It also returns an error if the current
+    /// directory cannot be determined.
+    pub fn check(&self) -> Result<(), PushdError> {
+        leaked(nesting::active_after(Some(self.started_at)))?;
+        let actual = current_dir()?;
+        if !compare::same_dir(&actual, &self.cwd) {
+            return Err(PushdError::CwdLeaked {
+                expected: self.cwd.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Starts a [`LeakCheck`] that runs when the process exits. If guards
+/// created since this was called are still in their targets at exit, or the
+/// current directory isn't the one it was when this was called, a report is
+/// written to stderr. The report lists where each leaked guard was created,
+/// and where the last guard that changed to the final current directory was
+/// created, if there was one.
+///
+/// This is meant for test binaries and other programs that should always end
+/// up back where they started. The check is run by an `atexit` handler, so it
+/// runs when `main` returns or when [`std::process::exit`] is called, but not
+/// when the process is killed by a signal or aborts. Calling this again
+/// restarts the check from the current state. A harness that has its own
+/// teardown hook can call [`run_exit_check`] there instead.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// pushd::install_exit_check()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This returns an error if the current directory cannot be determined, and
+/// a [`PushdError::ExitCheck`] error if the handler cannot be registered.
+pub fn install_exit_check() -> Result<(), PushdError> {
+    let check = LeakCheck::start()?;
+    let mut state = exit_check();
+    state.check = Some(check);
+    if !state.registered {
+        // SAFETY: `on_exit` is an `extern "C"` function that never unwinds.
+        if unsafe { atexit(on_exit) } != 0 {
+            state.check = None;
+            return Err(PushdError::ExitCheck);
+        }
+        state.registered = true;
+    }
+    Ok(())
+}
+
+/// Runs the check started by [`install_exit_check`] now, instead of waiting
+/// for the process to exit. This does nothing if [`install_exit_check`] has
+/// not been called.
+///
+/// # Errors
+///
+/// This returns the same errors as [`LeakCheck::check`].
+pub fn run_exit_check() -> Result<(), PushdError> {
+    match &exit_check().check {
+        Some(check) => check.check(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+use libc::atexit;
+
+// On other platforms the C runtime still provides `atexit`.
+#[cfg(not(unix))]
+extern "C" {
+    fn atexit(cb: extern "C" fn()) -> std::ffi::c_int;
+}
+
+extern "C" fn on_exit() {
+    // Nothing can unwind out of an `atexit` handler, and there's no one left
+    // to report a panic to.
+    let _ = panic::catch_unwind(|| {
+        if let Err(e) = run_exit_check() {
+            let _ = writeln!(io::stderr(), "{}", report(&e));
+        }
+    });
+}
+
+// Describes a failed exit check, including the locations of the guards that
+// are responsible.
+fn report(e: &PushdError) -> String {
+    let mut report = format!("The pushd exit check failed: {e}");
+    match e {
+        PushdError::GuardsLeaked { guards } => {
+            for g in guards {
+                let _ = write!(
+                    report,
+                    "\n  the guard created at {} is still in {}",
+                    g.caller,
+                    shown(&g.target),
+                );
+            }
+        }
+        PushdError::CwdLeaked { actual, .. } => {
+            let last = audit::history()
+                .into_iter()
+                .rev()
+                .find(|h| h.outcome.is_ok() && compare::same_dir(&h.to, actual));
+            match last {
+                Some(h) => {
+                    let _ = write!(
+                        report,
+                        "\n  the last guard to change to it was created at {}",
+                        h.caller,
+                    );
+                }
+                None => report.push_str(
+                    "\n  no guard changed to it, so something else changed the current directory",
+                ),
+            }
+        }
+        _ => {}
+    }
+    report
+}
+
+fn leaked(guards: Vec<ActiveGuard>) -> Result<(), PushdError> {
+    if guards.is_empty() {
+        return Ok(());
+    }
+    Err(PushdError::GuardsLeaked { guards })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn leak_check() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        verify_balanced()?;
+
+        let outer = Pushd::new("src")?;
+        assert!(matches!(
+            verify_balanced(),
+            Err(PushdError::GuardsLeaked { guards }) if guards.len() == 1,
+        ));
+
+        let check = LeakCheck::start()?;
+        check.check()?;
+        let inner = Pushd::new("..")?;
+        assert!(matches!(
+            check.check(),
+            Err(PushdError::GuardsLeaked { guards })
+                if guards.len() == 1 && guards[0].original == cwd.join("src"),
+        ));
+        drop(inner);
+        check.check()?;
+
+        env::set_current_dir(&cwd)?;
+        assert!(matches!(
+            check.check(),
+            Err(PushdError::CwdLeaked { expected, actual })
+                if expected == cwd.join("src") && actual == cwd,
+        ));
+        env::set_current_dir(cwd.join("src"))?;
+
+        drop(outer);
+        verify_balanced()?;
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn exit_check_report() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        // This starts the check without registering the handler, so that the
+        // test binary itself isn't checked when it exits.
+        exit_check().check = Some(LeakCheck::start()?);
+        run_exit_check()?;
+
+        let pd = Pushd::new("src")?;
+        let msg = report(&run_exit_check().expect_err("a guard is leaked"));
+        assert!(
+            msg.contains(&format!("the guard created at {}", pd.caller())),
+            "{msg}",
+        );
+        drop(pd);
+
+        // This guard changes back to a different directory than the one the
+        // check started in.
+        let pd = Pushd::new_from(cwd.join("pushd-macros"), "src")?;
+        let caller = pd.caller();
+        drop(pd);
+        let e = run_exit_check().expect_err("the current directory is leaked");
+        assert!(matches!(e, PushdError::CwdLeaked { .. }));
+        let msg = report(&e);
+        assert!(
+            msg.contains(&format!(
+                "the last guard to change to it was created at {caller}"
+            )),
+            "{msg}",
+        );
+
+        let td = tempdir()?;
+        env::set_current_dir(td.path())?;
+        let msg = report(&run_exit_check().expect_err("the current directory is leaked"));
+        assert!(msg.contains("something else changed"), "{msg}");
+
+        env::set_current_dir(&cwd)?;
+        run_exit_check()?;
+        exit_check().check = None;
+
+        Ok(())
+    }
+}
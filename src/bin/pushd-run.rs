@@ -0,0 +1,192 @@
+// This is the `pushd-run` binary, which is only built when the `cli` feature
+// is enabled. It runs a command in a directory and exits with the command's
+// exit code:
+//
+//     pushd-run [--create] <dir> -- <command> [args...]
+//
+// The exit codes for its own errors follow the conventions of `env` and
+// `nohup`. It exits with 125 if it cannot change to the directory, 126 if the
+// command cannot be run, and 127 if the command does not exist.
+
+use pushd::{Pushd, PushdError};
+use std::{
+    env,
+    ffi::OsString,
+    fs, io,
+    path::PathBuf,
+    process::{self, Command, ExitStatus},
+};
+
+const USAGE: &str = "usage: pushd-run [--create] <dir> -- <command> [args...]";
+
+const FAILED: i32 = 125;
+const CANNOT_RUN: i32 = 126;
+const NOT_FOUND: i32 = 127;
+
+#[derive(Debug, PartialEq)]
+struct Invocation {
+    create: bool,
+    dir: PathBuf,
+    program: OsString,
+    args: Vec<OsString>,
+}
+
+fn main() {
+    let args = match parse_args(env::args_os().skip(1)) {
+        Ok(args) => args,
+        Err(msg) => {
+            eprintln!("pushd-run: {msg}\n{USAGE}");
+            process::exit(FAILED);
+        }
+    };
+    process::exit(run(args));
+}
+
+fn parse_args<I: IntoIterator<Item = OsString>>(args: I) -> Result<Invocation, String> {
+    let mut args = args.into_iter();
+    let mut create = false;
+    let mut dir = None;
+    for arg in args.by_ref() {
+        if arg == "--" {
+            break;
+        }
+        if arg == "--create" || arg == "-c" {
+            create = true;
+        } else if arg == "--help" || arg == "-h" {
+            return Err("help requested".to_string());
+        } else if dir.is_none() {
+            dir = Some(PathBuf::from(arg));
+        } else {
+            return Err(format!(
+                "unexpected argument {}, did you forget the --?",
+                arg.to_string_lossy(),
+            ));
+        }
+    }
+    let dir = dir.ok_or("no directory was given")?;
+    let program = args.next().ok_or("no command was given")?;
+    Ok(Invocation {
+        create,
+        dir,
+        program,
+        args: args.collect(),
+    })
+}
+
+fn run(args: Invocation) -> i32 {
+    if args.create {
+        if let Err(e) = fs::create_dir_all(&args.dir) {
+            let e = PushdError::CreateDir {
+                path: args.dir,
+                source: e,
+            };
+            eprintln!("pushd-run: {e}");
+            return FAILED;
+        }
+    }
+    // The guard doesn't panic on drop, since we're about to exit anyway.
+    let _pd = match Pushd::new_no_panic(&args.dir) {
+        Ok(pd) => pd,
+        Err(e) => {
+            eprintln!("pushd-run: {e}");
+            return FAILED;
+        }
+    };
+    match Command::new(&args.program).args(&args.args).status() {
+        Ok(status) => exit_code(status),
+        Err(e) => {
+            eprintln!("pushd-run: {}: {e}", args.program.to_string_lossy());
+            if e.kind() == io::ErrorKind::NotFound {
+                NOT_FOUND
+            } else {
+                CANNOT_RUN
+            }
+        }
+    }
+}
+
+// Returns the code to exit with for a command that exited with `status`. A
+// command killed by a signal is reported the way shells report it, as 128
+// plus the signal number.
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    FAILED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Invocation, String> {
+        parse_args(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn args() {
+        assert_eq!(
+            parse(&["--create", "dir", "--", "ls", "-l", "--", "x"]),
+            Ok(Invocation {
+                create: true,
+                dir: PathBuf::from("dir"),
+                program: OsString::from("ls"),
+                args: ["-l", "--", "x"].map(OsString::from).to_vec(),
+            }),
+        );
+        assert_eq!(parse(&["dir", "--", "ls"]).map(|a| a.create), Ok(false),);
+        assert!(parse(&["dir", "ls"]).is_err());
+        assert!(parse(&["dir", "--"]).is_err());
+        assert!(parse(&["--", "ls"]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run() -> Result<(), Box<dyn std::error::Error>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let td = tempfile::tempdir()?;
+        let dir = td.path().join("a").join("b");
+        let script = r#"test "$(pwd -P)" = "$0" && exit 3"#;
+        let code = super::run(Invocation {
+            create: true,
+            dir: dir.clone(),
+            program: OsString::from("sh"),
+            args: vec![
+                OsString::from("-c"),
+                OsString::from(script),
+                fs::canonicalize(td.path())?.join("a").join("b").into(),
+            ],
+        });
+        assert_eq!(code, 3);
+        assert_eq!(env::current_dir()?, cwd);
+
+        let code = super::run(Invocation {
+            create: false,
+            dir: td.path().join("missing"),
+            program: OsString::from("true"),
+            args: vec![],
+        });
+        assert_eq!(code, FAILED);
+
+        let code = super::run(Invocation {
+            create: false,
+            dir,
+            program: td.path().join("no-such-program").into(),
+            args: vec![],
+        });
+        assert_eq!(code, NOT_FOUND);
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+//! Named directory bookmarks.
+//!
+//! This module keeps a process-wide table of named directories. Register a
+//! directory with [`set`] and later change to it with [`Pushd::bookmark`].
+//!
+//! With the `bookmarks-file` feature, the table can be saved to and loaded
+//! from a file, by default in the user's config directory, so that bookmarks
+//! can be shared between runs of a program, or between programs.
+//!
+//! # Examples
+//!
+//! ```
+//! use pushd::{bookmarks, Pushd};
+//!
+//! # fn main() -> Result<(), pushd::PushdError> {
+//! bookmarks::set("tmp", std::env::temp_dir());
+//! let _pd = Pushd::bookmark("tmp")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Panicking, Pushd, PushdBuilder, PushdError};
+use std::{
+    collections::BTreeMap,
+    path::{self, Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+static BOOKMARKS: Mutex<BTreeMap<String, PathBuf>> = Mutex::new(BTreeMap::new());
+
+fn table() -> MutexGuard<'static, BTreeMap<String, PathBuf>> {
+    BOOKMARKS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Sets a bookmark with the given name, returning the path that the name was
+/// previously bookmarked to, if any.
+///
+/// A relative path is made absolute against the current directory when this
+/// is called, so the bookmark keeps referring to the same directory after the
+/// current directory changes.
+pub fn set<N: Into<String>, P: AsRef<Path>>(name: N, path: P) -> Option<PathBuf> {
+    let path = path.as_ref();
+    let path = path::absolute(path).unwrap_or_else(|_| path.to_owned());
+    table().insert(name.into(), path)
+}
+
+/// Returns the path for the bookmark with the given name, if there is one.
+#[must_use]
+pub fn get(name: &str) -> Option<PathBuf> {
+    table().get(name).cloned()
+}
+
+/// Removes the bookmark with the given name, returning its path if it
+/// existed.
+#[allow(clippy::must_use_candidate)]
+pub fn remove(name: &str) -> Option<PathBuf> {
+    table().remove(name)
+}
+
+/// Returns all of the bookmarks, sorted by name.
+#[must_use]
+pub fn all() -> Vec<(String, PathBuf)> {
+    table()
+        .iter()
+        .map(|(n, p)| (n.clone(), p.clone()))
+        .collect()
+}
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that changes to the directory for the
+    /// bookmark with the given name. See the [`bookmarks`](crate::bookmarks)
+    /// module for details.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::UnknownBookmark`] error if there is no bookmark with
+    /// the given name. Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn bookmark(name: &str) -> Result<Pushd<Panicking>, PushdError> {
+        let path = get(name).ok_or_else(|| PushdError::UnknownBookmark {
+            name: name.to_owned(),
+        })?;
+        PushdBuilder::new(path).push()
+    }
+}
+
+#[cfg(feature = "bookmarks-file")]
+mod file {
+    use super::table;
+    use crate::PushdError;
+    use std::{
+        fs, io,
+        path::{Path, PathBuf},
+    };
+
+    /// Returns the path of the default bookmark file, which is
+    /// `pushd/bookmarks` in the user's config directory, or `None` if the
+    /// config directory cannot be determined.
+    ///
+    /// This requires the `bookmarks-file` feature.
+    #[must_use]
+    pub fn default_file() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("pushd").join("bookmarks"))
+    }
+
+    /// Loads bookmarks from the [default file](default_file). If the file
+    /// does not exist, this does nothing.
+    ///
+    /// This requires the `bookmarks-file` feature.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user's config directory cannot be determined, or
+    /// if the file exists but cannot be read or parsed.
+    pub fn load() -> Result<(), PushdError> {
+        let file = default_file().ok_or(PushdError::NoConfigDir)?;
+        match load_from(&file) {
+            Err(PushdError::BookmarkFile { source, .. })
+                if source.kind() == io::ErrorKind::NotFound =>
+            {
+                Ok(())
+            }
+            res => res,
+        }
+    }
+
+    /// Saves all bookmarks to the [default file](default_file), creating its
+    /// parent directory if needed.
+    ///
+    /// This requires the `bookmarks-file` feature.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user's config directory cannot be determined, or
+    /// if the file cannot be written.
+    pub fn save() -> Result<(), PushdError> {
+        let file = default_file().ok_or(PushdError::NoConfigDir)?;
+        save_to(file)
+    }
+
+    /// Loads bookmarks from the given file. Each bookmark in the file replaces
+    /// any existing bookmark with the same name.
+    ///
+    /// The file has one bookmark per line, with the name and path separated
+    /// by a tab. Empty lines are ignored.
+    ///
+    /// This requires the `bookmarks-file` feature.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be read or parsed.
+    pub fn load_from<P: AsRef<Path>>(file: P) -> Result<(), PushdError> {
+        let file = file.as_ref();
+        let err = |source| PushdError::BookmarkFile {
+            path: file.to_owned(),
+            source,
+        };
+
+        let content = fs::read_to_string(file).map_err(err)?;
+        let mut loaded = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let Some((name, path)) = line.split_once('\t') else {
+                return Err(err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {} is not a name and path separated by a tab", i + 1),
+                )));
+            };
+            loaded.push((name.to_owned(), PathBuf::from(path)));
+        }
+        table().extend(loaded);
+        Ok(())
+    }
+
+    /// Saves all bookmarks to the given file, creating its parent directory
+    /// if needed. See [`load_from`] for the format of the file.
+    ///
+    /// This requires the `bookmarks-file` feature.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file cannot be written, or if a bookmark cannot
+    /// be stored in the file because its name contains a tab or newline, or its path is not valid
+    /// UTF-8.
+    pub fn save_to<P: AsRef<Path>>(file: P) -> Result<(), PushdError> {
+        let file = file.as_ref();
+        let err = |source| PushdError::BookmarkFile {
+            path: file.to_owned(),
+            source,
+        };
+
+        let mut content = String::new();
+        for (name, path) in table().iter() {
+            if name.contains(['\t', '\n', '\r']) {
+                return Err(err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("the bookmark name {name:?} contains a tab or newline"),
+                )));
+            }
+            let Some(path) = path.to_str().filter(|p| !p.contains(['\n', '\r'])) else {
+                return Err(err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("the path for the bookmark {name:?} cannot be stored in the file"),
+                )));
+            };
+            content.push_str(name);
+            content.push('\t');
+            content.push_str(path);
+            content.push('\n');
+        }
+
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).map_err(err)?;
+        }
+        fs::write(file, content).map_err(err)
+    }
+}
+
+#[cfg(feature = "bookmarks-file")]
+pub use file::{default_file, load, load_from, save, save_to};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn set_and_push() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let td = tempdir()?;
+
+        assert_eq!(set("test-set-and-push", td.path()), None);
+        assert_eq!(set("test-src", "src"), None);
+        assert_eq!(get("test-src"), Some(cwd.join("src")));
+        {
+            let _pd = Pushd::bookmark("test-src")?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert_eq!(remove("test-src"), Some(cwd.join("src")));
+        assert!(matches!(
+            Pushd::bookmark("test-src"),
+            Err(PushdError::UnknownBookmark { name }) if name == "test-src",
+        ));
+        assert!(all().contains(&("test-set-and-push".to_owned(), td.path().to_owned())));
+        remove("test-set-and-push");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "bookmarks-file")]
+    #[test]
+    #[serial]
+    fn file_round_trip() -> Result<(), Box<dyn StdError>> {
+        let td = tempdir()?;
+        let file = td.path().join("sub").join("bookmarks");
+
+        set("test-round-trip", td.path());
+        save_to(&file)?;
+        remove("test-round-trip");
+        load_from(&file)?;
+        assert_eq!(get("test-round-trip"), Some(td.path().to_owned()));
+        remove("test-round-trip");
+
+        std::fs::write(&file, "no-tab\n")?;
+        assert!(matches!(
+            load_from(&file),
+            Err(PushdError::BookmarkFile { path, .. }) if path == file,
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,522 @@
+#[cfg(unix)]
+use crate::secure::HandleChecks;
+#[cfg(feature = "notify")]
+use crate::watch::Watch;
+#[cfg(windows)]
+use crate::windows::DriveOptions;
+use crate::{
+    config, context,
+    cwd_cache::{self, CwdCache},
+    diff::{DiffHandler, Tracking},
+    events::LogOptions,
+    expand::Expand,
+    expanded_target,
+    held::Held,
+    hooks::Hook,
+    CwdBackend, DropBehavior, DropHandler, DropPolicy, LockPolicy, NonPanicking, OutOfOrderPolicy,
+    Panicking, PoisonPolicy, Pushd, PushdError, PwdMode, RetryPolicy, SecurityPolicy,
+};
+use log::Level;
+use std::{
+    fs, io,
+    marker::PhantomData,
+    panic::Location,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// A builder for a [`Pushd`] with non-default options. Create one by calling
+/// [`Pushd::builder`].
+///
+/// Like [`Pushd`], the `B` type parameter records whether the resulting guard
+/// may panic when it is dropped. Calling [`PushdBuilder::no_panic`] or
+/// [`PushdBuilder::handler`] changes it to [`NonPanicking`].
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{ExternalChangePolicy, Pushd};
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let pd = Pushd::builder(std::env::temp_dir())
+///     .no_panic()
+///     .on_external_change(ExternalChangePolicy::Warn)
+///     .push()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PushdBuilder<B: DropBehavior = Panicking> {
+    pub(crate) path: PathBuf,
+    pub(crate) opts: Options,
+    behavior: PhantomData<B>,
+}
+
+// The options that are passed from the builder to the `Pushd` it creates.
+#[derive(Default)]
+pub(crate) struct Options {
+    pub(crate) handler: Option<DropHandler>,
+    pub(crate) backend: Option<Arc<dyn CwdBackend>>,
+    pub(crate) external_change: ExternalChangePolicy,
+    pub(crate) out_of_order: OutOfOrderPolicy,
+    pub(crate) poison: PoisonPolicy,
+    pub(crate) fallbacks: Vec<PathBuf>,
+    pub(crate) restore_to: Option<PathBuf>,
+    pub(crate) original: Option<PathBuf>,
+    pub(crate) base: Option<PathBuf>,
+    pub(crate) restore_retry: Option<RetryPolicy>,
+    pub(crate) enter_retry: Option<RetryPolicy>,
+    pub(crate) pwd: Option<PwdMode>,
+    pub(crate) expand: Expand,
+    pub(crate) tracking: Tracking,
+    pub(crate) diff_handler: Option<DiffHandler>,
+    pub(crate) on_enter: Option<Hook>,
+    pub(crate) on_exit: Option<Hook>,
+    pub(crate) held: Option<Held>,
+    pub(crate) drop_policy: DropPolicy,
+    pub(crate) ignored_drop_errors: Vec<io::ErrorKind>,
+    pub(crate) canonicalize: bool,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) cwd_cache: CwdCache,
+    pub(crate) backtraces: Backtraces,
+    pub(crate) lock: Option<LockPolicy>,
+    pub(crate) context: Option<String>,
+    pub(crate) log: LogOptions,
+    pub(crate) security: Option<SecurityPolicy>,
+    #[cfg(unix)]
+    pub(crate) handle: Option<HandleChecks>,
+    #[cfg(feature = "camino")]
+    pub(crate) require_utf8: bool,
+    #[cfg(target_os = "linux")]
+    pub(crate) thread_scoped: bool,
+    #[cfg(target_os = "linux")]
+    pub(crate) jail: Option<PathBuf>,
+    #[cfg(windows)]
+    pub(crate) drives: DriveOptions,
+    pub(crate) pin_interval: Option<Duration>,
+    pub(crate) timeout: Option<Duration>,
+    #[cfg(feature = "notify")]
+    pub(crate) watch: Option<Watch>,
+}
+
+/// What a [`Pushd`] does when it finds that the current directory was changed
+/// by something else while the guard was alive.
+///
+/// The check happens when the guard is popped, either explicitly or when it
+/// is dropped. If the current directory is no longer the directory the guard
+/// changed to, then some other code must have changed it. Regardless of the
+/// policy, the guard still changes back to its original directory.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ExternalChangePolicy {
+    /// Don't check for external changes at all. This is the default.
+    #[default]
+    Ignore,
+    /// Log a warning if the current directory was changed externally.
+    Warn,
+    /// Return a [`PushdError::CwdChangedExternally`] error from
+    /// [`Pushd::pop`] if the current directory was changed externally. When
+    /// this happens during drop, the error is handled like any other error
+    /// that happens during drop.
+    Error,
+}
+
+// Whether a guard captures backtraces, set with
+// `PushdBuilder::capture_backtraces`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Backtraces {
+    Capture,
+    #[default]
+    Skip,
+}
+
+impl Options {
+    // Returns the directory that a guard changes back to and its target. When
+    // the guard restores to a chosen directory, the target is made absolute so
+    // that it's still resolved against the directory the guard started in.
+    pub(crate) fn restore_dirs(&self, cwd: PathBuf, path: PathBuf) -> (PathBuf, PathBuf) {
+        match &self.restore_to {
+            Some(dir) => (cwd.join(dir), cwd.join(path)),
+            None => (cwd, path),
+        }
+    }
+}
+
+impl PushdBuilder<Panicking> {
+    pub(crate) fn new(path: PathBuf) -> PushdBuilder<Panicking> {
+        let opts = config::with_config(|config| Options {
+            drop_policy: config.default_drop_policy.clone(),
+            canonicalize: config.canonicalize,
+            max_depth: config.max_depth,
+            ignored_drop_errors: config.ignored_drop_errors.clone(),
+            backtraces: if config.capture_backtraces {
+                Backtraces::Capture
+            } else {
+                Backtraces::Skip
+            },
+            cwd_cache: if config.cache_cwd {
+                cwd_cache::enable();
+                CwdCache::Use
+            } else {
+                CwdCache::Skip
+            },
+            ..Options::default()
+        });
+        PushdBuilder {
+            path,
+            opts,
+            behavior: PhantomData,
+        }
+    }
+}
+
+/// This is the same as calling [`Pushd::builder`], except that the path is
+/// moved into the builder instead of being copied.
+impl From<PathBuf> for PushdBuilder<Panicking> {
+    fn from(path: PathBuf) -> Self {
+        PushdBuilder::new(path)
+    }
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`] log a warning instead of panicking when it
+    /// cannot change back to its original directory on drop. This is the
+    /// equivalent of using [`Pushd::new_no_panic`].
+    #[must_use]
+    pub fn no_panic(self) -> PushdBuilder<NonPanicking> {
+        self.cast()
+    }
+
+    /// Makes the resulting [`Pushd`] call the given handler when it cannot
+    /// change back to its original directory on drop. This is the equivalent
+    /// of using [`Pushd::new_with_handler`].
+    #[must_use]
+    pub fn handler<F>(self, handler: F) -> PushdBuilder<NonPanicking>
+    where
+        F: FnOnce(PushdError) + Send + 'static,
+    {
+        let mut b = self.cast();
+        b.opts.handler = Some(Box::new(handler));
+        b
+    }
+
+    /// Sets what the resulting [`Pushd`] does when it cannot change back to its
+    /// original directory when it is dropped, instead of using the
+    /// [crate-wide default](crate::Config::default_drop_policy). See
+    /// [`DropPolicy`] for details.
+    #[must_use]
+    pub fn drop_policy(mut self, policy: DropPolicy) -> Self {
+        self.opts.drop_policy = policy;
+        self
+    }
+
+    /// Sets the kinds of errors that the resulting [`Pushd`] ignores when it
+    /// cannot change back to its original directory when it is dropped with
+    /// [`DropPolicy::Panic`], instead of using the
+    /// [crate-wide default](crate::Config::ignored_drop_errors). Any other
+    /// error follows the drop policy as usual.
+    ///
+    /// The default is just [`io::ErrorKind::NotFound`], so a guard doesn't
+    /// panic when its original directory was deleted. Including
+    /// [`io::ErrorKind::PermissionDenied`] as well is useful when a test's
+    /// temporary directories are locked down as they are torn down.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pushd::Pushd;
+    /// use std::io::ErrorKind;
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// let pd = Pushd::builder(std::env::temp_dir())
+    ///     .ignore_drop_errors([ErrorKind::NotFound, ErrorKind::PermissionDenied])
+    ///     .push()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn ignore_drop_errors<I>(mut self, kinds: I) -> Self
+    where
+        I: IntoIterator<Item = io::ErrorKind>,
+    {
+        self.opts.ignored_drop_errors = kinds.into_iter().collect();
+        self
+    }
+
+    /// Sets the policy for what to do when the current directory was changed
+    /// by something else while the guard was alive. See
+    /// [`ExternalChangePolicy`] for details.
+    #[must_use]
+    pub fn on_external_change(mut self, policy: ExternalChangePolicy) -> Self {
+        self.opts.external_change = policy;
+        self
+    }
+
+    /// Sets a policy for retrying when the [`Pushd`] cannot change back to its
+    /// original directory when it is dropped. The retries happen before any
+    /// [fallback directories](PushdBuilder::fallback_dirs) are tried, and
+    /// before the error is handled by panicking, logging, or calling a
+    /// handler.
+    ///
+    /// This only applies to drop. An explicit call to [`Pushd::pop`] does not
+    /// retry.
+    #[must_use]
+    pub fn restore_retry(mut self, policy: RetryPolicy) -> Self {
+        self.opts.restore_retry = Some(policy);
+        self
+    }
+
+    /// Sets a policy for retrying when the [`Pushd`] cannot change to its
+    /// target. This is useful for a target that is created asynchronously,
+    /// like a network mount or another process's output directory.
+    ///
+    /// Only errors that might go away by waiting are retried, which are a
+    /// target that doesn't exist yet and, on platforms that report it, a
+    /// target that is busy. Anything that checks the target before changing
+    /// to it, like [`PushdBuilder::canonicalize`] and
+    /// [`PushdBuilder::security`], is not retried.
+    #[must_use]
+    pub fn enter_retry(mut self, policy: RetryPolicy) -> Self {
+        self.opts.enter_retry = Some(policy);
+        self
+    }
+
+    /// Adds directories to try, in order, if the [`Pushd`] cannot change back
+    /// to its original directory. This can be called more than once, and each
+    /// call appends to the list.
+    ///
+    /// This lets you make sure that the process isn't left in a deleted
+    /// directory, for example by falling back to a workspace root,
+    /// [`std::env::temp_dir`], or `/`. If the guard changes to one of these
+    /// directories instead of the original, this is considered a success, but
+    /// a warning is logged.
+    #[must_use]
+    pub fn fallback_dirs<I, P>(mut self, dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        self.opts
+            .fallbacks
+            .extend(dirs.into_iter().map(|d| d.as_ref().to_owned()));
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] change to `dir` when it is popped or
+    /// dropped, instead of the directory that was current when it was
+    /// created. A relative `dir` is resolved against the current directory
+    /// when the guard is created. The directory doesn't need to exist until
+    /// the guard changes to it.
+    ///
+    /// [`Pushd::original`] returns `dir`, and [`Pushd::path`] returns the
+    /// target as an absolute path, since the target is still resolved against
+    /// the directory that was current when the guard was created. The
+    /// [fallback directories](PushdBuilder::fallback_dirs) are tried if the
+    /// guard cannot change to `dir`.
+    #[must_use]
+    pub fn restore_to<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.opts.restore_to = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Tells the resulting [`Pushd`] that the current directory is `dir`, so
+    /// that it doesn't need to look it up. This saves a system call per
+    /// guard, which can matter in a loop that creates many guards.
+    ///
+    /// The guard trusts `dir` completely. It uses it as its original
+    /// directory, so it changes back to `dir` when it is popped or dropped,
+    /// and it resolves a relative target against `dir` everywhere except in
+    /// the directory change itself. If `dir` is not actually the current
+    /// directory, the guard's paths will be wrong. A relative `dir` is
+    /// resolved against the current directory, which means that it is looked
+    /// up after all.
+    ///
+    /// The original directory can't be looked up lazily instead, because once
+    /// the guard has changed directories there's no way to find out where it
+    /// came from.
+    #[must_use]
+    pub fn original_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.opts.original = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] resolve a relative target against
+    /// `base` instead of the current directory. This makes where the guard
+    /// goes independent of whatever directory the process happens to be in,
+    /// as long as `base` is absolute. An absolute target is used as is.
+    ///
+    /// The target is joined to `base` after it is
+    /// [expanded](PushdBuilder::expand_tilde) and its
+    /// [`..` components are handled](PushdBuilder::parent_dirs), so
+    /// [`ParentDirPolicy::Reject`](crate::ParentDirPolicy::Reject) keeps the
+    /// target inside `base`.
+    #[must_use]
+    pub fn relative_to<P: AsRef<Path>>(mut self, base: P) -> Self {
+        self.opts.base = Some(base.as_ref().to_owned());
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] resolve its target and original
+    /// directory to canonical, absolute paths with all symlinks resolved, as
+    /// if [`fs::canonicalize`] had been called on them. This means that
+    /// [`Pushd::path`] and [`Pushd::original`] can be compared directly to the
+    /// value of [`env::current_dir`](std::env::current_dir).
+    ///
+    /// This does the same thing for one guard as
+    /// [`Config::canonicalize`](crate::Config::canonicalize) does for all of
+    /// them.
+    #[must_use]
+    pub fn canonicalize(mut self) -> Self {
+        self.opts.canonicalize = true;
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] capture a backtrace when it is created,
+    /// and another one if it fails to change back. These are returned by
+    /// [`PushdError::guard_backtrace`] and [`PushdError::backtrace`].
+    ///
+    /// This does the same thing for one guard as
+    /// [`Config::capture_backtraces`](crate::Config::capture_backtraces) does
+    /// for all of them.
+    #[must_use]
+    pub fn capture_backtraces(mut self) -> Self {
+        self.opts.backtraces = Backtraces::Capture;
+        self
+    }
+
+    /// Makes pushing return a [`PushdError::RelativeTarget`] error if the
+    /// target is a relative path. This catches code that passes a relative
+    /// path by mistake, which would be resolved against whatever the current
+    /// directory is, even if another guard already changed it.
+    ///
+    /// The check is done after [`PushdBuilder::expand_tilde`] and
+    /// [`PushdBuilder::expand_env_vars`], so a target like `~/src` is
+    /// accepted with those. On Windows, a path like `\src` that has a root
+    /// but no drive is relative, since it depends on the current drive.
+    #[must_use]
+    pub fn require_absolute(mut self) -> Self {
+        self.opts.expand.require_absolute = true;
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] log its messages about changing
+    /// directories at `level` instead of [`Level::Debug`], and log all of its
+    /// messages with `target` as their target, so that they can be filtered
+    /// separately from other guards' messages. Warnings and errors are still
+    /// logged at their usual levels.
+    ///
+    /// The messages are still subject to
+    /// [`Config::log_level`](crate::Config::log_level). The target is not
+    /// used when the `tracing` feature is enabled, since `tracing` targets
+    /// must be known at compile time, or when messages are sent to a logger
+    /// set with [`set_logger`](crate::set_logger).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::Level;
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// let pd = Pushd::builder(std::env::temp_dir())
+    ///     .log(Level::Trace, "myapp::fs")
+    ///     .push()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn log<T: Into<String>>(mut self, level: Level, target: T) -> Self {
+        self.opts.log.level = level;
+        self.opts.log.target = Some(Arc::from(target.into()));
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] never log anything, including warnings.
+    /// The work of formatting its messages is skipped too, which can matter
+    /// in a hot loop even when the messages would be filtered out later.
+    ///
+    /// A guard that is quiet and doesn't panic will fail silently if it
+    /// cannot change back to its original directory when it is dropped, so
+    /// consider a [handler](PushdBuilder::handler) as well.
+    #[must_use]
+    pub fn quiet(mut self) -> Self {
+        self.opts.log.quiet = true;
+        self
+    }
+
+    /// Changes the current directory and returns the [`Pushd`] guard.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed.
+    #[track_caller]
+    pub fn push(self) -> Result<Pushd<B>, PushdError> {
+        let context = self.opts.context.clone();
+        Pushd::push(self, Location::caller())
+            .map_err(|e| context::with_context(context.as_deref(), e))
+    }
+
+    /// Checks that the target exists and is a directory, and returns a
+    /// [`DeferredPushd`] that will change to it when
+    /// [`DeferredPushd::enter`] is called. This does not change the current
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the target's metadata cannot be read, or if the target
+    /// is not a directory.
+    pub fn deferred(self) -> Result<DeferredPushd<B>, PushdError> {
+        let path = expanded_target(&self.opts, self.path.clone())?;
+        let md = fs::metadata(&path).map_err(|e| PushdError::Metadata {
+            path: path.clone(),
+            source: e,
+        })?;
+        if !md.is_dir() {
+            return Err(PushdError::NotADirectory { path });
+        }
+        Ok(DeferredPushd { builder: self })
+    }
+
+    pub(crate) fn cast<C: DropBehavior>(self) -> PushdBuilder<C> {
+        PushdBuilder {
+            path: self.path,
+            opts: self.opts,
+            behavior: PhantomData,
+        }
+    }
+}
+
+/// A `DeferredPushd` holds a validated target directory, but does not change
+/// to it until [`DeferredPushd::enter`] is called. Create one with
+/// [`Pushd::deferred`] or [`PushdBuilder::deferred`].
+///
+/// This is useful when the target is known in one phase of a program, but
+/// the directory change should only happen in a later phase.
+pub struct DeferredPushd<B: DropBehavior = Panicking> {
+    builder: PushdBuilder<B>,
+}
+
+impl<B: DropBehavior> DeferredPushd<B> {
+    /// Returns the path that this `DeferredPushd` will change to.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.builder.path
+    }
+
+    /// Changes the current directory and returns the [`Pushd`] guard, which
+    /// will change back to the directory that was current when this method
+    /// was called.
+    ///
+    /// If the target is a relative path, it is resolved against the current
+    /// directory when this method is called, not when the `DeferredPushd` was
+    /// created.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed.
+    #[track_caller]
+    pub fn enter(self) -> Result<Pushd<B>, PushdError> {
+        self.builder.push()
+    }
+}
@@ -0,0 +1,80 @@
+// This module contains the `cap-std` integration, which is only compiled when
+// the `cap-std` feature is enabled.
+
+use crate::{DropBehavior, Panicking, Pushd, PushdBuilder, PushdError};
+use cap_std::{ambient_authority, fs::Dir};
+use std::path::Path;
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct and also opens a [`cap_std::fs::Dir`]
+    /// for the target directory.
+    ///
+    /// This lets you use capability-based file operations on the target
+    /// directory, which are not affected by later changes to the current
+    /// directory, while still supporting code that relies on the process's
+    /// current directory.
+    ///
+    /// Other than returning the `Dir`, this behaves exactly like
+    /// [`Pushd::new`]. This requires the `cap-std` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed or if the target directory
+    /// cannot be opened. If the directory cannot be opened, the guard changes back to the original
+    /// directory before this method returns.
+    #[track_caller]
+    pub fn new_with_dir<P: AsRef<Path>>(path: P) -> Result<(Pushd<Panicking>, Dir), PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned()).push_with_dir()
+    }
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Changes the current directory and returns the [`Pushd`] guard along
+    /// with a [`cap_std::fs::Dir`] for the target directory.
+    ///
+    /// This requires the `cap-std` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed or if the target directory
+    /// cannot be opened.
+    #[track_caller]
+    pub fn push_with_dir(self) -> Result<(Pushd<B>, Dir), PushdError> {
+        let pd = self.push()?;
+        // We open the current directory rather than the target path, so that
+        // the `Dir` is guaranteed to refer to the directory we changed to,
+        // even if the target was a relative path.
+        let dir =
+            Dir::open_ambient_dir(".", ambient_authority()).map_err(|e| PushdError::OpenDir {
+                path: pd.path().to_owned(),
+                source: e,
+            })?;
+        Ok((pd, dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn new_with_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        fs::write(td.path().join("file"), "content")?;
+
+        let (mut pd, dir) = Pushd::new_with_dir(td.path())?;
+        pd.pop()?;
+        // The `Dir` still refers to the target after we change back.
+        assert_eq!(dir.read_to_string("file")?, "content");
+
+        Ok(())
+    }
+}
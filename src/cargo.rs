@@ -0,0 +1,68 @@
+// This module contains the `cargo_metadata` integration, which is only
+// compiled when the `cargo` feature is enabled.
+
+use crate::{Panicking, Pushd, PushdBuilder, PushdError};
+use cargo_metadata::MetadataCommand;
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that changes to the directory of the
+    /// workspace member with the given package name. This is the directory
+    /// that contains the package's `Cargo.toml`.
+    ///
+    /// The workspace is found by running `cargo metadata` in the current
+    /// directory, so Cargo must be installed, and the current directory must
+    /// be somewhere in the workspace.
+    ///
+    /// This requires the `cargo` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::CargoMetadata`] error if `cargo metadata` fails,
+    /// and a [`PushdError::UnknownPackage`] error if no workspace member has the given name.
+    /// Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn package(name: &str) -> Result<Pushd<Panicking>, PushdError> {
+        let metadata = MetadataCommand::new()
+            .no_deps()
+            .exec()
+            .map_err(|e| PushdError::CargoMetadata { source: e })?;
+        let dir = metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|p| p.name.as_ref() == name)
+            .and_then(|p| p.manifest_path.parent())
+            .ok_or_else(|| PushdError::UnknownPackage {
+                name: name.to_owned(),
+            })?
+            .to_owned();
+        PushdBuilder::new(dir.into_std_path_buf()).push()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+
+    #[test]
+    #[serial]
+    fn package() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = fs::canonicalize(".")?;
+        {
+            let _pd = Pushd::package("pushd-macros")?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                cwd.join("pushd-macros")
+            );
+        }
+        assert!(matches!(
+            Pushd::package("no-such-package"),
+            Err(PushdError::UnknownPackage { name }) if name == "no-such-package",
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+// This module contains `PushdChain`, a sequence of guards where each one
+// changes to a directory relative to the one before it.
+
+use crate::{DropBehavior, Panicking, Pushd, PushdBuilder, PushdError};
+use std::{fs, path::Path};
+
+/// A `PushdChain` is a sequence of [`Pushd`] guards, where each step changes
+/// to a directory relative to the previous one. Create one with
+/// [`Pushd::chain`] or [`Pushd::chain_created`].
+///
+/// When the chain is popped or dropped, it changes back through each step in
+/// reverse order, just like a set of nested guards. If one of the steps fails
+/// when the chain is created, the steps that already succeeded are rolled
+/// back before the error is returned.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// # let td = tempfile::tempdir().unwrap();
+/// # let _pd = Pushd::new(td.path())?;
+/// let _chain = Pushd::chain_created(["a", "b/c", "d"])?;
+/// assert!(std::env::current_dir().unwrap().ends_with("a/b/c/d"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PushdChain<B: DropBehavior = Panicking> {
+    guards: Vec<Pushd<B>>,
+}
+
+impl Pushd<Panicking> {
+    /// Constructs a new [`PushdChain`] that changes to each of the given
+    /// directories in order. A relative step is resolved against the
+    /// directory of the step before it.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::new`] for the first step that fails. In that
+    /// case, all of the earlier steps are rolled back first.
+    #[track_caller]
+    pub fn chain<I, P>(steps: I) -> Result<PushdChain<Panicking>, PushdError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        PushdChain::push(steps, false)
+    }
+
+    /// Like [`Pushd::chain`], but creates the directory for each step, and any
+    /// missing parents, before changing to it.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::CreateDir`] error if a directory cannot be created.
+    /// Otherwise it returns the same errors as [`Pushd::chain`].
+    #[track_caller]
+    pub fn chain_created<I, P>(steps: I) -> Result<PushdChain<Panicking>, PushdError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        PushdChain::push(steps, true)
+    }
+}
+
+impl PushdChain<Panicking> {
+    #[track_caller]
+    fn push<I, P>(steps: I, create: bool) -> Result<PushdChain<Panicking>, PushdError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        // If a step fails, returning drops the chain, which rolls back the
+        // steps that succeeded.
+        let mut chain = PushdChain { guards: Vec::new() };
+        for step in steps {
+            let step = step.as_ref();
+            if create {
+                fs::create_dir_all(step).map_err(|e| PushdError::CreateDir {
+                    path: step.to_owned(),
+                    source: e,
+                })?;
+            }
+            chain
+                .guards
+                .push(PushdBuilder::new(step.to_owned()).push()?);
+        }
+        Ok(chain)
+    }
+}
+
+impl<B: DropBehavior> PushdChain<B> {
+    /// Returns the guards for each step of the chain, in order.
+    #[must_use]
+    pub fn guards(&self) -> &[Pushd<B>] {
+        &self.guards
+    }
+
+    /// Changes back through each step of the chain in reverse order, ending
+    /// in the directory that was current when the chain was created. Like
+    /// [`Pushd::pop`], calling this more than once does nothing after the
+    /// first call.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the first error returned by [`Pushd::pop`] for a step. The steps before
+    /// that one are left in place, and they will be popped when the chain is dropped.
+    pub fn pop(&mut self) -> Result<(), PushdError> {
+        for g in self.guards.iter_mut().rev() {
+            g.pop()?;
+        }
+        Ok(())
+    }
+}
+
+impl<B: DropBehavior> Drop for PushdChain<B> {
+    /// Changes back through each step of the chain in reverse order. Each
+    /// guard handles errors as it would when dropped on its own.
+    fn drop(&mut self) {
+        while let Some(g) = self.guards.pop() {
+            drop(g);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn chain() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let td_path = fs::canonicalize(td.path())?;
+        let _pd = Pushd::new(&td_path)?;
+
+        {
+            let chain = Pushd::chain_created(["a", "b/c", "d"])?;
+            assert_eq!(env::current_dir()?, td_path.join("a/b/c/d"));
+            let guards = chain.guards();
+            assert_eq!(guards.len(), 3);
+            assert_eq!(guards[1].original(), td_path.join("a"));
+            assert_eq!(guards[2].original(), td_path.join("a/b/c"));
+        }
+        assert_eq!(env::current_dir()?, td_path);
+
+        let mut chain = Pushd::chain(["a", "b"])?;
+        assert_eq!(env::current_dir()?, td_path.join("a/b"));
+        chain.pop()?;
+        assert_eq!(env::current_dir()?, td_path);
+
+        // A failing step rolls back the earlier ones.
+        assert!(matches!(
+            Pushd::chain(["a", "b", "missing"]),
+            Err(PushdError::TargetNotFound { path, .. }) if path == Path::new("missing"),
+        ));
+        assert_eq!(env::current_dir()?, td_path);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+// This module contains the `ScopedChroot` guard, which is only available on
+// Unix.
+
+use crate::{
+    chdir, config, current_dir, cwd_cache, events, secure, DropBehavior, DropPolicy, NonPanicking,
+    Panicking, PushdError,
+};
+use std::{
+    ffi::CString,
+    fs::File,
+    io,
+    marker::PhantomData,
+    os::{fd::AsRawFd, unix::ffi::OsStrExt},
+    panic::Location,
+    path::{Path, PathBuf},
+};
+
+/// A `ScopedChroot` changes the process's root directory to a directory, and
+/// its current directory to the new root, when it's created. It changes both
+/// back when it's dropped.
+///
+/// The guard keeps the original root and current directory open, so it can
+/// return to them even though they are outside of the new root.
+///
+/// Calling `chroot` requires privileges, usually root or the
+/// `CAP_SYS_CHROOT` capability on Linux. Note that the root directory is
+/// shared by every thread in the process, and that paths held by other
+/// guards, like the original directory of a [`Pushd`](crate::Pushd), are not
+/// valid inside the new root.
+///
+/// Like [`Pushd`](crate::Pushd), the `B` type parameter records whether the
+/// guard may panic when it is dropped. When the guard cannot leave the new
+/// root it follows the [`Config::default_drop_policy`](crate::Config), and a
+/// guard created with [`ScopedChroot::new_no_panic`] never panics.
+///
+/// This is only available on Unix.
+///
+/// # Examples
+///
+/// ```no_run
+/// use pushd::ScopedChroot;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let _root = ScopedChroot::new("/srv/jail")?;
+/// // "/" is /srv/jail here.
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use = "the root directory is changed back as soon as the guard is dropped"]
+pub struct ScopedChroot<B: DropBehavior = Panicking> {
+    root: PathBuf,
+    old_root: File,
+    old_cwd: File,
+    restored: bool,
+    caller: &'static Location<'static>,
+    behavior: PhantomData<B>,
+}
+
+impl ScopedChroot<Panicking> {
+    /// Changes the root directory to `path`, and the current directory to
+    /// the new root, and returns a guard that changes both back when it is
+    /// dropped. A relative `path` is resolved against the current directory.
+    ///
+    /// # Errors
+    ///
+    /// This returns a [`PushdError::OpenDir`] error if the original root or
+    /// current directory cannot be opened, and a [`PushdError::Chroot`] error
+    /// if the root directory cannot be changed, for example because the
+    /// process doesn't have permission to call `chroot`.
+    #[track_caller]
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<ScopedChroot<Panicking>, PushdError> {
+        ScopedChroot::enter(path.as_ref(), Location::caller())
+    }
+}
+
+impl ScopedChroot<NonPanicking> {
+    /// Changes the root directory to `path`, like [`ScopedChroot::new`], and
+    /// returns a guard that will never panic when it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This returns the same errors as [`ScopedChroot::new`].
+    #[track_caller]
+    pub fn new_no_panic<P: AsRef<Path>>(path: P) -> Result<ScopedChroot<NonPanicking>, PushdError> {
+        ScopedChroot::enter(path.as_ref(), Location::caller())
+    }
+}
+
+impl<B: DropBehavior> ScopedChroot<B> {
+    fn enter(
+        path: &Path,
+        caller: &'static Location<'static>,
+    ) -> Result<ScopedChroot<B>, PushdError> {
+        let root = current_dir()?.join(path);
+        let open = |dir: &Path| {
+            secure::open_dir(dir).map_err(|source| PushdError::OpenDir {
+                path: dir.to_owned(),
+                source,
+            })
+        };
+        let old_root = open(Path::new("/"))?;
+        let old_cwd = open(Path::new("."))?;
+        chroot(&root).map_err(|source| PushdError::Chroot {
+            path: root.clone(),
+            source,
+        })?;
+        let mut guard = ScopedChroot {
+            root,
+            old_root,
+            old_cwd,
+            restored: false,
+            caller,
+            behavior: PhantomData,
+        };
+        if let Err(source) = chdir(Path::new("/")) {
+            // Leave the new root before reporting the error, so that a guard
+            // which failed to enter doesn't leave the process in it.
+            let _ = guard.restore();
+            return Err(PushdError::Chroot {
+                path: guard.root.clone(),
+                source,
+            });
+        }
+        Ok(guard)
+    }
+
+    /// Returns the directory that this guard made the root directory, as it
+    /// was named outside of the new root.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Changes the root and current directories back to what they were when
+    /// this guard was created. If this method is called repeatedly it will not
+    /// do anything on subsequent calls, even if the first call failed.
+    ///
+    /// # Errors
+    ///
+    /// This returns a [`PushdError::RestoreRoot`] error if the original root
+    /// or current directory cannot be restored.
+    pub fn restore(&mut self) -> Result<(), PushdError> {
+        if self.restored {
+            return Ok(());
+        }
+        self.restored = true;
+        cwd_cache::clear_cwd_cache();
+        // Changing to the original root, which is outside of the new one,
+        // and then calling `chroot(".")` is the only way to leave a chroot.
+        fchdir(&self.old_root)
+            .and_then(|()| chroot(Path::new(".")))
+            .and_then(|()| fchdir(&self.old_cwd))
+            .map_err(|source| PushdError::RestoreRoot {
+                path: self.root.clone(),
+                source,
+            })
+    }
+}
+
+impl<B: DropBehavior> Drop for ScopedChroot<B> {
+    /// Changes the root and current directories back. If this fails, the
+    /// guard follows the [`Config::default_drop_policy`](crate::Config) in the
+    /// same way that a [`Pushd`](crate::Pushd) does, except that a guard
+    /// created with [`ScopedChroot::new_no_panic`] logs the error instead of
+    /// panicking.
+    fn drop(&mut self) {
+        let Err(e) = self.restore() else {
+            return;
+        };
+        let config = config::config();
+        match &config.default_drop_policy {
+            DropPolicy::Ignore => {}
+            DropPolicy::Callback(callback) => callback(e),
+            #[cfg(not(feature = "no-drop-panic"))]
+            DropPolicy::Panic if B::MAY_PANIC => {
+                if config.ignored_drop_errors.iter().any(|k| e.is_kind(*k)) {
+                    return;
+                }
+                panic!(
+                    "Could not leave the new root for the guard created at {}: {e}",
+                    self.caller,
+                );
+            }
+            DropPolicy::Panic | DropPolicy::Warn => events::chroot_restore_failed(&e, self.caller),
+        }
+    }
+}
+
+fn chroot(path: &Path) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `path` is a valid NUL-terminated string.
+    if unsafe { libc::chroot(path.as_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn fchdir(dir: &File) -> io::Result<()> {
+    // SAFETY: `dir` is an open file descriptor, which `fchdir` only reads.
+    if unsafe { libc::fchdir(dir.as_raw_fd()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn scoped_chroot() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let td = tempdir()?;
+        fs::create_dir(td.path().join("inner"))?;
+
+        // SAFETY: `geteuid` always succeeds.
+        if unsafe { libc::geteuid() } != 0 {
+            let res = ScopedChroot::new(td.path());
+            assert!(
+                matches!(&res, Err(PushdError::Chroot { source, .. }) if source.kind() == io::ErrorKind::PermissionDenied),
+                "{res:?}",
+            );
+            return Ok(());
+        }
+
+        let mut guard = ScopedChroot::new(td.path())?;
+        assert_eq!(guard.root(), td.path());
+        assert_eq!(env::current_dir()?, Path::new("/"));
+        assert!(Path::new("/inner").is_dir());
+        guard.restore()?;
+        assert_eq!(env::current_dir()?, cwd);
+        assert!(td.path().join("inner").is_dir());
+
+        assert!(matches!(
+            ScopedChroot::new("no-such-dir"),
+            Err(PushdError::Chroot { .. }),
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+// This module contains the helpers for running commands in a directory
+// without changing the current directory of this process.
+
+use crate::{DropBehavior, Pushd};
+use std::{ffi::OsStr, path::Path, process::Command};
+
+/// Returns a [`Command`] for the given program which will run in `dir`.
+///
+/// This is a shortcut for calling [`Command::new`] followed by
+/// [`Command::current_dir`]. The current directory of this process is never
+/// changed, so this is a good alternative to a [`Pushd`] when you are only
+/// changing directories to run a subprocess.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> std::io::Result<()> {
+/// let status = pushd::cmd("cargo", "some/crate").arg("build").status()?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn cmd<S: AsRef<OsStr>, P: AsRef<Path>>(program: S, dir: P) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.current_dir(dir);
+    cmd
+}
+
+/// An extension trait for [`Command`] which configures it to run in a
+/// [`Pushd`]'s target directory.
+pub trait CommandExt {
+    /// Sets the command's working directory to the guard's target. The
+    /// target is resolved to an absolute path when the guard is created, so
+    /// this is not affected by later changes to the current directory, or by
+    /// the guard being popped.
+    fn pushd<B: DropBehavior>(&mut self, pd: &Pushd<B>) -> &mut Self;
+
+    /// Like [`CommandExt::pushd`], but also sets the `PWD` environment
+    /// variable for the command to the guard's target. If the guard was built
+    /// with [`PushdBuilder::update_pwd`](crate::PushdBuilder::update_pwd) in
+    /// [logical mode](crate::PwdMode::Logical), this is the logical path.
+    fn pushd_with_pwd<B: DropBehavior>(&mut self, pd: &Pushd<B>) -> &mut Self;
+}
+
+impl CommandExt for Command {
+    fn pushd<B: DropBehavior>(&mut self, pd: &Pushd<B>) -> &mut Self {
+        self.current_dir(pd.anchor.path())
+    }
+
+    fn pushd_with_pwd<B: DropBehavior>(&mut self, pd: &Pushd<B>) -> &mut Self {
+        let pwd = pd
+            .pwd
+            .as_ref()
+            .and_then(|p| p.logical())
+            .unwrap_or(pd.anchor.path());
+        self.pushd(pd).env("PWD", pwd)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn command_in_pushd() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let td_path = fs::canonicalize(td.path())?;
+
+        let out = cmd("pwd", td.path()).arg("-P").output()?;
+        assert_eq!(
+            String::from_utf8(out.stdout)?.trim_end(),
+            td_path.to_str().unwrap()
+        );
+
+        let mut pd = Pushd::new(&td_path)?;
+        pd.pop()?;
+        let out = Command::new("sh")
+            .args(["-c", "echo \"$PWD\"; pwd -P"])
+            .pushd_with_pwd(&pd)
+            .output()?;
+        let expect = format!("{0}\n{0}\n", td_path.display());
+        assert_eq!(String::from_utf8(out.stdout)?, expect);
+
+        Ok(())
+    }
+}
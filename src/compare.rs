@@ -0,0 +1,112 @@
+// This module compares directories, and simplifies paths for display, in a
+// way that knows about the symlinks that macOS has at the top of the
+// filesystem. On macOS, `/tmp`, `/var`, and `/etc` are symlinks into
+// `/private`, so the same directory can show up under two names, depending
+// on whether the path came from the OS or from the user.
+
+use std::{borrow::Cow, fmt, fs, path::Path};
+
+// The top-level directories that are symlinks into `/private` on macOS.
+#[cfg(target_os = "macos")]
+const PRIVATE_LINKS: &[&str] = &["etc", "tmp", "var"];
+
+// Returns true if both paths refer to the same directory. This is checked
+// lexically first, and then by canonicalizing both paths if that fails. On
+// Windows, the lexical check ignores case and the difference between `/` and
+// `\`, and the paths are compared by their file IDs before canonicalizing.
+pub(crate) fn same_dir(a: &Path, b: &Path) -> bool {
+    if a == b || simplify(a) == simplify(b) {
+        return true;
+    }
+    #[cfg(windows)]
+    if crate::windows::same_path(a, b) {
+        return true;
+    }
+    #[cfg(windows)]
+    if let Some(same) = crate::windows::same_file(a, b) {
+        return same;
+    }
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Returns the path without the `/private` prefix that the OS adds on macOS
+// to paths under `/tmp`, `/var`, and `/etc`. Everywhere else this returns the
+// path as is.
+pub(crate) fn simplify(path: &Path) -> Cow<'_, Path> {
+    #[cfg(target_os = "macos")]
+    if let Ok(rest) = path.strip_prefix("/private") {
+        let link = rest
+            .components()
+            .next()
+            .and_then(|c| c.as_os_str().to_str());
+        if link.is_some_and(|l| PRIVATE_LINKS.contains(&l)) {
+            return Cow::Owned(Path::new("/").join(rest));
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+// A path to include in a message, simplified with `simplify`.
+pub(crate) struct Shown<'a>(Cow<'a, Path>);
+
+pub(crate) fn shown(path: &Path) -> Shown<'_> {
+    Shown(simplify(path))
+}
+
+impl fmt::Display for Shown<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.display().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn same_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        assert!(super::same_dir(&cwd, &cwd));
+        assert!(super::same_dir(&cwd.join("src").join(".."), &cwd));
+        assert!(super::same_dir(Path::new("."), &cwd));
+        assert!(!super::same_dir(&cwd.join("src"), &cwd));
+        assert!(!super::same_dir(&cwd.join("no-such-dir"), &cwd));
+
+        let tmp = env::temp_dir();
+        assert!(super::same_dir(&fs::canonicalize(&tmp)?, &tmp));
+
+        Ok(())
+    }
+
+    #[test]
+    fn simplify() {
+        #[cfg(target_os = "macos")]
+        {
+            assert_eq!(
+                super::simplify(Path::new("/private/tmp/x")),
+                Path::new("/tmp/x")
+            );
+            assert_eq!(
+                super::simplify(Path::new("/private/var")),
+                Path::new("/var")
+            );
+            assert_eq!(
+                super::simplify(Path::new("/private/other")),
+                Path::new("/private/other")
+            );
+        }
+        assert_eq!(
+            super::simplify(Path::new("/usr/lib")),
+            Path::new("/usr/lib")
+        );
+        assert_eq!(shown(Path::new("src")).to_string(), "src");
+    }
+}
@@ -0,0 +1,303 @@
+// This module contains the constructors which only change directories when
+// some condition holds, and otherwise return a guard that does nothing.
+
+use crate::{
+    backtrace, builder::Backtraces, compare, events, expanded_target, files, nesting, original_dir,
+    DropBehavior, Panicking, Pushd, PushdBuilder, PushdError,
+};
+use std::{
+    marker::PhantomData,
+    panic::Location,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that changes to the target if it is an
+    /// existing directory. If it is not, this returns a guard that does
+    /// nothing, as if it had already been popped. Use [`Pushd::is_noop`] to
+    /// tell the two apart.
+    ///
+    /// This is useful for optional directories, like a plugin directory that
+    /// may not exist, without having to juggle an `Option<Pushd>`.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be
+    /// determined. If the target exists, it returns the same errors as
+    /// [`Pushd::new`].
+    #[track_caller]
+    pub fn new_if_exists<P: AsRef<Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned()).push_if_exists()
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the target only if
+    /// `cond` is true. Otherwise this returns a guard that does nothing, as if
+    /// it had already been popped. Use [`Pushd::is_noop`] to tell the two
+    /// apart.
+    ///
+    /// This is useful when changing directories is controlled by something
+    /// like a command line flag.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be
+    /// determined. If `cond` is true, it returns the same errors as
+    /// [`Pushd::new`].
+    #[track_caller]
+    pub fn new_if<P: AsRef<Path>>(cond: bool, path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned()).push_if(cond)
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the target unless the
+    /// current directory is already the target. If it is, this returns a
+    /// guard that does nothing, as if it had already been popped. Use
+    /// [`Pushd::is_noop`] to tell the two apart.
+    ///
+    /// The two are compared by their canonical paths, so this also detects
+    /// a target that is a symlink to the current directory. This is useful
+    /// for code that changes to the same directory over and over, like a
+    /// recursive task runner, since a guard that does nothing is cheaper to
+    /// create and drop.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be
+    /// determined. If the current directory is not the target, it returns the
+    /// same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn new_unless_here<P: AsRef<Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned()).push_unless_here()
+    }
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Like [`PushdBuilder::push`], but if the target is not an existing
+    /// directory, this returns a guard that does nothing. See
+    /// [`Pushd::new_if_exists`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::new_if_exists`].
+    #[track_caller]
+    pub fn push_if_exists(self) -> Result<Pushd<B>, PushdError> {
+        let target = expanded_target(&self.opts, self.path.clone())?;
+        let exists = self.opts.is_dir(&target);
+        self.push_if(exists)
+    }
+
+    /// Like [`PushdBuilder::push`], but if `cond` is false, this returns a
+    /// guard that does nothing. See [`Pushd::new_if`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::new_if`].
+    #[track_caller]
+    pub fn push_if(self, cond: bool) -> Result<Pushd<B>, PushdError> {
+        if cond {
+            return self.push();
+        }
+        let cwd = original_dir(&self.opts)?;
+        Ok(Pushd::noop(self, cwd, Location::caller()))
+    }
+
+    /// Like [`PushdBuilder::push`], but if the current directory is already
+    /// the target, this returns a guard that does nothing. See
+    /// [`Pushd::new_unless_here`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::new_unless_here`].
+    #[track_caller]
+    pub fn push_unless_here(self) -> Result<Pushd<B>, PushdError> {
+        let cwd = original_dir(&self.opts)?;
+        let target = cwd.join(expanded_target(&self.opts, self.path.clone())?);
+        if !compare::same_dir(&target, &cwd) {
+            return self.push();
+        }
+        Ok(Pushd::noop(self, cwd, Location::caller()))
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Returns true if this guard was created by a constructor like
+    /// [`Pushd::new_if_exists`], [`Pushd::new_if`], or
+    /// [`Pushd::new_unless_here`] without changing directories. Such a guard
+    /// is always popped, and [`Pushd::reenter`] does nothing for it.
+    #[must_use]
+    pub fn is_noop(&self) -> bool {
+        self.noop
+    }
+
+    fn noop(
+        builder: PushdBuilder<B>,
+        cwd: PathBuf,
+        caller: &'static Location<'static>,
+    ) -> Pushd<B> {
+        let target = builder.path;
+        Pushd {
+            id: nesting::next_id(),
+            span: events::span(&cwd, &target, caller, &builder.opts.log),
+            anchor: files::Anchor::unopened(cwd.join(&target)),
+            orig: cwd,
+            target,
+            popped: true,
+            noop: true,
+            caller,
+            created_at: Instant::now(),
+            entered_at: Instant::now(),
+            backtrace: backtrace(builder.opts.backtraces == Backtraces::Capture),
+            opts: builder.opts,
+            entered: None,
+            pwd: None,
+            tracker: None,
+            diff: None,
+            #[cfg(windows)]
+            mapped: None,
+            #[cfg(windows)]
+            drive_dirs: None,
+            pinner: None,
+            timer: None,
+            #[cfg(feature = "notify")]
+            watchdog: None,
+            lock: None,
+            behavior: PhantomData,
+            not_send: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeCwd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn new_if_exists() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        {
+            let pd = Pushd::new_if_exists("src")?;
+            assert!(!pd.is_noop());
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        {
+            let mut pd = Pushd::new_if_exists("no-such-dir")?;
+            assert!(pd.is_noop());
+            assert!(pd.is_popped());
+            assert_eq!(env::current_dir()?, cwd);
+            pd.reenter()?;
+            pd.pop()?;
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn push_if_exists_resolves_target() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        env::set_current_dir("src")?;
+        {
+            let pd = Pushd::builder("src").relative_to(&cwd).push_if_exists()?;
+            assert!(!pd.is_noop());
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        env::set_current_dir(&cwd)?;
+
+        let fake = FakeCwd::new().with_dir("/a/b");
+        {
+            let pd = Pushd::builder("/a/b")
+                .backend(fake.clone())
+                .push_if_exists()?;
+            assert!(!pd.is_noop());
+            assert_eq!(fake.cwd(), Path::new("/a/b"));
+        }
+        {
+            let pd = Pushd::builder("src")
+                .backend(fake.clone())
+                .push_if_exists()?;
+            assert!(pd.is_noop());
+        }
+        assert_eq!(fake.cwd(), Path::new("/"));
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn new_if() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        {
+            let _pd = Pushd::new_if(true, "src")?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        {
+            let pd = Pushd::new_if(false, "src")?;
+            assert!(pd.is_noop());
+            assert_eq!(env::current_dir()?, cwd);
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn new_unless_here() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        {
+            let pd = Pushd::new_unless_here("src/..")?;
+            assert!(pd.is_noop());
+            assert_eq!(env::current_dir()?, cwd);
+        }
+        {
+            let pd = Pushd::new_unless_here("src")?;
+            assert!(!pd.is_noop());
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn push_unless_here_with_backend() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let fake = FakeCwd::new().with_dir("/a/b").starting_in("/a");
+        {
+            let pd = Pushd::builder(".")
+                .backend(fake.clone())
+                .push_unless_here()?;
+            assert!(pd.is_noop());
+            assert_eq!(pd.original(), Path::new("/a"));
+        }
+        {
+            let pd = Pushd::builder("b")
+                .relative_to("/a")
+                .backend(fake.clone())
+                .push_unless_here()?;
+            assert!(!pd.is_noop());
+            assert_eq!(fake.cwd(), Path::new("/a/b"));
+        }
+        assert_eq!(fake.cwd(), Path::new("/a"));
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
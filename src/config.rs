@@ -0,0 +1,485 @@
+// This module contains the crate-wide configuration set with
+// `pushd::configure`.
+
+use crate::PushdError;
+use log::{Level, LevelFilter};
+use std::{
+    env, fmt, io,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, PoisonError, RwLock,
+    },
+};
+
+/// What a [`Pushd`](crate::Pushd) does when it cannot change back to its
+/// original directory when it is dropped. Set the default for the process
+/// with [`configure`], or set it for one guard with
+/// [`PushdBuilder::drop_policy`](crate::PushdBuilder::drop_policy).
+///
+/// A guard with a [handler](crate::PushdBuilder::handler) always calls its
+/// handler instead of following its policy.
+#[derive(Clone, Default)]
+pub enum DropPolicy {
+    /// Panic with the error, unless its kind is one of the guard's
+    /// [ignored kinds](crate::PushdBuilder::ignore_drop_errors), in which case
+    /// the error is ignored. By default, that is only
+    /// [`io::ErrorKind::NotFound`], for an original directory that no longer
+    /// exists. A guard that cannot panic, like one
+    /// returned by [`Pushd::new_no_panic`](crate::Pushd::new_no_panic), logs a
+    /// warning instead. This is the default.
+    #[default]
+    Panic,
+    /// Log a warning, as if the guard had been created with
+    /// [`Pushd::new_no_panic`](crate::Pushd::new_no_panic).
+    Warn,
+    /// Do nothing at all, not even logging.
+    Ignore,
+    /// Call the closure with the error. Unlike a
+    /// [handler](crate::PushdBuilder::handler), this closure can be shared by
+    /// any number of guards. Use [`DropPolicy::callback`] to create this.
+    Callback(DropCallback),
+}
+
+/// The type of the closure in a [`DropPolicy::Callback`].
+pub type DropCallback = Arc<dyn Fn(PushdError) + Send + Sync>;
+
+impl DropPolicy {
+    /// Returns a [`DropPolicy::Callback`] policy that calls the given closure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pushd::{DropPolicy, Pushd};
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// // Ignore an original directory that was deleted, but warn about
+    /// // everything else.
+    /// let policy = DropPolicy::callback(|e| {
+    ///     if !e.is_not_found() {
+    ///         eprintln!("{e}");
+    ///     }
+    /// });
+    /// let _pd = Pushd::builder(std::env::temp_dir())
+    ///     .drop_policy(policy)
+    ///     .push()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn callback<F>(callback: F) -> DropPolicy
+    where
+        F: Fn(PushdError) + Send + Sync + 'static,
+    {
+        DropPolicy::Callback(Arc::new(callback))
+    }
+}
+
+impl fmt::Debug for DropPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DropPolicy::Panic => f.write_str("Panic"),
+            DropPolicy::Warn => f.write_str("Warn"),
+            DropPolicy::Ignore => f.write_str("Ignore"),
+            DropPolicy::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
+// Two callback policies are equal if they share the same closure.
+impl PartialEq for DropPolicy {
+    fn eq(&self, other: &DropPolicy) -> bool {
+        match (self, other) {
+            (DropPolicy::Panic, DropPolicy::Panic)
+            | (DropPolicy::Warn, DropPolicy::Warn)
+            | (DropPolicy::Ignore, DropPolicy::Ignore) => true,
+            (DropPolicy::Callback(a), DropPolicy::Callback(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for DropPolicy {}
+
+/// The crate-wide configuration. Set it with [`configure`].
+///
+/// If [`configure`] hasn't been called, the configuration is read from the
+/// environment the first time it is needed. See [`Config::from_env`].
+///
+/// All of the settings apply to the guards created after [`configure`] is
+/// called, except for `log_level`, which applies to all logging from that
+/// point on.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{Config, DropPolicy};
+///
+/// pushd::configure(Config {
+///     default_drop_policy: DropPolicy::Warn,
+///     ..Config::default()
+/// });
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Config {
+    /// What a guard does when it cannot change back to its original directory
+    /// when it is dropped, unless the guard has its own
+    /// [policy](crate::PushdBuilder::drop_policy). See [`DropPolicy`] for
+    /// details.
+    pub default_drop_policy: DropPolicy,
+    /// The most verbose level that this crate logs at. Messages that are more
+    /// verbose than this are not logged at all. This is applied before the
+    /// logger's own filtering, so it can make the crate quieter, but not more
+    /// verbose, than the logger is configured to be. The default is
+    /// [`LevelFilter::Trace`], which does no filtering.
+    ///
+    /// This applies with the `tracing` feature as well.
+    pub log_level: LevelFilter,
+    /// Makes every guard resolve its target and original directory to
+    /// canonical, absolute paths with all symlinks resolved, as if
+    /// [`fs::canonicalize`](std::fs::canonicalize) had been called on them. This
+    /// means that [`Pushd::path`](crate::Pushd::path) and
+    /// [`Pushd::original`](crate::Pushd::original) can be compared directly
+    /// to the value of [`env::current_dir`](std::env::current_dir). The
+    /// default is false.
+    pub canonicalize: bool,
+    /// The most guards that can be in their target directories at once. Once
+    /// this many guards have changed directories without changing back,
+    /// creating another one returns a [`PushdError::TooDeep`] error. This
+    /// turns runaway recursion that creates a guard at each level into an
+    /// error that says what went wrong. The default is `None`, which means
+    /// there is no limit.
+    pub max_depth: Option<usize>,
+    /// The kinds of errors that a guard with [`DropPolicy::Panic`] ignores
+    /// when it cannot change back on drop, unless the guard has its own
+    /// [set](crate::PushdBuilder::ignore_drop_errors). The default is just
+    /// [`io::ErrorKind::NotFound`].
+    pub ignored_drop_errors: Vec<io::ErrorKind>,
+    /// Makes every guard use the directory that this crate last changed to on
+    /// the same thread as its original directory, instead of asking the OS
+    /// for the current directory, unless the guard was built with
+    /// [`PushdBuilder::no_cwd_cache`](crate::PushdBuilder::no_cwd_cache). See
+    /// [`PushdBuilder::cache_cwd`](crate::PushdBuilder::cache_cwd) for
+    /// details. The default is false.
+    pub cache_cwd: bool,
+    /// Makes errors capture a backtrace when they are created, and every
+    /// guard capture one when it is created, unless the guard was built with
+    /// [`PushdBuilder::capture_backtraces`](crate::PushdBuilder::capture_backtraces).
+    /// See [`PushdError::backtrace`] for details. The default is false, since
+    /// capturing a backtrace is slow.
+    pub capture_backtraces: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            default_drop_policy: DropPolicy::default(),
+            log_level: LevelFilter::Trace,
+            canonicalize: false,
+            max_depth: None,
+            ignored_drop_errors: vec![io::ErrorKind::NotFound],
+            cache_cwd: false,
+            capture_backtraces: false,
+        }
+    }
+}
+
+impl Config {
+    /// Returns the default configuration, with any settings from these
+    /// environment variables applied:
+    ///
+    /// * `PUSHD_NO_PANIC` - if this is `1`, `true`, or `yes`, then
+    ///   `default_drop_policy` is [`DropPolicy::Warn`]. If it is `0`, `false`,
+    ///   or `no`, then it is [`DropPolicy::Panic`].
+    /// * `PUSHD_LOG` - sets `log_level`. This can be `off`, `error`, `warn`,
+    ///   `info`, `debug`, or `trace`.
+    /// * `PUSHD_CANONICALIZE` - sets `canonicalize`. This takes the same values
+    ///   as `PUSHD_NO_PANIC`.
+    /// * `PUSHD_MAX_DEPTH` - sets `max_depth`. This must be a number.
+    /// * `PUSHD_CACHE_CWD` - sets `cache_cwd`. This takes the same values as
+    ///   `PUSHD_NO_PANIC`.
+    /// * `PUSHD_BACKTRACE` - sets `capture_backtraces`. This takes the same
+    ///   values as `PUSHD_NO_PANIC`.
+    ///
+    /// The values are not case-sensitive. A variable that is unset or has any
+    /// other value is ignored.
+    ///
+    /// This lets someone running a program change its behavior without
+    /// rebuilding it, for example to get more information about a failure
+    /// that only happens in CI.
+    #[must_use]
+    pub fn from_env() -> Config {
+        let mut config = Config::default();
+        match env_flag("PUSHD_NO_PANIC") {
+            Some(true) => config.default_drop_policy = DropPolicy::Warn,
+            Some(false) => config.default_drop_policy = DropPolicy::Panic,
+            None => {}
+        }
+        if let Some(level) = env::var("PUSHD_LOG").ok().and_then(|l| l.parse().ok()) {
+            config.log_level = level;
+        }
+        if let Some(canonicalize) = env_flag("PUSHD_CANONICALIZE") {
+            config.canonicalize = canonicalize;
+        }
+        if let Some(cache) = env_flag("PUSHD_CACHE_CWD") {
+            config.cache_cwd = cache;
+        }
+        if let Some(capture) = env_flag("PUSHD_BACKTRACE") {
+            config.capture_backtraces = capture;
+        }
+        if let Some(max) = env::var("PUSHD_MAX_DEPTH")
+            .ok()
+            .and_then(|m| m.parse().ok())
+        {
+            config.max_depth = Some(max);
+        }
+        config
+    }
+}
+
+fn env_flag(name: &str) -> Option<bool> {
+    match env::var(name).ok()?.to_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+static CONFIG: RwLock<Option<Config>> = RwLock::new(None);
+
+// A copy of the configuration's `log_level`, so that checking whether a
+// message should be logged doesn't need to take the lock. This is
+// `LOG_LEVEL_UNSET` until the configuration is set or read from the
+// environment.
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LOG_LEVEL_UNSET);
+const LOG_LEVEL_UNSET: u8 = u8::MAX;
+
+/// Sets the crate-wide configuration. This can be called more than once, and
+/// each call replaces the entire configuration, including any settings that
+/// were read from the environment. To keep those, start from
+/// [`Config::from_env`] instead of [`Config::default`].
+///
+/// This is meant to be called by an application, typically in `main`. That
+/// lets it change the behavior of guards that are created by library code it
+/// can't change.
+pub fn configure(config: Config) {
+    let mut current = CONFIG.write().unwrap_or_else(PoisonError::into_inner);
+    LOG_LEVEL.store(config.log_level as u8, Ordering::Relaxed);
+    *current = Some(config);
+}
+
+/// Returns the current crate-wide configuration.
+#[must_use]
+pub fn config() -> Config {
+    with_config(Config::clone)
+}
+
+// Calls `f` with the current configuration, reading it from the environment
+// first if it hasn't been set. This lets the crate look at a few settings
+// without cloning all of them. The lock is held while `f` runs, so `f` must
+// not call back into code that might call `configure`.
+pub(crate) fn with_config<T>(f: impl FnOnce(&Config) -> T) -> T {
+    if let Some(config) = &*CONFIG.read().unwrap_or_else(PoisonError::into_inner) {
+        return f(config);
+    }
+    let mut current = CONFIG.write().unwrap_or_else(PoisonError::into_inner);
+    let config = current.get_or_insert_with(|| {
+        let config = Config::from_env();
+        LOG_LEVEL.store(config.log_level as u8, Ordering::Relaxed);
+        config
+    });
+    f(config)
+}
+
+// Returns true if messages at the given level should be logged.
+pub(crate) fn log_enabled(level: Level) -> bool {
+    let mut max = LOG_LEVEL.load(Ordering::Relaxed);
+    if max == LOG_LEVEL_UNSET {
+        max = with_config(|c| c.log_level as u8);
+    }
+    level as u8 <= max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pushd, ScopedEnv};
+    use serial_test::serial;
+    #[cfg(all(not(target_os = "windows"), not(feature = "no-drop-panic")))]
+    use std::panic::{self, AssertUnwindSafe};
+    #[cfg(not(target_os = "windows"))]
+    use std::sync::Mutex;
+    use std::{error::Error as StdError, fs};
+    #[cfg(not(target_os = "windows"))]
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn canonicalize() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        configure(Config {
+            canonicalize: true,
+            ..Config::default()
+        });
+        let res = Pushd::new("src/..");
+        configure(Config::default());
+
+        let pd = res?;
+        assert_eq!(pd.path(), fs::canonicalize(".")?);
+        assert_eq!(pd.original(), fs::canonicalize(".")?);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn max_depth() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let active = crate::stats().active;
+        configure(Config {
+            max_depth: Some(active + 1),
+            ..Config::default()
+        });
+        let first = Pushd::new(".");
+        let second = Pushd::new(".");
+        configure(Config::default());
+
+        let _first = first?;
+        assert!(
+            matches!(second, Err(PushdError::TooDeep { max }) if max == active + 1),
+            "{:?}",
+            second.err(),
+        );
+        let _second = Pushd::new(".")?;
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn drop_policy() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+
+        configure(Config {
+            default_drop_policy: DropPolicy::Warn,
+            log_level: LevelFilter::Off,
+            ..Config::default()
+        });
+        let res = Pushd::new(td.path());
+        configure(Config::default());
+
+        // Replacing the original directory with a file means that changing
+        // back fails with an error other than "not found", so the guard
+        // would panic without the policy.
+        let pd = res?;
+        fs::remove_dir(&orig)?;
+        fs::write(&orig, "")?;
+        drop(pd);
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        assert_eq!(config(), Config::default());
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn guard_drop_policy() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let callback = {
+            let errors = Arc::clone(&errors);
+            DropPolicy::callback(move |e| errors.lock().unwrap().push(e))
+        };
+        for policy in [DropPolicy::Ignore, callback] {
+            env::set_current_dir(&orig)?;
+            let pd = Pushd::builder(td.path()).drop_policy(policy).push()?;
+            fs::remove_dir(&orig)?;
+            fs::write(&orig, "")?;
+            drop(pd);
+            fs::remove_file(&orig)?;
+            fs::create_dir(&orig)?;
+        }
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].is_restore_failure());
+
+        Ok(())
+    }
+
+    #[cfg(all(not(target_os = "windows"), not(feature = "no-drop-panic")))]
+    #[test]
+    #[serial]
+    fn ignore_drop_errors() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+
+        // Changing back to a file fails with `NotADirectory`, which would
+        // panic without being ignored.
+        let pd = Pushd::builder(td.path())
+            .ignore_drop_errors([io::ErrorKind::NotADirectory])
+            .push()?;
+        fs::remove_dir(&orig)?;
+        fs::write(&orig, "")?;
+        drop(pd);
+        fs::remove_file(&orig)?;
+
+        // Removing `NotFound` from the set means a deleted original directory
+        // panics.
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+        let pd = Pushd::builder(td.path()).ignore_drop_errors([]).push()?;
+        fs::remove_dir(&orig)?;
+        let res = panic::catch_unwind(AssertUnwindSafe(|| drop(pd)));
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn from_env() -> Result<(), Box<dyn StdError>> {
+        let mut env = ScopedEnv::new();
+        env.set("PUSHD_NO_PANIC", "Yes")?
+            .set("PUSHD_LOG", "warn")?
+            .set("PUSHD_CANONICALIZE", "bogus")?
+            .set("PUSHD_MAX_DEPTH", "100")?
+            .set("PUSHD_CACHE_CWD", "yes")?;
+        let config = Config::from_env();
+        env.restore();
+
+        assert_eq!(
+            config,
+            Config {
+                default_drop_policy: DropPolicy::Warn,
+                log_level: LevelFilter::Warn,
+                canonicalize: false,
+                max_depth: Some(100),
+                ignored_drop_errors: vec![io::ErrorKind::NotFound],
+                cache_cwd: true,
+                capture_backtraces: false,
+            },
+        );
+        assert_eq!(Config::from_env(), Config::default());
+
+        Ok(())
+    }
+}
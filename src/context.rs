@@ -0,0 +1,116 @@
+// This module contains the context descriptions that can be attached to a
+// guard, and the errors it returns, with `context`.
+
+use crate::{DropBehavior, Pushd, PushdBuilder, PushdError};
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Sets a description of what the resulting [`Pushd`] is for, like
+    /// `"while generating docs"`. This is the same as calling
+    /// [`Pushd::context`] on the guard, except that it also applies to an
+    /// error from creating the guard.
+    #[must_use]
+    pub fn context<C: Into<String>>(mut self, context: C) -> Self {
+        self.opts.context = Some(context.into());
+        self
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Sets a description of what this `Pushd` is for, like `"while
+    /// generating docs"`, and returns the guard.
+    ///
+    /// Any error returned by the guard after this is wrapped in a
+    /// [`PushdError::Context`] error, so the context is included in the
+    /// error's message. This includes the error that is logged, passed to a
+    /// handler, or included in the panic message if the guard cannot change
+    /// back to its original directory when it is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// let _pd = Pushd::new(std::env::temp_dir())?.context("while generating docs");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn context<C: Into<String>>(mut self, context: C) -> Self {
+        self.opts.context = Some(context.into());
+        self
+    }
+
+    pub(crate) fn with_context(&self, e: PushdError) -> PushdError {
+        with_context(self.opts.context.as_deref(), e)
+    }
+}
+
+pub(crate) fn with_context(context: Option<&str>, e: PushdError) -> PushdError {
+    match context {
+        Some(context) => PushdError::Context {
+            context: context.to_owned(),
+            source: Box::new(e),
+        },
+        None => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Pushd, PushdError};
+    use serial_test::serial;
+    #[cfg(not(target_os = "windows"))]
+    use std::fs;
+    use std::{env, error::Error as StdError};
+    #[cfg(not(target_os = "windows"))]
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn context() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let Err(e) = Pushd::builder("no-such-dir")
+            .context("while testing")
+            .push()
+        else {
+            return Err("changed to a directory that does not exist".into());
+        };
+        assert!(matches!(
+            &e,
+            PushdError::Context { context, source }
+                if context == "while testing"
+                    && matches!(**source, PushdError::TargetNotFound { .. }),
+        ));
+        assert!(e.to_string().starts_with("while testing: Could not set"));
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn context_on_pop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+
+        let mut pd = Pushd::new(td.path())?.context("while testing");
+        fs::remove_dir(&orig)?;
+        fs::write(&orig, "")?;
+        let res = pd.pop();
+        pd.forget();
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let Err(e) = res else {
+            return Err("changed back to a file".into());
+        };
+        assert!(e.to_string().starts_with("while testing: Could not set"));
+
+        Ok(())
+    }
+}
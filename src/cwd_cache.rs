@@ -0,0 +1,193 @@
+// This module remembers the last directory that this crate changed to on
+// each thread, so that a guard created inside another guard doesn't need to
+// ask the OS for the current directory that the crate just set.
+//
+// The cache is only trusted while a guard is in its target, and until
+// anything else in the crate changes directories, on any thread, since the
+// current directory is shared by the whole process. Code outside the crate
+// that changes directories while a guard is alive can't be detected, which is
+// why the cache is only used when it is turned on. Until a guard or the
+// crate's `Config` turns it on, directory changes aren't recorded at all.
+//
+// The cached directory is the path that the crate changed to, joined to the
+// previous one, with no symlinks resolved. It names the same directory that
+// `getcwd` would return, but it may be spelled differently.
+
+use crate::{nesting, DropBehavior, PushdBuilder};
+use std::{
+    cell::RefCell,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+// Counts every directory change made by the crate. A cached directory is
+// only valid if it was recorded by the most recent change.
+static CHANGES: AtomicU64 = AtomicU64::new(0);
+
+// Whether any guard has been built that may use the cache. Once this is true,
+// it stays true.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static LAST: RefCell<Option<(u64, PathBuf)>> = const { RefCell::new(None) };
+}
+
+// Whether a guard may use the cached directory as its original directory.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum CwdCache {
+    Use,
+    #[default]
+    Skip,
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`](crate::Pushd) use the directory that this
+    /// crate last changed to on the same thread as its original directory,
+    /// instead of asking the OS for the current directory. This is the
+    /// equivalent of setting [`Config::cache_cwd`](crate::Config::cache_cwd)
+    /// for just this guard.
+    ///
+    /// The cache is only used while another guard is in its target, so this
+    /// saves a `getcwd` call for each nested guard, which adds up when a
+    /// program creates many of them. It is forgotten whenever this crate
+    /// changes directories on another thread, but it can't tell when code
+    /// outside of this crate changes the current directory with
+    /// [`std::env::set_current_dir`]. Only use this if nothing else changes
+    /// the current directory while guards are alive, or call
+    /// [`clear_cwd_cache`] after it does.
+    ///
+    /// The cached directory is the path that this crate changed to, without
+    /// resolving any symlinks in it. That means that if the current
+    /// directory was reached through a symlink, then
+    /// [`Pushd::original`](crate::Pushd::original) is the path through the
+    /// symlink, rather than the path that
+    /// [`std::env::current_dir`] would return.
+    ///
+    /// Until a guard is built with this option, or
+    /// [`Config::cache_cwd`](crate::Config::cache_cwd) is set, the crate
+    /// doesn't record the directories it changes to, so the first guard that
+    /// uses the cache always asks the OS.
+    #[must_use]
+    pub fn cache_cwd(mut self) -> Self {
+        enable();
+        self.opts.cwd_cache = CwdCache::Use;
+        self
+    }
+
+    /// Makes the resulting [`Pushd`](crate::Pushd) always ask the OS for the
+    /// current directory, even if
+    /// [`Config::cache_cwd`](crate::Config::cache_cwd) is set. See
+    /// [`PushdBuilder::cache_cwd`] for details.
+    #[must_use]
+    pub fn no_cwd_cache(mut self) -> Self {
+        self.opts.cwd_cache = CwdCache::Skip;
+        self
+    }
+}
+
+/// Forgets the directory that this crate last changed to, on every thread.
+/// Call this after changing the current directory without this crate while a
+/// guard is alive, so that the next guard asks the OS for the current
+/// directory. See [`PushdBuilder::cache_cwd`] for details.
+pub fn clear_cwd_cache() {
+    CHANGES.fetch_add(1, Ordering::SeqCst);
+}
+
+// Starts recording the directories that the crate changes to, for a guard
+// that may use the cache.
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+// Records that the current directory was changed to `path`, which is relative
+// to the previous current directory if it isn't absolute. If the cache isn't
+// enabled, this only invalidates whatever was cached.
+pub(crate) fn record(path: &Path) {
+    let prev = CHANGES.fetch_add(1, Ordering::SeqCst);
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    LAST.with_borrow_mut(|last| {
+        let cwd = if path.is_absolute() {
+            Some(path.to_owned())
+        } else {
+            last.take()
+                .filter(|(change, _)| *change == prev)
+                .map(|(_, cwd)| cwd.join(path))
+        };
+        // A `..` might go through a symlink, so we only know where it leads
+        // after asking the OS.
+        *last = cwd
+            .filter(|cwd| !cwd.components().any(|c| c == Component::ParentDir))
+            .map(|cwd| (prev + 1, cwd));
+    });
+}
+
+// Returns the directory that this crate last changed to on this thread, if
+// a guard is in its target and nothing else in the crate has changed
+// directories since.
+pub(crate) fn get() -> Option<PathBuf> {
+    if !nesting::any_active() {
+        return None;
+    }
+    let changes = CHANGES.load(Ordering::SeqCst);
+    LAST.with_borrow(|last| {
+        last.as_ref()
+            .filter(|(change, _)| *change == changes)
+            .map(|(_, cwd)| cwd.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configure, Config, Pushd};
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn cwd_cache() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let src = cwd.join("src");
+        let _outer = Pushd::builder(&src).cache_cwd().push()?;
+        assert_eq!(get(), Some(src.clone()));
+        let inner = Pushd::builder("bin").cache_cwd().push()?;
+        assert_eq!(inner.original(), src);
+        assert_eq!(get(), Some(src.join("bin")));
+        drop(inner);
+        assert_eq!(get(), Some(src.clone()));
+
+        // A change made by another thread invalidates the cache.
+        std::thread::spawn(|| drop(Pushd::new("."))).join().unwrap();
+        assert_eq!(get(), None);
+
+        // A change made outside the crate while a guard is alive is only
+        // noticed without the cache, which is off by default.
+        let _pd = Pushd::new(&src)?;
+        env::set_current_dir(&cwd)?;
+        assert_eq!(get(), Some(src.clone()));
+        assert_eq!(Pushd::new(".")?.original(), cwd);
+
+        env::set_current_dir(&src)?;
+        configure(Config {
+            cache_cwd: true,
+            ..Config::default()
+        });
+        let res = Pushd::builder(".")
+            .no_cwd_cache()
+            .push()
+            .map(|pd| pd.original().to_owned());
+        configure(Config::default());
+        assert_eq!(res?, src);
+
+        env::set_current_dir(&cwd)?;
+        clear_cwd_cache();
+        assert_eq!(get(), None);
+        assert_eq!(Pushd::builder(".").cache_cwd().push()?.original(), cwd);
+
+        Ok(())
+    }
+}
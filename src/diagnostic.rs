@@ -0,0 +1,205 @@
+// This module implements `miette::Diagnostic` for `PushdError` when the
+// `miette` feature is enabled.
+
+use crate::PushdError;
+use miette::Diagnostic;
+use std::fmt::Display;
+
+// The error code for each variant. These are part of the public API, so they
+// should not change once they've been released.
+fn code(e: &PushdError) -> &'static str {
+    match e {
+        PushdError::Context { source, .. } => code(source),
+        PushdError::GetCurrentDir { .. } => "pushd::get_current_dir",
+        PushdError::SetCurrentDir { .. } => "pushd::set_current_dir",
+        PushdError::TargetNotFound { .. } => "pushd::target_not_found",
+        PushdError::PermissionDenied { .. } => "pushd::permission_denied",
+        PushdError::RestoreFailed { .. } => "pushd::restore_failed",
+        PushdError::CwdChangedExternally { .. } => "pushd::cwd_changed_externally",
+        PushdError::OutOfOrder { .. } => "pushd::out_of_order",
+        PushdError::TooDeep { .. } => "pushd::too_deep",
+        PushdError::Poisoned { .. } => "pushd::poisoned",
+        PushdError::GuardsLeaked { .. } => "pushd::guards_leaked",
+        PushdError::CwdLeaked { .. } => "pushd::cwd_leaked",
+        PushdError::ExitCheck => "pushd::exit_check",
+        PushdError::Locked { .. } => "pushd::locked",
+        PushdError::Lock { .. } => "pushd::lock",
+        PushdError::Metadata { .. } => "pushd::metadata",
+        PushdError::NotADirectory { .. } => "pushd::not_a_directory",
+        PushdError::TargetRemoved { .. } => "pushd::target_removed",
+        #[cfg(feature = "notify")]
+        PushdError::Watch { .. } => "pushd::watch",
+        PushdError::OpenDir { .. } => "pushd::open_dir",
+        #[cfg(unix)]
+        PushdError::Fork { .. } => "pushd::fork",
+        #[cfg(unix)]
+        PushdError::Chroot { .. } => "pushd::chroot",
+        #[cfg(unix)]
+        PushdError::RestoreRoot { .. } => "pushd::restore_root",
+        #[cfg(all(feature = "signals", any(unix, windows)))]
+        PushdError::Signal { .. } => "pushd::signal",
+        #[cfg(target_os = "linux")]
+        PushdError::Unshare { .. } => "pushd::unshare",
+        #[cfg(target_os = "linux")]
+        PushdError::OutsideJail { .. } => "pushd::outside_jail",
+        #[cfg(windows)]
+        PushdError::MapNetworkDrive { .. } => "pushd::map_network_drive",
+        #[cfg(windows)]
+        PushdError::NoFreeDriveLetter { .. } => "pushd::no_free_drive_letter",
+        #[cfg(windows)]
+        PushdError::UnmapNetworkDrive { .. } => "pushd::unmap_network_drive",
+        #[cfg(windows)]
+        PushdError::SubstDrive { .. } => "pushd::subst_drive",
+        #[cfg(windows)]
+        PushdError::UnsubstDrive { .. } => "pushd::unsubst_drive",
+        PushdError::InsecureDir { .. } => "pushd::insecure_dir",
+        PushdError::NonUtf8Path { .. } => "pushd::non_utf8_path",
+        PushdError::RelativeTarget { .. } => "pushd::relative_target",
+        PushdError::ParentDir { .. } => "pushd::parent_dir",
+        #[cfg(feature = "typed-path")]
+        PushdError::ForeignPath { .. } => "pushd::foreign_path",
+        PushdError::EmptyStack => "pushd::empty_stack",
+        PushdError::ShellFormat { .. } => "pushd::shell_format",
+        PushdError::NoStackEntry { .. } => "pushd::no_stack_entry",
+        PushdError::NoPreviousDir => "pushd::no_previous_dir",
+        PushdError::ExpandTilde { .. } => "pushd::expand_tilde",
+        PushdError::UnsetEnvVar { .. } => "pushd::unset_env_var",
+        #[cfg(feature = "dirs")]
+        PushdError::NoKnownDir { .. } => "pushd::no_known_dir",
+        PushdError::CreateDir { .. } => "pushd::create_dir",
+        PushdError::MissingEnvVar { .. } => "pushd::missing_env_var",
+        PushdError::InvalidEnvDir { .. } => "pushd::invalid_env_dir",
+        PushdError::NoCandidateDir { .. } => "pushd::no_candidate_dir",
+        #[cfg(feature = "glob")]
+        PushdError::InvalidGlob { .. } => "pushd::invalid_glob",
+        #[cfg(feature = "glob")]
+        PushdError::NoGlobMatch { .. } => "pushd::no_glob_match",
+        #[cfg(feature = "glob")]
+        PushdError::AmbiguousGlob { .. } => "pushd::ambiguous_glob",
+        #[cfg(feature = "walkdir")]
+        PushdError::WalkTree { .. } => "pushd::walk_tree",
+        PushdError::NoRootFound { .. } => "pushd::no_root_found",
+        #[cfg(feature = "cargo")]
+        PushdError::CargoMetadata { .. } => "pushd::cargo_metadata",
+        #[cfg(feature = "cargo")]
+        PushdError::UnknownPackage { .. } => "pushd::unknown_package",
+        #[cfg(feature = "run")]
+        PushdError::ParseCommand { .. } => "pushd::parse_command",
+        #[cfg(feature = "run")]
+        PushdError::RunCommand { .. } => "pushd::run_command",
+        #[cfg(feature = "run")]
+        PushdError::CommandFailed { .. } => "pushd::command_failed",
+        PushdError::Snapshot { .. } => "pushd::snapshot",
+        PushdError::NoHistoryMatch { .. } => "pushd::no_history_match",
+        PushdError::UnknownBookmark { .. } => "pushd::unknown_bookmark",
+        #[cfg(feature = "bookmarks-file")]
+        PushdError::BookmarkFile { .. } => "pushd::bookmark_file",
+        #[cfg(feature = "bookmarks-file")]
+        PushdError::NoConfigDir => "pushd::no_config_dir",
+        PushdError::InvalidEnvVar { .. } => "pushd::invalid_env_var",
+        #[cfg(feature = "temp")]
+        PushdError::CreateTempDir { .. } => "pushd::create_temp_dir",
+        #[cfg(feature = "temp")]
+        PushdError::CopyFixture { .. } => "pushd::copy_fixture",
+        #[cfg(feature = "temp")]
+        PushdError::CreateLayout { .. } => "pushd::create_layout",
+        #[cfg(feature = "tokio")]
+        PushdError::BlockingTask { .. } => "pushd::blocking_task",
+        #[cfg(feature = "async")]
+        PushdError::BlockingTaskDropped => "pushd::blocking_task_dropped",
+    }
+}
+
+fn help(e: &PushdError) -> Option<&'static str> {
+    let help = match e {
+        PushdError::Context { source, .. } => return help(source),
+        PushdError::RestoreFailed { .. } => {
+            "the original directory may have been deleted; \
+             consider new_no_panic or fallback dirs"
+        }
+        PushdError::TargetNotFound { .. } => "check that the directory exists",
+        PushdError::PermissionDenied { .. } => {
+            "check that you have permission to enter the directory and all of its parents"
+        }
+        PushdError::CwdChangedExternally { .. } => {
+            "something else changed the current directory while the guard was alive; \
+             use ExternalChangePolicy::Warn or Ignore to allow this"
+        }
+        PushdError::OutOfOrder { .. } => {
+            "pop or drop guards in the opposite order that they were created"
+        }
+        PushdError::TooDeep { .. } => {
+            "this is usually caused by recursion that creates a guard at each level"
+        }
+        PushdError::Poisoned { .. } => {
+            "code panicked while it held a guard; call pushd::clear_poison once things are back \
+             in order"
+        }
+        PushdError::GuardsLeaked { .. } => {
+            "drop or pop every guard before the check; pushd::active says where each was created"
+        }
+        PushdError::Locked { .. } => {
+            "another guard is in the directory; use LockPolicy::Wait to wait for it"
+        }
+        PushdError::NotADirectory { .. } => "a guard can only change to a directory",
+        PushdError::TargetRemoved { .. } => {
+            "something deleted the directory while the guard was in it"
+        }
+        PushdError::RelativeTarget { .. } => {
+            "join the path to a known directory, like the one from env!(\"CARGO_MANIFEST_DIR\")"
+        }
+        #[cfg(unix)]
+        PushdError::Chroot { .. } => "changing the root directory requires root or CAP_SYS_CHROOT",
+        PushdError::EmptyStack => "call pushd before calling popd",
+        PushdError::NoPreviousDir => "a guard can only go back after it has changed directories",
+        PushdError::MissingEnvVar { .. } => "this variable is normally set by Cargo",
+        _ => return None,
+    };
+    Some(help)
+}
+
+impl Diagnostic for PushdError {
+    fn code<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(code(self)))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        help(self).map(|h| Box::new(h) as Box<dyn Display>)
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        match self {
+            PushdError::Context { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Pushd;
+    use miette::Diagnostic;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn code_and_help() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let Err(e) = Pushd::builder("no-such-dir")
+            .context("while testing")
+            .push()
+        else {
+            return Err("changed to a directory that does not exist".into());
+        };
+        assert_eq!(
+            Diagnostic::code(&e).map(|c| c.to_string()).as_deref(),
+            Some("pushd::target_not_found"),
+        );
+        assert!(Diagnostic::help(&e).is_some());
+        assert!(e.diagnostic_source().is_some());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+// This module contains the support for reporting which files changed in the
+// target directory while a guard was alive, and for removing the files that
+// were created.
+
+use crate::{builder::Options, events, DropBehavior, Pushd, PushdBuilder, PushdError};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// A report of the files and directories that were created, modified, or
+/// deleted in a [`Pushd`]'s target directory while the guard was alive.
+///
+/// All of the paths are relative to the target directory, and each list is
+/// sorted. A directory is only reported as modified if it was replaced with
+/// something that isn't a directory, so creating a file in a directory does
+/// not make that directory show up as modified.
+///
+/// Get one by building the guard with [`PushdBuilder::track_changes`] or
+/// [`PushdBuilder::on_changes`]. With the `serde` feature, this implements
+/// `Serialize` and `Deserialize`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct DirDiff {
+    /// The paths which did not exist when the guard was created.
+    pub created: Vec<PathBuf>,
+    /// The paths whose size, modification time, or type changed.
+    pub modified: Vec<PathBuf>,
+    /// The paths which no longer exist.
+    pub deleted: Vec<PathBuf>,
+}
+
+impl DirDiff {
+    /// Returns true if nothing changed.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+// How much change tracking a guard does. Cleaning implies tracking.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum Tracking {
+    #[default]
+    Off,
+    Report,
+    Clean,
+}
+
+pub(crate) type DiffHandler = Box<dyn FnOnce(DirDiff) + Send>;
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`] take a snapshot of every entry under the
+    /// target directory, recursively, when it changes to it. When the guard is
+    /// popped, it compares the directory to the snapshot, and the resulting
+    /// [`DirDiff`] is available from [`Pushd::changes`].
+    ///
+    /// Taking the snapshot means reading the metadata for everything under
+    /// the target, so this can be slow for large directory trees.
+    #[must_use]
+    pub fn track_changes(mut self) -> Self {
+        self.opts.tracking = self.opts.tracking.max(Tracking::Report);
+        self
+    }
+
+    /// Like [`PushdBuilder::track_changes`], but also calls the given handler
+    /// with the [`DirDiff`] when the guard is popped or dropped.
+    #[must_use]
+    pub fn on_changes<F>(mut self, handler: F) -> Self
+    where
+        F: FnOnce(DirDiff) + Send + 'static,
+    {
+        self.opts.tracking = self.opts.tracking.max(Tracking::Report);
+        self.opts.diff_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] take a snapshot of the target directory
+    /// like [`PushdBuilder::track_changes`] does, and remove everything that
+    /// was created under the target while the guard was alive when it is
+    /// popped or dropped. Files that existed when the guard was created are
+    /// left alone, even if they were modified or deleted.
+    ///
+    /// This is useful for keeping a shared fixture directory unchanged
+    /// between tests. If something cannot be removed, a warning is logged and
+    /// the guard still changes back to its original directory.
+    #[must_use]
+    pub fn clean_on_pop(mut self) -> Self {
+        self.opts.tracking = Tracking::Clean;
+        self
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Returns the changes to the target directory, if the guard was built
+    /// with [`PushdBuilder::track_changes`], [`PushdBuilder::on_changes`], or
+    /// [`PushdBuilder::clean_on_pop`] and has been popped. The changes are
+    /// computed when the guard is popped, before it changes back to the
+    /// original directory.
+    #[must_use]
+    pub fn changes(&self) -> Option<&DirDiff> {
+        self.diff.as_ref()
+    }
+}
+
+// The state needed to compute a `DirDiff`.
+pub(crate) struct Tracker {
+    root: PathBuf,
+    snapshot: Snapshot,
+    handler: Option<DiffHandler>,
+    clean: bool,
+    quiet: bool,
+}
+
+impl Tracker {
+    // Returns a tracker for `root` if the options turn tracking on.
+    pub(crate) fn for_options(
+        opts: &mut Options,
+        root: PathBuf,
+    ) -> Result<Option<Tracker>, PushdError> {
+        if opts.tracking == Tracking::Off {
+            return Ok(None);
+        }
+        let handler = opts.diff_handler.take();
+        Tracker::new(
+            root,
+            handler,
+            opts.tracking == Tracking::Clean,
+            opts.log.quiet,
+        )
+        .map(Some)
+    }
+
+    fn new(
+        root: PathBuf,
+        handler: Option<DiffHandler>,
+        clean: bool,
+        quiet: bool,
+    ) -> Result<Tracker, PushdError> {
+        let snapshot = Snapshot::take(&root).map_err(|e| PushdError::Snapshot {
+            path: root.clone(),
+            source: e,
+        })?;
+        Ok(Tracker {
+            root,
+            snapshot,
+            handler,
+            clean,
+            quiet,
+        })
+    }
+
+    // Compares the directory to the snapshot, and removes anything that was
+    // created if the guard cleans on pop. If the directory can't be read, this
+    // logs a warning and returns `None`.
+    pub(crate) fn finish(&mut self) -> Option<DirDiff> {
+        let now = match Snapshot::take(&self.root) {
+            Ok(s) => s,
+            // If the whole directory is gone, then everything was deleted.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Snapshot::default(),
+            Err(e) => {
+                if !self.quiet {
+                    events::snapshot_failed(&self.root, &e);
+                }
+                return None;
+            }
+        };
+        let diff = self.snapshot.diff(&now);
+        if self.clean {
+            self.clean(&diff);
+        }
+        if let Some(handler) = self.handler.take() {
+            handler(diff.clone());
+        }
+        Some(diff)
+    }
+
+    fn clean(&self, diff: &DirDiff) {
+        // The paths are sorted, so a created directory comes before anything
+        // created inside it, and removing the directory removes those too.
+        let mut removed: Option<&Path> = None;
+        for path in &diff.created {
+            if removed.is_some_and(|r| path.starts_with(r)) {
+                continue;
+            }
+            let full = self.root.join(path);
+            let res = match fs::symlink_metadata(&full) {
+                Ok(md) if md.is_dir() => fs::remove_dir_all(&full),
+                Ok(_) => fs::remove_file(&full),
+                Err(e) => Err(e),
+            };
+            match res {
+                Ok(()) => removed = Some(path),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) if !self.quiet => events::clean_failed(&full, &e),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+struct Entry {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+// The metadata for every entry under a directory, keyed by the path relative
+// to that directory.
+#[derive(Default)]
+pub(crate) struct Snapshot(BTreeMap<PathBuf, Entry>);
+
+impl Snapshot {
+    pub(crate) fn take(root: &Path) -> io::Result<Snapshot> {
+        let mut snapshot = Snapshot::default();
+        snapshot.walk(root, Path::new(""))?;
+        Ok(snapshot)
+    }
+
+    fn walk(&mut self, root: &Path, rel: &Path) -> io::Result<()> {
+        for entry in fs::read_dir(root.join(rel))? {
+            let entry = entry?;
+            let path = rel.join(entry.file_name());
+            // We don't follow symlinks, so a symlink to a directory is
+            // recorded but not walked.
+            let md = entry.metadata()?;
+            self.0.insert(
+                path.clone(),
+                Entry {
+                    is_dir: md.is_dir(),
+                    len: md.len(),
+                    modified: md.modified().ok(),
+                },
+            );
+            if md.is_dir() {
+                self.walk(root, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn diff(&self, now: &Snapshot) -> DirDiff {
+        let mut diff = DirDiff::default();
+        for (path, entry) in &now.0 {
+            match self.0.get(path) {
+                None => diff.created.push(path.clone()),
+                Some(old) if old.is_dir && entry.is_dir => {}
+                Some(old) if old != entry => diff.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.deleted = self
+            .0
+            .keys()
+            .filter(|p| !now.0.contains_key(*p))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, sync::mpsc};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn track_changes() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        fs::create_dir(td.path().join("dir"))?;
+        fs::write(td.path().join("dir/modify"), "a")?;
+        fs::write(td.path().join("delete"), "a")?;
+        fs::write(td.path().join("keep"), "a")?;
+
+        let (tx, rx) = mpsc::channel();
+        let mut pd = Pushd::builder(td.path())
+            .on_changes(move |d| tx.send(d).unwrap())
+            .push()?;
+        assert!(pd.changes().is_none());
+        fs::write("dir/modify", "longer")?;
+        fs::write("dir/create", "a")?;
+        fs::remove_file("delete")?;
+        pd.pop()?;
+
+        let expect = DirDiff {
+            created: vec![PathBuf::from("dir/create")],
+            modified: vec![PathBuf::from("dir/modify")],
+            deleted: vec![PathBuf::from("delete")],
+        };
+        assert_eq!(pd.changes(), Some(&expect));
+        assert_eq!(rx.try_recv()?, expect);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn clean_on_pop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        fs::create_dir(td.path().join("dir"))?;
+        fs::write(td.path().join("dir/keep"), "a")?;
+
+        {
+            let _pd = Pushd::builder(td.path()).clean_on_pop().push()?;
+            fs::write("dir/keep", "modified")?;
+            fs::write("dir/new", "a")?;
+            fs::create_dir_all("new-dir/sub")?;
+            fs::write("new-dir/sub/file", "a")?;
+        }
+
+        let mut left = fs::read_dir(td.path())?
+            .map(|e| e.map(|e| e.file_name()))
+            .collect::<Result<Vec<_>, _>>()?;
+        left.sort();
+        assert_eq!(left, vec!["dir"]);
+        assert!(!td.path().join("dir/new").exists());
+        assert_eq!(fs::read_to_string(td.path().join("dir/keep"))?, "modified");
+
+        Ok(())
+    }
+}
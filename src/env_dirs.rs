@@ -0,0 +1,170 @@
+// This module contains the constructors which change to a directory named by
+// an environment variable.
+
+use crate::{Panicking, Pushd, PushdBuilder, PushdError};
+use std::{env, path::PathBuf};
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that changes to the directory in the
+    /// `CARGO_MANIFEST_DIR` environment variable. Cargo sets this when it
+    /// runs tests, so this changes to the root of the crate being tested.
+    ///
+    /// The variable is read when this is called, not when the crate is
+    /// compiled.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::MissingEnvVar`] error if `CARGO_MANIFEST_DIR` is
+    /// not set or is empty. Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn manifest_dir() -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(env_dir("CARGO_MANIFEST_DIR")?).push()
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the directory in the
+    /// `OUT_DIR` environment variable. Cargo sets this when it runs a build
+    /// script, so this is intended for use in `build.rs`, for example to run a
+    /// code generator in the directory where its output should go.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::MissingEnvVar`] error if `OUT_DIR` is not set or
+    /// is empty, and a [`PushdError::InvalidEnvDir`] error if it is not an absolute path.
+    /// Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn out_dir() -> Result<Pushd<Panicking>, PushdError> {
+        let dir = env_dir("OUT_DIR")?;
+        // Cargo always sets this to an absolute path, so a relative path means
+        // that something other than Cargo set it, and it probably isn't
+        // relative to the directory we're in.
+        if !dir.is_absolute() {
+            return Err(PushdError::InvalidEnvDir {
+                name: "OUT_DIR".to_owned(),
+                path: dir,
+            });
+        }
+        PushdBuilder::new(dir).push()
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the directory named by
+    /// the given environment variable, like `WORKSPACE` on a CI server. A
+    /// relative path is resolved against the current directory.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::MissingEnvVar`] error if the variable is not set
+    /// or is empty. If the directory it names does not exist, this returns a
+    /// [`PushdError::Metadata`] error, and if it names something that isn't a directory, this
+    /// returns a [`PushdError::NotADirectory`] error. Otherwise it returns the same errors as
+    /// [`Pushd::new`].
+    #[track_caller]
+    pub fn from_env(name: &str) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(env_dir(name)?).deferred()?.enter()
+    }
+}
+
+fn env_dir(name: &str) -> Result<PathBuf, PushdError> {
+    env::var_os(name)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| PushdError::MissingEnvVar {
+            name: name.to_owned(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScopedEnv;
+    use serial_test::serial;
+    use std::{error::Error as StdError, fs, path::Path};
+
+    #[test]
+    #[serial]
+    fn manifest_dir() -> Result<(), Box<dyn StdError>> {
+        let manifest = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?);
+        let td = tempfile::tempdir()?;
+        env::set_current_dir(td.path())?;
+
+        {
+            let _pd = Pushd::manifest_dir()?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(&manifest)?
+            );
+        }
+
+        let mut env = ScopedEnv::new();
+        env.remove("CARGO_MANIFEST_DIR")?;
+        assert!(matches!(
+            Pushd::manifest_dir(),
+            Err(PushdError::MissingEnvVar { name }) if name == "CARGO_MANIFEST_DIR",
+        ));
+        env.restore();
+        env::set_current_dir(manifest)?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn out_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempfile::tempdir()?;
+        let mut env = ScopedEnv::new();
+        env.set("OUT_DIR", td.path())?;
+        {
+            let _pd = Pushd::out_dir()?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+        }
+
+        env.set("OUT_DIR", "relative")?;
+        assert!(matches!(
+            Pushd::out_dir(),
+            Err(PushdError::InvalidEnvDir { name, .. }) if name == "OUT_DIR",
+        ));
+        env.remove("OUT_DIR")?;
+        assert!(matches!(
+            Pushd::out_dir(),
+            Err(PushdError::MissingEnvVar { name }) if name == "OUT_DIR",
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn from_env() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let mut env = ScopedEnv::new();
+        env.set("PUSHD_TEST_FROM_ENV", "src")?;
+        {
+            let _pd = Pushd::from_env("PUSHD_TEST_FROM_ENV")?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+
+        env.set("PUSHD_TEST_FROM_ENV", "no-such-dir")?;
+        assert!(matches!(
+            Pushd::from_env("PUSHD_TEST_FROM_ENV"),
+            Err(PushdError::Metadata { path, .. }) if path == Path::new("no-such-dir"),
+        ));
+        env.set("PUSHD_TEST_FROM_ENV", "Cargo.toml")?;
+        assert!(matches!(
+            Pushd::from_env("PUSHD_TEST_FROM_ENV"),
+            Err(PushdError::NotADirectory { .. }),
+        ));
+        env.remove("PUSHD_TEST_FROM_ENV")?;
+        assert!(matches!(
+            Pushd::from_env("PUSHD_TEST_FROM_ENV"),
+            Err(PushdError::MissingEnvVar { name }) if name == "PUSHD_TEST_FROM_ENV",
+        ));
+
+        Ok(())
+    }
+}
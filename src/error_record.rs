@@ -0,0 +1,97 @@
+// This module contains `ErrorRecord`, a plain data copy of a `PushdError`
+// that can be cloned, compared, and serialized.
+
+use crate::PushdError;
+use std::{error::Error as StdError, fmt};
+
+/// A plain data copy of a [`PushdError`], with the error's message and the
+/// messages of the errors that caused it.
+///
+/// A [`PushdError`] can't be cloned or serialized, because it may wrap an
+/// [`io::Error`](std::io::Error). An `ErrorRecord` can be, so it can be kept
+/// after the error is gone, or saved alongside other state. With the `serde`
+/// feature, this implements `Serialize` and `Deserialize`.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{ErrorRecord, Pushd};
+///
+/// let Err(err) = Pushd::new("/no/such/dir") else {
+///     panic!("changed to a directory that does not exist");
+/// };
+/// let record = ErrorRecord::from(&err);
+/// assert_eq!(record.message, err.to_string());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct ErrorRecord {
+    /// The error's message.
+    pub message: String,
+    /// The messages of the errors that caused this error, starting with its
+    /// immediate source.
+    pub causes: Vec<String>,
+}
+
+impl From<&PushdError> for ErrorRecord {
+    fn from(e: &PushdError) -> ErrorRecord {
+        let mut causes = Vec::new();
+        let mut source = e.source();
+        while let Some(s) = source {
+            causes.push(s.to_string());
+            source = s.source();
+        }
+        ErrorRecord {
+            message: e.to_string(),
+            causes,
+        }
+    }
+}
+
+impl From<PushdError> for ErrorRecord {
+    fn from(e: PushdError) -> ErrorRecord {
+        ErrorRecord::from(&e)
+    }
+}
+
+impl fmt::Display for ErrorRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn from_error() {
+        let err = PushdError::SetCurrentDir {
+            from: PathBuf::from("/tmp"),
+            path: PathBuf::from("/no/such/dir"),
+            source: std::io::Error::other("it broke"),
+            backtrace: std::sync::Arc::new(std::backtrace::Backtrace::disabled()),
+        };
+        let record = ErrorRecord::from(&err);
+        assert_eq!(
+            record.message,
+            "Could not set current directory from /tmp to /no/such/dir: it broke",
+        );
+        assert_eq!(record.causes, ["it broke"]);
+        assert_eq!(record.to_string(), record.message);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() -> Result<(), Box<dyn StdError>> {
+        let record = ErrorRecord::from(PushdError::EmptyStack);
+        let json = serde_json::to_string(&record)?;
+        assert_eq!(
+            json,
+            r#"{"message":"The directory stack is empty","causes":[]}"#,
+        );
+        assert_eq!(serde_json::from_str::<ErrorRecord>(&json)?, record);
+        Ok(())
+    }
+}
@@ -0,0 +1,858 @@
+// This module contains all of the crate's logging. By default messages are
+// sent to the `log` facade. With the `tracing` feature enabled they are sent
+// to `tracing` instead, and each `Pushd` gets a span that lives as long as
+// the guard does. The span is only used as the explicit parent of the guard's
+// own events. It's never entered, because entered spans have to be exited in
+// the reverse order, and guards can be dropped in any order. Whether via `log`
+// or `tracing`, messages are sent to the logger set with `set_logger` instead,
+// if there is one. All of the messages are subject to the `log_level` in the
+// crate's `Config`.
+
+use crate::{compare::shown, config, logger, PushdError};
+use log::Level;
+#[cfg(not(feature = "tracing"))]
+use std::fmt;
+use std::{io, panic::Location, path::Path, sync::Arc, time::Duration};
+
+// How a guard logs its messages, set with `PushdBuilder::log` and
+// `PushdBuilder::quiet`.
+#[derive(Clone, Debug)]
+pub(crate) struct LogOptions {
+    pub(crate) level: Level,
+    // This isn't used with `tracing`, which only supports constant targets.
+    #[cfg_attr(feature = "tracing", allow(dead_code))]
+    pub(crate) target: Option<Arc<str>>,
+    pub(crate) quiet: bool,
+}
+
+impl Default for LogOptions {
+    fn default() -> LogOptions {
+        LogOptions {
+            level: Level::Debug,
+            target: None,
+            quiet: false,
+        }
+    }
+}
+
+// The logging state of a guard, which is passed to all of the functions that
+// log a guard's messages.
+#[derive(Clone, Debug)]
+pub(crate) struct Span {
+    #[cfg(feature = "tracing")]
+    inner: tracing::Span,
+    // The level for the guard's messages about changing directories. Warnings
+    // and errors keep their own levels.
+    level: Level,
+    #[cfg(not(feature = "tracing"))]
+    target: Option<Arc<str>>,
+    quiet: bool,
+}
+
+impl LogOptions {
+    // Returns true if the guard's messages at the given level should be
+    // logged.
+    fn enabled(&self, level: Level) -> bool {
+        !self.quiet && config::log_enabled(level)
+    }
+}
+
+impl Span {
+    // Returns true if the guard's messages at the given level should be
+    // logged.
+    fn enabled(&self, level: Level) -> bool {
+        !self.quiet && config::log_enabled(level)
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+}
+
+// Returns from the calling function unless messages at the given level should
+// be logged. When a guard's `Span` or `LogOptions` is given, this also returns
+// if the guard is quiet.
+macro_rules! return_unless_enabled {
+    ($guard:expr, $level:expr) => {
+        if !$guard.enabled($level) {
+            return;
+        }
+    };
+    ($level:expr) => {
+        if !config::log_enabled($level) {
+            return;
+        }
+    };
+}
+
+// Sends a message to the logger set with `set_logger`, or to `log` if there
+// isn't one.
+#[cfg(not(feature = "tracing"))]
+fn emit(target: Option<&str>, level: Level, message: fmt::Arguments<'_>) {
+    if logger::log(level, message) {
+        return;
+    }
+    match target {
+        Some(target) => log::log!(target: target, level, "{message}"),
+        None => log::log!(level, "{message}"),
+    }
+}
+
+// Like `tracing::event!`, but the level doesn't need to be a constant.
+#[cfg(feature = "tracing")]
+macro_rules! event {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            Level::Error => tracing::error!($($arg)+),
+            Level::Warn => tracing::warn!($($arg)+),
+            Level::Info => tracing::info!($($arg)+),
+            Level::Debug => tracing::debug!($($arg)+),
+            Level::Trace => tracing::trace!($($arg)+),
+        }
+    };
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn span(
+    from: &Path,
+    to: &Path,
+    caller: &'static Location<'static>,
+    log: &LogOptions,
+) -> Span {
+    Span {
+        inner: tracing::debug_span!(
+            "pushd",
+            from = %shown(from),
+            to = %shown(to),
+            caller = %caller,
+        ),
+        level: log.level,
+        quiet: log.quiet,
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn span(
+    _from: &Path,
+    _to: &Path,
+    _caller: &'static Location<'static>,
+    log: &LogOptions,
+) -> Span {
+    Span {
+        level: log.level,
+        target: log.target.clone(),
+        quiet: log.quiet,
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn pushed(span: &Span, from: &Path, to: &Path) {
+    return_unless_enabled!(span, span.level);
+    if logger::log(
+        span.level,
+        format_args!("Set current dir to {} from {}.", shown(to), shown(from)),
+    ) {
+        return;
+    }
+    event!(
+        span.level,
+        parent: &span.inner,
+        "Set current dir to {} from {}.",
+        shown(to),
+        shown(from),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn pushed(span: &Span, from: &Path, to: &Path) {
+    return_unless_enabled!(span, span.level);
+    emit(
+        span.target(),
+        span.level,
+        format_args!("Set current dir to {} from {}.", shown(to), shown(from)),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn swapped(span: &Span, from: &Path, to: &Path) {
+    return_unless_enabled!(span, span.level);
+    if logger::log(
+        span.level,
+        format_args!("Swapped current dir from {} to {}.", shown(from), shown(to)),
+    ) {
+        return;
+    }
+    event!(
+        span.level,
+        parent: &span.inner,
+        "Swapped current dir from {} to {}.",
+        shown(from),
+        shown(to),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn swapped(span: &Span, from: &Path, to: &Path) {
+    return_unless_enabled!(span, span.level);
+    emit(
+        span.target(),
+        span.level,
+        format_args!("Swapped current dir from {} to {}.", shown(from), shown(to)),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn popping(span: &Span, orig: &Path, held: Duration) {
+    return_unless_enabled!(span, span.level);
+    if logger::log(
+        span.level,
+        format_args!(
+            "Setting current dir back to {} after {held:?}.",
+            shown(orig),
+        ),
+    ) {
+        return;
+    }
+    event!(
+        span.level,
+        parent: &span.inner,
+        held_seconds = held.as_secs_f64(),
+        "Setting current dir back to {} after {held:?}.",
+        shown(orig),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn popping(span: &Span, orig: &Path, held: Duration) {
+    return_unless_enabled!(span, span.level);
+    emit(
+        span.target(),
+        span.level,
+        format_args!(
+            "Setting current dir back to {} after {held:?}.",
+            shown(orig),
+        ),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn pop_failed(span: &Span, orig: &Path, e: &PushdError) {
+    return_unless_enabled!(span, span.level);
+    if logger::log(
+        span.level,
+        format_args!("Could not set current dir back to {}: {e}", shown(orig)),
+    ) {
+        return;
+    }
+    event!(
+        span.level,
+        parent: &span.inner,
+        error = %e,
+        "Could not set current dir back to {}.",
+        shown(orig),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn pop_failed(span: &Span, orig: &Path, e: &PushdError) {
+    return_unless_enabled!(span, span.level);
+    emit(
+        span.target(),
+        span.level,
+        format_args!("Could not set current dir back to {}: {e}", shown(orig)),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn restore_failed(
+    span: &Span,
+    orig: &Path,
+    caller: &Location<'_>,
+    e: &PushdError,
+    will_panic: bool,
+) {
+    if will_panic {
+        return_unless_enabled!(span, Level::Error);
+        if logger::log(
+            Level::Error,
+            format_args!(
+                "Could not return to original dir {} for the guard created at {caller}: {e}",
+                shown(orig),
+            ),
+        ) {
+            return;
+        }
+        tracing::error!(
+            parent: &span.inner,
+            error = %e,
+            "Could not return to original dir {} for the guard created at {caller}.",
+            shown(orig),
+        );
+    } else if span.enabled(Level::Warn) {
+        if logger::log(
+            Level::Warn,
+            format_args!(
+                "Could not return to original dir {} for the guard created at {caller}: {e}",
+                shown(orig),
+            ),
+        ) {
+            return;
+        }
+        tracing::warn!(
+            parent: &span.inner,
+            error = %e,
+            "Could not return to original dir {} for the guard created at {caller}.",
+            shown(orig),
+        );
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn restore_failed(
+    span: &Span,
+    orig: &Path,
+    caller: &Location<'_>,
+    e: &PushdError,
+    will_panic: bool,
+) {
+    // When we're about to panic, the panic message carries the error, so
+    // there's no need to log it too.
+    if !will_panic && span.enabled(Level::Warn) {
+        emit(
+            span.target(),
+            Level::Warn,
+            format_args!(
+                "Could not return to original dir {} for the guard created at {caller}: {e}",
+                shown(orig),
+            ),
+        );
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn external_change(span: &Span, e: &PushdError) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(Level::Warn, format_args!("{e}")) {
+        return;
+    }
+    tracing::warn!(parent: &span.inner, "{e}");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn external_change(span: &Span, e: &PushdError) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(span.target(), Level::Warn, format_args!("{e}"));
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn out_of_order(span: &Span, e: &PushdError) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(Level::Warn, format_args!("{e}")) {
+        return;
+    }
+    tracing::warn!(parent: &span.inner, "{e}");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn out_of_order(span: &Span, e: &PushdError) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(span.target(), Level::Warn, format_args!("{e}"));
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn held_too_long(
+    span: &Span,
+    caller: &Location<'_>,
+    elapsed: Duration,
+    threshold: Duration,
+) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!(
+            "The guard created at {caller} was held for {elapsed:?}, which is longer than \
+         {threshold:?}.",
+        ),
+    ) {
+        return;
+    }
+    tracing::warn!(
+        parent: &span.inner,
+        "The guard created at {caller} was held for {elapsed:?}, which is longer than \
+         {threshold:?}.",
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn held_too_long(
+    span: &Span,
+    caller: &Location<'_>,
+    elapsed: Duration,
+    threshold: Duration,
+) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(
+        span.target(),
+        Level::Warn,
+        format_args!(
+            "The guard created at {caller} was held for {elapsed:?}, which is longer than \
+         {threshold:?}.",
+        ),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn timed_out(span: &Span, orig: &Path, caller: &Location<'_>, timeout: Duration) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!(
+            "Changed back to {} because the guard created at {caller} was still in its target \
+         after {timeout:?}.",
+            shown(orig),
+        ),
+    ) {
+        return;
+    }
+    tracing::warn!(
+        parent: &span.inner,
+        "Changed back to {} because the guard created at {caller} was still in its target \
+         after {timeout:?}.",
+        shown(orig),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn timed_out(span: &Span, orig: &Path, caller: &Location<'_>, timeout: Duration) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(
+        span.target(),
+        Level::Warn,
+        format_args!(
+            "Changed back to {} because the guard created at {caller} was still in its target \
+         after {timeout:?}.",
+            shown(orig),
+        ),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn timeout_failed(span: &Span, orig: &Path, caller: &Location<'_>, e: &io::Error) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!(
+            "Could not change back to {} after the timeout for the guard created at {caller}: {e}",
+            shown(orig),
+        ),
+    ) {
+        return;
+    }
+    tracing::warn!(
+        parent: &span.inner,
+        "Could not change back to {} after the timeout for the guard created at {caller}: {e}",
+        shown(orig),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn timeout_failed(span: &Span, orig: &Path, caller: &Location<'_>, e: &io::Error) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(
+        span.target(),
+        Level::Warn,
+        format_args!(
+            "Could not change back to {} after the timeout for the guard created at {caller}: {e}",
+            shown(orig),
+        ),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn repinned(span: &Span, target: &Path, found: &Path) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!(
+            "Changed the current directory back to {} after something else changed it to {}.",
+            shown(target),
+            shown(found),
+        ),
+    ) {
+        return;
+    }
+    tracing::warn!(
+        parent: &span.inner,
+        "Changed the current directory back to {} after something else changed it to {}.",
+        shown(target),
+        shown(found),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn repinned(span: &Span, target: &Path, found: &Path) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(
+        span.target(),
+        Level::Warn,
+        format_args!(
+            "Changed the current directory back to {} after something else changed it to {}.",
+            shown(target),
+            shown(found),
+        ),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn repin_failed(span: &Span, e: &PushdError) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!("Could not change back to the pinned directory: {e}"),
+    ) {
+        return;
+    }
+    tracing::warn!(parent: &span.inner, "Could not change back to the pinned directory: {e}");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn repin_failed(span: &Span, e: &PushdError) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(
+        span.target(),
+        Level::Warn,
+        format_args!("Could not change back to the pinned directory: {e}"),
+    );
+}
+
+#[cfg(all(feature = "notify", feature = "tracing"))]
+pub(crate) fn watchdog(span: &Span, event: &crate::WatchEvent) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(Level::Warn, format_args!("{event}")) {
+        return;
+    }
+    tracing::warn!(parent: &span.inner, "{event}");
+}
+
+#[cfg(all(feature = "notify", not(feature = "tracing")))]
+pub(crate) fn watchdog(span: &Span, event: &crate::WatchEvent) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(span.target(), Level::Warn, format_args!("{event}"));
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn restored_to_fallback(
+    span: &Span,
+    orig: &Path,
+    fallback: &Path,
+    caller: &Location<'_>,
+    e: &PushdError,
+) {
+    return_unless_enabled!(span, Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!(
+            "Could not return to original dir {} for the guard created at {caller}, so changed to \
+         fallback dir {} instead: {e}",
+            shown(orig),
+            shown(fallback),
+        ),
+    ) {
+        return;
+    }
+    tracing::warn!(
+        parent: &span.inner,
+        error = %e,
+        "Could not return to original dir {} for the guard created at {caller}, so changed to \
+         fallback dir {} instead.",
+        shown(orig),
+        shown(fallback),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn restored_to_fallback(
+    span: &Span,
+    orig: &Path,
+    fallback: &Path,
+    caller: &Location<'_>,
+    e: &PushdError,
+) {
+    return_unless_enabled!(span, Level::Warn);
+    emit(
+        span.target(),
+        Level::Warn,
+        format_args!(
+            "Could not return to original dir {} for the guard created at {caller}, so changed to \
+         fallback dir {} instead: {e}",
+            shown(orig),
+            shown(fallback),
+        ),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn retrying_restore(span: &Span, orig: &Path, e: &io::Error, delay: Duration) {
+    return_unless_enabled!(span, span.level);
+    if logger::log(
+        span.level,
+        format_args!(
+            "Could not return to original dir {}, retrying in {delay:?}: {e}",
+            shown(orig),
+        ),
+    ) {
+        return;
+    }
+    event!(
+        span.level,
+        parent: &span.inner,
+        error = %e,
+        "Could not return to original dir {}, retrying in {delay:?}.",
+        shown(orig),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn retrying_restore(span: &Span, orig: &Path, e: &io::Error, delay: Duration) {
+    return_unless_enabled!(span, span.level);
+    emit(
+        span.target(),
+        span.level,
+        format_args!(
+            "Could not return to original dir {}, retrying in {delay:?}: {e}",
+            shown(orig),
+        ),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn retrying_enter(log: &LogOptions, path: &Path, e: &PushdError, delay: Duration) {
+    return_unless_enabled!(log, log.level);
+    if logger::log(
+        log.level,
+        format_args!(
+            "Could not change to {}, retrying in {delay:?}: {e}",
+            shown(path),
+        ),
+    ) {
+        return;
+    }
+    event!(
+        log.level,
+        error = %e,
+        "Could not change to {}, retrying in {delay:?}.",
+        shown(path),
+    );
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn retrying_enter(log: &LogOptions, path: &Path, e: &PushdError, delay: Duration) {
+    return_unless_enabled!(log, log.level);
+    emit(
+        log.target.as_deref(),
+        log.level,
+        format_args!(
+            "Could not change to {}, retrying in {delay:?}: {e}",
+            shown(path),
+        ),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn poisoned(log: &LogOptions, e: &PushdError) {
+    return_unless_enabled!(log, Level::Warn);
+    if logger::log(Level::Warn, format_args!("{e}")) {
+        return;
+    }
+    tracing::warn!("{e}");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn poisoned(log: &LogOptions, e: &PushdError) {
+    return_unless_enabled!(log, Level::Warn);
+    emit(log.target.as_deref(), Level::Warn, format_args!("{e}"));
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn snapshot_failed(root: &Path, e: &io::Error) {
+    return_unless_enabled!(Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!("Could not read {} to check for changes: {e}", shown(root)),
+    ) {
+        return;
+    }
+    tracing::warn!(error = %e, "Could not read {} to check for changes.", shown(root));
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn snapshot_failed(root: &Path, e: &io::Error) {
+    return_unless_enabled!(Level::Warn);
+    emit(
+        None,
+        Level::Warn,
+        format_args!("Could not read {} to check for changes: {e}", shown(root)),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn clean_failed(path: &Path, e: &io::Error) {
+    return_unless_enabled!(Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!("Could not remove {}: {e}", shown(path)),
+    ) {
+        return;
+    }
+    tracing::warn!(error = %e, "Could not remove {}.", shown(path));
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn clean_failed(path: &Path, e: &io::Error) {
+    return_unless_enabled!(Level::Warn);
+    emit(
+        None,
+        Level::Warn,
+        format_args!("Could not remove {}: {e}", shown(path)),
+    );
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn env_restore_failed() {
+    return_unless_enabled!(Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!("Could not restore the original environment variables."),
+    ) {
+        return;
+    }
+    tracing::warn!("Could not restore the original environment variables.");
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn env_restore_failed() {
+    return_unless_enabled!(Level::Warn);
+    emit(
+        None,
+        Level::Warn,
+        format_args!("Could not restore the original environment variables."),
+    );
+}
+
+#[cfg(all(unix, feature = "tracing"))]
+pub(crate) fn chroot_restore_failed(e: &PushdError, caller: &Location<'_>) {
+    return_unless_enabled!(Level::Warn);
+    if logger::log(
+        Level::Warn,
+        format_args!("Could not leave the new root for the guard created at {caller}: {e}"),
+    ) {
+        return;
+    }
+    tracing::warn!(error = %e, "Could not leave the new root for the guard created at {caller}.");
+}
+
+#[cfg(all(unix, not(feature = "tracing")))]
+pub(crate) fn chroot_restore_failed(e: &PushdError, caller: &Location<'_>) {
+    return_unless_enabled!(Level::Warn);
+    emit(
+        None,
+        Level::Warn,
+        format_args!("Could not leave the new root for the guard created at {caller}: {e}"),
+    );
+}
+
+#[cfg(all(windows, feature = "tracing"))]
+pub(crate) fn unmap_failed(e: &PushdError) {
+    return_unless_enabled!(Level::Warn);
+    if logger::log(Level::Warn, format_args!("{e}")) {
+        return;
+    }
+    tracing::warn!("{e}");
+}
+
+#[cfg(all(windows, not(feature = "tracing")))]
+pub(crate) fn unmap_failed(e: &PushdError) {
+    return_unless_enabled!(Level::Warn);
+    emit(None, Level::Warn, format_args!("{e}"));
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{
+        env,
+        error::Error as StdError,
+        sync::{Mutex, PoisonError},
+    };
+    use tracing::{
+        span::{Attributes, Id, Record},
+        subscriber, Event, Metadata, Subscriber,
+    };
+
+    // Records the spans that are created and entered, and the parent of each
+    // event.
+    #[derive(Default)]
+    struct Recorder {
+        spans: Mutex<Vec<&'static str>>,
+        entered: Mutex<Vec<u64>>,
+        parents: Mutex<Vec<Option<u64>>>,
+    }
+
+    impl Subscriber for &'static Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.spans.lock().unwrap_or_else(PoisonError::into_inner);
+            spans.push(span.metadata().name());
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            self.parents
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(event.parent().map(Id::into_u64));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.entered
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(span.into_u64());
+        }
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[test]
+    #[serial]
+    fn span() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let recorder: &'static Recorder = Box::leak(Box::default());
+        subscriber::with_default(recorder, || -> Result<(), Box<dyn StdError>> {
+            let _pd = Pushd::new("src")?;
+            tracing::info!("not from the guard");
+            Ok(())
+        })?;
+
+        assert_eq!(*recorder.spans.lock()?, ["pushd"]);
+        // The guard's span is the parent of its own events, but it isn't
+        // entered, so other events aren't in it.
+        assert!(recorder.entered.lock()?.is_empty());
+        let parents = recorder.parents.lock()?;
+        assert_eq!(parents.first(), Some(&Some(1)));
+        assert!(parents.contains(&None));
+        assert_eq!(parents.last(), Some(&Some(1)));
+
+        Ok(())
+    }
+}
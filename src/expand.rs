@@ -0,0 +1,275 @@
+// This module contains the opt-in expansion of target paths, which happens
+// before a guard changes directories.
+
+use crate::{DropBehavior, ParentDirPolicy, PushdBuilder, PushdError};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    path::{Component, Path, PathBuf},
+};
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`](crate::Pushd) expand a leading `~` or
+    /// `~user` in the target path, like a shell does. This is useful for
+    /// paths that come from config files or user input.
+    ///
+    /// A bare `~` is replaced with the current user's home directory, from
+    /// [`env::home_dir`]. On Unix, `~user` is replaced with the home
+    /// directory of the named user from the password database. On Windows,
+    /// `~user` is replaced with the directory named `user` next to the current
+    /// user's home directory, like `C:\Users\user`, if that directory exists.
+    /// On Windows, both `/` and `\` are accepted after the `~`.
+    ///
+    /// A `~` anywhere other than at the start of the path is left alone.
+    #[must_use]
+    pub fn expand_tilde(mut self) -> Self {
+        self.opts.expand.tilde = true;
+        self
+    }
+
+    /// Makes the resulting [`Pushd`](crate::Pushd) expand environment
+    /// variables written as `${NAME}` in the target path, so a target like
+    /// `${PROJECT_ROOT}/fixtures` can come straight from a config file.
+    ///
+    /// Only the `${NAME}` form is expanded. A `$` that is not followed by `{`,
+    /// and a `${` without a closing `}`, are left alone. If the builder also
+    /// uses [`PushdBuilder::expand_tilde`], the tilde is expanded first, like
+    /// a shell does.
+    ///
+    /// If a variable is not set, pushing returns a
+    /// [`PushdError::UnsetEnvVar`] error. A variable that is set to an empty
+    /// string expands to nothing.
+    #[must_use]
+    pub fn expand_env_vars(mut self) -> Self {
+        self.opts.expand.env_vars = true;
+        self
+    }
+}
+
+// The expansions to apply to a target path, and the checks for the expanded
+// path.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Expand {
+    pub(crate) tilde: bool,
+    pub(crate) env_vars: bool,
+    pub(crate) parent_dirs: ParentDirPolicy,
+    pub(crate) require_absolute: bool,
+}
+
+impl Expand {
+    // Expands `path`, then joins it to `base` if there is one.
+    pub(crate) fn apply(
+        self,
+        mut path: PathBuf,
+        base: Option<&Path>,
+    ) -> Result<PathBuf, PushdError> {
+        if self.tilde {
+            path = expand_tilde(path)?;
+        }
+        if self.env_vars {
+            path = expand_env_vars(path)?;
+        }
+        path = self.parent_dirs.apply(path)?;
+        if let Some(base) = base {
+            path = base.join(path);
+        }
+        if self.require_absolute && !path.is_absolute() {
+            return Err(PushdError::RelativeTarget { path });
+        }
+        Ok(path)
+    }
+}
+
+pub(crate) fn expand_tilde(path: PathBuf) -> Result<PathBuf, PushdError> {
+    let mut components = path.components();
+    let Some(Component::Normal(first)) = components.next() else {
+        return Ok(path);
+    };
+    let Some(user) = first.to_str().and_then(|f| f.strip_prefix('~')) else {
+        return Ok(path);
+    };
+
+    let home = if user.is_empty() {
+        env::home_dir()
+    } else {
+        user_home(user)
+    };
+    match home {
+        Some(home) => Ok(home.join(components.as_path())),
+        None => Err(PushdError::ExpandTilde { path }),
+    }
+}
+
+fn expand_env_vars(path: PathBuf) -> Result<PathBuf, PushdError> {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    let mut expanded = OsString::new();
+    let mut rest = bytes;
+    while let Some(start) = find(rest, b"${") {
+        let Some(len) = find(&rest[start + 2..], b"}") else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + len];
+        // SAFETY: The slices are split next to ASCII characters, which is
+        // always a valid boundary in the encoded bytes of an `OsStr`.
+        let (before, name) = unsafe {
+            (
+                OsStr::from_encoded_bytes_unchecked(&rest[..start]),
+                OsStr::from_encoded_bytes_unchecked(name),
+            )
+        };
+        let Some(value) = env::var_os(name) else {
+            return Err(PushdError::UnsetEnvVar {
+                name: name.to_owned(),
+                path,
+            });
+        };
+        expanded.push(before);
+        expanded.push(value);
+        rest = &rest[start + 2 + len + 1..];
+    }
+    // SAFETY: `rest` is either all of the bytes or starts right after a `}`.
+    expanded.push(unsafe { OsStr::from_encoded_bytes_unchecked(rest) });
+    Ok(PathBuf::from(expanded))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(unix)]
+fn user_home(user: &str) -> Option<PathBuf> {
+    use std::{
+        ffi::{CStr, CString, OsStr},
+        os::unix::ffi::OsStrExt,
+        ptr,
+    };
+
+    let name = CString::new(user).ok()?;
+    let mut buf = vec![0; 1024];
+    // SAFETY: `passwd` is a plain C struct, so all zeroes is a valid value.
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = ptr::null_mut();
+    loop {
+        // SAFETY: All of the pointers are valid for the duration of the call,
+        // and `buf.len()` is the real length of `buf`.
+        let err = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &raw mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &raw mut result,
+            )
+        };
+        if err == libc::ERANGE && buf.len() < 1024 * 1024 {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if err != 0 || result.is_null() || pwd.pw_dir.is_null() {
+            return None;
+        }
+        break;
+    }
+    // SAFETY: `getpwnam_r` succeeded, so `pw_dir` points to a NUL-terminated
+    // string in `buf`, which is still alive.
+    let dir = unsafe { CStr::from_ptr(pwd.pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+#[cfg(not(unix))]
+fn user_home(user: &str) -> Option<PathBuf> {
+    let dir = env::home_dir()?.parent()?.join(user);
+    dir.is_dir().then_some(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{error::Error as StdError, path::Path};
+
+    #[test]
+    #[serial]
+    fn expand_tilde_paths() -> Result<(), Box<dyn StdError>> {
+        let Some(home) = env::home_dir() else {
+            return Ok(());
+        };
+        let expand = |p: &str| expand_tilde(PathBuf::from(p));
+
+        assert_eq!(expand("~")?, home);
+        assert_eq!(expand("~/a/b")?, home.join("a/b"));
+        assert_eq!(expand("a/~")?, Path::new("a/~"));
+        assert_eq!(expand("/~")?, Path::new("/~"));
+        assert!(matches!(
+            expand("~no-such-user-for-pushd/a"),
+            Err(PushdError::ExpandTilde { .. }),
+        ));
+        #[cfg(unix)]
+        assert_eq!(expand("~root")?, user_home("root").unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn expand_env_var_paths() -> Result<(), Box<dyn StdError>> {
+        let mut env = crate::ScopedEnv::new();
+        env.set("PUSHD_TEST_ROOT", "/root/dir")?
+            .set("PUSHD_TEST_EMPTY", "")?;
+        env.remove("PUSHD_TEST_UNSET")?;
+        let expand = |p: &str| expand_env_vars(PathBuf::from(p));
+
+        assert_eq!(expand("${PUSHD_TEST_ROOT}/a")?, Path::new("/root/dir/a"));
+        assert_eq!(
+            expand("a${PUSHD_TEST_EMPTY}/${PUSHD_TEST_ROOT}")?,
+            Path::new("a//root/dir"),
+        );
+        assert_eq!(
+            expand("$PUSHD_TEST_ROOT/a$")?,
+            Path::new("$PUSHD_TEST_ROOT/a$")
+        );
+        assert_eq!(
+            expand("a/${PUSHD_TEST_ROOT")?,
+            Path::new("a/${PUSHD_TEST_ROOT")
+        );
+        assert!(matches!(
+            expand("a/${PUSHD_TEST_UNSET}"),
+            Err(PushdError::UnsetEnvVar { name, .. }) if name == "PUSHD_TEST_UNSET",
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn push_expanded() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempfile::tempdir()?;
+        let mut env = crate::ScopedEnv::new();
+        env.set("HOME", td.path())?;
+        #[cfg(windows)]
+        env.set("USERPROFILE", td.path())?;
+
+        let pd = Pushd::builder("~").expand_tilde().push()?;
+        assert_eq!(pd.path(), td.path());
+        assert_eq!(
+            std::fs::canonicalize(env::current_dir()?)?,
+            std::fs::canonicalize(td.path())?,
+        );
+        drop(pd);
+
+        assert!(Pushd::new("~").is_err());
+
+        env.set("PUSHD_TEST_DIR", "dir")?;
+        std::fs::create_dir(td.path().join("dir"))?;
+        let pd = Pushd::builder("~/${PUSHD_TEST_DIR}")
+            .expand_tilde()
+            .expand_env_vars()
+            .push()?;
+        assert_eq!(pd.path(), td.path().join("dir"));
+
+        Ok(())
+    }
+}
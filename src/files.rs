@@ -0,0 +1,302 @@
+// This module contains the file helpers that resolve paths against a guard's
+// target directory instead of the process's current directory.
+
+use crate::{DropBehavior, Pushd};
+#[cfg(unix)]
+use std::cell::OnceCell;
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+// A handle to a guard's target directory. On Unix this holds an open file
+// descriptor for the directory, so it keeps referring to the same directory
+// even if the directory is renamed or the current directory changes. The
+// directory is opened the first time something needs the descriptor, rather
+// than when the guard changes to it, so that a guard which never uses it
+// doesn't pay for opening it. If the directory could not be opened, and on
+// other platforms, paths are resolved against the absolute path of the
+// target.
+pub(crate) struct Anchor {
+    path: PathBuf,
+    #[cfg(unix)]
+    fd: OnceCell<Option<std::os::fd::OwnedFd>>,
+}
+
+impl Anchor {
+    // Creates an anchor for the target directory at `path`, which must be
+    // absolute. This is called right after changing to the target.
+    pub(crate) fn here(path: PathBuf) -> Anchor {
+        Anchor {
+            path,
+            #[cfg(unix)]
+            fd: OnceCell::new(),
+        }
+    }
+
+    // Creates an anchor that resolves paths against `path` without ever
+    // opening it, for a guard that never changed to its target.
+    pub(crate) fn unopened(path: PathBuf) -> Anchor {
+        Anchor {
+            path,
+            #[cfg(unix)]
+            fd: OnceCell::from(None),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Returns the directory's file descriptor, opening the directory if this
+    // is the first time it's needed.
+    #[cfg(unix)]
+    fn fd(&self) -> Option<&std::os::fd::OwnedFd> {
+        self.fd
+            .get_or_init(|| unix::open_dir(&self.path).ok())
+            .as_ref()
+    }
+
+    // Returns another anchor for the same directory. If the directory's file
+    // descriptor can't be duplicated, the new anchor is unopened.
+    pub(crate) fn duplicate(&self) -> Anchor {
+        Anchor {
+            path: self.path.clone(),
+            #[cfg(unix)]
+            fd: OnceCell::from(self.fd().and_then(|fd| fd.try_clone().ok())),
+        }
+    }
+
+    // Returns the device and inode number of the anchored directory, and
+    // whether the directory still exists. This returns `None` if the
+    // directory couldn't be opened.
+    #[cfg(unix)]
+    pub(crate) fn identity(&self) -> Option<io::Result<((u64, u64), bool)>> {
+        use std::os::unix::fs::MetadataExt;
+
+        let fd = self.fd()?;
+        let md = fd.try_clone().and_then(|fd| File::from(fd).metadata());
+        // A directory that was deleted has no links left, even if something
+        // still has it open.
+        Some(md.map(|md| ((md.dev(), md.ino()), md.nlink() > 0)))
+    }
+
+    fn open(&self, rel: &Path, write: bool) -> io::Result<File> {
+        #[cfg(unix)]
+        if let Some(fd) = self.fd() {
+            return unix::open_at(fd, rel, write);
+        }
+        let path = self.path.join(rel);
+        if write {
+            File::create(path)
+        } else {
+            File::open(path)
+        }
+    }
+
+    // Changes to the anchored directory.
+    pub(crate) fn enter(&self) -> io::Result<()> {
+        crate::cwd_cache::clear_cwd_cache();
+        #[cfg(unix)]
+        if let Some(fd) = self.fd() {
+            return unix::fchdir(fd);
+        }
+        std::env::set_current_dir(&self.path)
+    }
+
+    fn create_dir(&self, rel: &Path) -> io::Result<()> {
+        #[cfg(unix)]
+        if let Some(fd) = self.fd() {
+            return unix::mkdir_at(fd, rel);
+        }
+        std::fs::create_dir(self.path.join(rel))
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Opens a file in read-only mode, like [`File::open`], resolving a
+    /// relative path against the guard's target directory rather than the
+    /// current directory.
+    ///
+    /// On Unix, the target is opened the first time a method like this one
+    /// needs it, and this uses `openat` with that directory, so from then on
+    /// relative paths keep resolving against it even if other code changes
+    /// the current directory or renames the target, and even after the guard
+    /// is popped. On other platforms, relative paths are
+    /// joined to the absolute path of the target.
+    ///
+    /// An absolute path is used as is.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`File::open`].
+    pub fn open<P: AsRef<Path>>(&self, rel: P) -> io::Result<File> {
+        self.anchor.open(rel.as_ref(), false)
+    }
+
+    /// Reads the entire contents of a file, like [`std::fs::read`]. The path
+    /// is resolved like it is for [`Pushd::open`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`std::fs::read`].
+    pub fn read<P: AsRef<Path>>(&self, rel: P) -> io::Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        self.open(rel)?.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+
+    /// Reads the entire contents of a file into a string, like
+    /// [`std::fs::read_to_string`]. The path is resolved like it is for
+    /// [`Pushd::open`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`std::fs::read_to_string`].
+    pub fn read_to_string<P: AsRef<Path>>(&self, rel: P) -> io::Result<String> {
+        let mut contents = String::new();
+        self.open(rel)?.read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    /// Writes `contents` to a file, creating it if it doesn't exist and
+    /// truncating it if it does, like [`std::fs::write`]. The path is
+    /// resolved like it is for [`Pushd::open`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`std::fs::write`].
+    pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&self, rel: P, contents: C) -> io::Result<()> {
+        self.anchor
+            .open(rel.as_ref(), true)?
+            .write_all(contents.as_ref())
+    }
+
+    /// Creates a new, empty directory, like [`std::fs::create_dir`]. The path
+    /// is resolved like it is for [`Pushd::open`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`std::fs::create_dir`].
+    pub fn create_dir<P: AsRef<Path>>(&self, rel: P) -> io::Result<()> {
+        self.anchor.create_dir(rel.as_ref())
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{
+        ffi::CString,
+        fs::File,
+        io,
+        os::{
+            fd::{AsRawFd, FromRawFd, OwnedFd},
+            unix::ffi::OsStrExt,
+        },
+        path::Path,
+    };
+
+    // Where we can, we open the directory without needing read permission on
+    // it, just like changing to it only needs search permission.
+    const DIR_FLAGS: libc::c_int = crate::secure::SEARCH_FLAGS | libc::O_CLOEXEC;
+
+    pub(super) fn open_dir(path: &Path) -> io::Result<OwnedFd> {
+        let path = c_path(path)?;
+        // SAFETY: `path` is a NUL-terminated string which outlives this call.
+        let fd = unsafe { libc::open(path.as_ptr(), DIR_FLAGS) };
+        owned(fd)
+    }
+
+    pub(super) fn open_at(dir: &OwnedFd, rel: &Path, write: bool) -> io::Result<File> {
+        let rel = c_path(rel)?;
+        let flags = if write {
+            libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC
+        } else {
+            libc::O_RDONLY
+        };
+        // SAFETY: `dir` is an open file descriptor and `rel` is a
+        // NUL-terminated string which outlives this call.
+        let fd = unsafe {
+            libc::openat(
+                dir.as_raw_fd(),
+                rel.as_ptr(),
+                flags | libc::O_CLOEXEC,
+                libc::c_uint::from(0o666u16),
+            )
+        };
+        owned(fd).map(File::from)
+    }
+
+    pub(super) fn fchdir(dir: &OwnedFd) -> io::Result<()> {
+        // SAFETY: `dir` is an open file descriptor.
+        if unsafe { libc::fchdir(dir.as_raw_fd()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn mkdir_at(dir: &OwnedFd, rel: &Path) -> io::Result<()> {
+        let rel = c_path(rel)?;
+        // SAFETY: `dir` is an open file descriptor and `rel` is a
+        // NUL-terminated string which outlives this call.
+        if unsafe { libc::mkdirat(dir.as_raw_fd(), rel.as_ptr(), 0o777) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn c_path(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "file name contained an unexpected NUL byte",
+            )
+        })
+    }
+
+    fn owned(fd: libc::c_int) -> io::Result<OwnedFd> {
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` was just returned by a successful call to `open` or
+        // `openat`, so it is an open file descriptor that nothing else owns.
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn relative_files() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let other = tempdir()?;
+
+        let mut pd = Pushd::new(td.path())?;
+        // Something else changes the current directory behind our back.
+        env::set_current_dir(other.path())?;
+
+        pd.create_dir("dir")?;
+        pd.write("dir/file", "content")?;
+        assert_eq!(pd.read_to_string("dir/file")?, "content");
+        assert_eq!(pd.read("dir/file")?, b"content");
+        let mut s = String::new();
+        pd.open("dir/file")?.read_to_string(&mut s)?;
+        assert_eq!(s, "content");
+        assert_eq!(fs::read_to_string(td.path().join("dir/file"))?, "content");
+        assert!(!other.path().join("dir").exists());
+
+        pd.pop()?;
+        assert_eq!(pd.read_to_string("dir/file")?, "content");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+// This module contains `in_forked_child`, which is only available on Unix.
+
+use crate::{chdir, chdir_error, current_dir, PushdError};
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::{
+        fd::{FromRawFd, OwnedFd},
+        unix::process::ExitStatusExt,
+    },
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    process::ExitStatus,
+    ptr,
+};
+
+// The exit code for a child whose closure panicked, which is the same code
+// that a Rust program exits with when its main thread panics.
+const PANICKED: i32 = 101;
+
+/// Forks the process, changes to `path` in the child, runs `f` there, and
+/// returns the child's exit status. The child exits with the code that `f`
+/// returns, or with 101 if `f` panics. A relative `path` is resolved against
+/// the current directory.
+///
+/// Nothing that the child does can change the parent's current directory,
+/// or any other state of the parent, so this is useful for work that must not
+/// disturb the calling process at all. Since the child is a copy of the
+/// parent, `f` can use anything that the parent had when it was called, but
+/// nothing that it does is visible to the parent except through the
+/// filesystem or its exit code.
+///
+/// The child exits with `_exit`, so destructors and `atexit` handlers are not
+/// run in the child. Its standard output is flushed first.
+///
+/// This is only available on Unix.
+///
+/// # Safety
+///
+/// Only the thread that calls this exists in the child. If the process has
+/// other threads, they may have held locks when the process forked, which
+/// will never be released in the child. The caller must make sure that `f`
+/// doesn't need any lock that another thread could hold, for example by only
+/// calling this when the process has a single thread.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// // SAFETY: This doesn't use anything that another thread could lock.
+/// let status = unsafe {
+///     pushd::in_forked_child(std::env::temp_dir(), || {
+///         i32::from(std::fs::write("pushd-fork-example", "").is_err())
+///     })?
+/// };
+/// assert!(status.success());
+/// # std::fs::remove_file(std::env::temp_dir().join("pushd-fork-example")).unwrap();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This returns the same errors as [`Pushd::new`](crate::Pushd::new) if the
+/// child cannot change to `path`, in which case `f` is not called. It returns
+/// a [`PushdError::Fork`] error if the process cannot fork or cannot wait for
+/// the child.
+pub unsafe fn in_forked_child<P, F>(path: P, f: F) -> Result<ExitStatus, PushdError>
+where
+    P: AsRef<Path>,
+    F: FnOnce() -> i32,
+{
+    let path = path.as_ref();
+    let from = current_dir()?;
+    let (read, write) = pipe().map_err(|source| PushdError::Fork { source })?;
+
+    // SAFETY: The caller is responsible for what the child does with other
+    // threads' locks. The child never returns from this function.
+    match unsafe { libc::fork() } {
+        -1 => Err(PushdError::Fork {
+            source: io::Error::last_os_error(),
+        }),
+        0 => {
+            drop(read);
+            run_child(path, write, f)
+        }
+        pid => {
+            drop(write);
+            // The child only writes to the pipe if it cannot change
+            // directories, in which case it writes the OS error code.
+            let mut errno = Vec::new();
+            let read_res = File::from(read).read_to_end(&mut errno);
+            let status = wait(pid).map_err(|source| PushdError::Fork { source })?;
+            read_res.map_err(|source| PushdError::Fork { source })?;
+            if let Ok(errno) = <[u8; 4]>::try_from(errno.as_slice()) {
+                let e = io::Error::from_raw_os_error(i32::from_ne_bytes(errno));
+                return Err(chdir_error(from, path.to_owned(), e));
+            }
+            Ok(status)
+        }
+    }
+}
+
+fn run_child<F: FnOnce() -> i32>(path: &Path, write: OwnedFd, f: F) -> ! {
+    let code = match chdir(path) {
+        Ok(()) => {
+            drop(write);
+            let code = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(PANICKED);
+            let _ = io::stdout().flush();
+            code
+        }
+        Err(e) => {
+            let errno = e.raw_os_error().unwrap_or(libc::EIO);
+            let _ = File::from(write).write_all(&errno.to_ne_bytes());
+            // The parent returns an error instead of this status.
+            1
+        }
+    };
+    // SAFETY: `_exit` is always safe to call. It doesn't return.
+    unsafe { libc::_exit(code) }
+}
+
+// Both ends of the pipe are close-on-exec, so that a program that another
+// thread runs while the child is starting doesn't keep the write end open,
+// which would make the parent wait for that program to exit.
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "illumos",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "solaris",
+))]
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two file descriptors that `pipe2` writes.
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `pipe2` succeeded, so both descriptors are open, and nothing
+    // else owns them.
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+// Other Unixes, like macOS, don't have `pipe2`, so the flag is set after the
+// pipe is created instead.
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "illumos",
+    target_os = "linux",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "solaris",
+)))]
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    use std::os::fd::AsRawFd;
+
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two file descriptors that `pipe` writes.
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `pipe` succeeded, so both descriptors are open, and nothing
+    // else owns them.
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    for fd in [&read, &write] {
+        // SAFETY: `fd` is an open descriptor that we own.
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok((read, write))
+}
+
+fn wait(pid: libc::pid_t) -> io::Result<ExitStatus> {
+    let mut status = 0;
+    loop {
+        // SAFETY: `pid` is our child, and `status` is a valid place for
+        // `waitpid` to write to.
+        if unsafe { libc::waitpid(pid, ptr::addr_of_mut!(status), 0) } != -1 {
+            return Ok(ExitStatus::from_raw(status));
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, os::fd::AsRawFd};
+
+    #[test]
+    fn pipe_is_close_on_exec() -> Result<(), Box<dyn StdError>> {
+        let (read, write) = pipe()?;
+        for fd in [&read, &write] {
+            // SAFETY: `fd` is an open descriptor that we own.
+            let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFD) };
+            assert_ne!(flags, -1);
+            assert_ne!(flags & libc::FD_CLOEXEC, 0);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn in_forked_child() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let src = cwd.join("src");
+        // SAFETY: The closures don't use anything that another thread could
+        // lock.
+        let status = unsafe {
+            super::in_forked_child("src", move || {
+                let in_src = env::current_dir().is_ok_and(|d| d == src);
+                env::set_current_dir("/").unwrap();
+                if in_src {
+                    3
+                } else {
+                    1
+                }
+            })?
+        };
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(env::current_dir()?, cwd);
+
+        // SAFETY: See above.
+        let res = unsafe { super::in_forked_child("no-such-dir", || 0) };
+        assert!(matches!(res, Err(PushdError::TargetNotFound { .. })));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+// This module implements the warning for a guard that is held in its target
+// for longer than a threshold.
+
+use crate::{events, DropBehavior, Pushd, PushdBuilder};
+use std::{panic::Location, time::Duration};
+
+pub(crate) type HeldHook = Box<dyn FnMut(&'static Location<'static>, Duration) + Send>;
+
+// The threshold, and the hook to call instead of logging, if there is one.
+pub(crate) struct Held {
+    threshold: Duration,
+    hook: Option<HeldHook>,
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`] log a warning if it stays in its target
+    /// for longer than `threshold`. The warning includes the location in the
+    /// source where the guard was created, so this can be used to find the
+    /// guards that hold up other code that needs to change directories, like
+    /// tests that are run serially.
+    ///
+    /// The time is measured from when the guard changes to its target until
+    /// it changes back, and the check happens when it changes back. Each time
+    /// the guard is [re-entered](Pushd::reenter), the time starts over.
+    #[must_use]
+    pub fn warn_if_held_longer_than(mut self, threshold: Duration) -> Self {
+        self.opts.held = Some(Held {
+            threshold,
+            hook: None,
+        });
+        self
+    }
+
+    /// Like [`PushdBuilder::warn_if_held_longer_than`], but calls the closure
+    /// instead of logging a warning. The closure is passed the guard's
+    /// [`caller`](Pushd::caller) and how long the guard was held.
+    #[must_use]
+    pub fn on_held_longer_than<F>(mut self, threshold: Duration, hook: F) -> Self
+    where
+        F: FnMut(&'static Location<'static>, Duration) + Send + 'static,
+    {
+        self.opts.held = Some(Held {
+            threshold,
+            hook: Some(Box::new(hook)),
+        });
+        self
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    // Reports it if the guard has been in its target for too long. This is
+    // called when the guard changes back.
+    pub(crate) fn check_held(&mut self) {
+        let Some(held) = &mut self.opts.held else {
+            return;
+        };
+        let elapsed = self.entered_at.elapsed();
+        if elapsed <= held.threshold {
+            return;
+        }
+        match &mut held.hook {
+            Some(hook) => hook(self.caller, elapsed),
+            None => events::held_too_long(&self.span, self.caller, elapsed, held.threshold),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{
+        env,
+        error::Error as StdError,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    #[test]
+    #[serial]
+    fn held_longer_than() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let hook = {
+            let calls = Arc::clone(&calls);
+            move |caller: &'static Location<'static>, elapsed| {
+                calls.lock().unwrap().push((caller, elapsed));
+            }
+        };
+        let threshold = Duration::from_millis(50);
+        let mut pd = Pushd::builder("src")
+            .on_held_longer_than(threshold, hook)
+            .push()?;
+        pd.pop()?;
+        assert!(calls.lock().unwrap().is_empty());
+
+        pd.reenter()?;
+        thread::sleep(threshold * 2);
+        let caller = pd.caller();
+        drop(pd);
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0, caller);
+        assert!(calls[0].1 > threshold);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+// This module tracks the directories that guards have changed to and from,
+// for `Pushd::back` and `Pushd::jump`.
+
+use crate::{normalize::normalize, Panicking, Pushd, PushdBuilder, PushdError};
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+const HOUR: Duration = Duration::from_hours(1);
+
+// The number of visited directories that are remembered for `Pushd::jump`.
+// Once there are this many, the one with the lowest frecency is forgotten
+// each time a new one is visited.
+const VISIT_LIMIT: usize = 500;
+
+struct History {
+    // The directory that was current before the most recent directory change
+    // made by a guard.
+    previous: Option<PathBuf>,
+    visits: HashMap<PathBuf, Visits>,
+}
+
+struct Visits {
+    count: u32,
+    last: Instant,
+}
+
+impl Visits {
+    // This is the same scoring as `z`. Each visit counts for more if the
+    // directory was visited recently.
+    fn frecency(&self, now: Instant) -> f64 {
+        let age = now.duration_since(self.last);
+        let weight = if age < HOUR {
+            4.0
+        } else if age < HOUR * 24 {
+            2.0
+        } else if age < HOUR * 24 * 7 {
+            0.5
+        } else {
+            0.25
+        };
+        f64::from(self.count) * weight
+    }
+}
+
+static HISTORY: Mutex<Option<History>> = Mutex::new(None);
+
+fn history() -> MutexGuard<'static, Option<History>> {
+    HISTORY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn with_history<T>(f: impl FnOnce(&mut History) -> T) -> T {
+    f(history().get_or_insert_with(|| History {
+        previous: None,
+        visits: HashMap::new(),
+    }))
+}
+
+// Records that a guard changed the current directory away from `from`.
+pub(crate) fn record_change(from: &Path) {
+    with_history(|h| h.previous = Some(from.to_owned()));
+}
+
+// Records that a guard changed the current directory to `to`, which must be
+// an absolute path. Its `.` and `..` components are removed first, so each
+// directory is usually only recorded once.
+pub(crate) fn record_visit(to: &Path) {
+    let to = normalize(to);
+    with_history(|h| {
+        let now = Instant::now();
+        if !h.visits.contains_key(&to) && h.visits.len() >= VISIT_LIMIT {
+            forget_least_frecent(&mut h.visits, now);
+        }
+        let v = h.visits.entry(to).or_insert(Visits {
+            count: 0,
+            last: now,
+        });
+        v.count = v.count.saturating_add(1);
+        v.last = now;
+    });
+}
+
+fn forget_least_frecent(visits: &mut HashMap<PathBuf, Visits>, now: Instant) {
+    let least = visits
+        .iter()
+        .min_by(|(_, v1), (_, v2)| v1.frecency(now).total_cmp(&v2.frecency(now)))
+        .map(|(p, _)| p.clone());
+    if let Some(least) = least {
+        visits.remove(&least);
+    }
+}
+
+// Returns the previous directory. If no guard has changed directories yet,
+// this falls back to `$OLDPWD`.
+pub(crate) fn previous() -> Option<PathBuf> {
+    with_history(|h| h.previous.clone()).or_else(|| env::var_os("OLDPWD").map(PathBuf::from))
+}
+
+// Returns the visited directory that best matches the query. A directory
+// matches if its path contains the query, ignoring case. The best match is the
+// one with the highest frecency. Directories that no longer exist are skipped.
+fn best_match(query: &str) -> Option<PathBuf> {
+    let query = query.to_lowercase();
+    let now = Instant::now();
+    let mut matches = with_history(|h| {
+        h.visits
+            .iter()
+            .filter(|(p, _)| p.to_string_lossy().to_lowercase().contains(&query))
+            .map(|(p, v)| (p.clone(), v.frecency(now)))
+            .collect::<Vec<_>>()
+    });
+    // We sort by path as well so that ties are broken consistently.
+    matches.sort_by(|(p1, f1), (p2, f2)| f2.total_cmp(f1).then_with(|| p1.cmp(p2)));
+    matches.into_iter().map(|(p, _)| p).find(|p| p.is_dir())
+}
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that changes to the directory that
+    /// best matches the query, out of all the directories that a `Pushd` has
+    /// changed to in this process. This works like `z` or `autojump`.
+    ///
+    /// A directory matches if its path contains the query, ignoring case. If
+    /// more than one directory matches, the one with the highest "frecency"
+    /// is used. Frecency combines how often and how recently the directory
+    /// was visited. Only the 500 directories with the highest frecency are
+    /// remembered.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::NoHistoryMatch`] error if no visited directory
+    /// matches the query. Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn jump(query: &str) -> Result<Pushd<Panicking>, PushdError> {
+        let path = best_match(query).ok_or_else(|| PushdError::NoHistoryMatch {
+            query: query.to_owned(),
+        })?;
+        PushdBuilder::new(path).push()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    fn frecency() {
+        let now = Instant::now();
+        let v = Visits {
+            count: 3,
+            last: now,
+        };
+        assert!((v.frecency(now) - 12.0).abs() < f64::EPSILON);
+        let later = now + HOUR * 2;
+        assert!((v.frecency(later) - 6.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    #[serial]
+    fn visit_limit() {
+        let prefix = Path::new("/pushd-visit-limit");
+        let kept = prefix.join("kept");
+        record_visit(&kept);
+        record_visit(&prefix.join("x/../kept"));
+        for i in 0..VISIT_LIMIT {
+            record_visit(&prefix.join(i.to_string()));
+        }
+
+        with_history(|h| {
+            assert!(h.visits.len() <= VISIT_LIMIT);
+            assert_eq!(h.visits.get(&kept).map(|v| v.count), Some(2));
+            assert!(!h.visits.contains_key(&prefix.join("x/../kept")));
+            h.visits.retain(|p, _| !p.starts_with(prefix));
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn jump() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let td = tempdir()?;
+        let often = fs::canonicalize(td.path())?.join("jump-target-often");
+        let rarely = fs::canonicalize(td.path())?.join("jump-target-rarely");
+        fs::create_dir(&often)?;
+        fs::create_dir(&rarely)?;
+
+        for _ in 0..3 {
+            Pushd::new(&often)?;
+        }
+        Pushd::new(&rarely)?;
+
+        {
+            let _pd = Pushd::jump("JUMP-TARGET")?;
+            assert_eq!(env::current_dir()?, often);
+        }
+        {
+            let _pd = Pushd::jump("target-rar")?;
+            assert_eq!(env::current_dir()?, rarely);
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert!(matches!(
+            Pushd::jump("no directory has this name"),
+            Err(PushdError::NoHistoryMatch { .. }),
+        ));
+
+        Ok(())
+    }
+}
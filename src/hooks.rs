@@ -0,0 +1,96 @@
+// This module contains the `on_enter` and `on_exit` hooks, which are called
+// with a guard's target when it changes into and out of it.
+
+use crate::{DropBehavior, PushdBuilder};
+use std::path::Path;
+
+pub(crate) type Hook = Box<dyn FnMut(&Path) + Send>;
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Sets a closure that the resulting [`Pushd`](crate::Pushd) calls right
+    /// before it changes into its target directory. The closure is passed the
+    /// absolute path of the target. It is called again each time the guard is
+    /// [re-entered](crate::Pushd::reenter).
+    ///
+    /// The closure is called after all of the guard's other setup is done, so
+    /// if it is called, the only thing left that can fail is the directory
+    /// change itself. Calling this more than once replaces the closure.
+    #[must_use]
+    pub fn on_enter<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&Path) + Send + 'static,
+    {
+        self.opts.on_enter = Some(Box::new(hook));
+        self
+    }
+
+    /// Sets a closure that the resulting [`Pushd`](crate::Pushd) calls right
+    /// after it changes back, whether that happens because the guard was
+    /// popped or dropped. The closure is passed the directory that the guard
+    /// changed back to, which is the original directory unless the guard had
+    /// to change to one of its
+    /// [fallback directories](PushdBuilder::fallback_dirs). If the guard
+    /// cannot change back at all, the closure is not called.
+    ///
+    /// This is useful for work that depends on the current directory, like
+    /// clearing a cache keyed on it. Calling this more than once replaces the
+    /// closure.
+    #[must_use]
+    pub fn on_exit<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(&Path) + Send + 'static,
+    {
+        self.opts.on_exit = Some(Box::new(hook));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{
+        env,
+        error::Error as StdError,
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    #[serial]
+    fn enter_and_exit() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (enter, exit) = (Arc::clone(&calls), Arc::clone(&calls));
+        let mut pd = Pushd::builder("src")
+            .on_enter(move |p| {
+                // The hook runs before the directory change.
+                let cwd = env::current_dir().unwrap_or_default();
+                enter.lock().unwrap().push(("enter", p.to_owned(), cwd));
+            })
+            .on_exit(move |p| {
+                let cwd = env::current_dir().unwrap_or_default();
+                exit.lock().unwrap().push(("exit", p.to_owned(), cwd));
+            })
+            .push()?;
+        pd.pop()?;
+        pd.reenter()?;
+        drop(pd);
+
+        let enter = ("enter", cwd.join("src"), cwd.clone());
+        let exit = ("exit", cwd.clone(), cwd.clone());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [enter.clone(), exit.clone(), enter, exit],
+        );
+
+        assert!(Pushd::builder("no-such-dir")
+            .on_exit(|_: &Path| panic!("on_exit was called"))
+            .push()
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+// This module contains `JsonSink`, an observer that writes each directory
+// change as a line of JSON. It is only compiled when the `json` feature is
+// enabled.
+
+use crate::{ChdirEvent, Observer, PushdError};
+use serde_json::{json, Value};
+use std::{
+    io::Write,
+    sync::{Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// An [`Observer`] that writes every directory change to `W` as one line of
+/// JSON, for tools that ingest structured logs. Add one with
+/// [`subscribe`](crate::subscribe). This requires the `json` feature.
+///
+/// Each line is an object with these fields:
+///
+/// * `event` - One of `"push"`, `"pop"`, or `"restore_failure"`.
+/// * `from` - The current directory before the change.
+/// * `to` - The directory that was changed to, or that the change tried to
+///   change to if it failed.
+/// * `caller` - The location in the source of the code that created the
+///   guard, like `"src/main.rs:10:14"`.
+/// * `timestamp_ms` - When the change happened, in milliseconds since the
+///   Unix epoch.
+/// * `error` - The error message. This is only present for
+///   `"restore_failure"` events.
+///
+/// Paths that are not valid UTF-8 are converted lossily. Errors writing to
+/// `W` are ignored, since there's nothing to report them to.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{JsonSink, Pushd};
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let id = pushd::subscribe(Box::new(JsonSink::new(std::io::stderr())));
+/// let _pd = Pushd::new(std::env::temp_dir())?;
+/// pushd::unsubscribe(id);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct JsonSink<W> {
+    out: Mutex<W>,
+}
+
+impl<W: Write + Send + 'static> JsonSink<W> {
+    /// Constructs a new `JsonSink` that writes to `out`. Each line is flushed
+    /// as soon as it is written.
+    pub fn new(out: W) -> Self {
+        Self {
+            out: Mutex::new(out),
+        }
+    }
+
+    fn write(&self, name: &str, event: &ChdirEvent<'_>, error: Option<&PushdError>) {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX));
+        let mut line = json!({
+            "event": name,
+            "from": event.from.to_string_lossy(),
+            "to": event.to.to_string_lossy(),
+            "caller": event.caller.to_string(),
+            "timestamp_ms": timestamp_ms,
+        });
+        if let (Some(e), Value::Object(obj)) = (error, &mut line) {
+            obj.insert("error".to_string(), Value::String(e.to_string()));
+        }
+
+        let mut out = self.out.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = writeln!(out, "{line}");
+        let _ = out.flush();
+    }
+}
+
+impl<W: Write + Send + 'static> Observer for JsonSink<W> {
+    fn on_push(&self, event: &ChdirEvent<'_>) {
+        self.write("push", event, None);
+    }
+
+    fn on_pop(&self, event: &ChdirEvent<'_>) {
+        self.write("pop", event, None);
+    }
+
+    fn on_restore_failure(&self, event: &ChdirEvent<'_>, error: &PushdError) {
+        self.write("restore_failure", event, Some(error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{subscribe, unsubscribe, Pushd};
+    use serial_test::serial;
+    use std::{
+        env,
+        error::Error as StdError,
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn json_lines() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let buf = Buffer::default();
+        let id = subscribe(Box::new(JsonSink::new(buf.clone())));
+        drop(Pushd::new("src")?);
+        unsubscribe(id);
+
+        let out = String::from_utf8(buf.0.lock().unwrap().clone())?;
+        let lines = out
+            .lines()
+            .map(serde_json::from_str::<Value>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(lines.len(), 2);
+        let src = cwd.join("src");
+        for (line, (event, from, to)) in lines
+            .iter()
+            .zip([("push", &cwd, &src), ("pop", &src, &cwd)])
+        {
+            assert_eq!(line["event"], event);
+            assert_eq!(line["from"], from.to_string_lossy().as_ref());
+            assert_eq!(line["to"], to.to_string_lossy().as_ref());
+            assert!(line["caller"]
+                .as_str()
+                .is_some_and(|c| c.starts_with("src/json.rs:")));
+            assert!(line["timestamp_ms"].as_u64().is_some_and(|t| t > 0));
+            assert!(line.get("error").is_none());
+        }
+
+        Ok(())
+    }
+}
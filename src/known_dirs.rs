@@ -0,0 +1,223 @@
+// This module contains the constructors for well-known directories, which are
+// only compiled when the `dirs` feature is enabled.
+
+use crate::{Panicking, Pushd, PushdBuilder, PushdError};
+use std::{env, fmt, fs, path::PathBuf};
+
+/// A well-known directory, like the user's home or cache directory, that can
+/// be changed to with [`Pushd::known_dir`].
+///
+/// Other than [`KnownDir::Temp`], which comes from [`env::temp_dir`], these
+/// are found with the [`dirs`](https://docs.rs/dirs) crate, so they follow
+/// each platform's conventions, like the XDG base directory specification on
+/// Linux. Not every platform has every directory.
+///
+/// This requires the `dirs` feature.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum KnownDir {
+    /// The user's home directory.
+    Home,
+    /// The system's temporary directory.
+    Temp,
+    /// The user's config directory, like `~/.config` on Linux.
+    Config,
+    /// The user's cache directory, like `~/.cache` on Linux.
+    Cache,
+    /// The user's data directory, like `~/.local/share` on Linux.
+    Data,
+    /// The user's local data directory. This is the same as
+    /// [`KnownDir::Data`] except on Windows, where it is not roamed.
+    DataLocal,
+    /// The user's state directory, like `~/.local/state` on Linux. This only
+    /// exists on Linux.
+    State,
+    /// The user's runtime directory, from `$XDG_RUNTIME_DIR` on Linux. This
+    /// only exists on Linux.
+    Runtime,
+}
+
+impl KnownDir {
+    /// Returns the path of the directory, or `None` if it cannot be
+    /// determined on this platform.
+    #[must_use]
+    pub fn path(self) -> Option<PathBuf> {
+        match self {
+            KnownDir::Home => dirs::home_dir(),
+            KnownDir::Temp => Some(env::temp_dir()),
+            KnownDir::Config => dirs::config_dir(),
+            KnownDir::Cache => dirs::cache_dir(),
+            KnownDir::Data => dirs::data_dir(),
+            KnownDir::DataLocal => dirs::data_local_dir(),
+            KnownDir::State => dirs::state_dir(),
+            KnownDir::Runtime => dirs::runtime_dir(),
+        }
+    }
+}
+
+impl fmt::Display for KnownDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KnownDir::Home => "home",
+            KnownDir::Temp => "temp",
+            KnownDir::Config => "config",
+            KnownDir::Cache => "cache",
+            KnownDir::Data => "data",
+            KnownDir::DataLocal => "local data",
+            KnownDir::State => "state",
+            KnownDir::Runtime => "runtime",
+        })
+    }
+}
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that changes to the given well-known
+    /// directory. See [`KnownDir`] for details.
+    ///
+    /// This requires the `dirs` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::NoKnownDir`] error if the directory cannot be
+    /// determined on this platform. Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn known_dir(dir: KnownDir) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(known_path(dir)?).push()
+    }
+
+    /// Like [`Pushd::known_dir`], but creates the directory and any missing
+    /// parents first if it doesn't exist. This is useful for directories like
+    /// [`KnownDir::Cache`], which may not exist yet on a new system.
+    ///
+    /// This requires the `dirs` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::NoKnownDir`] error if the directory cannot be
+    /// determined on this platform, and a [`PushdError::CreateDir`] error if it cannot be created.
+    /// Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn known_dir_created(dir: KnownDir) -> Result<Pushd<Panicking>, PushdError> {
+        let path = known_path(dir)?;
+        fs::create_dir_all(&path).map_err(|e| PushdError::CreateDir {
+            path: path.clone(),
+            source: e,
+        })?;
+        PushdBuilder::new(path).push()
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the user's home
+    /// directory. This is a shortcut for [`Pushd::known_dir`] with
+    /// [`KnownDir::Home`].
+    ///
+    /// This requires the `dirs` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::known_dir`].
+    #[track_caller]
+    pub fn home() -> Result<Pushd<Panicking>, PushdError> {
+        Pushd::known_dir(KnownDir::Home)
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the system's temporary
+    /// directory. This is a shortcut for [`Pushd::known_dir`] with
+    /// [`KnownDir::Temp`]. Unlike `Pushd::new_temp`, this does not create a
+    /// new directory.
+    ///
+    /// This requires the `dirs` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn temp() -> Result<Pushd<Panicking>, PushdError> {
+        Pushd::known_dir(KnownDir::Temp)
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the user's config
+    /// directory. This is a shortcut for [`Pushd::known_dir`] with
+    /// [`KnownDir::Config`].
+    ///
+    /// This requires the `dirs` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::known_dir`].
+    #[track_caller]
+    pub fn config_dir() -> Result<Pushd<Panicking>, PushdError> {
+        Pushd::known_dir(KnownDir::Config)
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the user's cache
+    /// directory. This is a shortcut for [`Pushd::known_dir`] with
+    /// [`KnownDir::Cache`].
+    ///
+    /// This requires the `dirs` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::known_dir`].
+    #[track_caller]
+    pub fn cache_dir() -> Result<Pushd<Panicking>, PushdError> {
+        Pushd::known_dir(KnownDir::Cache)
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the user's data
+    /// directory. This is a shortcut for [`Pushd::known_dir`] with
+    /// [`KnownDir::Data`].
+    ///
+    /// This requires the `dirs` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::known_dir`].
+    #[track_caller]
+    pub fn data_dir() -> Result<Pushd<Panicking>, PushdError> {
+        Pushd::known_dir(KnownDir::Data)
+    }
+}
+
+fn known_path(dir: KnownDir) -> Result<PathBuf, PushdError> {
+    dir.path().ok_or(PushdError::NoKnownDir { dir })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::error::Error as StdError;
+
+    #[test]
+    #[serial]
+    fn known_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        {
+            let _pd = Pushd::temp()?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(env::temp_dir())?,
+            );
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            use crate::ScopedEnv;
+
+            let td = tempfile::tempdir()?;
+            let cache = td.path().join("cache");
+            let mut env = ScopedEnv::new();
+            env.set("XDG_CACHE_HOME", &cache)?;
+
+            assert!(Pushd::cache_dir().is_err());
+            let _pd = Pushd::known_dir_created(KnownDir::Cache)?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(&cache)?,
+            );
+        }
+
+        Ok(())
+    }
+}
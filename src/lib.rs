@@ -25,23 +25,371 @@
 //! }
 //! ```
 //!
+//! The [`pushd!`] macro creates a guard that lives until the end of the
+//! current scope without needing a named variable.
+//!
 //! # Panics
 //!
 //! The [`Pushd`] may panic if it cannot change back to the original directory
 //! when it's dropped. Use the [`Pushd::new_no_panic`](Pushd::new_no_panic)
 //! constructor to prevent this.
-use log::{debug, warn};
+//!
+//! Whether a guard may panic is part of its type. [`Pushd::new`] returns a
+//! `Pushd<Panicking>` while [`Pushd::new_no_panic`] returns a
+//! `Pushd<NonPanicking>`. The type parameter defaults to [`Panicking`], so a
+//! bare `Pushd` in a signature means a guard that may panic. Code that must
+//! never hold a guard which can panic in drop can require a
+//! `Pushd<NonPanicking>`:
+//!
+//! ```
+//! use pushd::{NonPanicking, Pushd};
+//!
+//! fn keep_guard(pd: Pushd<NonPanicking>) {
+//!     // ...
+//! }
+//!
+//! # fn main() -> Result<(), pushd::PushdError> {
+//! keep_guard(Pushd::new_no_panic(std::env::temp_dir())?);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! An application can change what guards that may panic do, including guards
+//! created by library code it can't change, with [`configure`]. See
+//! [`Config`] for the other crate-wide settings. To make sure that no guard in
+//! the program ever panics in drop, including guards in dependencies, enable
+//! the `no-drop-panic` feature. With it, the code that panics is not compiled
+//! at all, and every guard logs a warning instead.
+//!
+//! # Async
+//!
+//! If the `tokio` feature is enabled, this crate also provides an
+//! [`AsyncPushd`] type which does its directory changes on tokio's blocking
+//! thread pool, and a [`task`] module which tracks a per-task virtual current
+//! directory without touching the process's current directory at all.
+//!
+//! If the `async` feature is enabled, the [`AnyAsyncPushd`] type works the
+//! same way with any async runtime. Its directory changes run on new threads,
+//! or with the [`BlockingSpawner`] set with [`set_blocking_spawner`].
+//!
+//! # Running Commands
+//!
+//! With the `run` feature, [`Pushd::run`] runs a command line in the guard's
+//! target directory, streaming its output and returning a [`RunOutput`]. Even
+//! without that feature, [`cmd`] and [`CommandExt`] can be used to run a
+//! [`std::process::Command`] in a directory without changing the current
+//! directory at all.
+//!
+//! # Logging
+//!
+//! By default this crate logs directory changes with the [`log`] crate. If the
+//! `tracing` feature is enabled, it uses
+//! [`tracing`](https://docs.rs/tracing/latest/tracing/) instead. Each
+//! [`Pushd`] gets a `pushd` span with `from`, `to`, and `caller` fields that
+//! lives as long as the guard does, and all of the guard's events are
+//! recorded in that span. The span is never entered, since guards don't have
+//! to be dropped in the order they were created, so events from other code
+//! that runs while the guard is alive are not recorded in it.
+//!
+//! Separately from logging, the crate keeps a capped, in-process log of every
+//! directory change it makes, with the location of the code that asked for
+//! it. Use [`history`] to get it.
+//!
+//! For tools that ingest structured logs, the `json` feature adds
+//! [`JsonSink`], an [`Observer`] that writes each push, pop, and restore
+//! failure as a line of JSON.
+//!
+//! # Metrics
+//!
+//! [`stats`] returns counters for the guards in the process. With the
+//! `metrics` feature, the crate also sends these metrics through the
+//! [`metrics`](https://docs.rs/metrics/latest/metrics/) facade:
+//!
+//! * `pushd_pushes_total` - a counter of the times a guard changed into its
+//!   target directory.
+//! * `pushd_pops_total` - a counter of the times a guard changed back.
+//! * `pushd_restore_failures_total` - a counter of the guards that could not
+//!   change back when they were dropped.
+//! * `pushd_guard_hold_seconds` - a histogram of how long guards stayed in
+//!   their target directory before changing back.
+//!
+//! # Locking
+//!
+//! The crate keeps a process-wide registry of the guards that are in their
+//! targets, and the attribute macros hold a process-wide lock while they
+//! change directories. Both use the std mutex by default. With the
+//! `parking_lot` feature they use
+//! [`parking_lot`](https://docs.rs/parking_lot/latest/parking_lot/)'s mutex
+//! instead, which is faster and fairer when many threads contend for it.
+
+// This lets the code generated by our proc macros refer to `::pushd` when it
+// is used in this crate's own tests.
+extern crate self as pushd;
+
+#[doc(hidden)]
+#[path = "private.rs"]
+pub mod __private;
+#[cfg(feature = "assert_fs")]
+mod assert_dir;
+#[cfg(feature = "async")]
+mod async_any;
+#[cfg(feature = "tokio")]
+mod async_pushd;
+mod audit;
+mod backend;
+mod balance;
+pub mod bookmarks;
+mod builder;
+#[cfg(feature = "cap-std")]
+mod cap;
+#[cfg(feature = "cargo")]
+mod cargo;
+mod chain;
+#[cfg(unix)]
+mod chroot;
+mod command;
+mod compare;
+mod conditional;
+mod config;
+mod context;
+mod cwd_cache;
+#[cfg(feature = "miette")]
+mod diagnostic;
+mod diff;
+mod env_dirs;
+mod error_record;
+mod events;
+mod expand;
+mod files;
+#[cfg(unix)]
+mod fork;
+mod held;
+mod history;
+mod hooks;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "dirs")]
+mod known_dirs;
+#[cfg(target_os = "linux")]
+mod linux;
+mod lock;
+mod logger;
+mod macros;
+mod nesting;
+mod normalize;
+mod observer;
+mod panic_hook;
+mod path_ext;
+mod pin;
+mod poison;
+mod pwd;
+mod retry;
+mod roots;
+#[cfg(feature = "run")]
+mod run;
+#[cfg(feature = "temp")]
+mod sandbox;
+mod scoped_env;
+mod secure;
+mod select;
+mod sendable;
+mod session;
+mod shared;
+#[cfg(all(feature = "signals", any(unix, windows)))]
+mod signals;
+mod spawn;
+pub mod stack;
+mod stats;
+mod status;
+mod swap;
+mod sync;
+#[cfg(feature = "tokio")]
+pub mod task;
+mod telemetry;
+#[cfg(feature = "temp")]
+mod temp;
+pub mod testing;
+pub mod thread_local;
+mod timeout;
+#[cfg(feature = "typed-path")]
+mod typed;
+#[cfg(unix)]
+mod umask;
+#[cfg(feature = "camino")]
+mod utf8;
+#[cfg(feature = "vfs")]
+mod vfs_backend;
+mod visit;
+#[cfg(feature = "notify")]
+mod watch;
+#[cfg(windows)]
+mod windows;
+mod with_dir;
+
+#[cfg(feature = "assert_fs")]
+pub use assert_dir::AssertPushd;
+#[cfg(feature = "async")]
+pub use async_any::{clear_blocking_spawner, set_blocking_spawner, AnyAsyncPushd, BlockingSpawner};
+#[cfg(feature = "tokio")]
+pub use async_pushd::AsyncPushd;
+pub use audit::{clear_history, history, HistoryAction, HistoryEntry, HISTORY_LIMIT};
+pub use backend::{CwdBackend, OsBackend};
+pub use balance::{install_exit_check, run_exit_check, verify_balanced, LeakCheck};
+pub use builder::{DeferredPushd, ExternalChangePolicy, PushdBuilder};
+pub use chain::PushdChain;
+#[cfg(unix)]
+pub use chroot::ScopedChroot;
+pub use command::{cmd, CommandExt};
+pub use config::{config, configure, Config, DropCallback, DropPolicy};
+pub use cwd_cache::clear_cwd_cache;
+pub use diff::DirDiff;
+pub use error_record::ErrorRecord;
+#[cfg(unix)]
+pub use fork::in_forked_child;
+#[cfg(feature = "json")]
+pub use json::JsonSink;
+#[cfg(feature = "dirs")]
+pub use known_dirs::KnownDir;
+pub use lock::LockPolicy;
+pub use logger::{clear_logger, set_logger, PushdLogger};
+pub use nesting::{active, current, restore_all, ActiveGuard, OutOfOrderPolicy, RestoreReport};
+pub use normalize::ParentDirPolicy;
+pub use observer::{subscribe, unsubscribe, ChdirEvent, Observer, SubscriptionId};
+pub use panic_hook::install_panic_hook;
+pub use path_ext::PathExt;
+pub use poison::{clear_poison, is_poisoned, PoisonPolicy};
+/// An attribute macro that runs the annotated function with the current
+/// directory changed to the given path. This is most useful for tests.
+///
+/// This requires the `macros` feature.
+///
+/// # Examples
+///
+/// ```
+/// #[pushd::in_dir(std::env::temp_dir())]
+/// fn in_temp() {
+///     // The current directory is the temp dir here.
+/// }
+/// # in_temp();
+/// ```
+///
+/// When combining this with an attribute like `#[serial]` that also wraps the
+/// function, put `#[in_dir]` first, so that the directory change happens
+/// inside the other attribute's wrapper.
+#[cfg(feature = "macros")]
+pub use pushd_macros::in_dir;
+/// An attribute macro that runs the annotated function with the current
+/// directory changed to a new temporary directory, which is deleted
+/// afterwards. This is most useful for tests.
+///
+/// Use `#[in_temp_dir(serial)]` to also hold a process-wide lock while the
+/// function runs, so that functions annotated this way never change the
+/// current directory at the same time. This lock is separate from the one
+/// used by the `serial_test` crate.
+///
+/// This requires the `macros` feature.
+///
+/// # Examples
+///
+/// ```
+/// #[pushd::in_temp_dir(serial)]
+/// fn writes_files() {
+///     std::fs::write("scratch", "data").unwrap();
+/// }
+/// # writes_files();
+/// ```
+#[cfg(feature = "macros")]
+pub use pushd_macros::in_temp_dir;
+/// An attribute macro that runs the annotated function with the current
+/// directory changed to the given path, while holding a process-wide lock.
+/// This replaces the combination of `serial_test`'s `#[serial]` and a
+/// [`Pushd`] guard at the top of each test.
+///
+/// The lock is acquired before the directory is changed, and released after
+/// it is changed back. It is the same lock that `#[in_temp_dir(serial)]`
+/// uses, but it is separate from the one used by the `serial_test` crate.
+///
+/// This requires the `macros` feature.
+///
+/// # Examples
+///
+/// ```
+/// #[pushd::serial(std::env::temp_dir())]
+/// fn in_temp() {
+///     // The current directory is the temp dir here, and no other function
+///     // annotated with `#[pushd::serial]` is running.
+/// }
+/// # in_temp();
+/// ```
+#[cfg(feature = "macros")]
+pub use pushd_macros::serial;
+pub use pwd::PwdMode;
+pub use retry::RetryPolicy;
+#[cfg(feature = "run")]
+pub use run::RunOutput;
+#[cfg(feature = "temp")]
+pub use sandbox::{Sandbox, SandboxGuard};
+pub use scoped_env::ScopedEnv;
+pub use secure::SecurityPolicy;
+pub use sendable::SendablePushd;
+pub use session::Session;
+pub use shared::SharedPushd;
+#[cfg(all(feature = "signals", any(unix, windows)))]
+pub use signals::restore_on_signal;
+pub use spawn::spawn_in_dir;
+pub use stack::DirStack;
+pub use stats::{stats, Stats};
+pub use status::DirStatus;
+#[cfg(feature = "temp")]
+pub use temp::{Layout, TempPushd};
+#[cfg(feature = "typed-path")]
+pub use typed::to_native_path;
+#[cfg(unix)]
+pub use umask::ScopedUmask;
+#[cfg(feature = "vfs")]
+pub use vfs_backend::VfsBackend;
+pub use visit::visit_each;
+#[cfg(feature = "walkdir")]
+pub use visit::visit_tree;
+#[cfg(feature = "notify")]
+pub use watch::WatchEvent;
+pub use with_dir::{with_dir_checked, WithDirError};
+
 use std::error::Error as StdError;
 use std::{
-    env, io,
+    backtrace::Backtrace,
+    env,
+    ffi::OsString,
+    fmt,
+    fs::{self, File},
+    io,
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    panic::Location,
     path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
 /// `Error` is an enum containing the structured errors that can be returned
 /// by this module.
+///
+/// With the `miette` feature, this implements
+/// [`miette::Diagnostic`](https://docs.rs/miette/latest/miette/trait.Diagnostic.html).
+/// Each variant has a code like `pushd::set_current_dir`, and the most common
+/// ones have help text.
 #[derive(Debug, Error)]
 pub enum PushdError {
+    /// Wraps another error with the context given to [`Pushd::context`] or
+    /// [`PushdBuilder::context`].
+    #[error("{context}: {source}")]
+    Context {
+        context: String,
+        source: Box<PushdError>,
+    },
     /// Indicates that the current directory could not be retrieved. It wraps
     /// the [`io::Error`] returned by [`env::current_dir`].
     #[error("Could not get current directory: {source}")]
@@ -49,24 +397,823 @@ pub enum PushdError {
         #[from]
         source: io::Error,
     },
-    /// Indicates that the current directory could not be changed. It wraps
-    /// the [`io::Error`] returned by [`env::set_current_dir`].
-    #[error("Could not set current directory to {path}: {source}")]
-    SetCurrentDir { path: PathBuf, source: io::Error },
+    /// Indicates that the current directory could not be changed from `from`
+    /// to `path`. It wraps the [`io::Error`] returned by
+    /// [`env::set_current_dir`].
+    ///
+    /// This is only returned for failures that don't have a more specific
+    /// variant, like [`PushdError::TargetNotFound`]. The `backtrace` is
+    /// captured when the error is created. See [`PushdError::backtrace`].
+    #[error("Could not set current directory from {from} to {path}: {source}")]
+    SetCurrentDir {
+        from: PathBuf,
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Arc<Backtrace>,
+    },
+    /// Indicates that the current directory could not be changed from `from`
+    /// to `path` because `path` does not exist. It wraps the [`io::Error`]
+    /// returned by [`env::set_current_dir`].
+    #[error("Could not set current directory from {from} to {path}: {source}")]
+    TargetNotFound {
+        from: PathBuf,
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Arc<Backtrace>,
+    },
+    /// Indicates that the current directory could not be changed from `from`
+    /// to `path` because of the permissions on `path` or one of its parents.
+    /// It wraps the [`io::Error`] returned by [`env::set_current_dir`].
+    #[error("Could not set current directory from {from} to {path}: {source}")]
+    PermissionDenied {
+        from: PathBuf,
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Arc<Backtrace>,
+    },
+    /// Indicates that a guard could not change back from `from` to its
+    /// original directory, `path`. It wraps the [`io::Error`] returned by
+    /// [`env::set_current_dir`].
+    ///
+    /// The `backtrace` is captured when the error is created, and the
+    /// `guard_backtrace` is the one captured when the guard was created. See
+    /// [`PushdError::guard_backtrace`].
+    #[error(
+        "Could not set current directory from {from} back to the original directory {path}: \
+         {source}"
+    )]
+    RestoreFailed {
+        from: PathBuf,
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Arc<Backtrace>,
+        guard_backtrace: Arc<Backtrace>,
+    },
+    /// Indicates that the current directory was changed by something other
+    /// than the [`Pushd`] while the guard was alive. This is only returned
+    /// when the guard was built with [`ExternalChangePolicy::Error`].
+    #[error("The current directory was changed externally: expected {expected} but found {found}")]
+    CwdChangedExternally { expected: PathBuf, found: PathBuf },
+    /// Indicates that the guard for `path` was popped while the guard for
+    /// `newer`, which was created after it, was still in its target. This is
+    /// only returned when the guard was built with
+    /// [`OutOfOrderPolicy::Error`].
+    #[error(
+        "The guard for {path} created at {caller} was popped before the guard for {newer} \
+         created at {newer_caller}"
+    )]
+    OutOfOrder {
+        path: PathBuf,
+        caller: &'static Location<'static>,
+        newer: PathBuf,
+        newer_caller: &'static Location<'static>,
+    },
+    /// Indicates that a guard could not be created because `max` guards were
+    /// already in their target directories. See
+    /// [`Config::max_depth`](crate::Config::max_depth).
+    #[error("Could not create a guard because {max} guards are already active")]
+    TooDeep { max: usize },
+    /// Indicates that the guard for `path` created at `caller` was dropped
+    /// while its thread was panicking, so the current directory may not be
+    /// what the rest of the program expects. This is only returned when the
+    /// guard was built with [`PoisonPolicy::Error`]. Call [`clear_poison`] to
+    /// clear it.
+    #[error("The guard for {path} created at {caller} was dropped while its thread was panicking")]
+    Poisoned {
+        path: PathBuf,
+        caller: &'static Location<'static>,
+    },
+    /// Indicates that guards were still in their targets when
+    /// [`verify_balanced`] or [`LeakCheck::check`] was called. The guards are
+    /// listed oldest first.
+    #[error(
+        "{} guard(s) are still in their targets, the oldest created at {}",
+        .guards.len(),
+        .guards[0].caller,
+    )]
+    GuardsLeaked { guards: Vec<ActiveGuard> },
+    /// Indicates that the current directory was `actual` when
+    /// [`LeakCheck::check`] was called, instead of `expected`, which was the
+    /// current directory when the check was started.
+    #[error(
+        "The current directory is {actual}, but it was {expected} when the leak check started"
+    )]
+    CwdLeaked { expected: PathBuf, actual: PathBuf },
+    /// Indicates that [`install_exit_check`] could not register the handler
+    /// that runs the check when the process exits.
+    #[error("Could not register the exit check with atexit")]
+    ExitCheck,
+    /// Indicates that a guard created with [`PushdBuilder::lock`] and
+    /// [`LockPolicy::Fail`] could not lock `path` because something else held
+    /// the lock.
+    #[error("Could not lock {path} because it is already locked")]
+    Locked { path: PathBuf },
+    /// Indicates that a guard created with [`PushdBuilder::lock`] could not
+    /// lock `path`. It wraps the [`io::Error`] from creating or locking the
+    /// lock file.
+    #[error("Could not lock {path}: {source}")]
+    Lock { path: PathBuf, source: io::Error },
+    /// Indicates that the metadata for a target directory could not be read.
+    /// It wraps the [`io::Error`] returned by [`fs::metadata`].
+    #[error("Could not get metadata for {path}: {source}")]
+    Metadata { path: PathBuf, source: io::Error },
+    /// Indicates that a target path exists but is not a directory, or that
+    /// one of its parents is not a directory.
+    #[error("{path} is not a directory")]
+    NotADirectory { path: PathBuf },
+    /// Indicates that a guard's target directory was deleted after the guard
+    /// changed to it. This is returned by [`Pushd::assert_current`].
+    #[error("The directory {path} no longer exists")]
+    TargetRemoved { path: PathBuf },
+    /// Indicates that a guard's [watchdog](PushdBuilder::watchdog) could not
+    /// start watching its target directory. It wraps the error returned by
+    /// the `notify` crate.
+    ///
+    /// This requires the `notify` feature.
+    #[cfg(feature = "notify")]
+    #[error("Could not watch {path}: {source}")]
+    Watch {
+        path: PathBuf,
+        source: notify::Error,
+    },
+    /// Indicates that a target directory could not be opened. It wraps the
+    /// [`io::Error`] returned when opening the directory. This is only
+    /// returned by constructors that open the target, which are available with
+    /// the `cap-std` feature.
+    #[error("Could not open directory {path}: {source}")]
+    OpenDir { path: PathBuf, source: io::Error },
+    /// Indicates that the call to `unshare(CLONE_FS)` made by
+    /// [`Pushd::new_thread_scoped`] failed. It wraps the [`io::Error`] for the
+    /// OS error. This is only available on Linux.
+    #[cfg(target_os = "linux")]
+    #[error("Could not unshare filesystem attributes for the current thread: {source}")]
+    Unshare { source: io::Error },
+    /// Indicates that the `fork` or `waitpid` call made by [`in_forked_child`]
+    /// failed. It wraps the [`io::Error`] for the OS error. This is only
+    /// available on Unix.
+    #[cfg(unix)]
+    #[error("Could not run a forked child process: {source}")]
+    Fork { source: io::Error },
+    /// Indicates that [`ScopedChroot`] could not change the root directory to
+    /// `path`, or could not change to the new root once it had. It wraps the
+    /// [`io::Error`] for the OS error. This is only available on Unix.
+    #[cfg(unix)]
+    #[error("Could not change the root directory to {path}: {source}")]
+    Chroot { path: PathBuf, source: io::Error },
+    /// Indicates that a [`ScopedChroot`] could not change the root and current
+    /// directories back after making `path` the root. It wraps the
+    /// [`io::Error`] for the OS error. This is only available on Unix.
+    #[cfg(unix)]
+    #[error("Could not leave the root directory {path}: {source}")]
+    RestoreRoot { path: PathBuf, source: io::Error },
+    /// Indicates that [`restore_on_signal`] could not install a signal
+    /// handler. It wraps the [`io::Error`] for the OS error. This requires the
+    /// `signals` feature.
+    #[cfg(all(feature = "signals", any(unix, windows)))]
+    #[error("Could not install a signal handler: {source}")]
+    Signal { source: io::Error },
+    /// Indicates that the target of a guard created with [`Pushd::new_jailed`]
+    /// or [`PushdBuilder::jail`] is not inside the jail's root, or that the
+    /// path to it goes through a symlink. It wraps the [`io::Error`] returned
+    /// by `openat2`. This is only available on Linux.
+    #[cfg(target_os = "linux")]
+    #[error("Refusing to change to {path} because it is not inside {root} without following symlinks: {source}")]
+    OutsideJail {
+        root: PathBuf,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Indicates that the network share for a UNC path could not be mapped to
+    /// a drive letter. It wraps the [`io::Error`] for the OS error. This is
+    /// only available on Windows.
+    #[cfg(windows)]
+    #[error("Could not map the network share for {path} to a drive letter: {source}")]
+    MapNetworkDrive { path: PathBuf, source: io::Error },
+    /// Indicates that there was no free drive letter to map the network share
+    /// for a UNC path, or a directory passed to [`PushdBuilder::subst_to_drive`],
+    /// to. This is only available on Windows.
+    #[cfg(windows)]
+    #[error("Could not find a free drive letter to map {path} to")]
+    NoFreeDriveLetter { path: PathBuf },
+    /// Indicates that a drive letter mapped to a network share could not be
+    /// unmapped. It wraps the [`io::Error`] for the OS error. This is only
+    /// available on Windows.
+    #[cfg(windows)]
+    #[error("Could not unmap the network drive {drive}: {source}")]
+    UnmapNetworkDrive { drive: char, source: io::Error },
+    /// Indicates that a directory passed to [`PushdBuilder::subst_to_drive`]
+    /// could not be mapped to a drive letter. It wraps the [`io::Error`] for
+    /// the OS error. This is only available on Windows.
+    #[cfg(windows)]
+    #[error("Could not map {path} to a drive letter: {source}")]
+    SubstDrive { path: PathBuf, source: io::Error },
+    /// Indicates that a drive letter mapped by [`PushdBuilder::subst_to_drive`]
+    /// could not be removed. It wraps the [`io::Error`] for the OS error. This
+    /// is only available on Windows.
+    #[cfg(windows)]
+    #[error("Could not remove the drive {drive}: {source}")]
+    UnsubstDrive { drive: char, source: io::Error },
+    /// Indicates that a target directory failed one of the checks made by
+    /// [`Pushd::new_secure`] or [`PushdBuilder::security`], or that it is not
+    /// the directory a guard built with [`PushdBuilder::expect_owner`] or
+    /// [`PushdBuilder::expect_device`] expected. The `path` is the target or
+    /// the directory above it that failed the check.
+    #[error("Refusing to change to {target} because {path} {reason}")]
+    InsecureDir {
+        target: PathBuf,
+        path: PathBuf,
+        reason: &'static str,
+    },
+    /// Indicates that a path was required to be valid UTF-8 but was not. This
+    /// is only returned by constructors that require UTF-8 paths, which are
+    /// available with the `camino` feature.
+    #[error("{path} is not valid UTF-8")]
+    NonUtf8Path { path: PathBuf },
+    /// Indicates that the target `path` is relative, but the guard was built
+    /// with [`PushdBuilder::require_absolute`].
+    #[error("The target {path} is a relative path, but an absolute path is required")]
+    RelativeTarget { path: PathBuf },
+    /// Indicates that the target `path` has a `..` component, but the guard
+    /// was built with [`ParentDirPolicy::Reject`].
+    #[error("The target {path} has a .. component, which is not allowed")]
+    ParentDir { path: PathBuf },
+    /// Indicates that `path`, which uses the path syntax for `encoding`, could
+    /// not be converted to a path for this platform. This is only returned
+    /// with the `typed-path` feature.
+    #[cfg(feature = "typed-path")]
+    #[error("Could not use the {encoding} path {path} on this platform: {reason}")]
+    ForeignPath {
+        path: String,
+        encoding: &'static str,
+        reason: String,
+    },
+    /// Indicates that [`stack::popd`] or [`DirStack::popd`] was called with
+    /// an empty directory stack.
+    #[error("The directory stack is empty")]
+    EmptyStack,
+    /// Indicates that a directory in a [`DirStack`] cannot be written in the
+    /// requested [`ShellFormat`](stack::ShellFormat), because the directory
+    /// contains the format's separator.
+    #[error("{path} cannot be written in the shell directory stack format")]
+    ShellFormat { path: PathBuf },
+    /// Indicates that an index passed to a [`DirStack`] method like
+    /// [`DirStack::goto`] is past the end of the stack's listing, which has
+    /// `len` entries, including the current directory.
+    #[error("There is no entry {n} in a directory stack listing with {len} entries")]
+    NoStackEntry { n: usize, len: usize },
+    /// Indicates that [`Pushd::back`] was called, but there is no previous
+    /// directory to go back to.
+    #[error("There is no previous directory to go back to")]
+    NoPreviousDir,
+    /// Indicates that a leading `~` in a target built with
+    /// [`PushdBuilder::expand_tilde`] could not be expanded, because the home
+    /// directory or the named user could not be found.
+    #[error("Could not expand the ~ in {path}")]
+    ExpandTilde { path: PathBuf },
+    /// Indicates that a target built with [`PushdBuilder::expand_env_vars`]
+    /// refers to an environment variable that is not set.
+    #[error("The environment variable {name:?} in {path} is not set")]
+    UnsetEnvVar { name: OsString, path: PathBuf },
+    /// Indicates that a well-known directory could not be determined on this
+    /// platform. This is only available with the `dirs` feature.
+    #[cfg(feature = "dirs")]
+    #[error("Could not determine the user's {dir} directory")]
+    NoKnownDir { dir: KnownDir },
+    /// Indicates that a directory could not be created before changing to it.
+    /// It wraps the [`io::Error`] for the failure.
+    #[error("Could not create directory {path}: {source}")]
+    CreateDir { path: PathBuf, source: io::Error },
+    /// Indicates that an environment variable naming a directory to change to,
+    /// like `CARGO_MANIFEST_DIR`, is not set or is empty.
+    #[error("The {name} environment variable is not set")]
+    MissingEnvVar { name: String },
+    /// Indicates that an environment variable naming a directory to change to
+    /// does not contain a valid path for that variable, like an `OUT_DIR`
+    /// that is not absolute.
+    #[error("The {name} environment variable does not contain a valid directory: {path}")]
+    InvalidEnvDir { name: String, path: PathBuf },
+    /// Indicates that none of the candidates passed to [`Pushd::new_any`] is
+    /// an existing directory. It contains all of the candidates that were
+    /// tried.
+    #[error(
+        "None of the candidate directories exist: {}",
+        select::display_paths(candidates)
+    )]
+    NoCandidateDir { candidates: Vec<PathBuf> },
+    /// Indicates that a glob pattern passed to [`Pushd::new_glob`] or
+    /// [`Pushd::new_glob_newest`] is invalid. This is only available with the
+    /// `glob` feature.
+    #[cfg(feature = "glob")]
+    #[error("Invalid glob pattern {pattern:?}: {message}")]
+    InvalidGlob { pattern: String, message: String },
+    /// Indicates that no directory matches a glob pattern. This is only
+    /// available with the `glob` feature.
+    #[cfg(feature = "glob")]
+    #[error("No directory matches the glob pattern {pattern:?}")]
+    NoGlobMatch { pattern: String },
+    /// Indicates that more than one directory matches a glob pattern passed to
+    /// [`Pushd::new_glob`]. It contains all of the matching directories. This
+    /// is only available with the `glob` feature.
+    #[cfg(feature = "glob")]
+    #[error(
+        "More than one directory matches the glob pattern {pattern:?}: {}",
+        select::display_paths(matches)
+    )]
+    AmbiguousGlob {
+        pattern: String,
+        matches: Vec<PathBuf>,
+    },
+    /// Indicates that part of a directory tree could not be read by
+    /// [`visit_tree`]. It wraps the [`io::Error`] for the failure. This is
+    /// only available with the `walkdir` feature.
+    #[cfg(feature = "walkdir")]
+    #[error("Could not read {path} while walking the directory tree: {source}")]
+    WalkTree { path: PathBuf, source: io::Error },
+    /// Indicates that no project root, like the root of a Git repository, was
+    /// found at or above the starting directory.
+    #[error("Could not find a {kind} root at or above {start}")]
+    NoRootFound { kind: &'static str, start: PathBuf },
+    /// Indicates that `cargo metadata` failed when looking for a package with
+    /// [`Pushd::package`]. This is only available with the `cargo` feature.
+    #[cfg(feature = "cargo")]
+    #[error("Could not get the Cargo workspace metadata: {source}")]
+    CargoMetadata { source: cargo_metadata::Error },
+    /// Indicates that [`Pushd::package`] was called with a name that is not a
+    /// member of the current Cargo workspace. This is only available with the
+    /// `cargo` feature.
+    #[cfg(feature = "cargo")]
+    #[error("There is no package named {name} in the Cargo workspace")]
+    UnknownPackage { name: String },
+    /// Indicates that a command line passed to [`Pushd::run`] could not be
+    /// split into words, or was empty. This is only available with the `run`
+    /// feature.
+    #[cfg(feature = "run")]
+    #[error("Could not parse the command {command:?}: {message}")]
+    ParseCommand { command: String, message: String },
+    /// Indicates that a command passed to [`Pushd::run`] could not be run. It
+    /// wraps the [`io::Error`] for the failure. This is only available with
+    /// the `run` feature.
+    #[cfg(feature = "run")]
+    #[error("Could not run the command {command:?}: {source}")]
+    RunCommand { command: String, source: io::Error },
+    /// Indicates that a command passed to [`Pushd::run`] exited with a
+    /// non-zero status. This is only available with the `run` feature.
+    #[cfg(feature = "run")]
+    #[error("The command {command:?} failed: {status}")]
+    CommandFailed {
+        command: String,
+        status: std::process::ExitStatus,
+    },
+    /// Indicates that the snapshot of a target directory for
+    /// [`PushdBuilder::track_changes`] could not be taken. It wraps the
+    /// [`io::Error`] for the failure.
+    #[error("Could not read {path} to take a snapshot: {source}")]
+    Snapshot { path: PathBuf, source: io::Error },
+    /// Indicates that [`Pushd::jump`] was called with a query that does not
+    /// match any directory in the history.
+    #[error("No visited directory matches {query}")]
+    NoHistoryMatch { query: String },
+    /// Indicates that [`Pushd::bookmark`] was called with a name that has no
+    /// bookmark.
+    #[error("There is no bookmark named {name}")]
+    UnknownBookmark { name: String },
+    /// Indicates that a bookmark file could not be read, parsed, or written.
+    /// It wraps the [`io::Error`] for the failure. This is only available with
+    /// the `bookmarks-file` feature.
+    #[cfg(feature = "bookmarks-file")]
+    #[error("Could not use the bookmark file {path}: {source}")]
+    BookmarkFile { path: PathBuf, source: io::Error },
+    /// Indicates that the user's config directory, where the default bookmark
+    /// file lives, could not be determined. This is only available with the
+    /// `bookmarks-file` feature.
+    #[cfg(feature = "bookmarks-file")]
+    #[error("Could not determine the user's config directory")]
+    NoConfigDir,
+    /// Indicates that [`ScopedEnv`] was asked to set or remove an environment
+    /// variable with an invalid name or value. Names must not be empty or
+    /// contain `=` or NUL, and values must not contain NUL.
+    #[error("Invalid name or value for the environment variable {name:?}")]
+    InvalidEnvVar { name: OsString },
+    /// Indicates that a temporary directory could not be created. It wraps
+    /// the [`io::Error`] for the failure. This is only available with the
+    /// `temp` feature.
+    #[cfg(feature = "temp")]
+    #[error("Could not create a temporary directory: {source}")]
+    CreateTempDir { source: io::Error },
+    /// Indicates that a file or directory could not be copied from a fixture
+    /// by [`Pushd::new_temp_from`] from `path` to `dest`. It wraps the
+    /// [`io::Error`] for the failure. This is only available with the `temp`
+    /// feature.
+    #[cfg(feature = "temp")]
+    #[error("Could not copy {path} from the fixture to {dest}: {source}")]
+    CopyFixture {
+        path: PathBuf,
+        dest: PathBuf,
+        source: io::Error,
+    },
+    /// Indicates that a file or directory in a [`Layout`] could not be
+    /// created by [`Pushd::temp_with`]. It wraps the [`io::Error`] for the
+    /// failure. This is only available with the `temp` feature.
+    #[cfg(feature = "temp")]
+    #[error("Could not create {path} in the temporary directory: {source}")]
+    CreateLayout { path: PathBuf, source: io::Error },
+    /// Indicates that a blocking task used by [`AsyncPushd`] was cancelled
+    /// before it could complete. This can happen if the tokio runtime is
+    /// shutting down.
+    #[cfg(feature = "tokio")]
+    #[error("The blocking task used to change directories was cancelled: {source}")]
+    BlockingTask { source: tokio::task::JoinError },
+    /// Indicates that the [`BlockingSpawner`] used by [`AnyAsyncPushd`]
+    /// dropped the work to change directories without running it. This is
+    /// only available with the `async` feature.
+    #[cfg(feature = "async")]
+    #[error("The blocking task used to change directories was dropped without being run")]
+    BlockingTaskDropped,
+}
+
+impl PushdError {
+    /// Returns the backtrace that was captured when this error was created,
+    /// if there is one. Currently only the errors for a failed directory
+    /// change, like [`PushdError::SetCurrentDir`], have a backtrace. For a
+    /// [`PushdError::Context`] error, this returns the backtrace of the error
+    /// it wraps.
+    ///
+    /// Backtraces are only captured if
+    /// [`Config::capture_backtraces`] is true, or, for a
+    /// [`PushdError::RestoreFailed`] error, if the guard was built with
+    /// [`PushdBuilder::capture_backtraces`]. Otherwise the backtrace's status
+    /// is [`BacktraceStatus::Disabled`](std::backtrace::BacktraceStatus::Disabled).
+    #[must_use]
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        match self {
+            PushdError::Context { source, .. } => source.backtrace(),
+            PushdError::SetCurrentDir { backtrace, .. }
+            | PushdError::TargetNotFound { backtrace, .. }
+            | PushdError::PermissionDenied { backtrace, .. }
+            | PushdError::RestoreFailed { backtrace, .. } => Some(backtrace),
+            _ => None,
+        }
+    }
+
+    /// Returns the backtrace that was captured when the guard that returned
+    /// this error was created, if this is a [`PushdError::RestoreFailed`]
+    /// error. Since the guard may fail to change back when it is dropped, far
+    /// away from the code that created it, this backtrace shows where the
+    /// guard came from.
+    ///
+    /// Like [`PushdError::backtrace`], this is only useful if backtraces are
+    /// enabled with [`Config::capture_backtraces`] or
+    /// [`PushdBuilder::capture_backtraces`].
+    #[must_use]
+    pub fn guard_backtrace(&self) -> Option<&Backtrace> {
+        match self {
+            PushdError::Context { source, .. } => source.guard_backtrace(),
+            PushdError::RestoreFailed {
+                guard_backtrace, ..
+            } => Some(guard_backtrace),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this error happened because a directory does not
+    /// exist. This is true for a [`PushdError::TargetNotFound`] error, and for
+    /// any other error that wraps an [`io::Error`] with the kind
+    /// [`io::ErrorKind::NotFound`], like a [`PushdError::RestoreFailed`]
+    /// error for an original directory that was deleted.
+    #[must_use]
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            PushdError::Context { source, .. } => source.is_not_found(),
+            PushdError::TargetNotFound { .. } => true,
+            _ => self.io_error_kind() == Some(io::ErrorKind::NotFound),
+        }
+    }
+
+    /// Returns true if this error happened because of the permissions on a
+    /// directory. This is true for a [`PushdError::PermissionDenied`] error,
+    /// and for any other error that wraps an [`io::Error`] with the kind
+    /// [`io::ErrorKind::PermissionDenied`].
+    #[must_use]
+    pub fn is_permission_denied(&self) -> bool {
+        match self {
+            PushdError::Context { source, .. } => source.is_permission_denied(),
+            PushdError::PermissionDenied { .. } => true,
+            _ => self.io_error_kind() == Some(io::ErrorKind::PermissionDenied),
+        }
+    }
+
+    /// Returns true if this error happened because a path is not a
+    /// directory. This is true for a [`PushdError::NotADirectory`] error, and
+    /// for any other error that wraps an [`io::Error`] with the kind
+    /// [`io::ErrorKind::NotADirectory`], like a
+    /// [`PushdError::SetCurrentDir`] error for a target that is a file.
+    #[must_use]
+    pub fn is_not_a_directory(&self) -> bool {
+        match self {
+            PushdError::Context { source, .. } => source.is_not_a_directory(),
+            PushdError::NotADirectory { .. } => true,
+            _ => self.io_error_kind() == Some(io::ErrorKind::NotADirectory),
+        }
+    }
+
+    /// Returns true if this is a [`PushdError::RestoreFailed`] error, meaning
+    /// that a guard could not change back to its original directory.
+    #[must_use]
+    pub fn is_restore_failure(&self) -> bool {
+        match self {
+            PushdError::Context { source, .. } => source.is_restore_failure(),
+            PushdError::RestoreFailed { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the status that a command-line tool should exit with for this
+    /// error. The codes are the ones from BSD's `sysexits.h`, and the code for
+    /// a given kind of error will not change:
+    ///
+    /// | Code | Name             | Errors |
+    /// |------|------------------|--------|
+    /// | 64   | `EX_USAGE`       | Targets that a guard refuses, like [`PushdError::RelativeTarget`], and paths that cannot be parsed or expanded. |
+    /// | 65   | `EX_DATAERR`     | Any error for which [`PushdError::is_not_a_directory`] is true. |
+    /// | 66   | `EX_NOINPUT`     | Any error for which [`PushdError::is_not_found`] is true, other than a [`PushdError::RestoreFailed`] error, and [`PushdError::TargetRemoved`]. |
+    /// | 69   | `EX_UNAVAILABLE` | Lookups that found nothing, like [`PushdError::NoRootFound`] and [`PushdError::EmptyStack`]. |
+    /// | 70   | `EX_SOFTWARE`    | Guards that were used incorrectly, like [`PushdError::OutOfOrder`], and any error not listed here. |
+    /// | 71   | `EX_OSERR`       | Failed system calls that aren't about a directory, like [`PushdError::GetCurrentDir`]. |
+    /// | 73   | `EX_CANTCREAT`   | [`PushdError::CreateDir`] and the other errors for directories that could not be created. |
+    /// | 74   | `EX_IOERR`       | [`PushdError::RestoreFailed`], and any other error that wraps an [`io::Error`]. |
+    /// | 75   | `EX_TEMPFAIL`    | [`PushdError::Locked`], which may succeed if it is retried. |
+    /// | 77   | `EX_NOPERM`      | Any error for which [`PushdError::is_permission_denied`] is true, and [`PushdError::InsecureDir`]. |
+    /// | 78   | `EX_CONFIG`      | Environment variables that are missing or invalid, like [`PushdError::MissingEnvVar`]. |
+    ///
+    /// A [`PushdError::Context`] error has the code of the error it wraps.
+    /// The same code is used by this type's `From` implementation for
+    /// [`ExitCode`](std::process::ExitCode).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::process::ExitCode;
+    ///
+    /// fn status(res: Result<(), pushd::PushdError>) -> ExitCode {
+    ///     match res {
+    ///         Ok(()) => ExitCode::SUCCESS,
+    ///         Err(e) => ExitCode::from(e),
+    ///     }
+    /// }
+    ///
+    /// let e = pushd::Pushd::new("no-such-dir").unwrap_err();
+    /// assert_eq!(e.exit_code(), 66);
+    /// assert_eq!(status(Err(e)), ExitCode::from(66));
+    /// ```
+    #[must_use]
+    pub fn exit_code(&self) -> u8 {
+        const EX_USAGE: u8 = 64;
+        const EX_DATAERR: u8 = 65;
+        const EX_NOINPUT: u8 = 66;
+        const EX_UNAVAILABLE: u8 = 69;
+        const EX_SOFTWARE: u8 = 70;
+        const EX_OSERR: u8 = 71;
+        const EX_CANTCREAT: u8 = 73;
+        const EX_IOERR: u8 = 74;
+        const EX_TEMPFAIL: u8 = 75;
+        const EX_NOPERM: u8 = 77;
+        const EX_CONFIG: u8 = 78;
+
+        match self {
+            PushdError::Context { source, .. } => source.exit_code(),
+            PushdError::RestoreFailed { .. } => EX_IOERR,
+            PushdError::TargetRemoved { .. } => EX_NOINPUT,
+            _ if self.is_not_found() => EX_NOINPUT,
+            PushdError::InsecureDir { .. } => EX_NOPERM,
+            #[cfg(target_os = "linux")]
+            PushdError::OutsideJail { .. } => EX_NOPERM,
+            _ if self.is_permission_denied() => EX_NOPERM,
+            _ if self.is_not_a_directory() => EX_DATAERR,
+            PushdError::Locked { .. } => EX_TEMPFAIL,
+            PushdError::RelativeTarget { .. }
+            | PushdError::ParentDir { .. }
+            | PushdError::NonUtf8Path { .. }
+            | PushdError::ShellFormat { .. }
+            | PushdError::ExpandTilde { .. } => EX_USAGE,
+            #[cfg(feature = "typed-path")]
+            PushdError::ForeignPath { .. } => EX_USAGE,
+            #[cfg(feature = "glob")]
+            PushdError::InvalidGlob { .. } => EX_USAGE,
+            #[cfg(feature = "run")]
+            PushdError::ParseCommand { .. } => EX_USAGE,
+            PushdError::EmptyStack
+            | PushdError::NoStackEntry { .. }
+            | PushdError::NoPreviousDir
+            | PushdError::NoCandidateDir { .. }
+            | PushdError::NoRootFound { .. }
+            | PushdError::NoHistoryMatch { .. }
+            | PushdError::UnknownBookmark { .. } => EX_UNAVAILABLE,
+            #[cfg(feature = "dirs")]
+            PushdError::NoKnownDir { .. } => EX_UNAVAILABLE,
+            #[cfg(feature = "glob")]
+            PushdError::NoGlobMatch { .. } | PushdError::AmbiguousGlob { .. } => EX_UNAVAILABLE,
+            #[cfg(feature = "cargo")]
+            PushdError::UnknownPackage { .. } => EX_UNAVAILABLE,
+            #[cfg(windows)]
+            PushdError::NoFreeDriveLetter { .. } => EX_UNAVAILABLE,
+            PushdError::MissingEnvVar { .. }
+            | PushdError::InvalidEnvVar { .. }
+            | PushdError::InvalidEnvDir { .. }
+            | PushdError::UnsetEnvVar { .. } => EX_CONFIG,
+            #[cfg(feature = "bookmarks-file")]
+            PushdError::NoConfigDir => EX_CONFIG,
+            PushdError::CreateDir { .. } => EX_CANTCREAT,
+            #[cfg(feature = "temp")]
+            PushdError::CreateTempDir { .. }
+            | PushdError::CopyFixture { .. }
+            | PushdError::CreateLayout { .. } => EX_CANTCREAT,
+            PushdError::GetCurrentDir { .. } | PushdError::ExitCheck => EX_OSERR,
+            #[cfg(unix)]
+            PushdError::Fork { .. }
+            | PushdError::Chroot { .. }
+            | PushdError::RestoreRoot { .. } => EX_OSERR,
+            #[cfg(target_os = "linux")]
+            PushdError::Unshare { .. } => EX_OSERR,
+            #[cfg(all(feature = "signals", any(unix, windows)))]
+            PushdError::Signal { .. } => EX_OSERR,
+            _ if self.io_error_kind().is_some() => EX_IOERR,
+            _ => EX_SOFTWARE,
+        }
+    }
+
+    /// Returns the directory that the failed operation was for, if there is
+    /// one. For an error from changing directories, this is the directory
+    /// that could not be changed to. For a [`PushdError::RestoreFailed`]
+    /// error, that is the guard's original directory.
+    #[must_use]
+    pub fn target_path(&self) -> Option<&Path> {
+        let path = match self {
+            PushdError::Context { source, .. } => return source.target_path(),
+            PushdError::SetCurrentDir { path, .. }
+            | PushdError::TargetNotFound { path, .. }
+            | PushdError::PermissionDenied { path, .. }
+            | PushdError::RestoreFailed { path, .. }
+            | PushdError::Metadata { path, .. }
+            | PushdError::NotADirectory { path }
+            | PushdError::TargetRemoved { path }
+            | PushdError::Locked { path }
+            | PushdError::Lock { path, .. }
+            | PushdError::OpenDir { path, .. }
+            | PushdError::NonUtf8Path { path }
+            | PushdError::RelativeTarget { path }
+            | PushdError::ParentDir { path }
+            | PushdError::ExpandTilde { path }
+            | PushdError::UnsetEnvVar { path, .. }
+            | PushdError::CreateDir { path, .. }
+            | PushdError::InvalidEnvDir { path, .. } => path,
+            #[cfg(unix)]
+            PushdError::Chroot { path, .. } | PushdError::RestoreRoot { path, .. } => path,
+            #[cfg(windows)]
+            PushdError::MapNetworkDrive { path, .. }
+            | PushdError::NoFreeDriveLetter { path }
+            | PushdError::SubstDrive { path, .. } => path,
+            #[cfg(feature = "notify")]
+            PushdError::Watch { path, .. } => path,
+            _ => return None,
+        };
+        Some(path)
+    }
+
+    // Returns true if this error is of the given kind, treating the errors
+    // for a missing or inaccessible target like the `io::Error`s they wrap.
+    #[cfg(not(feature = "no-drop-panic"))]
+    pub(crate) fn is_kind(&self, kind: io::ErrorKind) -> bool {
+        match kind {
+            io::ErrorKind::NotFound => self.is_not_found(),
+            io::ErrorKind::PermissionDenied => self.is_permission_denied(),
+            io::ErrorKind::NotADirectory => self.is_not_a_directory(),
+            _ => match self {
+                PushdError::Context { source, .. } => source.is_kind(kind),
+                _ => self.io_error_kind() == Some(kind),
+            },
+        }
+    }
+
+    fn io_error_kind(&self) -> Option<io::ErrorKind> {
+        self.source()?
+            .downcast_ref::<io::Error>()
+            .map(io::Error::kind)
+    }
+}
+
+impl From<PushdError> for std::process::ExitCode {
+    /// Returns the status from [`PushdError::exit_code`].
+    fn from(e: PushdError) -> std::process::ExitCode {
+        std::process::ExitCode::from(e.exit_code())
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A marker trait for the types that determine whether a [`Pushd`] may panic
+/// when it is dropped.
+///
+/// This trait is sealed. The only types that implement it are [`Panicking`]
+/// and [`NonPanicking`].
+pub trait DropBehavior: sealed::Sealed + Send + Sync + 'static {
+    #[doc(hidden)]
+    const MAY_PANIC: bool;
+}
+
+/// A marker type for a [`Pushd`] that may panic if it cannot change back to
+/// its original directory when it is dropped. This is what [`Pushd::new`]
+/// returns.
+///
+/// If the `no-drop-panic` feature is enabled, the code that panics is not
+/// compiled at all, and these guards behave like [`NonPanicking`] guards when
+/// they are dropped.
+#[derive(Debug)]
+pub enum Panicking {}
+
+/// A marker type for a [`Pushd`] that will never panic when it is dropped.
+/// This is what [`Pushd::new_no_panic`] returns.
+///
+/// A guard that may panic cannot be used where a `Pushd<NonPanicking>` is
+/// expected:
+///
+/// ```compile_fail
+/// use pushd::{NonPanicking, Pushd};
+///
+/// fn keep_guard(pd: Pushd<NonPanicking>) {}
+///
+/// keep_guard(Pushd::new(std::env::temp_dir()).unwrap());
+/// ```
+#[derive(Debug)]
+pub enum NonPanicking {}
+
+impl sealed::Sealed for Panicking {}
+impl sealed::Sealed for NonPanicking {}
+
+impl DropBehavior for Panicking {
+    const MAY_PANIC: bool = !cfg!(feature = "no-drop-panic");
+}
+
+impl DropBehavior for NonPanicking {
+    const MAY_PANIC: bool = false;
 }
 
 /// A `Pushd` changes the current directory when it's created and returns to
 /// the original current directory when it's dropped.
-pub struct Pushd {
+///
+/// The `B` type parameter records whether the guard may panic when it is
+/// dropped. See [`Panicking`] and [`NonPanicking`].
+///
+/// A `Pushd` can't be sent to another thread, since changing back from a
+/// thread other than the one that created the guard is almost always a
+/// mistake. Use [`Pushd::into_sendable`] when it isn't.
+pub struct Pushd<B: DropBehavior = Panicking> {
+    // Identifies the guard in the stack of guards that are in their targets.
+    id: u64,
     orig: PathBuf,
-    panic_on_err: bool,
+    target: PathBuf,
     popped: bool,
+    // True for a guard that was created without changing directories.
+    noop: bool,
+    caller: &'static Location<'static>,
+    created_at: Instant,
+    // When the guard last changed into its target directory.
+    entered_at: Instant,
+    // The backtrace from when the guard was created.
+    backtrace: Arc<Backtrace>,
+    span: events::Span,
+    opts: builder::Options,
+    // The absolute path of the directory we changed to. This is only set when
+    // we need to check for external changes.
+    entered: Option<PathBuf>,
+    pwd: Option<pwd::Pwd>,
+    tracker: Option<diff::Tracker>,
+    diff: Option<DirDiff>,
+    anchor: files::Anchor,
+    #[cfg(windows)]
+    mapped: Option<windows::MappedDrive>,
+    #[cfg(windows)]
+    drive_dirs: Option<windows::DriveDirs>,
+    pinner: Option<pin::Pinner>,
+    timer: Option<timeout::Timer>,
+    #[cfg(feature = "notify")]
+    watchdog: Option<watch::Watchdog>,
+    lock: Option<File>,
+    behavior: PhantomData<B>,
+    // This makes the guard `!Send`. See `SendablePushd`.
+    not_send: PhantomData<*const ()>,
 }
 
-impl Pushd {
+type DropHandler = Box<dyn FnOnce(PushdError) + Send>;
+
+impl Pushd<Panicking> {
     /// Constructs a new `Pushd` struct.
     ///
-    /// This accepts any type that implements [`AsRef<Path>`].
+    /// This accepts any type that implements [`AsRef<Path>`], and copies the
+    /// path into the guard. If you already have a [`PathBuf`] that you don't
+    /// need anymore, use [`Pushd::try_from`] to move it into the guard
+    /// instead.
     ///
     /// The `Pushd` returned by this constructor will panic if it cannot
     /// change back to its original directory when it is dropped.
@@ -79,24 +1226,189 @@ impl Pushd {
     ///
     /// This method will return an error if the current directory cannot be determined. It will also
     /// return an error if the current directory cannot be changed.
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Pushd, PushdError> {
-        let cwd = env::current_dir()?;
-        env::set_current_dir(path.as_ref()).map_err(|e| PushdError::SetCurrentDir {
-            path: path.as_ref().to_owned(),
-            source: e,
-        })?;
-        debug!(
-            "Set current dir to {} from {}.",
-            path.as_ref().display(),
-            cwd.display(),
-        );
-        Ok(Pushd {
-            orig: cwd,
-            panic_on_err: true,
-            popped: false,
-        })
+    #[track_caller]
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned()).push()
+    }
+
+    /// Constructs a new `Pushd` struct that never logs anything. See
+    /// [`PushdBuilder::quiet`].
+    ///
+    /// This accepts any type that implements [`AsRef<Path>`].
+    ///
+    /// Like [`Pushd::new`], the guard will panic if it cannot change back to
+    /// its original directory when it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed.
+    #[track_caller]
+    pub fn new_quiet<P: AsRef<Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned()).quiet().push()
+    }
+
+    /// Constructs a new `Pushd` struct that changes to `path` resolved
+    /// against `base`, instead of against the current directory. See
+    /// [`PushdBuilder::relative_to`].
+    ///
+    /// Like [`Pushd::new`], the guard will panic if it cannot change back to
+    /// its original directory when it is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// let _pd = Pushd::new_in(env!("CARGO_MANIFEST_DIR"), "src")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed.
+    #[track_caller]
+    pub fn new_in<B: AsRef<Path>, P: AsRef<Path>>(
+        base: B,
+        path: P,
+    ) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .relative_to(base)
+            .push()
+    }
+
+    /// Constructs a new `Pushd` struct for a caller that already knows the
+    /// current directory, which is passed as `original`. See
+    /// [`PushdBuilder::original_dir`].
+    ///
+    /// This never asks the OS for the current directory, so it works even
+    /// when that would fail because the current directory was deleted. That
+    /// makes it a way out of a dead directory, as long as `target` is
+    /// absolute. Since the guard trusts `original` completely, it changes back
+    /// to `original` when it is dropped, whether or not that was really the
+    /// current directory.
+    ///
+    /// Like [`Pushd::new`], the guard will panic if it cannot change back to
+    /// its original directory when it is dropped, unless the original
+    /// directory no longer exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// let cwd = std::env::current_dir()?;
+    /// let _pd = Pushd::new_from(&cwd, std::env::temp_dir())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be changed. If `original`
+    /// is relative, it is resolved against the current directory, so it will also return an error
+    /// if the current directory cannot be determined.
+    #[track_caller]
+    pub fn new_from<O: AsRef<Path>, P: AsRef<Path>>(
+        original: O,
+        target: P,
+    ) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(target.as_ref().to_owned())
+            .original_dir(original)
+            .push()
+    }
+
+    /// Constructs a new `Pushd` struct for an absolute path. A relative path
+    /// returns a [`PushdError::RelativeTarget`] error instead of being
+    /// resolved against the current directory. See
+    /// [`PushdBuilder::require_absolute`].
+    ///
+    /// Like [`Pushd::new`], the guard will panic if it cannot change back to
+    /// its original directory when it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the path is relative or if the current directory cannot
+    /// be determined. It will also return an error if the current directory cannot be changed.
+    #[track_caller]
+    pub fn new_absolute<P: AsRef<Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .require_absolute()
+            .push()
+    }
+
+    /// Constructs a new `Pushd` struct, retrying up to `attempts` times,
+    /// waiting `delay` before each retry, if the target doesn't exist yet or
+    /// is busy. See [`PushdBuilder::enter_retry`].
+    ///
+    /// This accepts any type that implements [`AsRef<Path>`].
+    ///
+    /// Like [`Pushd::new`], the guard will panic if it cannot change back to
+    /// its original directory when it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed after all of the retries.
+    #[track_caller]
+    pub fn new_with_retry<P: AsRef<Path>>(
+        path: P,
+        attempts: u32,
+        delay: Duration,
+    ) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .enter_retry(RetryPolicy::new(attempts, delay))
+            .push()
+    }
+
+    /// Returns a [`PushdBuilder`] for a `Pushd` that will change to the given
+    /// path. Use this to create a `Pushd` with non-default options.
+    ///
+    /// This accepts any type that implements [`AsRef<Path>`]. Use
+    /// [`PushdBuilder::from`] to move a [`PathBuf`] into the builder instead
+    /// of copying it.
+    pub fn builder<P: AsRef<Path>>(path: P) -> PushdBuilder<Panicking> {
+        PushdBuilder::new(path.as_ref().to_owned())
+    }
+
+    /// Returns a [`DeferredPushd`] for the given path without changing the
+    /// current directory. The directory change happens when
+    /// [`DeferredPushd::enter`] is called.
+    ///
+    /// This accepts any type that implements [`AsRef<Path>`].
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the path's metadata cannot be read, or if the path is
+    /// not a directory.
+    pub fn deferred<P: AsRef<Path>>(path: P) -> Result<DeferredPushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned()).deferred()
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the previous
+    /// directory, like `cd -` in a shell.
+    ///
+    /// The previous directory is the one that was current before the most
+    /// recent directory change made by any `Pushd` in this process, whether
+    /// that change was a push or a pop. If no `Pushd` has changed directories
+    /// yet, the value of the `OLDPWD` environment variable is used instead.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::NoPreviousDir`] error if there is no previous
+    /// directory. Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn back() -> Result<Pushd<Panicking>, PushdError> {
+        let prev = history::previous().ok_or(PushdError::NoPreviousDir)?;
+        PushdBuilder::new(prev).push()
     }
+}
 
+impl Pushd<NonPanicking> {
     /// Constructs a new `Pushd` struct that will never panic.
     ///
     /// This accepts any type that implements `AsRef<Path>`.
@@ -114,124 +1426,1544 @@ impl Pushd {
     ///
     /// This method will return an error if the current directory cannot be determined. It will also
     /// return an error if the current directory cannot be changed.
-    pub fn new_no_panic<P: AsRef<Path>>(path: P) -> Result<Pushd, PushdError> {
-        let mut pd = Self::new(path)?;
-        pd.panic_on_err = false;
-        Ok(pd)
+    #[track_caller]
+    pub fn new_no_panic<P: AsRef<Path>>(path: P) -> Result<Pushd<NonPanicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .no_panic()
+            .push()
+    }
+
+    /// Constructs a new `Pushd` struct that calls the given handler if it
+    /// cannot change back to the original directory when it is dropped.
+    ///
+    /// This accepts any type that implements `AsRef<Path>`.
+    ///
+    /// The handler is called with the error instead of panicking or logging a
+    /// warning. It is called for every error, including the
+    /// [`io::ErrorKind::NotFound`] errors that a `Pushd` created with
+    /// [`Pushd::new`] ignores. It is never called when the restore happens
+    /// via an explicit call to [`Pushd::pop`] or [`Pushd::into_pop`], since
+    /// those methods return the error instead.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will also
+    /// return an error if the current directory cannot be changed.
+    #[track_caller]
+    pub fn new_with_handler<P, F>(path: P, handler: F) -> Result<Pushd<NonPanicking>, PushdError>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(PushdError) + Send + 'static,
+    {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .handler(handler)
+            .push()
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    fn push(
+        mut builder: PushdBuilder<B>,
+        caller: &'static Location<'static>,
+    ) -> Result<Pushd<B>, PushdError> {
+        if let Some(max) = builder.opts.max_depth.filter(|max| stats::active() >= *max) {
+            return Err(PushdError::TooDeep { max });
+        }
+        poison::check(&builder)?;
+        let path = resolve_target(&mut builder.opts, builder.path)?;
+        #[cfg(target_os = "linux")]
+        if builder.opts.thread_scoped {
+            linux::unshare_fs()?;
+        }
+        let cwd = original_dir(&builder.opts)?;
+        #[cfg(feature = "camino")]
+        if builder.opts.require_utf8 && cwd.to_str().is_none() {
+            return Err(PushdError::NonUtf8Path { path: cwd });
+        }
+        let checked = match builder.opts.security {
+            Some(policy) => Some(secure::check(&cwd.join(&path), policy)?),
+            None => None,
+        };
+
+        #[cfg(windows)]
+        let mapped = windows::map_drive(&builder.opts, &cwd.join(&path))?;
+        let logical = match builder.opts.pwd {
+            Some(PwdMode::Logical) => Some(pwd::Pwd::logical_target(&cwd, &path)),
+            _ => None,
+        };
+        let chdir_to = logical.as_deref().unwrap_or(&path);
+        #[cfg(windows)]
+        let chdir_to = mapped.as_ref().map_or(chdir_to, windows::MappedDrive::path);
+        // The snapshot is taken before changing directories so that a failure
+        // doesn't leave us in the target.
+        let tracker = diff::Tracker::for_options(&mut builder.opts, cwd.join(chdir_to))?;
+        let lock = lock::acquire(builder.opts.lock, &cwd.join(&path))?;
+
+        if let Some(hook) = &mut builder.opts.on_enter {
+            hook(&cwd.join(&path));
+        }
+        if let Err(e) = enter_with_retry(&builder.opts, checked.as_ref(), &cwd, &path, chdir_to) {
+            report(HistoryAction::Push, &cwd, &cwd.join(&path), caller, Err(&e));
+            return Err(e);
+        }
+        report(HistoryAction::Push, &cwd, &cwd.join(&path), caller, Ok(()));
+        #[cfg(windows)]
+        let drive_dirs = windows::DriveDirs::save(&builder.opts, &cwd, &cwd.join(&path));
+        stats::entered();
+        telemetry::pushed();
+        let anchor = builder.opts.anchor(cwd.join(chdir_to));
+        history::record_change(&cwd);
+        history::record_visit(anchor.path());
+        let span = events::span(&cwd, &path, caller, &builder.opts.log);
+        events::pushed(&span, &cwd, &path);
+
+        // This uses the path we changed to instead of asking for the current
+        // directory again, since that could fail after we've already changed
+        // directories.
+        let entered = (builder.opts.external_change != ExternalChangePolicy::Ignore)
+            .then(|| anchor.path().to_owned());
+        let pwd = builder.opts.pwd.map(|_| pwd::Pwd::enter(&cwd, logical));
+        let (cwd, path) = builder.opts.restore_dirs(cwd, path);
+
+        let pd = Pushd {
+            id: nesting::next_id(),
+            orig: cwd,
+            target: path,
+            popped: false,
+            noop: false,
+            caller,
+            created_at: Instant::now(),
+            entered_at: Instant::now(),
+            backtrace: backtrace(builder.opts.backtraces == builder::Backtraces::Capture),
+            span,
+            opts: builder.opts,
+            entered,
+            pwd,
+            tracker,
+            diff: None,
+            anchor,
+            #[cfg(windows)]
+            mapped,
+            #[cfg(windows)]
+            drive_dirs,
+            pinner: None,
+            timer: None,
+            #[cfg(feature = "notify")]
+            watchdog: None,
+            lock,
+            behavior: PhantomData,
+            not_send: PhantomData,
+        };
+        pd.nest();
+        pd.start_threads()
+    }
+
+    // Starts the threads that watch the target, if the guard has any. If
+    // starting the watchdog fails, dropping the guard changes back to the
+    // original directory.
+    #[cfg_attr(not(feature = "notify"), allow(clippy::unnecessary_wraps))]
+    fn start_threads(mut self) -> Result<Pushd<B>, PushdError> {
+        if let Some(interval) = self.opts.pin_interval {
+            self.pinner = Some(pin::Pinner::start(
+                &self.anchor,
+                interval,
+                self.span.clone(),
+            ));
+        }
+        if let Some(timeout) = self.opts.timeout {
+            self.timer = Some(timeout::Timer::start(
+                self.orig.clone(),
+                self.caller,
+                timeout,
+                self.span.clone(),
+            ));
+        }
+        #[cfg(feature = "notify")]
+        if let Some(watch) = self.opts.watch.take() {
+            self.watchdog = Some(watch::Watchdog::start(
+                watch,
+                &self.anchor,
+                self.span.clone(),
+            )?);
+        }
+        Ok(self)
+    }
+
+    // Restarts the threads that watch the target for a new target, or for the
+    // same target after changing back into it, and records the directory for
+    // detecting external changes.
+    fn retarget_threads(&mut self) -> Result<(), PushdError> {
+        if self.opts.external_change != ExternalChangePolicy::Ignore {
+            self.entered = Some(self.anchor.path().to_owned());
+        }
+        if let Some(interval) = self.opts.pin_interval {
+            self.pinner = Some(pin::Pinner::start(
+                &self.anchor,
+                interval,
+                self.span.clone(),
+            ));
+        }
+        #[cfg(feature = "notify")]
+        if let Some(watchdog) = self.watchdog.take() {
+            self.watchdog = watchdog.retarget(&self.anchor, self.span.clone())?;
+        }
+        self.set_threads_active(true);
+        Ok(())
+    }
+
+    // Tells the threads that watch the target whether the guard is in it.
+    fn set_threads_active(&self, active: bool) {
+        if let Some(pinner) = &self.pinner {
+            pinner.set_active(active);
+        }
+        if let Some(timer) = &self.timer {
+            timer.set_active(active);
+        }
+        #[cfg(feature = "notify")]
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.set_active(active);
+        }
+    }
+
+    /// Returns the path that this `Pushd` changed to. This is the path exactly
+    /// as it was passed to the constructor, so it may be relative. If the
+    /// guard was built with [`PushdBuilder::expand_tilde`] or
+    /// [`PushdBuilder::expand_env_vars`], this is the path after expansion.
+    ///
+    /// On Windows, the `\\?\` prefix is removed from the path if the path
+    /// means the same thing without it.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.target
+    }
+
+    /// Returns the original directory that this `Pushd` will change back to.
+    /// This is the value that [`env::current_dir`] returned when the `Pushd`
+    /// was created. On Windows, the `\\?\` prefix is removed if it isn't
+    /// needed, like it is for [`Pushd::path`].
+    #[must_use]
+    pub fn original(&self) -> &Path {
+        &self.orig
+    }
+
+    /// Returns true if `path` is the same directory as the one this `Pushd`
+    /// changed to. Both paths are canonicalized before they are compared, so
+    /// this is true even if one of them is relative or goes through a
+    /// symlink. This returns false if either path cannot be canonicalized,
+    /// for example because it doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let pd = Pushd::new(std::env::temp_dir())?;
+    /// assert!(pd.is_same_dir(std::env::current_dir()?));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn is_same_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        compare::same_dir(&self.orig.join(&self.target), path.as_ref())
+    }
+
+    /// Returns the location in the source of the code that created this
+    /// `Pushd`. This is included in the messages that are logged, and in the
+    /// panic message, when the guard cannot change back to its original
+    /// directory.
+    #[must_use]
+    pub fn caller(&self) -> &'static Location<'static> {
+        self.caller
+    }
+
+    /// Returns when this `Pushd` was created.
+    #[must_use]
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+    }
+
+    /// Returns how long ago this `Pushd` was created. This keeps counting
+    /// after the guard is popped, and it does not start over when the guard
+    /// is [re-entered](Pushd::reenter).
+    ///
+    /// The message that is logged when the guard changes back includes how
+    /// long it was in its target.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.created_at.elapsed()
+    }
+
+    /// Returns true if this `Pushd` has already changed back to its original
+    /// directory.
+    #[must_use]
+    pub fn is_popped(&self) -> bool {
+        self.popped || self.timer.as_ref().is_some_and(timeout::Timer::fired)
+    }
+
+    /// Changes back to the original directory the first time it is called. If
+    /// this method is called repeatedly it will not do anything on subsequent
+    /// calls.
+    ///
+    /// If the guard was built with an [`ExternalChangePolicy`] other than
+    /// [`ExternalChangePolicy::Ignore`], this first checks whether the current
+    /// directory is still the one the guard changed to.
+    ///
+    /// # Errors
+    ///
+    /// This method return an error if the current directory cannot be changed. With
+    /// [`ExternalChangePolicy::Error`], it also returns a [`PushdError::CwdChangedExternally`]
+    /// error if the current directory was changed externally. In that case the guard has still
+    /// changed back to the original directory.
+    pub fn pop(&mut self) -> Result<(), PushdError> {
+        self.pop_with_retry(None).map_err(|e| self.with_context(e))
     }
 
-    /// Changes back to the original directory the first time it is called. If
-    /// this method is called repeatedly it will not do anything on subsequent
-    /// calls.
-    ///
-    /// # Errors
-    ///
-    /// This method return an error if the current directory cannot be changed.
-    pub fn pop(&mut self) -> Result<(), PushdError> {
-        if self.popped {
-            return Ok(());
-        }
+    fn pop_with_retry(&mut self, retry: Option<RetryPolicy>) -> Result<(), PushdError> {
+        self.sync_timeout(true);
+        if self.popped {
+            return Ok(());
+        }
+        if self.take_restored() || self.hand_off() {
+            self.set_threads_active(false);
+            self.finish_pop();
+            return Ok(());
+        }
+
+        let reparented = self.take_reparented();
+        let out_of_order = self.unnest();
+        let changed = self.external_change();
+        self.set_threads_active(false);
+        if let Some(tracker) = &mut self.tracker {
+            self.diff = tracker.finish();
+        }
+
+        events::popping(&self.span, &self.orig, self.entered_at.elapsed());
+        let from = self.orig.join(&self.target);
+        if let Err(e) = self.set_orig(retry) {
+            let e = PushdError::RestoreFailed {
+                from: from.clone(),
+                path: self.orig.clone(),
+                source: e,
+                backtrace: backtrace(self.opts.backtraces == builder::Backtraces::Capture),
+                guard_backtrace: Arc::clone(&self.backtrace),
+            };
+            events::pop_failed(&self.span, &self.orig, &e);
+            let Some(fallback) = self.restore_fallback(&e) else {
+                report(HistoryAction::Pop, &from, &self.orig, self.caller, Err(&e));
+                return Err(e);
+            };
+            let fallback = fallback.to_owned();
+            report(HistoryAction::Pop, &from, &fallback, self.caller, Ok(()));
+            if let Some(hook) = &mut self.opts.on_exit {
+                hook(&fallback);
+            }
+        } else {
+            match &self.pwd {
+                Some(pwd) if reparented || self.opts.restore_to.is_some() => {
+                    pwd.restore_elsewhere(&self.orig);
+                }
+                Some(pwd) => pwd.restore(),
+                None => (),
+            }
+            report(HistoryAction::Pop, &from, &self.orig, self.caller, Ok(()));
+            if let Some(hook) = &mut self.opts.on_exit {
+                hook(&self.orig);
+            }
+        }
+        self.finish_pop();
+
+        #[cfg(windows)]
+        if let Some(dirs) = self.drive_dirs.take() {
+            dirs.restore();
+        }
+        #[cfg(windows)]
+        if let Some(mut mapped) = self.mapped.take() {
+            mapped.unmap()?;
+        }
+
+        match out_of_order.or(changed) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    // Does the bookkeeping for a guard that has changed back.
+    fn finish_pop(&mut self) {
+        history::record_change(&self.orig.join(&self.target));
+        stats::popped();
+        telemetry::popped(self.entered_at);
+        self.check_held();
+        self.lock = None;
+        self.disarm();
+    }
+
+    fn set_orig(&self, retry: Option<RetryPolicy>) -> io::Result<()> {
+        let mut res = self.opts.set_current_dir(&self.orig);
+        if let Some(policy) = retry {
+            for delay in policy.delays() {
+                match &res {
+                    Err(e) if RetryPolicy::should_retry(e) => {
+                        events::retrying_restore(&self.span, &self.orig, e, delay);
+                    }
+                    _ => break,
+                }
+                thread::sleep(delay);
+                res = self.opts.set_current_dir(&self.orig);
+            }
+        }
+        res
+    }
+
+    // Tries each fallback directory in turn, returning the one we were able
+    // to change to, if any.
+    fn restore_fallback(&self, e: &PushdError) -> Option<&Path> {
+        for fallback in &self.opts.fallbacks {
+            if self.opts.set_current_dir(fallback).is_ok() {
+                if let Some(pwd) = &self.pwd {
+                    pwd.restore_elsewhere(fallback);
+                }
+                events::restored_to_fallback(&self.span, &self.orig, fallback, self.caller, e);
+                return Some(fallback);
+            }
+        }
+        None
+    }
+
+    /// Changes back into the guard's target directory after it has been
+    /// popped, re-arming the guard so that it will change back to the original
+    /// directory again when it is popped or dropped.
+    ///
+    /// This reuses the paths stored in the guard. If the target was given as
+    /// a relative path, it is resolved against the original directory, not
+    /// whatever the current directory is when this is called. If the guard has
+    /// not been popped, or [is a no-op](Pushd::is_noop), this does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This method return an error if the current directory cannot be changed.
+    pub fn reenter(&mut self) -> Result<(), PushdError> {
+        self.reenter_inner().map_err(|e| self.with_context(e))
+    }
+
+    fn reenter_inner(&mut self) -> Result<(), PushdError> {
+        self.sync_timeout(false);
+        if !self.popped || self.noop {
+            return Ok(());
+        }
+
+        let target = self
+            .pwd
+            .as_ref()
+            .and_then(pwd::Pwd::logical)
+            .map_or_else(|| self.orig.join(&self.target), Path::to_path_buf);
+
+        #[cfg(windows)]
+        if self.opts.drives != windows::DriveOptions::default() {
+            self.mapped = windows::map_drive(&self.opts, &target)?;
+        }
+        #[cfg(windows)]
+        let chdir_to = self
+            .mapped
+            .as_ref()
+            .map_or(target.as_path(), windows::MappedDrive::path);
+        #[cfg(not(windows))]
+        let chdir_to = target.as_path();
+
+        let checked = match self.opts.security {
+            Some(policy) => Some(secure::check(&target, policy)?),
+            None => None,
+        };
+        let lock = lock::acquire(self.opts.lock, &target)?;
+        if let Some(hook) = &mut self.opts.on_enter {
+            hook(&target);
+        }
+        if let Err(e) = enter(&self.opts, checked.as_ref(), &self.orig, &target, chdir_to) {
+            report(
+                HistoryAction::Push,
+                &self.orig,
+                &target,
+                self.caller,
+                Err(&e),
+            );
+            return Err(e);
+        }
+        report(
+            HistoryAction::Push,
+            &self.orig,
+            &target,
+            self.caller,
+            Ok(()),
+        );
+        if let Some(pwd) = &mut self.pwd {
+            pwd.reenter(&self.orig);
+        }
+        #[cfg(windows)]
+        {
+            self.drive_dirs = windows::DriveDirs::save(&self.opts, &self.orig, &target);
+        }
+        // The target may have been replaced since the guard last changed to
+        // it, so the anchor is recreated for the directory that's there now.
+        self.anchor = self.opts.anchor(chdir_to.to_owned());
+        self.nest();
+        history::record_change(&self.orig);
+        history::record_visit(&target);
+        events::pushed(&self.span, &self.orig, &self.target);
+        stats::entered();
+        telemetry::pushed();
+        self.entered_at = Instant::now();
+        self.lock = lock;
+        self.popped = false;
+        self.retarget_threads()
+    }
+
+    fn external_change(&self) -> Option<PushdError> {
+        let expected = self.entered.as_ref()?;
+        // If we can't get the current directory, that's most likely because
+        // the directory we changed to was deleted, which isn't an external
+        // change.
+        let found = self.opts.current_dir().ok()?;
+        if compare::same_dir(&found, expected) {
+            return None;
+        }
+
+        let e = PushdError::CwdChangedExternally {
+            expected: expected.clone(),
+            found,
+        };
+        match self.opts.external_change {
+            ExternalChangePolicy::Ignore => None,
+            ExternalChangePolicy::Warn => {
+                events::external_change(&self.span, &e);
+                None
+            }
+            ExternalChangePolicy::Error => Some(e),
+        }
+    }
+
+    /// Changes back to the original directory and returns that directory's
+    /// path, consuming the `Pushd`.
+    ///
+    /// Unlike [`Pushd::pop`], this always disarms the guard. If changing back
+    /// to the original directory fails, the error is returned and nothing else
+    /// happens when the guard is dropped. This lets you handle a restore
+    /// failure at a precise point, rather than relying on the drop behavior.
+    ///
+    /// If the guard was already popped, this simply returns the original
+    /// path.
+    ///
+    /// # Errors
+    ///
+    /// This method return an error if the current directory cannot be changed.
+    pub fn into_pop(mut self) -> Result<PathBuf, PushdError> {
+        let res = self.pop();
+        self.disarm();
+        res.map(|()| mem::take(&mut self.orig))
+    }
+
+    /// Consumes the `Pushd` without changing back to the original directory.
+    ///
+    /// After calling this, the directory change made by this `Pushd` is
+    /// permanent. Unlike passing the guard to [`mem::forget`], this does not
+    /// leak any memory.
+    pub fn forget(mut self) {
+        self.disarm();
+    }
+
+    // Marks the guard as popped, so that it does nothing more when it is
+    // dropped.
+    fn disarm(&mut self) {
+        self.unnest_quietly();
+        if !self.popped {
+            stats::exited();
+            self.popped = true;
+        }
+    }
+}
+
+// Returns the current directory. On Windows, this removes the `\\?\` prefix
+// if it isn't needed.
+fn current_dir() -> io::Result<PathBuf> {
+    let cwd = env::current_dir()?;
+    #[cfg(windows)]
+    let cwd = windows::simplify(cwd);
+    Ok(cwd)
+}
+
+// Applies the options that change the target before it is changed to.
+fn resolve_target(opts: &mut builder::Options, path: PathBuf) -> Result<PathBuf, PushdError> {
+    let path = expanded_target(opts, path)?;
+    #[cfg(target_os = "linux")]
+    linux::settle_jail(opts)?;
+    let path = if opts.canonicalize {
+        fs::canonicalize(&path).map_err(|e| PushdError::Metadata {
+            path: path.clone(),
+            source: e,
+        })?
+    } else {
+        path
+    };
+    #[cfg(windows)]
+    let path = windows::simplify(path);
+    Ok(path)
+}
+
+// Returns the target that a guard with these options would change to, before
+// it's canonicalized. This doesn't change the options, so it's also used to
+// check the target before deciding whether to push at all.
+fn expanded_target(opts: &builder::Options, path: PathBuf) -> Result<PathBuf, PushdError> {
+    let path = opts.expand.apply(path, opts.base.as_deref())?;
+    #[cfg(windows)]
+    let path = windows::resolve_drive_relative(path);
+    #[cfg(target_os = "linux")]
+    let path = linux::jailed_target(opts, path)?;
+    Ok(path)
+}
+
+// Returns the directory that a new guard should change back to.
+fn original_dir(opts: &builder::Options) -> Result<PathBuf, PushdError> {
+    let cached = match opts.cwd_cache {
+        cwd_cache::CwdCache::Use if opts.original.is_none() && opts.backend.is_none() => {
+            cwd_cache::get()
+        }
+        _ => None,
+    };
+    let cwd = match (&opts.original, cached) {
+        (_, Some(cwd)) => cwd,
+        (Some(dir), None) if dir.is_absolute() => dir.clone(),
+        (Some(dir), None) => opts.current_dir()?.join(dir),
+        (None, None) => opts.current_dir()?,
+    };
+    if !opts.canonicalize {
+        return Ok(cwd);
+    }
+    let cwd = fs::canonicalize(&cwd).map_err(|e| PushdError::Metadata {
+        path: cwd,
+        source: e,
+    })?;
+    #[cfg(windows)]
+    let cwd = windows::simplify(cwd);
+    Ok(cwd)
+}
+
+// Changes the current directory. On Windows, this adds the `\\?\` prefix to
+// paths that are too long to use without it. The new directory is recorded
+// for guards that use the cached current directory.
+fn chdir(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    let res = env::set_current_dir(windows::extend_long_path(path));
+    #[cfg(not(windows))]
+    let res = env::set_current_dir(path);
+    match &res {
+        Ok(()) => cwd_cache::record(path),
+        Err(_) => cwd_cache::clear_cwd_cache(),
+    }
+    res
+}
+
+// Changes into a guard's target, `chdir_to`, which is usually `path`. On
+// Linux, a jailed guard uses `openat2` to make sure that the target is inside
+// the jail. On Unix, a guard that checks its target opens it first, checks
+// the open directory, and then changes to it with `fchdir`, so that the
+// directory can't be swapped out between the check and the change.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn enter(
+    opts: &builder::Options,
+    checked: Option<&secure::Checked>,
+    from: &Path,
+    path: &Path,
+    chdir_to: &Path,
+) -> Result<(), PushdError> {
+    if opts.backend.is_some() {
+        return opts
+            .set_current_dir(chdir_to)
+            .map_err(|e| chdir_error(from.to_owned(), path.to_owned(), e));
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(root) = &opts.jail {
+        let dir = linux::open_beneath(root, chdir_to).map_err(|e| match e.raw_os_error() {
+            Some(libc::EXDEV | libc::ELOOP) => PushdError::OutsideJail {
+                root: root.clone(),
+                path: path.to_owned(),
+                source: e,
+            },
+            _ => chdir_error(from.to_owned(), path.to_owned(), e),
+        })?;
+        return secure::enter_dir(&dir, opts.handle, checked, from, path);
+    }
+    #[cfg(unix)]
+    if opts.handle.is_some() || checked.is_some() {
+        let dir = secure::open_dir(chdir_to)
+            .map_err(|e| chdir_error(from.to_owned(), path.to_owned(), e))?;
+        return secure::enter_dir(&dir, opts.handle, checked, from, path);
+    }
+    chdir(chdir_to).map_err(|e| chdir_error(from.to_owned(), path.to_owned(), e))
+}
+
+// Calls `enter`, retrying the errors that the target might recover from if the
+// options have a policy for that.
+fn enter_with_retry(
+    opts: &builder::Options,
+    checked: Option<&secure::Checked>,
+    from: &Path,
+    path: &Path,
+    chdir_to: &Path,
+) -> Result<(), PushdError> {
+    let mut res = enter(opts, checked, from, path, chdir_to);
+    if let Some(policy) = opts.enter_retry {
+        for delay in policy.delays() {
+            match &res {
+                Err(e) if RetryPolicy::should_retry_enter(e) => {
+                    events::retrying_enter(&opts.log, &from.join(path), e, delay);
+                }
+                _ => break,
+            }
+            thread::sleep(delay);
+            res = enter(opts, checked, from, path, chdir_to);
+        }
+    }
+    res
+}
+
+// Returns a new backtrace if `capture` is true, and a disabled one, which
+// costs nothing to create, if it isn't.
+fn backtrace(capture: bool) -> Arc<Backtrace> {
+    Arc::new(if capture {
+        Backtrace::force_capture()
+    } else {
+        Backtrace::disabled()
+    })
+}
+
+// Returns the most specific error for a failure to change from `from` into
+// `path`.
+fn chdir_error(from: PathBuf, path: PathBuf, source: io::Error) -> PushdError {
+    let backtrace = backtrace(config::with_config(|c| c.capture_backtraces));
+    match source.kind() {
+        io::ErrorKind::NotFound => PushdError::TargetNotFound {
+            from,
+            path,
+            source,
+            backtrace,
+        },
+        io::ErrorKind::PermissionDenied => PushdError::PermissionDenied {
+            from,
+            path,
+            source,
+            backtrace,
+        },
+        _ => PushdError::SetCurrentDir {
+            from,
+            path,
+            source,
+            backtrace,
+        },
+    }
+}
+
+// Records a directory change in the log returned by `history` and tells any
+// observers about it.
+fn report(
+    action: HistoryAction,
+    from: &Path,
+    to: &Path,
+    caller: &'static Location<'static>,
+    outcome: Result<(), &PushdError>,
+) {
+    audit::record(action, from, to, caller, outcome);
+    observer::notify(action, from, to, caller, outcome);
+}
+
+/// Dereferences to the absolute path of the guard's target, so you can use
+/// [`Path`] methods on the guard, like `pd.join("Cargo.toml")`.
+///
+/// Unlike [`Pushd::path`], this is always absolute, so the result of joining
+/// a relative path to it doesn't depend on the current directory.
+impl<B: DropBehavior> Deref for Pushd<B> {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.anchor.path()
+    }
+}
+
+impl<B: DropBehavior> AsRef<Path> for Pushd<B> {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+
+/// This is the same as calling [`Pushd::new`].
+impl TryFrom<&Path> for Pushd<Panicking> {
+    type Error = PushdError;
+
+    #[track_caller]
+    fn try_from(path: &Path) -> Result<Self, PushdError> {
+        Pushd::new(path)
+    }
+}
+
+/// This is the same as calling [`Pushd::new`], except that the path is moved
+/// into the guard instead of being copied.
+impl TryFrom<PathBuf> for Pushd<Panicking> {
+    type Error = PushdError;
+
+    #[track_caller]
+    fn try_from(path: PathBuf) -> Result<Self, PushdError> {
+        PushdBuilder::new(path).push()
+    }
+}
+
+/// This is the same as calling [`Pushd::new_no_panic`].
+impl TryFrom<&Path> for Pushd<NonPanicking> {
+    type Error = PushdError;
+
+    #[track_caller]
+    fn try_from(path: &Path) -> Result<Self, PushdError> {
+        Pushd::new_no_panic(path)
+    }
+}
+
+/// This is the same as calling [`Pushd::new_no_panic`], except that the path
+/// is moved into the guard instead of being copied.
+impl TryFrom<PathBuf> for Pushd<NonPanicking> {
+    type Error = PushdError;
+
+    #[track_caller]
+    fn try_from(path: PathBuf) -> Result<Self, PushdError> {
+        PushdBuilder::new(path).no_panic().push()
+    }
+}
+
+impl<B: DropBehavior> fmt::Debug for Pushd<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pushd")
+            .field("id", &self.id)
+            .field("original", &self.orig)
+            .field("target", &self.target)
+            .field("popped", &self.is_popped())
+            .field("noop", &self.noop)
+            .field("context", &self.opts.context)
+            .field("caller", &self.caller)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Shows the guard's original directory and the absolute path of its target,
+/// like `/home/me -> /home/me/src (popped: false)`.
+impl<B: DropBehavior> fmt::Display for Pushd<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {} (popped: {})",
+            self.orig.display(),
+            self.orig.join(&self.target).display(),
+            self.is_popped(),
+        )
+    }
+}
+
+impl<B: DropBehavior> Drop for Pushd<B> {
+    /// Changes back to the original directory.
+    ///
+    /// When the [`Pushd`] struct is dropped, it will change back to the
+    /// original directory. If this fails, it's behavior is as follows:
+    ///
+    /// * If the [`Pushd`] was constructed with [`Pushd::new_with_handler`],
+    ///   it will call the handler with the error.
+    ///
+    /// * Otherwise it follows the guard's [`DropPolicy`], which is
+    ///   [`DropPolicy::Panic`] unless the guard was built with
+    ///   [`PushdBuilder::drop_policy`] or a different default was set with
+    ///   [`configure`].
+    ///
+    /// With [`DropPolicy::Panic`], a [`Pushd`] constructed with
+    /// [`Pushd::new_no_panic`] will log the error by calling
+    /// [`log::warn!`](https://docs.rs/log/latest/log/macro.warn.html). A
+    /// [`Pushd`] constructed with [`Pushd::new`] will do nothing if the error
+    /// is one of the guard's
+    /// [ignored kinds](PushdBuilder::ignore_drop_errors). By default, that is
+    /// when the original directory no longer exists, so that
+    /// [`PushdError::is_not_found`] returns true. Otherwise it will panic with
+    /// the error from attempting to change the current directory.
+    fn drop(&mut self) {
+        self.poison_if_panicking();
+        let res = self.pop_with_retry(self.opts.restore_retry);
+        if !self.popped {
+            stats::restore_failed();
+            telemetry::restore_failed();
+            self.disarm();
+        }
+        if let Err(e) = res {
+            if let Some(handler) = self.opts.handler.take() {
+                handler(self.with_context(e));
+                return;
+            }
+
+            let e = self.with_context(e);
+            match &self.opts.drop_policy {
+                DropPolicy::Ignore => {}
+                DropPolicy::Callback(callback) => callback(e),
+                #[cfg(not(feature = "no-drop-panic"))]
+                DropPolicy::Panic if B::MAY_PANIC => {
+                    if self.opts.ignored_drop_errors.iter().any(|k| e.is_kind(*k)) {
+                        return;
+                    }
+                    events::restore_failed(&self.span, &self.orig, self.caller, &e, true);
+                    panic!(
+                        "Could not return to original dir for the guard created at {}: {e}",
+                        self.caller,
+                    );
+                }
+                DropPolicy::Panic | DropPolicy::Warn => {
+                    events::restore_failed(&self.span, &self.orig, self.caller, &e, false);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    // Anything that does pushd must be run serially or else chaos ensues.
+    use serial_test::serial;
+    #[cfg(all(not(target_os = "windows"), not(feature = "no-drop-panic")))]
+    use std::panic;
+    #[cfg(not(target_os = "windows"))]
+    use std::time::Duration;
+    #[cfg(not(target_os = "windows"))]
+    use std::{backtrace::BacktraceStatus, os::unix::fs::PermissionsExt};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn no_errors() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = fs::canonicalize(env::current_dir()?)?;
+
+        {
+            let td = tempdir()?;
+            let _pd = Pushd::new(td.path());
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+        }
+        assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn no_errors_explicit_pop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = fs::canonicalize(env::current_dir()?)?;
+
+        {
+            let td = tempdir()?;
+            let mut pd = Pushd::new(td.path())?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+            assert!(!pd.is_popped());
+            pd.pop()?;
+            assert!(pd.is_popped());
+            assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+        }
+
+        assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn accessors() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+
+        let pd = Pushd::new("src")?;
+        assert_eq!(pd.path(), Path::new("src"));
+        assert_eq!(pd.original(), cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn into_pop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+
+        let td = tempdir()?;
+        let pd = Pushd::new(td.path())?;
+        let orig = pd.into_pop()?;
+        assert_eq!(orig, cwd);
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn reenter() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let src = cwd.join("src");
+
+        {
+            let mut pd = Pushd::new("src")?;
+            pd.pop()?;
+            assert_eq!(env::current_dir()?, cwd);
+
+            // The relative target is resolved against the original directory.
+            let td = tempdir()?;
+            env::set_current_dir(td.path())?;
+            pd.reenter()?;
+            assert!(!pd.is_popped());
+            assert_eq!(env::current_dir()?, src);
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        // Reentering a target that was replaced changes to the new directory
+        // and anchors the guard there.
+        let td = tempdir()?;
+        let target = td.path().join("target");
+        fs::create_dir(&target)?;
+        let mut pd = Pushd::new(&target)?;
+        pd.assert_current()?;
+        pd.pop()?;
+        fs::remove_dir(&target)?;
+        fs::create_dir(&target)?;
+        pd.reenter()?;
+        pd.assert_current()?;
+        pd.write("file", "content")?;
+        assert!(target.join("file").is_file());
+        pd.pop()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn back() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let src = cwd.join("src");
+
+        {
+            let _pd = Pushd::new("src")?;
+            // This goes back to where we were before the push.
+            let mut back = Pushd::back()?;
+            assert_eq!(env::current_dir()?, cwd);
+            back.pop()?;
+            assert_eq!(env::current_dir()?, src);
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        // After popping, the previous directory is the one we popped from.
+        let _pd = Pushd::back()?;
+        assert_eq!(env::current_dir()?, src);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    #[serial]
+    fn in_dir_macro() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+
+        #[in_dir("src")]
+        fn in_src() -> io::Result<PathBuf> {
+            env::current_dir()
+        }
+        assert_eq!(in_src()?, cwd.join("src"));
+        assert_eq!(env::current_dir()?, cwd);
+
+        #[in_temp_dir(serial)]
+        fn in_temp() -> io::Result<PathBuf> {
+            env::current_dir()
+        }
+        let temp = in_temp()?;
+        assert_ne!(temp, cwd);
+        assert!(!temp.exists());
+        assert_eq!(env::current_dir()?, cwd);
+
+        #[pushd::serial("src")]
+        fn serial_in_src() -> io::Result<PathBuf> {
+            assert!(crate::sync::try_lock(&crate::__private::CWD_LOCK).is_none());
+            env::current_dir()
+        }
+        assert_eq!(serial_in_src()?, cwd.join("src"));
+        assert!(crate::sync::try_lock(&crate::__private::CWD_LOCK).is_some());
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn deferred() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+
+        let td = tempdir()?;
+        let deferred = Pushd::deferred(td.path())?;
+        assert_eq!(env::current_dir()?, cwd);
+        {
+            let _pd = deferred.enter()?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert!(matches!(
+            Pushd::deferred(td.path().join("missing")),
+            Err(PushdError::Metadata { .. }),
+        ));
+        assert!(matches!(
+            Pushd::deferred("Cargo.toml"),
+            Err(PushdError::NotADirectory { path }) if path == Path::new("Cargo.toml"),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn deferred_relative_to() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        env::set_current_dir("src")?;
+        let deferred = Pushd::builder("src").relative_to(&cwd).deferred()?;
+        {
+            let _pd = deferred.enter()?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd.join("src"));
+
+        assert!(matches!(
+            Pushd::builder("Cargo.toml").relative_to(&cwd).deferred(),
+            Err(PushdError::NotADirectory { path }) if path == cwd.join("Cargo.toml"),
+        ));
+        env::set_current_dir(&cwd)?;
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn fallback_dirs() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td1 = tempdir()?;
+        env::set_current_dir(td1.path())?;
+
+        let fallback = tempdir()?;
+        {
+            let td2 = tempdir()?;
+            let _pd = Pushd::builder(td2.path())
+                .fallback_dirs([td1.path().join("missing"), fallback.path().to_owned()])
+                .push()?;
+            td1.close()?;
+        }
+        assert_eq!(
+            fs::canonicalize(env::current_dir()?)?,
+            fs::canonicalize(fallback.path())?,
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn elapsed() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let before = Instant::now();
+        let mut pd = Pushd::new("src")?;
+        assert!(pd.created_at() >= before);
+        thread::sleep(Duration::from_millis(10));
+        pd.pop()?;
+        let popped = pd.elapsed();
+        assert!(popped >= Duration::from_millis(10));
+        pd.reenter()?;
+        assert!(pd.elapsed() >= popped);
+        assert!(pd.elapsed() <= before.elapsed());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn deref() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let pd = Pushd::new("src")?;
+        assert_eq!(pd.join("lib.rs"), cwd.join("src/lib.rs"));
+        assert!(pd.join("lib.rs").is_file());
+        assert_eq!(AsRef::<Path>::as_ref(&pd), cwd.join("src"));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn try_from() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        {
+            let pd = Pushd::<Panicking>::try_from(Path::new("src"))?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+            assert_eq!(pd.caller().file(), file!());
+        }
+        assert_eq!(env::current_dir()?, cwd);
+        {
+            let _pd: Pushd<NonPanicking> = cwd.join("src").try_into()?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
+        {
+            let pd = PushdBuilder::from(PathBuf::from("src")).push()?;
+            assert_eq!(pd.path(), Path::new("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
+        assert!(matches!(
+            Pushd::<Panicking>::try_from(PathBuf::from("no-such-dir")),
+            Err(PushdError::TargetNotFound { .. }),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn debug_and_display() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let mut pd = Pushd::new("src")?;
+        assert!(format!("{pd:?}").starts_with("Pushd { id: "));
+        let display = |popped| {
+            format!(
+                "{} -> {} (popped: {popped})",
+                cwd.display(),
+                cwd.join("src").display(),
+            )
+        };
+        assert_eq!(pd.to_string(), display(false));
+        pd.pop()?;
+        assert_eq!(pd.to_string(), display(true));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn restore_to() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let mut pd = Pushd::builder("src").restore_to("pushd-macros").push()?;
+        assert_eq!(pd.original(), cwd.join("pushd-macros"));
+        assert_eq!(pd.path(), cwd.join("src"));
+        pd.pop()?;
+        assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
+
+        // The target is still resolved against the original directory.
+        pd.reenter()?;
+        assert_eq!(env::current_dir()?, cwd.join("src"));
+        drop(pd);
+        assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
 
-        debug!("Setting current dir back to {}.", self.orig.display());
-        env::set_current_dir(&self.orig).map_err(|e| PushdError::SetCurrentDir {
-            path: self.orig.clone(),
-            source: e,
-        })?;
-        self.popped = true;
         Ok(())
     }
-}
 
-impl Drop for Pushd {
-    /// Changes back to the original directory.
-    ///
-    /// When the [`Pushd`] struct is dropped, it will change back to the
-    /// original directory. If this fails, it's behavior is as follows:
-    ///
-    /// * If the [`Pushd`] was constructed with [`Pushd::new_no_panic`], it
-    /// will log the error by calling
-    /// [`log::warn!`](https://docs.rs/log/latest/log/macro.warn.html).
-    ///
-    /// * If the [`Pushd`] was constructed with [`Pushd::new`] and the error
-    /// is an [`io::Error`] and the error's [`io::Error::kind`] method returns
-    /// [`io::ErrorKind::NotFound`], it will do nothing.
-    ///
-    /// * Otherwise it will panic with the error from attempting to change the
-    /// current directory.
-    fn drop(&mut self) {
-        if let Err(e) = self.pop() {
-            if !self.panic_on_err {
-                warn!(
-                    "Could not return to original dir {}: {e}",
-                    self.orig.display(),
-                );
-                return;
-            }
+    #[test]
+    #[serial]
+    fn original_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
 
-            if let Some(s) = e.source() {
-                if let Some(i) = s.downcast_ref::<io::Error>() {
-                    if i.kind() == io::ErrorKind::NotFound {
-                        return;
-                    }
-                }
-            }
+        let cwd = env::current_dir()?;
+        {
+            let pd = Pushd::builder("src").original_dir(&cwd).push()?;
+            assert_eq!(pd.original(), cwd);
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
 
-            panic!("Could not return to original dir: {e}");
+        // The guard trusts the directory it's given, even when it's wrong.
+        {
+            let _pd = Pushd::builder(cwd.join("src"))
+                .original_dir(cwd.join("pushd-macros"))
+                .push()?;
         }
+        assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    // Anything that does pushd must be run serially or else chaos ensues.
-    use serial_test::serial;
-    #[cfg(not(target_os = "windows"))]
-    use std::os::unix::fs::PermissionsExt;
     #[cfg(not(target_os = "windows"))]
-    use std::panic;
-    use tempfile::tempdir;
+    #[test]
+    #[serial]
+    fn new_from_deleted_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let dead = td.path().join("dead");
+        fs::create_dir(&dead)?;
+        env::set_current_dir(&dead)?;
+        fs::remove_dir(&dead)?;
+        let res = Pushd::new(td.path());
+        assert!(
+            matches!(res, Err(PushdError::GetCurrentDir { .. })),
+            "{res:?}"
+        );
+
+        let pd = Pushd::new_from(&dead, td.path())?;
+        assert_eq!(pd.original(), dead);
+        assert_eq!(
+            fs::canonicalize(env::current_dir()?)?,
+            fs::canonicalize(td.path())?
+        );
+        // The original directory is gone, so the guard can't go back there.
+        drop(pd);
+        assert_eq!(
+            fs::canonicalize(env::current_dir()?)?,
+            fs::canonicalize(td.path())?
+        );
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        Ok(())
+    }
 
+    #[cfg(not(target_os = "windows"))]
     #[test]
     #[serial]
-    fn no_errors() -> Result<(), Box<dyn StdError>> {
+    fn restore_retry() -> Result<(), Box<dyn StdError>> {
         env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
 
-        let cwd = fs::canonicalize(env::current_dir()?)?;
+        let td1 = tempdir()?;
+        env::set_current_dir(td1.path())?;
+        let orig = env::current_dir()?;
 
         {
-            let td = tempdir()?;
-            let _pd = Pushd::new(td.path());
-            assert_eq!(
-                fs::canonicalize(env::current_dir()?)?,
-                fs::canonicalize(td.path())?,
-            );
+            let td2 = tempdir()?;
+            let _pd = Pushd::builder(td2.path())
+                .restore_retry(RetryPolicy::new(20, Duration::from_millis(10)))
+                .push()?;
+
+            // Make the original directory inaccessible, then fix it after a
+            // short delay, while the drop is retrying.
+            let mut perms = fs::metadata(td1.path())?.permissions();
+            perms.set_mode(0o0000);
+            fs::set_permissions(td1.path(), perms.clone())?;
+            let path = td1.path().to_owned();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(30));
+                perms.set_mode(0o0700);
+                fs::set_permissions(path, perms).unwrap();
+            });
         }
-        assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+        assert_eq!(env::current_dir()?, orig);
 
         Ok(())
     }
 
     #[test]
     #[serial]
-    fn no_errors_explicit_pop() -> Result<(), Box<dyn StdError>> {
+    fn new_in() -> Result<(), Box<dyn StdError>> {
         env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
 
-        let cwd = fs::canonicalize(env::current_dir()?)?;
+        let cwd = env::current_dir()?;
+        {
+            let _src = Pushd::new("src")?;
+            let pd = Pushd::new_in(&cwd, "pushd-macros")?;
+            assert_eq!(pd.path(), cwd.join("pushd-macros"));
+            assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
+            drop(pd);
+
+            let _pd = Pushd::new_in(cwd.join("pushd-macros"), &cwd)?;
+            assert_eq!(env::current_dir()?, cwd);
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert!(matches!(
+            Pushd::builder("../..")
+                .relative_to(&cwd)
+                .parent_dirs(ParentDirPolicy::Reject)
+                .push(),
+            Err(PushdError::ParentDir { .. }),
+        ));
+        let _pd = Pushd::builder("src")
+            .relative_to(&cwd)
+            .require_absolute()
+            .push()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn new_absolute() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
 
+        let cwd = env::current_dir()?;
         {
-            let td = tempdir()?;
-            let mut pd = Pushd::new(td.path())?;
+            let _pd = Pushd::new_absolute(cwd.join("src"))?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+            assert!(matches!(
+                Pushd::new_absolute(".."),
+                Err(PushdError::RelativeTarget { path }) if path == Path::new(".."),
+            ));
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert!(matches!(
+            Pushd::builder(Path::new("~").join("pushd-no-such-dir"))
+                .require_absolute()
+                .expand_tilde()
+                .push(),
+            Err(PushdError::TargetNotFound { .. }),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn new_with_retry() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let td = tempdir()?;
+        let target = td.path().join("later");
+        {
+            let path = target.clone();
+            let creator = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(30));
+                fs::create_dir(path).unwrap();
+            });
+            let _pd = Pushd::new_with_retry(&target, 50, Duration::from_millis(10))?;
+            creator.join().unwrap();
             assert_eq!(
                 fs::canonicalize(env::current_dir()?)?,
-                fs::canonicalize(td.path())?,
+                fs::canonicalize(&target)?,
             );
-            pd.pop()?;
-            assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
         }
+        assert_eq!(env::current_dir()?, cwd);
 
-        assert_eq!(fs::canonicalize(env::current_dir()?)?, cwd);
+        assert!(matches!(
+            Pushd::new_with_retry("no-such-dir", 2, Duration::from_millis(1)),
+            Err(PushdError::TargetNotFound { .. }),
+        ));
+        assert!(matches!(
+            Pushd::new_with_retry("Cargo.toml", 2, Duration::from_secs(30)),
+            Err(e) if e.is_not_a_directory(),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn forget() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        Pushd::new(td.path())?.forget();
+        assert_eq!(
+            fs::canonicalize(env::current_dir()?)?,
+            fs::canonicalize(td.path())?,
+        );
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn into_pop_error() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td1 = tempdir()?;
+        env::set_current_dir(td1.path())?;
+
+        let orig = env::current_dir()?;
+        let td2 = tempdir()?;
+        let pd = Pushd::new_no_panic(td2.path())?;
+        td1.close()?;
+
+        let res = pd.into_pop();
+        assert!(res.is_err());
+        assert!(matches!(
+            res.unwrap_err(),
+            PushdError::RestoreFailed { path, .. } if path == orig,
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn external_change() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+
+        let td1 = tempdir()?;
+        let td2 = tempdir()?;
+        let mut pd = Pushd::builder(td1.path())
+            .on_external_change(ExternalChangePolicy::Error)
+            .push()?;
+        env::set_current_dir(td2.path())?;
+        let elsewhere = env::current_dir()?;
+
+        let res = pd.pop();
+        assert!(matches!(
+            &res,
+            Err(PushdError::CwdChangedExternally { expected, found })
+                if expected == td1.path() && *found == elsewhere,
+        ));
+        assert!(pd.is_popped());
+        assert_eq!(env::current_dir()?, cwd);
+
+        // Re-entering records the target again.
+        pd.reenter()?;
+        env::set_current_dir(td2.path())?;
+        assert!(matches!(
+            pd.pop(),
+            Err(PushdError::CwdChangedExternally { expected, .. }) if expected == td1.path(),
+        ));
+        assert_eq!(env::current_dir()?, cwd);
+
+        let mut pd = Pushd::builder(td1.path())
+            .on_external_change(ExternalChangePolicy::Warn)
+            .push()?;
+        env::set_current_dir(td2.path())?;
+        pd.pop()?;
+        assert_eq!(env::current_dir()?, cwd);
 
         Ok(())
     }
@@ -267,6 +2999,29 @@ mod tests {
     #[cfg(not(target_os = "windows"))]
     #[test]
     #[serial]
+    fn handler_on_drop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td1 = tempdir()?;
+        env::set_current_dir(td1.path())?;
+        let orig = env::current_dir()?;
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        {
+            let td2 = tempdir()?;
+            let _pd = Pushd::new_with_handler(td2.path(), move |e| tx.send(e).unwrap())?;
+            td1.close()?;
+        }
+
+        let e = rx.try_recv()?;
+        assert!(matches!(e, PushdError::RestoreFailed { path, .. } if path == orig));
+
+        Ok(())
+    }
+
+    #[cfg(all(not(target_os = "windows"), not(feature = "no-drop-panic")))]
+    #[test]
+    #[serial]
     fn permissions_error_panic_on_drop() -> Result<(), Box<dyn StdError>> {
         let result = panic::catch_unwind(|| {
             let man_dir = match env::var("CARGO_MANIFEST_DIR") {
@@ -368,4 +3123,221 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    #[serial]
+    fn caller_location() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let line = line!() + 1;
+        let pd = Pushd::new("src")?;
+        assert_eq!(pd.caller().line(), line);
+        assert!(pd.caller().file().ends_with("lib.rs"));
+
+        let pd = Pushd::builder("..").no_panic().push()?;
+        assert_eq!(pd.caller().line(), line!() - 1);
+
+        Ok(())
+    }
+
+    #[cfg(all(not(target_os = "windows"), not(feature = "no-drop-panic")))]
+    #[test]
+    #[serial]
+    fn caller_in_panic_message() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+
+        let line = line!() + 1;
+        let pd = Pushd::new(td.path())?;
+        // Replacing the original directory with a file makes changing back
+        // fail with an error that isn't ignored.
+        fs::remove_dir(&orig)?;
+        fs::write(&orig, "")?;
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(pd)));
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let payload = res.expect_err("dropping the guard did not panic");
+        let msg = payload
+            .downcast_ref::<String>()
+            .ok_or("the panic payload is not a string")?;
+        assert!(
+            msg.contains(&format!("lib.rs:{line}:")),
+            "{msg} contains the caller location",
+        );
+
+        Ok(())
+    }
+
+    #[cfg(all(not(target_os = "windows"), feature = "no-drop-panic"))]
+    #[test]
+    #[serial]
+    fn no_drop_panic() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+
+        let pd = Pushd::new(td.path())?;
+        fs::remove_dir(&orig)?;
+        fs::write(&orig, "")?;
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(pd)));
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        assert!(res.is_ok(), "dropping the guard panicked");
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn backtraces() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let e = Pushd::new("no-such-dir")
+            .err()
+            .ok_or("pushed to a missing dir")?;
+        assert_eq!(
+            e.backtrace().map(Backtrace::status),
+            Some(BacktraceStatus::Disabled),
+        );
+        assert!(e.guard_backtrace().is_none());
+
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+
+        let mut pd = Pushd::builder(td.path())
+            .capture_backtraces()
+            .push()?
+            .context("while testing");
+        fs::remove_dir(&orig)?;
+        fs::write(&orig, "")?;
+        let res = pd.pop();
+        pd.forget();
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let e = res.err().ok_or("changed back to a file")?;
+        assert_eq!(
+            e.backtrace().map(Backtrace::status),
+            Some(BacktraceStatus::Captured),
+        );
+        assert_eq!(
+            e.guard_backtrace().map(Backtrace::status),
+            Some(BacktraceStatus::Captured),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn error_includes_both_paths() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let e = Pushd::new("no-such-dir")
+            .err()
+            .ok_or("pushed to a missing dir")?;
+        let expect = format!(
+            "Could not set current directory from {} to no-such-dir: ",
+            cwd.display(),
+        );
+        assert!(e.to_string().starts_with(&expect), "{e}");
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn error_predicates() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let e = Pushd::new("no-such-dir")
+            .err()
+            .ok_or("pushed to a missing dir")?;
+        assert!(matches!(e, PushdError::TargetNotFound { .. }));
+        assert!(e.is_not_found());
+        assert!(!e.is_permission_denied());
+        assert!(!e.is_restore_failure());
+        assert_eq!(e.target_path(), Some(Path::new("no-such-dir")));
+        assert_eq!(e.exit_code(), 66);
+        assert_eq!(
+            std::process::ExitCode::from(e),
+            std::process::ExitCode::from(66),
+        );
+
+        let e = Pushd::new("Cargo.toml").err().ok_or("pushed to a file")?;
+        // The error from changing to a file keeps the original directory and
+        // the OS's error.
+        let PushdError::SetCurrentDir { from, source, .. } = &e else {
+            return Err(format!("unexpected error: {e:?}").into());
+        };
+        assert_eq!(from, &env::current_dir()?);
+        assert_eq!(source.kind(), io::ErrorKind::NotADirectory);
+        assert!(e.is_not_a_directory());
+        assert!(!e.is_not_found());
+        assert_eq!(e.target_path(), Some(Path::new("Cargo.toml")));
+        assert_eq!(e.exit_code(), 65);
+
+        assert_eq!(PushdError::EmptyStack.target_path(), None);
+        assert_eq!(PushdError::EmptyStack.exit_code(), 69);
+        let e = PushdError::Locked {
+            path: PathBuf::from("src"),
+        };
+        assert_eq!(e.exit_code(), 75);
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn restore_failure_predicates() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td1 = tempdir()?;
+        env::set_current_dir(td1.path())?;
+        let orig = env::current_dir()?;
+        let td2 = tempdir()?;
+        let pd = Pushd::new(td2.path())?.context("while testing");
+        td1.close()?;
+
+        let e = pd.into_pop().err().ok_or("changed back to a deleted dir")?;
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+        assert!(e.is_restore_failure());
+        assert!(e.is_not_found());
+        assert_eq!(e.target_path(), Some(orig.as_path()));
+        assert_eq!(e.exit_code(), 74);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn canonicalize_and_is_same_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let pd = Pushd::builder("src/..").canonicalize().push()?;
+        assert_eq!(pd.path(), fs::canonicalize(&cwd)?);
+        assert_eq!(pd.original(), fs::canonicalize(&cwd)?);
+        drop(pd);
+
+        let pd = Pushd::new("src/..")?;
+        assert_eq!(pd.path(), Path::new("src/.."));
+        assert!(pd.is_same_dir(&cwd));
+        assert!(pd.is_same_dir("."));
+        assert!(!pd.is_same_dir("src"));
+        assert!(!pd.is_same_dir("no-such-dir"));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,262 @@
+// This module contains Linux-specific functionality.
+
+use crate::{
+    builder::Options, current_dir, DropBehavior, Panicking, Pushd, PushdBuilder, PushdError,
+};
+use std::{
+    ffi::CString,
+    fs::{File, OpenOptions},
+    io, mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+};
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct whose directory change only affects the
+    /// current thread.
+    ///
+    /// This calls `unshare(CLONE_FS)` before changing directories, which
+    /// gives the calling thread its own copy of the filesystem attributes that
+    /// it previously shared with the rest of the process, including the current
+    /// directory. After that, directory changes on this thread are not visible
+    /// to other threads, and vice versa. This makes the guard safe to use from
+    /// parallel tests and worker threads.
+    ///
+    /// Note that this is permanent for the calling thread. Once a thread has
+    /// unshared its filesystem attributes, it never shares them with the rest
+    /// of the process again, even after the guard is dropped. Threads spawned
+    /// from the calling thread afterwards share its attributes.
+    ///
+    /// This is only available on Linux.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if `unshare` fails. It will also return an error if the
+    /// current directory cannot be determined or if it cannot be changed.
+    #[track_caller]
+    pub fn new_thread_scoped<P: AsRef<Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .thread_scoped()
+            .push()
+    }
+
+    /// Constructs a new `Pushd` struct that changes to `path` inside of the
+    /// jail directory `root`, which it can never leave. The `path` is resolved
+    /// against `root`, not the current directory. A relative `root` is
+    /// resolved against the current directory.
+    ///
+    /// The guard opens the target with `openat2`, relative to `root`, using
+    /// `RESOLVE_BENEATH` and `RESOLVE_NO_SYMLINKS`, and then changes to it with
+    /// `fchdir`. The kernel guarantees that the directory that is opened is
+    /// inside `root`, and that no symlinks were followed to get there. This
+    /// means that a target like `../..`, an absolute target, or a target
+    /// containing a symlink that points outside the jail is rejected, even
+    /// if the path comes from untrusted input. The same check is done each
+    /// time the guard is [re-entered](Pushd::reenter).
+    ///
+    /// A jailed guard never canonicalizes its target, since that would follow
+    /// symlinks. This requires Linux 5.6 or later, and is only available on
+    /// Linux.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::OutsideJail`] error if the target is not inside the
+    /// jail. Otherwise it returns the same errors as [`Pushd::new`]. On kernels without `openat2`,
+    /// it returns a [`PushdError::SetCurrentDir`] error wrapping an [`io::ErrorKind::Unsupported`]
+    /// error.
+    #[track_caller]
+    pub fn new_jailed<R, P>(root: R, path: P) -> Result<Pushd<Panicking>, PushdError>
+    where
+        R: AsRef<Path>,
+        P: AsRef<Path>,
+    {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .jail(root)
+            .push()
+    }
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the [`Pushd`] only affect the current thread, by calling
+    /// `unshare(CLONE_FS)` before changing directories. See
+    /// [`Pushd::new_thread_scoped`] for details.
+    ///
+    /// This is only available on Linux.
+    #[must_use]
+    pub fn thread_scoped(mut self) -> Self {
+        self.opts.thread_scoped = true;
+        self
+    }
+
+    /// Makes the [`Pushd`] treat its target as a path inside of the jail
+    /// directory `root`, which it can never leave. See [`Pushd::new_jailed`]
+    /// for details.
+    ///
+    /// This is only available on Linux.
+    #[must_use]
+    pub fn jail<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.opts.jail = Some(root.as_ref().to_owned());
+        self
+    }
+}
+
+// Returns a jailed guard's target inside its root. This is a no-op for a guard
+// that isn't jailed.
+pub(crate) fn jailed_target(opts: &Options, path: PathBuf) -> Result<PathBuf, PushdError> {
+    let Some(root) = &opts.jail else {
+        return Ok(path);
+    };
+    Ok(current_dir()?.join(root).join(path))
+}
+
+// Makes a jailed guard's root absolute, and turns off canonicalizing its
+// target. This is a no-op for a guard that isn't jailed.
+pub(crate) fn settle_jail(opts: &mut Options) -> Result<(), PushdError> {
+    let Some(root) = &opts.jail else {
+        return Ok(());
+    };
+    opts.jail = Some(current_dir()?.join(root));
+    opts.canonicalize = false;
+    Ok(())
+}
+
+// Opens `path`, which must be inside `root`, using `openat2` so that the
+// kernel makes sure that it is.
+pub(crate) fn open_beneath(root: &Path, path: &Path) -> io::Result<File> {
+    // An absolute target, or one that was canonicalized, will not be inside
+    // the root.
+    let rel = path
+        .strip_prefix(root)
+        .map_err(|_| io::Error::from_raw_os_error(libc::EXDEV))?;
+    let rel = if rel.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        rel
+    };
+    let rel = CString::new(rel.as_os_str().as_bytes()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "file name contained an unexpected NUL byte",
+        )
+    })?;
+    let root = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_DIRECTORY)
+        .open(root)?;
+
+    // SAFETY: `open_how` is a plain C struct, so all zeroes is a valid value.
+    let mut how: libc::open_how = unsafe { mem::zeroed() };
+    how.flags =
+        u64::try_from(libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC).unwrap_or_default();
+    how.resolve = libc::RESOLVE_BENEATH | libc::RESOLVE_NO_SYMLINKS;
+    // SAFETY: `root` is an open file descriptor, and `rel` and `how` outlive
+    // this call. The size is the real size of `how`.
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_openat2,
+            root.as_raw_fd(),
+            rel.as_ptr(),
+            &raw const how,
+            mem::size_of::<libc::open_how>(),
+        )
+    };
+    if fd == -1 {
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() == Some(libc::ENOSYS) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "openat2 is not supported by this kernel",
+            ));
+        }
+        return Err(e);
+    }
+    let fd = libc::c_int::try_from(fd).map_err(|_| io::Error::from_raw_os_error(libc::EBADF))?;
+    // SAFETY: `openat2` returned a new file descriptor, which nothing else
+    // owns.
+    Ok(File::from(unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+pub(crate) fn unshare_fs() -> Result<(), PushdError> {
+    // SAFETY: `unshare` has no memory safety requirements. With `CLONE_FS`
+    // it only affects the calling thread's filesystem attributes.
+    if unsafe { libc::unshare(libc::CLONE_FS) } == -1 {
+        return Err(PushdError::Unshare {
+            source: io::Error::last_os_error(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs, sync::mpsc, thread};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn thread_scoped() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let td = tempdir()?;
+        let target = fs::canonicalize(td.path())?;
+
+        let (entered_tx, entered_rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let path = target.clone();
+        let handle = thread::spawn(move || -> Result<_, PushdError> {
+            let _pd = Pushd::new_thread_scoped(&path)?;
+            entered_tx.send(env::current_dir()?).unwrap();
+            done_rx.recv().unwrap();
+            Ok(())
+        });
+
+        // The other thread sees its new directory while this thread's current
+        // directory is unchanged.
+        assert_eq!(entered_rx.recv()?, target);
+        assert_eq!(env::current_dir()?, cwd);
+        done_tx.send(())?;
+        handle.join().unwrap()?;
+
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn jailed() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let root = fs::canonicalize(td.path())?;
+        fs::create_dir_all(root.join("a/b"))?;
+        std::os::unix::fs::symlink("a", root.join("link"))?;
+
+        let cwd = env::current_dir()?;
+        let mut pd = Pushd::new_jailed(&root, "a/b")?;
+        assert_eq!(env::current_dir()?, root.join("a/b"));
+        pd.pop()?;
+        pd.reenter()?;
+        assert_eq!(env::current_dir()?, root.join("a/b"));
+        drop(pd);
+        assert_eq!(env::current_dir()?, cwd);
+
+        for target in ["..", "a/../..", "/tmp", "link"] {
+            let res = Pushd::new_jailed(&root, target);
+            assert!(
+                matches!(&res, Err(PushdError::OutsideJail { .. })),
+                "{target}: {:?}",
+                res.err(),
+            );
+            assert_eq!(env::current_dir()?, cwd);
+        }
+
+        Ok(())
+    }
+}
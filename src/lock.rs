@@ -0,0 +1,118 @@
+// This module contains the code for `PushdBuilder::lock`, which holds an
+// advisory lock on a guard's target directory.
+
+use crate::{DropBehavior, PushdBuilder, PushdError};
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    path::Path,
+};
+
+// The name of the file in the target directory that is locked.
+const LOCK_FILE: &str = ".pushd.lock";
+
+/// What a [`Pushd`](crate::Pushd) does when it cannot lock its target
+/// directory because something else holds the lock. See
+/// [`PushdBuilder::lock`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LockPolicy {
+    /// Wait until the lock is released.
+    Wait,
+    /// Return a [`PushdError::Locked`] error.
+    Fail,
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`](crate::Pushd) hold an exclusive advisory
+    /// lock on its target directory for as long as it is in the directory.
+    /// The `policy` determines what happens if another guard, in this or any
+    /// other process, already holds the lock.
+    ///
+    /// The lock is taken on a file named `.pushd.lock` in the target, which is
+    /// created if it doesn't exist, so the target must be writable. The file
+    /// is left in place after the lock is released. The lock is released
+    /// when the guard changes back, and taken again if the guard is
+    /// [re-entered](crate::Pushd::reenter).
+    ///
+    /// The lock is advisory, so it only keeps out other code that asks for
+    /// the same lock.
+    #[must_use]
+    pub fn lock(mut self, policy: LockPolicy) -> Self {
+        self.opts.lock = Some(policy);
+        self
+    }
+}
+
+// Locks `dir` according to `policy`, returning the open lock file, which
+// holds the lock until it is closed.
+pub(crate) fn acquire(policy: Option<LockPolicy>, dir: &Path) -> Result<Option<File>, PushdError> {
+    let Some(policy) = policy else {
+        return Ok(None);
+    };
+    let lock_error = |source| PushdError::Lock {
+        path: dir.to_owned(),
+        source,
+    };
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dir.join(LOCK_FILE))
+        .map_err(lock_error)?;
+    match policy {
+        LockPolicy::Wait => file.lock().map_err(lock_error)?,
+        LockPolicy::Fail => match file.try_lock() {
+            Ok(()) => (),
+            Err(TryLockError::WouldBlock) => {
+                return Err(PushdError::Locked {
+                    path: dir.to_owned(),
+                })
+            }
+            Err(TryLockError::Error(e)) => return Err(lock_error(e)),
+        },
+    }
+    Ok(Some(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, sync::mpsc, thread, time::Duration};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn lock() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let mut pd = Pushd::builder(td.path()).lock(LockPolicy::Fail).push()?;
+        assert!(td.path().join(LOCK_FILE).exists());
+        // Another open file description for the same file can't take the lock
+        // while the guard holds it, just like one in another process.
+        assert!(matches!(
+            acquire(Some(LockPolicy::Fail), td.path()),
+            Err(PushdError::Locked { path }) if path == td.path(),
+        ));
+
+        let (tx, rx) = mpsc::channel();
+        let dir = td.path().to_owned();
+        let waiter = thread::spawn(move || {
+            let lock = acquire(Some(LockPolicy::Wait), &dir);
+            tx.send(()).unwrap();
+            lock.map(|_| ())
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        pd.pop()?;
+        rx.recv_timeout(Duration::from_secs(5))?;
+        waiter.join().unwrap()?;
+
+        pd.reenter()?;
+        assert!(acquire(Some(LockPolicy::Fail), td.path()).is_err());
+        drop(pd);
+        assert!(acquire(Some(LockPolicy::Fail), td.path())?.is_some());
+
+        Ok(())
+    }
+}
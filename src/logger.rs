@@ -0,0 +1,156 @@
+// This module contains the global logger for `pushd::set_logger`, which
+// replaces the `log` facade or `tracing` as the destination for the crate's
+// messages.
+
+use log::Level;
+use std::{
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// A destination for the messages that this crate logs. Set one with
+/// [`set_logger`].
+///
+/// # Examples
+///
+/// ```
+/// use pushd::PushdLogger;
+/// use std::{fmt, sync::Mutex};
+///
+/// struct Collect(Mutex<Vec<String>>);
+///
+/// impl PushdLogger for Collect {
+///     fn log(&self, level: log::Level, message: fmt::Arguments<'_>) {
+///         self.0.lock().unwrap().push(format!("{level}: {message}"));
+///     }
+/// }
+///
+/// pushd::set_logger(Box::new(Collect(Mutex::new(Vec::new()))));
+/// # pushd::clear_logger();
+/// ```
+pub trait PushdLogger: Send + Sync + 'static {
+    /// Called with each message that this crate logs, on whichever thread
+    /// logged it. Messages that are filtered out by
+    /// [`Config::log_level`](crate::Config::log_level) are never formatted or
+    /// passed to the logger.
+    fn log(&self, level: Level, message: fmt::Arguments<'_>);
+}
+
+static LOGGER: Mutex<Option<Arc<dyn PushdLogger>>> = Mutex::new(None);
+
+fn logger() -> MutexGuard<'static, Option<Arc<dyn PushdLogger>>> {
+    LOGGER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Sends all of the messages that this crate logs to `logger` instead of the
+/// `log` facade, or `tracing` when the `tracing` feature is enabled. This
+/// replaces any logger that was set before.
+pub fn set_logger(logger: Box<dyn PushdLogger>) {
+    *self::logger() = Some(Arc::from(logger));
+}
+
+/// Removes the logger set with [`set_logger`], so that messages go to the
+/// `log` facade or `tracing` again.
+pub fn clear_logger() {
+    *logger() = None;
+}
+
+// Passes the message to the logger set with `set_logger` and returns true,
+// or returns false if there isn't one.
+pub(crate) fn log(level: Level, message: fmt::Arguments<'_>) -> bool {
+    // We copy the logger out so that it can log, or replace itself, without
+    // deadlocking.
+    let Some(logger) = logger().clone() else {
+        return false;
+    };
+    logger.log(level, message);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    type Messages = Arc<Mutex<Vec<(Level, String)>>>;
+
+    struct Recorder(Messages);
+
+    impl PushdLogger for Recorder {
+        fn log(&self, level: Level, message: fmt::Arguments<'_>) {
+            self.0.lock().unwrap().push((level, message.to_string()));
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn set_and_clear_logger() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let messages = Messages::default();
+        set_logger(Box::new(Recorder(Arc::clone(&messages))));
+        drop(Pushd::new("src")?);
+        clear_logger();
+        drop(Pushd::new("src")?);
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[0],
+            (
+                Level::Debug,
+                format!("Set current dir to src from {}.", cwd.display()),
+            ),
+        );
+        assert_eq!(messages[1].0, Level::Debug);
+        let back = format!("Setting current dir back to {} after ", cwd.display());
+        assert!(
+            messages[1].1.starts_with(&back) && messages[1].1.ends_with("s."),
+            "{}",
+            messages[1].1,
+        );
+
+        Ok(())
+    }
+    #[test]
+    #[serial]
+    fn guard_log_level() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let messages = Messages::default();
+        set_logger(Box::new(Recorder(Arc::clone(&messages))));
+        drop(
+            Pushd::builder("src")
+                .log(Level::Trace, "myapp::fs")
+                .push()?,
+        );
+        clear_logger();
+
+        let levels = messages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(l, _)| *l)
+            .collect::<Vec<_>>();
+        assert_eq!(levels, [Level::Trace, Level::Trace]);
+
+        Ok(())
+    }
+    #[test]
+    #[serial]
+    fn quiet() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let messages = Messages::default();
+        set_logger(Box::new(Recorder(Arc::clone(&messages))));
+        drop(Pushd::new_quiet("src")?);
+        drop(Pushd::builder("src").no_panic().quiet().push()?);
+        clear_logger();
+        assert!(messages.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+}
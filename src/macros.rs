@@ -0,0 +1,74 @@
+/// Changes the current directory for the rest of the enclosing scope.
+///
+/// This creates a [`Pushd`](crate::Pushd) and binds it to a hidden variable,
+/// so the guard lives until the end of the scope the macro is called in. This
+/// avoids the common mistake of writing `let _ = Pushd::new(path)`, which
+/// drops the guard, and changes back to the original directory, immediately.
+///
+/// The macro must be called as a statement. Any error from creating the guard
+/// is returned from the enclosing function with `?`, so that function must
+/// return a `Result` whose error type implements `From<PushdError>`.
+///
+/// Calling `pushd!(path)` creates a guard with [`Pushd::new`](crate::Pushd::new),
+/// and `pushd!(path, no_panic)` creates one with
+/// [`Pushd::new_no_panic`](crate::Pushd::new_no_panic). Like those
+/// constructors, the guard records the location of the macro call, which
+/// appears in its log messages.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::pushd;
+/// use std::env;
+///
+/// fn in_temp_dir() -> Result<(), pushd::PushdError> {
+///     pushd!(env::temp_dir());
+///     // The current directory is the temp dir until the end of this
+///     // function.
+///     Ok(())
+/// }
+/// # in_temp_dir().unwrap();
+/// ```
+#[macro_export]
+macro_rules! pushd {
+    ($path:expr, no_panic $(,)?) => {
+        let _pushd = $crate::Pushd::new_no_panic($path)?;
+    };
+    ($path:expr $(,)?) => {
+        let _pushd = $crate::Pushd::new($path)?;
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn pushd_macro() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let td = tempdir()?;
+        {
+            pushd!(td.path());
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+            {
+                pushd!(&cwd, no_panic);
+                assert_eq!(env::current_dir()?, cwd);
+            }
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,659 @@
+// This module keeps track of the guards that are in their targets, in the
+// order they entered them, so that a guard can tell when it is popped while a
+// guard that was created after it is still in its target.
+
+use crate::{
+    chdir, current_dir, events,
+    sync::{self, Mutex},
+    DropBehavior, Pushd, PushdBuilder, PushdError,
+};
+use std::{
+    io,
+    panic::Location,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// What a [`Pushd`] does when it is popped, either explicitly or when it is
+/// dropped, while a guard that was created after it is still in its target.
+///
+/// Guards are meant to be popped in the opposite order that they were
+/// created. If they aren't, the newer guard will later change back to the
+/// older guard's target, leaving the process in the wrong directory. With
+/// [`Reparent`](OutOfOrderPolicy::Reparent) or
+/// [`ForcePop`](OutOfOrderPolicy::ForcePop) the newer guards are adjusted so
+/// that this doesn't happen. With any other policy, the guard still changes
+/// back to its original directory.
+///
+/// Guards created with [`Pushd::new_thread_scoped`] are not tracked, since
+/// they don't change the directory for the rest of the process.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutOfOrderPolicy {
+    /// Don't check the order at all.
+    Ignore,
+    /// Log a warning if the guard is popped out of order. This is the default.
+    #[default]
+    Warn,
+    /// Return a [`PushdError::OutOfOrder`] error from [`Pushd::pop`] if the
+    /// guard is popped out of order. When this happens during drop, the error
+    /// is handled like any other error that happens during drop.
+    Error,
+    /// Panic if the guard is popped out of order. A guard that cannot panic,
+    /// like one returned by [`Pushd::new_no_panic`], logs a warning instead,
+    /// as does any guard when the `no-drop-panic` feature is enabled or the
+    /// thread is already panicking.
+    Panic,
+    /// Leave the current directory alone and hand the guard's original
+    /// directory to the next guard that was created after it, so that that
+    /// guard changes back to it instead of to this guard's target. The
+    /// process stays in the newest guard's target until that guard is popped,
+    /// and ends up in the right directory once all of the guards are popped.
+    ///
+    /// Since the guard doesn't change directories, it doesn't run its
+    /// [exit hooks](PushdBuilder::on_exit). The newer guard's
+    /// [`Pushd::original`] is updated when it is popped.
+    Reparent,
+    /// Pop every guard that was created after this one, then change back to
+    /// the guard's original directory. Like the guards popped by
+    /// [`restore_all`], those guards don't change directories or run their
+    /// [exit hooks](PushdBuilder::on_exit) when they are popped or dropped
+    /// later.
+    ForcePop,
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Sets the policy for what to do when the resulting [`Pushd`] is popped
+    /// while a guard that was created after it is still in its target. See
+    /// [`OutOfOrderPolicy`] for details.
+    #[must_use]
+    pub fn on_out_of_order(mut self, policy: OutOfOrderPolicy) -> Self {
+        self.opts.out_of_order = policy;
+        self
+    }
+}
+
+#[derive(Clone)]
+struct Entry {
+    id: u64,
+    target: PathBuf,
+    caller: &'static Location<'static>,
+    orig: PathBuf,
+    entered_at: Instant,
+    // True if the guard uses a `CwdBackend`, so it never changes the
+    // process's directory.
+    backend: bool,
+    // True if `restore_all` has changed back for the guard.
+    restored: bool,
+    // True if an older guard popped with `OutOfOrderPolicy::Reparent` handed
+    // its original directory to this one, in which case `orig` is that
+    // directory.
+    reparented: bool,
+}
+
+/// A guard that is in its target, as returned by [`active`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ActiveGuard {
+    /// The guard's target, as an absolute path.
+    pub target: PathBuf,
+    /// The directory that the guard will change back to.
+    pub original: PathBuf,
+    /// The location in the source of the code that created the guard.
+    pub caller: &'static Location<'static>,
+    /// How long ago the guard changed into its target.
+    pub age: Duration,
+}
+
+/// What [`restore_all`] did.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct RestoreReport {
+    /// The directory that the process changed to, or `None` if there were no
+    /// guards in their targets or it could not change to any of their
+    /// original directories.
+    pub restored_to: Option<PathBuf>,
+    /// The original directories that the process could not change to, with
+    /// the errors, in the order they were tried.
+    pub failed: Vec<(PathBuf, io::Error)>,
+    /// The targets of the guards that were popped, oldest first.
+    pub popped: Vec<PathBuf>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static STACK: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+// Whether any guard that changed the process's directory is still in its
+// target. This is a copy of what the stack says, kept so that it can be
+// checked without taking the stack's lock.
+static ANY_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    // Records that the guard is in its target.
+    pub(crate) fn nest(&self) {
+        #[cfg(target_os = "linux")]
+        if self.opts.thread_scoped {
+            return;
+        }
+        let mut stack = sync::lock(&STACK);
+        stack.push(Entry {
+            id: self.id,
+            target: self.orig.join(&self.target),
+            caller: self.caller,
+            orig: self.orig.clone(),
+            entered_at: Instant::now(),
+            backend: self.opts.backend.is_some(),
+            restored: false,
+            reparented: false,
+        });
+        stack_changed(&stack);
+    }
+
+    // Records that the guard is leaving its target, and returns an error if
+    // it was popped out of order and the policy says to return one.
+    pub(crate) fn unnest(&self) -> Option<PushdError> {
+        if self.opts.out_of_order == OutOfOrderPolicy::ForcePop {
+            pop_newer(self.id);
+        }
+        let newer = remove(self.id)?;
+        let e = PushdError::OutOfOrder {
+            path: self.orig.join(&self.target),
+            caller: self.caller,
+            newer: newer.target,
+            newer_caller: newer.caller,
+        };
+        match self.opts.out_of_order {
+            OutOfOrderPolicy::Ignore | OutOfOrderPolicy::ForcePop => None,
+            OutOfOrderPolicy::Error => Some(e),
+            #[cfg(not(feature = "no-drop-panic"))]
+            OutOfOrderPolicy::Panic if B::MAY_PANIC && !std::thread::panicking() => panic!("{e}"),
+            OutOfOrderPolicy::Warn | OutOfOrderPolicy::Panic | OutOfOrderPolicy::Reparent => {
+                events::out_of_order(&self.span, &e);
+                None
+            }
+        }
+    }
+
+    // Returns true if `restore_all` has already changed back for the guard, in
+    // which case the guard is removed from the stack.
+    pub(crate) fn take_restored(&self) -> bool {
+        let mut stack = sync::lock(&STACK);
+        match stack.iter().position(|e| e.id == self.id) {
+            Some(i) if stack[i].restored => {
+                stack.remove(i);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Hands the guard's original directory to the next guard that was created
+    // after it and removes it from the stack, if it uses
+    // `OutOfOrderPolicy::Reparent` and there is such a guard. Returns true if
+    // the guard was handed off, in which case it must not change directories.
+    pub(crate) fn hand_off(&self) -> bool {
+        if self.opts.out_of_order != OutOfOrderPolicy::Reparent {
+            return false;
+        }
+        let mut stack = sync::lock(&STACK);
+        let Some(i) = stack.iter().rposition(|e| e.id == self.id) else {
+            return false;
+        };
+        let backend = stack[i].backend;
+        let Some(j) =
+            (i + 1..stack.len()).find(|&j| !stack[j].restored && stack[j].backend == backend)
+        else {
+            return false;
+        };
+        let orig = stack.remove(i).orig;
+        let next = &mut stack[j - 1];
+        next.orig = orig;
+        next.reparented = true;
+        stack_changed(&stack);
+        true
+    }
+
+    // Returns true if an older guard handed its original directory to this
+    // one, in which case the guard's original directory is replaced with it.
+    // The guard's target is made absolute first, since it may be relative to
+    // the directory it is replacing.
+    pub(crate) fn take_reparented(&mut self) -> bool {
+        let orig = {
+            let mut stack = sync::lock(&STACK);
+            match stack.iter_mut().rfind(|e| e.id == self.id) {
+                Some(e) if e.reparented => {
+                    e.reparented = false;
+                    e.orig.clone()
+                }
+                _ => return false,
+            }
+        };
+        self.target = self.orig.join(&self.target);
+        self.orig = orig;
+        true
+    }
+
+    // Removes the guard from the stack without checking the order, for a guard
+    // that is being disarmed. This does nothing if the guard was already
+    // removed.
+    pub(crate) fn unnest_quietly(&self) {
+        remove(self.id);
+    }
+}
+
+// Changes back to the original directory of the oldest guard that is still in
+// its target, and forgets about all of the guards, for the panic hook. The
+// stack's lock is only tried, since the panic may have happened while it was
+// held.
+pub(crate) fn restore_outermost() {
+    let Some(mut stack) = sync::try_lock(&STACK) else {
+        return;
+    };
+    if let Some(e) = outermost(&stack) {
+        let _ = chdir(&e.orig);
+    }
+    stack.clear();
+    stack_changed(&stack);
+}
+
+// Updates everything that depends on which guards are in their targets, after
+// the stack changes.
+fn stack_changed(stack: &[Entry]) {
+    ANY_ACTIVE.store(outermost(stack).is_some(), Ordering::SeqCst);
+    #[cfg(all(feature = "signals", unix))]
+    set_signal_target(stack);
+}
+
+// Tells the signal handlers which directory to change back to, for
+// `restore_on_signal`.
+#[cfg(all(feature = "signals", unix))]
+pub(crate) fn update_signal_target() {
+    set_signal_target(&sync::lock(&STACK));
+}
+
+#[cfg(all(feature = "signals", unix))]
+fn set_signal_target(stack: &[Entry]) {
+    crate::signals::set_target(outermost(stack).map(|e| (e.id, e.orig.as_path())));
+}
+
+// Returns the oldest guard that changed the process's directory and hasn't
+// changed back.
+fn outermost(stack: &[Entry]) -> Option<&Entry> {
+    stack.iter().find(|e| !e.backend && !e.restored)
+}
+
+/// Pops every [`Pushd`] that is in its target, changing back to the original
+/// directory of the oldest one, and returns a report of what happened. If the
+/// process cannot change to that directory, then the next oldest guard's
+/// original directory is tried, and so on.
+///
+/// This is a last resort for getting a process back to a known directory,
+/// for example in a long-running program's recovery path. The guards don't
+/// change directories again when they are popped or dropped later. They
+/// don't run their [exit hooks](PushdBuilder::on_exit) either, or do any of
+/// the other work that a guard does when it changes back, except for
+/// releasing [locks](PushdBuilder::lock).
+///
+/// Guards created with [`Pushd::new_thread_scoped`] and guards that use a
+/// [`CwdBackend`](crate::CwdBackend) don't change the process's directory, so
+/// they are not popped.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let cwd = std::env::current_dir()?;
+/// let _pd = Pushd::new(std::env::temp_dir())?;
+/// let report = pushd::restore_all();
+/// assert_eq!(report.restored_to, Some(cwd));
+/// # Ok(())
+/// # }
+/// ```
+#[must_use = "the report says whether the process could change back"]
+pub fn restore_all() -> RestoreReport {
+    let mut stack = sync::lock(&STACK);
+    let mut report = RestoreReport::default();
+    for e in stack.iter_mut().filter(|e| !e.backend && !e.restored) {
+        e.restored = true;
+        report.popped.push(e.target.clone());
+        if report.restored_to.is_some() {
+            continue;
+        }
+        match chdir(&e.orig) {
+            Ok(()) => report.restored_to = Some(e.orig.clone()),
+            Err(err) => report.failed.push((e.orig.clone(), err)),
+        }
+    }
+    stack_changed(&stack);
+    report
+}
+
+/// Returns the guards that are in their targets, oldest first. This is
+/// useful for finding out why the process is in the directory it's in.
+///
+/// Guards created with [`Pushd::new_thread_scoped`] are not included, and
+/// neither are guards that were popped by [`restore_all`] or guards that use a
+/// [`CwdBackend`](crate::CwdBackend).
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let _pd = Pushd::new(std::env::temp_dir())?;
+/// for guard in pushd::active() {
+///     println!(
+///         "{} -> {}, created at {} {:?} ago",
+///         guard.original.display(),
+///         guard.target.display(),
+///         guard.caller,
+///         guard.age,
+///     );
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[must_use]
+pub fn active() -> Vec<ActiveGuard> {
+    active_after(None)
+}
+
+/// Returns the target of the newest guard that is in its target, which is the
+/// directory this crate thinks the process is in. If no guard is in its
+/// target, this returns the current directory.
+///
+/// This is meant for annotating log messages and errors without passing a
+/// guard around. It doesn't check that the process is really in the target,
+/// so if something else changed the current directory while the guard was
+/// alive, this still returns the target. Like [`active`], this ignores guards
+/// created with [`Pushd::new_thread_scoped`], guards popped by
+/// [`restore_all`], and guards that use a [`CwdBackend`](crate::CwdBackend),
+/// since they don't change the process's directory.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let _pd = Pushd::new(std::env::temp_dir())?;
+/// assert_eq!(pushd::current()?, std::env::temp_dir());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This returns an error if no guard is in its target and the current
+/// directory cannot be determined.
+pub fn current() -> Result<PathBuf, PushdError> {
+    let newest = sync::lock(&STACK)
+        .iter()
+        .rev()
+        .find(|e| !e.restored && !e.backend)
+        .map(|e| e.target.clone());
+    match newest {
+        Some(target) => Ok(target),
+        None => Ok(current_dir()?),
+    }
+}
+
+// Like `active`, but if `id` is given this only returns the guards created
+// after the one with that id.
+pub(crate) fn active_after(id: Option<u64>) -> Vec<ActiveGuard> {
+    let now = Instant::now();
+    sync::lock(&STACK)
+        .iter()
+        .filter(|e| !e.restored && !e.backend && id.is_none_or(|id| e.id > id))
+        .map(|e| ActiveGuard {
+            target: e.target.clone(),
+            original: e.orig.clone(),
+            caller: e.caller,
+            age: now.duration_since(e.entered_at),
+        })
+        .collect()
+}
+
+// Returns true if any guard that changed the process's directory is still in
+// its target.
+pub(crate) fn any_active() -> bool {
+    ANY_ACTIVE.load(Ordering::SeqCst)
+}
+
+// Marks every guard that is newer than the one with the given id as popped,
+// like `restore_all` does, for `OutOfOrderPolicy::ForcePop`.
+fn pop_newer(id: u64) {
+    let mut stack = sync::lock(&STACK);
+    let Some(i) = stack.iter().rposition(|e| e.id == id) else {
+        return;
+    };
+    let backend = stack[i].backend;
+    for e in stack[i + 1..].iter_mut().filter(|e| e.backend == backend) {
+        e.restored = true;
+    }
+    stack_changed(&stack);
+}
+
+// Removes a guard from the stack. If there's a newer guard in the stack, this
+// returns the newest one.
+fn remove(id: u64) -> Option<Entry> {
+    let mut stack = sync::lock(&STACK);
+    let i = stack.iter().rposition(|e| e.id == id)?;
+    stack.remove(i);
+    stack_changed(&stack);
+    stack[i..].iter().rev().find(|e| !e.restored).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::FakeCwd, verify_balanced};
+    use serial_test::serial;
+    #[cfg(not(feature = "no-drop-panic"))]
+    use std::panic::{self, AssertUnwindSafe};
+    use std::{env, error::Error as StdError, path::Path};
+
+    #[test]
+    #[serial]
+    fn out_of_order() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let mut outer = Pushd::builder("src")
+            .on_out_of_order(OutOfOrderPolicy::Error)
+            .push()?;
+        let inner = Pushd::builder("..")
+            .on_out_of_order(OutOfOrderPolicy::Error)
+            .push()?;
+        let res = outer.pop();
+        assert_eq!(env::current_dir()?, cwd);
+        drop(inner);
+
+        assert!(
+            matches!(
+                &res,
+                Err(PushdError::OutOfOrder { path, newer, .. })
+                    if *path == cwd.join("src") && *newer == cwd.join("src").join(".."),
+            ),
+            "{res:?}",
+        );
+
+        // Dropping the inner guard left us in the outer guard's target.
+        assert_eq!(env::current_dir()?, cwd.join("src"));
+        env::set_current_dir(&cwd)?;
+
+        // Popping in order is fine.
+        let mut outer = Pushd::builder("src")
+            .on_out_of_order(OutOfOrderPolicy::Error)
+            .push()?;
+        let mut inner = Pushd::builder("..")
+            .on_out_of_order(OutOfOrderPolicy::Error)
+            .push()?;
+        inner.pop()?;
+        outer.pop()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn reparent_and_force_pop() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let mut outer = Pushd::builder("src")
+            .on_out_of_order(OutOfOrderPolicy::Reparent)
+            .push()?;
+        let mut middle = Pushd::builder("..")
+            .on_out_of_order(OutOfOrderPolicy::Reparent)
+            .push()?;
+        let inner = Pushd::new("pushd-macros")?;
+        outer.pop()?;
+        // The process stays in the newest guard's target.
+        assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
+        middle.pop()?;
+        assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
+        assert_eq!(super::active().len(), 1);
+        assert_eq!(super::active()[0].original, cwd);
+        drop(inner);
+        assert_eq!(env::current_dir()?, cwd);
+        assert!(super::active().is_empty());
+
+        let mut outer = Pushd::builder("src")
+            .on_out_of_order(OutOfOrderPolicy::ForcePop)
+            .push()?;
+        let inner = Pushd::new("..")?;
+        outer.pop()?;
+        assert_eq!(env::current_dir()?, cwd);
+        assert!(super::active().is_empty());
+        // The inner guard was popped, so dropping it doesn't change
+        // directories.
+        drop(inner);
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn restore_outermost() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let outer = Pushd::new("src")?;
+        let inner = Pushd::builder("..")
+            .on_out_of_order(OutOfOrderPolicy::Error)
+            .push()?;
+        super::restore_outermost();
+        assert_eq!(env::current_dir()?, cwd);
+
+        // The guards were forgotten, so popping them out of order is fine.
+        drop(outer);
+        inner.into_pop()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn active() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let outer = Pushd::new("src")?;
+        let inner = Pushd::new("..")?;
+        let active = super::active();
+        assert_eq!(
+            active
+                .iter()
+                .map(|g| (g.original.clone(), g.target.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (cwd.clone(), cwd.join("src")),
+                (cwd.join("src"), cwd.join("src").join("..")),
+            ],
+        );
+        assert_eq!(active[0].caller.file(), file!());
+        assert!(active[0].age >= active[1].age);
+        assert_eq!(current()?, cwd.join("src").join(".."));
+        drop(inner);
+        assert_eq!(current()?, cwd.join("src"));
+        drop(outer);
+        assert!(super::active().is_empty());
+        assert_eq!(current()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn active_ignores_backend_guards() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let fake = FakeCwd::new().with_dir("/a");
+        let pd = Pushd::with_backend(fake.clone(), "/a")?;
+        assert_eq!(fake.cwd(), Path::new("/a"));
+        assert!(super::active().is_empty());
+        verify_balanced()?;
+        drop(pd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn restore_all() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let outer = Pushd::builder("src")
+            .on_out_of_order(OutOfOrderPolicy::Error)
+            .push()?;
+        let mut inner = Pushd::builder("..")
+            .on_out_of_order(OutOfOrderPolicy::Error)
+            .push()?;
+        let report = super::restore_all();
+        assert_eq!(env::current_dir()?, cwd);
+        assert_eq!(report.restored_to, Some(cwd.clone()));
+        assert!(report.failed.is_empty());
+        assert_eq!(report.popped, [cwd.join("src"), cwd.join("src").join("..")]);
+
+        // The guards don't change directories again, and they aren't out of
+        // order.
+        let other = Pushd::new("pushd-macros")?;
+        drop(outer);
+        inner.pop()?;
+        assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
+        drop(other);
+        assert_eq!(env::current_dir()?, cwd);
+        assert!(super::restore_all().restored_to.is_none());
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "no-drop-panic"))]
+    #[test]
+    #[serial]
+    fn out_of_order_panic() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let mut outer = Pushd::builder("src")
+            .on_out_of_order(OutOfOrderPolicy::Panic)
+            .push()?;
+        let inner = Pushd::new("..")?;
+        let res = panic::catch_unwind(AssertUnwindSafe(|| outer.pop()));
+        drop(inner);
+        drop(outer);
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        assert!(res.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+// This module contains the code for `PushdBuilder::parent_dirs`, which
+// normalizes or rejects the `..` components in a target path.
+
+use crate::{DropBehavior, PushdBuilder, PushdError};
+use std::path::{Component, Path, PathBuf};
+
+/// What a [`Pushd`](crate::Pushd) does with the `.` and `..` components of
+/// its target. See [`PushdBuilder::parent_dirs`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ParentDirPolicy {
+    /// Use the target as is, so the OS resolves each `..` after following
+    /// any symlink before it. This is the default.
+    #[default]
+    Keep,
+    /// Remove each `.`, and remove each `..` along with the component before
+    /// it, without looking at the filesystem. A `..` at the start of a
+    /// relative path is kept, and a `..` right after the root is removed.
+    ///
+    /// This is different from what the OS does if a component before a `..`
+    /// is a symlink, since the OS would go to the parent of the symlink's
+    /// target instead.
+    Normalize,
+    /// Return a [`PushdError::ParentDir`] error if the target has any `..`
+    /// components.
+    Reject,
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Sets what the resulting [`Pushd`](crate::Pushd) does with the `.`
+    /// and `..` components of its target. See [`ParentDirPolicy`] for
+    /// details.
+    ///
+    /// This happens before the guard changes directories, and after
+    /// [`PushdBuilder::expand_tilde`] and [`PushdBuilder::expand_env_vars`],
+    /// so a `..` from an environment variable is also caught. The normalized
+    /// path is the one that is logged and included in errors.
+    ///
+    /// To make sure that a path from somewhere like a config file can't
+    /// escape a directory, use [`ParentDirPolicy::Reject`] with a relative
+    /// target.
+    #[must_use]
+    pub fn parent_dirs(mut self, policy: ParentDirPolicy) -> Self {
+        self.opts.expand.parent_dirs = policy;
+        self
+    }
+}
+
+impl ParentDirPolicy {
+    pub(crate) fn apply(self, path: PathBuf) -> Result<PathBuf, PushdError> {
+        match self {
+            ParentDirPolicy::Keep => Ok(path),
+            ParentDirPolicy::Normalize => Ok(normalize(&path)),
+            ParentDirPolicy::Reject => {
+                if path.components().any(|c| c == Component::ParentDir) {
+                    return Err(PushdError::ParentDir { path });
+                }
+                Ok(path)
+            }
+        }
+    }
+}
+
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normal = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => (),
+            Component::ParentDir => match normal.last() {
+                Some(Component::Normal(_)) => {
+                    normal.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => (),
+                _ => normal.push(c),
+            },
+            _ => normal.push(c),
+        }
+    }
+    if normal.is_empty() {
+        return PathBuf::from(".");
+    }
+    normal.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    fn normalize() {
+        for (path, expect) in [
+            ("a/./b/../c", "a/c"),
+            ("./a/..", "."),
+            ("../a/../../b", "../../b"),
+            ("a/b/../../..", ".."),
+            ("/a/../../b", "/b"),
+            ("/..", "/"),
+        ] {
+            assert_eq!(
+                super::normalize(Path::new(path)),
+                Path::new(expect),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn parent_dirs() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        {
+            let pd = Pushd::builder("src/../pushd-macros/./src")
+                .parent_dirs(ParentDirPolicy::Normalize)
+                .push()?;
+            assert_eq!(pd.path(), Path::new("pushd-macros").join("src"));
+            assert_eq!(env::current_dir()?, cwd.join("pushd-macros").join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert!(matches!(
+            Pushd::builder("src/../..")
+                .parent_dirs(ParentDirPolicy::Reject)
+                .push(),
+            Err(PushdError::ParentDir { path }) if path == Path::new("src/../.."),
+        ));
+        assert!(matches!(
+            Pushd::builder("src/../no-such-dir")
+                .parent_dirs(ParentDirPolicy::Normalize)
+                .push(),
+            Err(PushdError::TargetNotFound { path, .. }) if path == Path::new("no-such-dir"),
+        ));
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
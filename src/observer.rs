@@ -0,0 +1,216 @@
+// This module contains the global registry of observers for
+// `pushd::subscribe`.
+
+use crate::{HistoryAction, PushdError};
+use std::{
+    panic::Location,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// A type that is told about the directory changes made by this crate. Add
+/// one with [`subscribe`].
+///
+/// Observers are called for every [`Pushd`](crate::Pushd) and
+/// [`DirStack`](crate::DirStack) in the process, on whichever thread made the
+/// change, right after the change happens. All of the methods do nothing by
+/// default, so an observer only needs to implement the ones it cares about.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{ChdirEvent, Observer, Pushd};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+///
+/// struct CountPushes(AtomicUsize);
+///
+/// impl Observer for CountPushes {
+///     fn on_push(&self, _event: &ChdirEvent<'_>) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let id = pushd::subscribe(Box::new(CountPushes(AtomicUsize::new(0))));
+/// let _pd = Pushd::new(std::env::temp_dir())?;
+/// pushd::unsubscribe(id);
+/// # Ok(())
+/// # }
+/// ```
+pub trait Observer: Send + Sync + 'static {
+    /// Called after a guard changes into its target directory, or after
+    /// [`DirStack::pushd`](crate::DirStack::pushd) changes directories.
+    fn on_push(&self, _event: &ChdirEvent<'_>) {}
+
+    /// Called after a guard changes back to its original directory, or to one
+    /// of its [fallback directories](crate::PushdBuilder::fallback_dirs), or
+    /// after [`DirStack::popd`](crate::DirStack::popd) changes directories.
+    fn on_pop(&self, _event: &ChdirEvent<'_>) {}
+
+    /// Called when a guard cannot change back to its original directory, or
+    /// [`DirStack::popd`](crate::DirStack::popd) cannot change to the top
+    /// directory of the stack. This is called before the error is returned,
+    /// logged, or passed to a handler.
+    fn on_restore_failure(&self, _event: &ChdirEvent<'_>, _error: &PushdError) {}
+}
+
+/// A directory change that is passed to an [`Observer`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct ChdirEvent<'a> {
+    /// The current directory before the change.
+    pub from: &'a Path,
+    /// The directory that was changed to, or that the change tried to change
+    /// to if it failed.
+    pub to: &'a Path,
+    /// The location in the source of the code that created the guard, or that
+    /// called the [`DirStack`](crate::DirStack) method.
+    pub caller: &'static Location<'static>,
+}
+
+/// Identifies an [`Observer`] added with [`subscribe`], so it can be removed
+/// with [`unsubscribe`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SubscriptionId(u64);
+
+struct Registry {
+    next_id: u64,
+    observers: Vec<(SubscriptionId, Arc<dyn Observer>)>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    next_id: 0,
+    observers: Vec::new(),
+});
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Adds an observer that will be told about every directory change made by
+/// this crate in the process. See [`Observer`] for details.
+#[allow(clippy::must_use_candidate)]
+pub fn subscribe(observer: Box<dyn Observer>) -> SubscriptionId {
+    let mut reg = registry();
+    let id = SubscriptionId(reg.next_id);
+    reg.next_id += 1;
+    reg.observers.push((id, Arc::from(observer)));
+    id
+}
+
+/// Removes an observer added with [`subscribe`]. Returns false if there is
+/// no observer with the given ID.
+#[allow(clippy::must_use_candidate)]
+pub fn unsubscribe(id: SubscriptionId) -> bool {
+    let mut reg = registry();
+    let len = reg.observers.len();
+    reg.observers.retain(|(i, _)| *i != id);
+    reg.observers.len() != len
+}
+
+pub(crate) fn notify(
+    action: HistoryAction,
+    from: &Path,
+    to: &Path,
+    caller: &'static Location<'static>,
+    outcome: Result<(), &PushdError>,
+) {
+    // We copy the list so that an observer can subscribe or unsubscribe
+    // without deadlocking.
+    let observers = registry()
+        .observers
+        .iter()
+        .map(|(_, o)| Arc::clone(o))
+        .collect::<Vec<_>>();
+    if observers.is_empty() {
+        return;
+    }
+
+    let event = ChdirEvent { from, to, caller };
+    for o in observers {
+        match (action, outcome) {
+            (HistoryAction::Push, Ok(())) => o.on_push(&event),
+            (HistoryAction::Pop, Ok(())) => o.on_pop(&event),
+            (HistoryAction::Pop, Err(e)) => o.on_restore_failure(&event, e),
+            (HistoryAction::Push, Err(_)) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs, path::PathBuf};
+
+    type Events = Arc<Mutex<Vec<(&'static str, PathBuf, PathBuf)>>>;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Events,
+    }
+
+    impl Recorder {
+        fn record(&self, name: &'static str, event: &ChdirEvent<'_>) {
+            self.events
+                .lock()
+                .unwrap()
+                .push((name, event.from.to_owned(), event.to.to_owned()));
+        }
+    }
+
+    impl Observer for Recorder {
+        fn on_push(&self, event: &ChdirEvent<'_>) {
+            self.record("push", event);
+        }
+
+        fn on_pop(&self, event: &ChdirEvent<'_>) {
+            self.record("pop", event);
+        }
+
+        fn on_restore_failure(&self, event: &ChdirEvent<'_>, _error: &PushdError) {
+            self.record("restore failure", event);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn subscribe_and_unsubscribe() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let recorder = Recorder::default();
+        let events = Arc::clone(&recorder.events);
+        let id = subscribe(Box::new(recorder));
+        drop(Pushd::new("src")?);
+        assert!(Pushd::new("no-such-dir").is_err());
+
+        // A guard whose original directory was removed can't change back.
+        let td = tempfile::tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+        let mut pd = Pushd::new_no_panic(td.path())?;
+        fs::remove_dir(&orig)?;
+        let res = pd.pop();
+        env::set_current_dir(&cwd)?;
+        assert!(res.is_err());
+
+        assert!(unsubscribe(id));
+        assert!(!unsubscribe(id));
+        drop(Pushd::new("src")?);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                ("push", cwd.clone(), cwd.join("src")),
+                ("pop", cwd.join("src"), cwd.clone()),
+                ("push", orig.clone(), td.path().to_owned()),
+                ("restore failure", td.path().to_owned(), orig),
+            ],
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+// This module contains `install_panic_hook`, which restores the original
+// directory of the outermost guard when the process panics.
+
+use crate::nesting;
+use std::{panic, sync::Once};
+
+/// Installs a panic hook that changes back to the original directory of the
+/// oldest [`Pushd`](crate::Pushd) that is still in its target, before calling
+/// the hook that was installed before it.
+///
+/// Normally the guards change back as the panic unwinds, but that doesn't
+/// help when the panic happens on a different thread than the one that owns
+/// the guards, or when the process is built with `panic = "abort"`. With this
+/// hook, the process is back in the directory it started in as soon as any
+/// thread panics, for example when a test harness catches the panic and runs
+/// the next test.
+///
+/// The hook also forgets which guards are in their targets, so guards that
+/// are dropped while unwinding don't report that they were popped out of
+/// order. Guards created with
+/// [`Pushd::new_thread_scoped`](crate::Pushd::new_thread_scoped) and guards
+/// that use a [`CwdBackend`](crate::CwdBackend) don't change the process's
+/// directory, so they are ignored.
+///
+/// Calling this more than once does nothing after the first call.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+/// use std::{env, thread};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// pushd::install_panic_hook();
+///
+/// let cwd = env::current_dir()?;
+/// let _pd = Pushd::new(env::temp_dir())?;
+/// let _ = thread::spawn(|| panic!("something went wrong")).join();
+/// assert_eq!(env::current_dir()?, cwd);
+/// # Ok(())
+/// # }
+/// ```
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            nesting::restore_outermost();
+            prev(info);
+        }));
+    });
+}
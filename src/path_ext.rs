@@ -0,0 +1,87 @@
+// This module contains the `PathExt` extension trait, which creates a guard
+// from a path.
+
+use crate::{NonPanicking, Panicking, Pushd, PushdError};
+use std::path::{Path, PathBuf};
+
+/// An extension trait for [`Path`] and [`PathBuf`] which changes to the path
+/// and returns a [`Pushd`] guard for it.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::PathExt;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let tmp = std::env::temp_dir();
+/// let _pd = tmp.pushd()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait PathExt {
+    /// This is the same as calling [`Pushd::new`] with this path.
+    ///
+    /// # Errors
+    ///
+    /// This returns the same errors as [`Pushd::new`].
+    fn pushd(&self) -> Result<Pushd<Panicking>, PushdError>;
+
+    /// This is the same as calling [`Pushd::new_no_panic`] with this path.
+    ///
+    /// # Errors
+    ///
+    /// This returns the same errors as [`Pushd::new_no_panic`].
+    fn pushd_no_panic(&self) -> Result<Pushd<NonPanicking>, PushdError>;
+}
+
+impl PathExt for Path {
+    #[track_caller]
+    fn pushd(&self) -> Result<Pushd<Panicking>, PushdError> {
+        Pushd::new(self)
+    }
+
+    #[track_caller]
+    fn pushd_no_panic(&self) -> Result<Pushd<NonPanicking>, PushdError> {
+        Pushd::new_no_panic(self)
+    }
+}
+
+impl PathExt for PathBuf {
+    #[track_caller]
+    fn pushd(&self) -> Result<Pushd<Panicking>, PushdError> {
+        self.as_path().pushd()
+    }
+
+    #[track_caller]
+    fn pushd_no_panic(&self) -> Result<Pushd<NonPanicking>, PushdError> {
+        self.as_path().pushd_no_panic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn path_ext() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        {
+            let pd = Path::new("src").pushd()?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+            assert_eq!(pd.caller().file(), file!());
+        }
+        assert_eq!(env::current_dir()?, cwd);
+        {
+            let _pd = cwd.join("src").pushd_no_panic()?;
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
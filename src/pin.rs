@@ -0,0 +1,197 @@
+// This module implements pinned guards, which change back to their target if
+// something else changes the current directory. `Pushd::repin` does this on
+// demand, and a guard built with `PushdBuilder::pinned` has a thread that does
+// it periodically.
+
+use crate::{
+    chdir_error, current_dir, events, files::Anchor, status, DropBehavior, Pushd, PushdBuilder,
+    PushdError,
+};
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`] start a thread that calls
+    /// [`Pushd::repin`] every `interval`, so that if some other code changes
+    /// the current directory while the guard is in its target, the guard
+    /// changes back to the target. Each time it does this, it logs a warning.
+    ///
+    /// This is meant for running code you don't control, like plugins, that
+    /// needs to stay in one directory. Since any change is undone, this
+    /// should not be used with a guard that other guards are nested inside
+    /// of. The thread does nothing while the guard is popped, and it is
+    /// stopped when the guard is dropped.
+    #[must_use]
+    pub fn pinned(mut self, interval: Duration) -> Self {
+        self.opts.pin_interval = Some(interval);
+        self
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Checks that the current directory is still this guard's target, like
+    /// [`Pushd::status`], and changes back to the target if it isn't. This
+    /// returns true if it had to change back.
+    ///
+    /// On Unix the guard changes back to the directory it holds open, so this
+    /// works even if the target was renamed. This does nothing if the guard
+    /// has been popped.
+    ///
+    /// # Errors
+    ///
+    /// This returns a [`PushdError::TargetRemoved`] error if the target was
+    /// deleted, or an error if the current directory cannot be changed.
+    pub fn repin(&mut self) -> Result<bool, PushdError> {
+        self.sync_timeout(false);
+        if self.popped {
+            return Ok(false);
+        }
+        repin(&self.anchor).map_err(|e| self.with_context(e))
+    }
+}
+
+// Changes back to the anchored directory if it isn't the current directory,
+// and returns true if it did.
+fn repin(anchor: &Anchor) -> Result<bool, PushdError> {
+    let status = status::dir_status(anchor);
+    if !status.exists {
+        return Err(PushdError::TargetRemoved {
+            path: anchor.path().to_owned(),
+        });
+    }
+    if status.is_current {
+        return Ok(false);
+    }
+    let found = current_dir()?;
+    anchor
+        .enter()
+        .map_err(|e| chdir_error(found, anchor.path().to_owned(), e))?;
+    Ok(true)
+}
+
+pub(crate) struct Pinner {
+    // The thread exits when this is dropped.
+    tx: Option<mpsc::Sender<()>>,
+    // Whether the guard is in its target. The thread holds the lock while it
+    // repins, so once a guard has set this to false, the thread won't change
+    // directories.
+    active: Arc<Mutex<bool>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Pinner {
+    // Starts repinning the anchored directory, which must be the current
+    // directory, every `interval`.
+    pub(crate) fn start(anchor: &Anchor, interval: Duration, span: events::Span) -> Pinner {
+        let (tx, rx) = mpsc::channel();
+        let active = Arc::new(Mutex::new(true));
+        let thread = {
+            let anchor = anchor.duplicate();
+            let active = Arc::clone(&active);
+            thread::spawn(move || run(&rx, &anchor, interval, &active, &span))
+        };
+        Pinner {
+            tx: Some(tx),
+            active,
+            thread: Some(thread),
+        }
+    }
+
+    pub(crate) fn set_active(&self, active: bool) {
+        *self.active.lock().unwrap_or_else(PoisonError::into_inner) = active;
+    }
+}
+
+impl Drop for Pinner {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(
+    rx: &mpsc::Receiver<()>,
+    anchor: &Anchor,
+    interval: Duration,
+    active: &Mutex<bool>,
+    span: &events::Span,
+) {
+    while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+        let active = active.lock().unwrap_or_else(PoisonError::into_inner);
+        if !*active {
+            continue;
+        }
+        let found = current_dir().unwrap_or_default();
+        match repin(anchor) {
+            Ok(true) => events::repinned(span, anchor.path(), &found),
+            Ok(false) => {}
+            Err(e) => events::repin_failed(span, &e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, time::Instant};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn repin() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let mut pd = Pushd::new(td.path())?;
+        assert!(!pd.repin()?);
+
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+        assert!(pd.repin()?);
+        assert!(pd.is_same_dir(env::current_dir()?));
+
+        pd.pop()?;
+        let cwd = env::current_dir()?;
+        assert!(!pd.repin()?);
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn pinned() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let other = tempdir()?;
+        let interval = Duration::from_millis(10);
+        let mut pd = Pushd::builder(td.path()).pinned(interval).push()?;
+
+        env::set_current_dir(other.path())?;
+        let start = Instant::now();
+        while !pd.is_same_dir(env::current_dir()?) {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "the guard did not change back",
+            );
+            thread::sleep(interval);
+        }
+
+        // The guard stays where it is once it's popped.
+        pd.pop()?;
+        let cwd = env::current_dir()?;
+        thread::sleep(interval * 10);
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
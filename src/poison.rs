@@ -0,0 +1,171 @@
+// This module records when a guard is dropped while its thread is panicking,
+// so that later guards can tell that the current directory may not be what
+// the code that panicked expected.
+
+use crate::{events, DropBehavior, Pushd, PushdBuilder, PushdError};
+use std::{
+    panic::Location,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    thread,
+};
+
+/// What a new [`Pushd`] does when an earlier guard was dropped while its
+/// thread was panicking.
+///
+/// Like a [`Mutex`], the crate's record of the current directory is
+/// "poisoned" when a panic unwinds through code that holds a guard. The guard
+/// still changes back to its original directory, but the code that panicked
+/// may have left files, other guards, or the directory itself in an
+/// unexpected state. The poison stays until [`clear_poison`] is called.
+///
+/// Guards created with [`Pushd::new_thread_scoped`] and guards that use a
+/// [`CwdBackend`](crate::CwdBackend) don't change the process's directory, so
+/// they never poison it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PoisonPolicy {
+    /// Don't check for poison at all. This is the default.
+    #[default]
+    Ignore,
+    /// Log a warning when a guard is created while the state is poisoned.
+    Warn,
+    /// Return a [`PushdError::Poisoned`] error instead of creating the guard
+    /// while the state is poisoned.
+    Error,
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Sets the policy for what to do when the resulting [`Pushd`] is created
+    /// after an earlier guard was dropped while its thread was panicking. See
+    /// [`PoisonPolicy`] for details.
+    #[must_use]
+    pub fn on_poison(mut self, policy: PoisonPolicy) -> Self {
+        self.opts.poison = policy;
+        self
+    }
+}
+
+// The target and creator of the first guard that was dropped while its
+// thread was panicking.
+static POISON: Mutex<Option<(PathBuf, &'static Location<'static>)>> = Mutex::new(None);
+
+/// Returns true if a guard was dropped while its thread was panicking since
+/// the process started or [`clear_poison`] was last called.
+#[must_use]
+pub fn is_poisoned() -> bool {
+    POISON
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Clears the poison left by a guard that was dropped while its thread was
+/// panicking, so that new guards no longer warn or return an error. This is
+/// meant for test harnesses that catch panics and can put things back in
+/// order before running the next test.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+/// use std::{env, panic};
+///
+/// let _ = panic::catch_unwind(|| {
+///     let _pd = Pushd::new(env::temp_dir()).unwrap();
+///     panic!("something went wrong");
+/// });
+/// assert!(pushd::is_poisoned());
+///
+/// pushd::clear_poison();
+/// assert!(!pushd::is_poisoned());
+/// ```
+pub fn clear_poison() {
+    *POISON.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    // Poisons the state if the guard is being dropped during a panic while it
+    // is still in its target.
+    pub(crate) fn poison_if_panicking(&self) {
+        if self.popped || !thread::panicking() || self.opts.backend.is_some() {
+            return;
+        }
+        #[cfg(target_os = "linux")]
+        if self.opts.thread_scoped {
+            return;
+        }
+        POISON
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert_with(|| (self.orig.join(&self.target), self.caller));
+    }
+}
+
+// Checks for poison before a guard is created, following the builder's
+// policy.
+pub(crate) fn check<B: DropBehavior>(builder: &PushdBuilder<B>) -> Result<(), PushdError> {
+    if builder.opts.poison == PoisonPolicy::Ignore {
+        return Ok(());
+    }
+    let Some((path, caller)) = POISON
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+    else {
+        return Ok(());
+    };
+    let e = PushdError::Poisoned { path, caller };
+    if builder.opts.poison == PoisonPolicy::Error {
+        return Err(e);
+    }
+    events::poisoned(&builder.opts.log, &e);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, panic};
+
+    #[test]
+    #[serial]
+    fn poison() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        clear_poison();
+        let cwd = env::current_dir()?;
+        let _ = panic::catch_unwind(|| {
+            let _pd = Pushd::new("src").unwrap();
+            panic!("poison");
+        });
+        assert_eq!(env::current_dir()?, cwd);
+        assert!(is_poisoned());
+
+        let res = Pushd::builder("src").on_poison(PoisonPolicy::Error).push();
+        let Err(PushdError::Poisoned { path, .. }) = res else {
+            return Err("created a guard while poisoned".into());
+        };
+        assert_eq!(path, cwd.join("src"));
+        drop(Pushd::builder("src").on_poison(PoisonPolicy::Warn).push()?);
+        drop(Pushd::new("src")?);
+
+        clear_poison();
+        assert!(!is_poisoned());
+        drop(
+            Pushd::builder("src")
+                .on_poison(PoisonPolicy::Error)
+                .push()?,
+        );
+
+        // A guard that was popped before the panic doesn't poison anything.
+        let _ = panic::catch_unwind(|| {
+            let mut pd = Pushd::new("src").unwrap();
+            pd.pop().unwrap();
+            panic!("no poison");
+        });
+        assert!(!is_poisoned());
+
+        Ok(())
+    }
+}
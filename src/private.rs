@@ -0,0 +1,12 @@
+// This module contains items used by the code that the `pushd-macros` crate
+// generates. None of this is part of the public API.
+
+use crate::sync::{self, Mutex, MutexGuard};
+
+pub(crate) static CWD_LOCK: Mutex<()> = Mutex::new(());
+
+// Returns a guard for a process-wide lock, which the macros use to serialize
+// tests that change the current directory.
+pub fn serial_lock() -> MutexGuard<'static, ()> {
+    sync::lock(&CWD_LOCK)
+}
@@ -0,0 +1,221 @@
+use crate::{compare, DropBehavior, PushdBuilder};
+use std::{
+    env,
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+};
+
+/// How a [`Pushd`](crate::Pushd) built with [`PushdBuilder::update_pwd`]
+/// treats symlinks in its target, like the `-L` and `-P` flags for a shell's
+/// `cd` builtin.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PwdMode {
+    /// The target is resolved lexically against the current value of `$PWD`,
+    /// so `..` removes the previous component even if that component is a
+    /// symlink, and `$PWD` is set to the resulting path without resolving any
+    /// symlinks in it. This is what `cd -L` does.
+    Logical,
+    /// The target is resolved by the OS, and `$PWD` is set to the physical
+    /// path of the new current directory, with all symlinks resolved. This is
+    /// what `cd -P` does.
+    Physical,
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`](crate::Pushd) keep the `PWD` and `OLDPWD`
+    /// environment variables in sync with the current directory, like a shell
+    /// does. When the guard changes directories, `OLDPWD` is set to the
+    /// previous value of `PWD` and `PWD` is set to the new directory. When the
+    /// guard is popped, both variables are restored to the values they had
+    /// before.
+    ///
+    /// The `mode` controls whether symlinks in the target are resolved. See
+    /// [`PwdMode`] for details.
+    ///
+    /// Note that changing environment variables affects the whole process, so
+    /// this has the same thread safety concerns as [`env::set_var`].
+    #[must_use]
+    pub fn update_pwd(mut self, mode: PwdMode) -> Self {
+        self.opts.pwd = Some(mode);
+        self
+    }
+}
+
+// The state needed to restore `PWD` and `OLDPWD` when a guard is popped.
+pub(crate) struct Pwd {
+    saved: Option<OsString>,
+    saved_old: Option<OsString>,
+    // The logical path of the target, which is only set in logical mode.
+    logical: Option<PathBuf>,
+}
+
+impl Pwd {
+    // Returns the path to change to for a target in logical mode. This is the
+    // target joined to `$PWD` with `.` and `..` resolved lexically. If `$PWD`
+    // isn't set, or it doesn't refer to the current directory, then `cwd` is
+    // used instead, like a shell does.
+    pub(crate) fn logical_target(cwd: &Path, target: &Path) -> PathBuf {
+        let base = env::var_os("PWD")
+            .map(PathBuf::from)
+            .filter(|pwd| pwd.is_absolute() && compare::same_dir(pwd, cwd))
+            .unwrap_or_else(|| cwd.to_owned());
+        normalize(&base.join(target))
+    }
+
+    // Sets `PWD` and `OLDPWD` after changing to a new directory. The `cwd` is
+    // the directory we changed from.
+    pub(crate) fn enter(cwd: &Path, logical: Option<PathBuf>) -> Pwd {
+        let pwd = Pwd {
+            saved: env::var_os("PWD"),
+            saved_old: env::var_os("OLDPWD"),
+            logical,
+        };
+        pwd.set_vars(cwd);
+        pwd
+    }
+
+    pub(crate) fn logical(&self) -> Option<&Path> {
+        self.logical.as_deref()
+    }
+
+    // Sets the variables again when a popped guard is re-entered.
+    // Sets the logical path of the target after the guard's target changes.
+    pub(crate) fn set_logical(&mut self, logical: Option<PathBuf>) {
+        self.logical = logical;
+    }
+
+    // Updates the variables after a guard changes directly from one target to
+    // another, like `cd` does.
+    pub(crate) fn swap(&mut self, logical: Option<PathBuf>) {
+        self.logical = logical;
+        if let Some(pwd) = env::var_os("PWD") {
+            env::set_var("OLDPWD", pwd);
+        }
+        self.set_pwd();
+    }
+
+    pub(crate) fn reenter(&mut self, cwd: &Path) {
+        self.saved = env::var_os("PWD");
+        self.saved_old = env::var_os("OLDPWD");
+        self.set_vars(cwd);
+    }
+
+    // Restores the variables to the values they had before the guard changed
+    // directories.
+    pub(crate) fn restore(&self) {
+        restore_var("PWD", self.saved.as_ref());
+        restore_var("OLDPWD", self.saved_old.as_ref());
+    }
+
+    // Restores `OLDPWD` and sets `PWD` to the directory that the guard changed
+    // to instead of its original directory, like a fallback directory.
+    pub(crate) fn restore_elsewhere(&self, fallback: &Path) {
+        restore_var("OLDPWD", self.saved_old.as_ref());
+        match env::current_dir() {
+            Ok(cwd) => env::set_var("PWD", cwd),
+            Err(_) => env::set_var("PWD", fallback),
+        }
+    }
+
+    fn set_vars(&self, cwd: &Path) {
+        match &self.saved {
+            Some(old) => env::set_var("OLDPWD", old),
+            None => env::set_var("OLDPWD", cwd),
+        }
+        self.set_pwd();
+    }
+
+    fn set_pwd(&self) {
+        match (&self.logical, env::current_dir()) {
+            (Some(logical), _) => env::set_var("PWD", logical),
+            (None, Ok(physical)) => env::set_var("PWD", physical),
+            (None, Err(_)) => env::remove_var("PWD"),
+        }
+    }
+}
+
+fn restore_var(name: &str, value: Option<&OsString>) {
+    match value {
+        Some(v) => env::set_var(name, v),
+        None => env::remove_var(name),
+    }
+}
+
+// Resolves `.` and `..` in a path without looking at the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
+            }
+            c => normal.push(c),
+        }
+    }
+    normal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(target_os = "windows"))]
+    use crate::Pushd;
+    #[cfg(not(target_os = "windows"))]
+    use serial_test::serial;
+    #[cfg(not(target_os = "windows"))]
+    use std::{error::Error as StdError, fs};
+    #[cfg(not(target_os = "windows"))]
+    use tempfile::tempdir;
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize(Path::new("/a/b/../c/./d")), Path::new("/a/c/d"));
+        assert_eq!(normalize(Path::new("/a/..")), Path::new("/"));
+        assert_eq!(normalize(Path::new("/..")), Path::new("/"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn logical_and_physical() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let dir = tempdir()?;
+        let td = fs::canonicalize(dir.path())?;
+        fs::create_dir_all(td.join("real/sub"))?;
+        std::os::unix::fs::symlink(td.join("real/sub"), td.join("link"))?;
+
+        env::set_current_dir(&td)?;
+        env::set_var("PWD", &td);
+        env::set_var("OLDPWD", "/old");
+
+        {
+            let _pd = Pushd::builder("link").update_pwd(PwdMode::Logical).push()?;
+            assert_eq!(env::var_os("PWD"), Some(td.join("link").into()));
+            assert_eq!(env::var_os("OLDPWD"), Some(td.clone().into()));
+
+            // In logical mode, `..` goes back through the symlink.
+            let _pd = Pushd::builder("..").update_pwd(PwdMode::Logical).push()?;
+            assert_eq!(env::current_dir()?, td);
+            assert_eq!(env::var_os("PWD"), Some(td.clone().into()));
+        }
+        assert_eq!(env::var_os("PWD"), Some(td.clone().into()));
+        assert_eq!(env::var_os("OLDPWD"), Some("/old".into()));
+
+        {
+            let _pd = Pushd::builder("link")
+                .update_pwd(PwdMode::Physical)
+                .push()?;
+            assert_eq!(env::var_os("PWD"), Some(td.join("real/sub").into()));
+
+            // In physical mode, `..` is the parent of the real directory.
+            let _pd = Pushd::builder("..").update_pwd(PwdMode::Physical).push()?;
+            assert_eq!(env::current_dir()?, td.join("real"));
+            assert_eq!(env::var_os("PWD"), Some(td.join("real").into()));
+        }
+        assert_eq!(env::var_os("PWD"), Some(td.clone().into()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::PushdError;
+use std::{io, time::Duration};
+
+/// A policy for retrying a directory change that fails.
+///
+/// This is used by [`PushdBuilder::restore_retry`](crate::PushdBuilder::restore_retry)
+/// to retry changing back to the original directory when a [`Pushd`](crate::Pushd)
+/// is dropped. Errors with a kind of [`io::ErrorKind::NotFound`] are never
+/// retried then, since an original directory that does not exist is not going
+/// to appear by waiting for it.
+///
+/// It is also used by [`PushdBuilder::enter_retry`](crate::PushdBuilder::enter_retry)
+/// and [`Pushd::new_with_retry`](crate::Pushd::new_with_retry) to retry
+/// changing to a target that doesn't exist yet or is busy.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::RetryPolicy;
+/// use std::time::Duration;
+///
+/// // Retry up to 5 times, waiting 10ms, 20ms, 40ms, 80ms, and 160ms.
+/// let policy = RetryPolicy::new(5, Duration::from_millis(10)).exponential();
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    attempts: u32,
+    delay: Duration,
+    exponential: bool,
+}
+
+impl RetryPolicy {
+    /// Creates a policy that retries up to `attempts` times, waiting `delay`
+    /// before each retry.
+    #[must_use]
+    pub fn new(attempts: u32, delay: Duration) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            delay,
+            exponential: false,
+        }
+    }
+
+    /// Makes the delay double after each retry.
+    #[must_use]
+    pub fn exponential(mut self) -> RetryPolicy {
+        self.exponential = true;
+        self
+    }
+
+    /// Returns the number of retries this policy allows.
+    #[must_use]
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub(crate) fn delays(&self) -> impl Iterator<Item = Duration> + '_ {
+        (0..self.attempts).map(|i| {
+            if self.exponential {
+                self.delay.saturating_mul(2u32.saturating_pow(i))
+            } else {
+                self.delay
+            }
+        })
+    }
+
+    pub(crate) fn should_retry(e: &io::Error) -> bool {
+        e.kind() != io::ErrorKind::NotFound
+    }
+
+    pub(crate) fn should_retry_enter(e: &PushdError) -> bool {
+        match e {
+            PushdError::TargetNotFound { .. } => true,
+            PushdError::SetCurrentDir { source, .. } => {
+                source.kind() == io::ErrorKind::ResourceBusy
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays() {
+        let d = Duration::from_millis(10);
+        assert_eq!(
+            RetryPolicy::new(3, d).delays().collect::<Vec<_>>(),
+            vec![d, d, d],
+        );
+        assert_eq!(
+            RetryPolicy::new(4, d)
+                .exponential()
+                .delays()
+                .collect::<Vec<_>>(),
+            vec![d, d * 2, d * 4, d * 8],
+        );
+        assert_eq!(RetryPolicy::new(0, d).delays().count(), 0);
+    }
+}
@@ -0,0 +1,185 @@
+// This module contains the constructors which find a project root, like a
+// Git repository or Cargo workspace, by walking up from a starting directory.
+
+use crate::{Panicking, Pushd, PushdBuilder, PushdError};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that changes to the root of the Git
+    /// repository containing the current directory. See
+    /// [`Pushd::git_root_from`] for details.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::git_root_from`].
+    #[track_caller]
+    pub fn git_root() -> Result<Pushd<Panicking>, PushdError> {
+        Pushd::git_root_from(".")
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the root of the Git
+    /// repository containing `start`. A relative `start` is resolved against
+    /// the current directory.
+    ///
+    /// The root is the closest directory at or above `start` that contains a
+    /// `.git` entry. This may be a directory, or a file for a worktree or
+    /// submodule.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::Metadata`] error if `start` cannot be resolved, and
+    /// a [`PushdError::NoRootFound`] error if there is no `.git` entry at or above it. Otherwise it
+    /// returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn git_root_from<P: AsRef<Path>>(start: P) -> Result<Pushd<Panicking>, PushdError> {
+        let root = find_root(start.as_ref(), "Git repository", |dir| {
+            dir.join(".git").symlink_metadata().is_ok()
+        })?;
+        PushdBuilder::new(root).push()
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the root of the Cargo
+    /// workspace containing the current directory. See
+    /// [`Pushd::workspace_root_from`] for details.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Pushd::workspace_root_from`].
+    #[track_caller]
+    pub fn workspace_root() -> Result<Pushd<Panicking>, PushdError> {
+        Pushd::workspace_root_from(".")
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the root of the Cargo
+    /// workspace containing `start`. A relative `start` is resolved against
+    /// the current directory.
+    ///
+    /// The root is the closest directory at or above `start` with a
+    /// `Cargo.toml` that has a `[workspace]` table. If there is no such
+    /// directory, but there is a `Cargo.toml` at or above `start`, then that
+    /// package is not part of a workspace, and its directory is used instead,
+    /// just like Cargo does.
+    ///
+    /// This looks at the manifests directly rather than running
+    /// `cargo locate-project`, so it works without Cargo installed, but it
+    /// does not handle a package that uses the `package.workspace` key to
+    /// point to a workspace that is not one of its parents.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::Metadata`] error if `start` cannot be resolved, and
+    /// a [`PushdError::NoRootFound`] error if there is no `Cargo.toml` at or above it. Otherwise
+    /// it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn workspace_root_from<P: AsRef<Path>>(start: P) -> Result<Pushd<Panicking>, PushdError> {
+        let start = start.as_ref();
+        let root = find_root(start, "Cargo workspace", is_workspace_root).or_else(|_| {
+            find_root(start, "Cargo workspace", |dir| {
+                dir.join("Cargo.toml").is_file()
+            })
+        })?;
+        PushdBuilder::new(root).push()
+    }
+}
+
+fn is_workspace_root(dir: &Path) -> bool {
+    let Ok(manifest) = fs::read_to_string(dir.join("Cargo.toml")) else {
+        return false;
+    };
+    manifest.lines().any(|l| {
+        let l = l.trim();
+        l == "[workspace]" || l.starts_with("[workspace.")
+    })
+}
+
+// Returns the closest directory at or above `start` for which `is_root`
+// returns true. The start is canonicalized first, so that `..` components and
+// symlinks don't make us skip a directory.
+fn find_root(
+    start: &Path,
+    kind: &'static str,
+    is_root: impl Fn(&Path) -> bool,
+) -> Result<PathBuf, PushdError> {
+    let start = fs::canonicalize(start).map_err(|e| PushdError::Metadata {
+        path: start.to_owned(),
+        source: e,
+    })?;
+    #[cfg(windows)]
+    let start = crate::windows::simplify(start);
+    start
+        .ancestors()
+        .find(|dir| is_root(dir))
+        .map(Path::to_path_buf)
+        .ok_or(PushdError::NoRootFound { kind, start })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn git_root() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let td_path = fs::canonicalize(td.path())?;
+        fs::create_dir_all(td_path.join("repo/.git"))?;
+        fs::create_dir_all(td_path.join("repo/a/b"))?;
+
+        {
+            let _pd = Pushd::git_root_from(td_path.join("repo/a/b"))?;
+            assert_eq!(env::current_dir()?, td_path.join("repo"));
+
+            let _pd = Pushd::new("a")?;
+            let _pd = Pushd::git_root()?;
+            assert_eq!(env::current_dir()?, td_path.join("repo"));
+        }
+
+        assert!(matches!(
+            Pushd::git_root_from(&td_path),
+            Err(PushdError::NoRootFound { start, .. }) if start == td_path,
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn workspace_root() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = fs::canonicalize(".")?;
+        {
+            let _pd = Pushd::workspace_root_from("pushd-macros/src")?;
+            assert_eq!(env::current_dir()?, cwd);
+        }
+        {
+            let _pd = Pushd::new("src")?;
+            let _pd = Pushd::workspace_root()?;
+            assert_eq!(env::current_dir()?, cwd);
+        }
+
+        // A package that isn't in a workspace is its own root.
+        let td = tempdir()?;
+        let td_path = fs::canonicalize(td.path())?;
+        fs::create_dir_all(td_path.join("pkg/src"))?;
+        fs::write(td_path.join("pkg/Cargo.toml"), "[package]\n")?;
+        {
+            let _pd = Pushd::workspace_root_from(td_path.join("pkg/src"))?;
+            assert_eq!(env::current_dir()?, td_path.join("pkg"));
+        }
+        assert!(matches!(
+            Pushd::workspace_root_from(&td_path),
+            Err(PushdError::NoRootFound { .. }),
+        ));
+
+        Ok(())
+    }
+}
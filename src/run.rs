@@ -0,0 +1,169 @@
+// This module contains the command runner, which is only compiled when the
+// `run` feature is enabled.
+
+use crate::{CommandExt, DropBehavior, Pushd, PushdError};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{ExitStatus, Stdio},
+    thread,
+};
+
+/// The result of a command run with [`Pushd::run`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RunOutput {
+    /// The command's exit status. This is always a success, since a command
+    /// that fails is returned as a [`PushdError::CommandFailed`] error.
+    pub status: ExitStatus,
+    /// Everything the command wrote to its stdout. Invalid UTF-8 is replaced
+    /// with U+FFFD.
+    pub stdout: String,
+    /// Everything the command wrote to its stderr. Invalid UTF-8 is replaced
+    /// with U+FFFD.
+    pub stderr: String,
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Runs a command in the guard's target directory and waits for it to
+    /// finish.
+    ///
+    /// The command line is split into words like a POSIX shell does, so
+    /// quotes and backslashes can be used to include spaces in an argument.
+    /// No other shell features, like globs, variables, or pipes, are
+    /// supported. The command's stdout and stderr are streamed to this
+    /// process's stdout and stderr as it runs, and are also captured in the
+    /// returned [`RunOutput`].
+    ///
+    /// The command runs in the target directory even if the current directory
+    /// was changed by other code, or if the guard was popped. See
+    /// [`CommandExt::pushd`].
+    ///
+    /// This requires the `run` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// let pd = Pushd::new("some/crate")?;
+    /// let output = pd.run("cargo build --release")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the command line cannot be split into words or is
+    /// empty, if the command cannot be run, or if it exits with a non-zero status.
+    pub fn run(&self, command: &str) -> Result<RunOutput, PushdError> {
+        let words = shell_words::split(command).map_err(|e| PushdError::ParseCommand {
+            command: command.to_owned(),
+            message: e.to_string(),
+        })?;
+        let Some((program, args)) = words.split_first() else {
+            return Err(PushdError::ParseCommand {
+                command: command.to_owned(),
+                message: "the command is empty".to_owned(),
+            });
+        };
+
+        let err = |source| PushdError::RunCommand {
+            command: command.to_owned(),
+            source,
+        };
+        let mut child = std::process::Command::new(program)
+            .args(args)
+            .pushd(self)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(err)?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .map(|out| thread::spawn(move || tee(out, |line| print!("{line}"))));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|out| thread::spawn(move || tee(out, |line| eprint!("{line}"))));
+        let status = child.wait().map_err(err)?;
+        let stdout = join(stdout).map_err(err)?;
+        let stderr = join(stderr).map_err(err)?;
+
+        if !status.success() {
+            return Err(PushdError::CommandFailed {
+                command: command.to_owned(),
+                status,
+            });
+        }
+        Ok(RunOutput {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+// Reads lines from a child's output, passing each one to `echo` as it
+// arrives, and returns all of the output.
+fn tee<R: Read>(out: R, echo: impl Fn(&str)) -> io::Result<String> {
+    let mut out = BufReader::new(out);
+    let mut all = String::new();
+    let mut line = Vec::new();
+    while out.read_until(b'\n', &mut line)? > 0 {
+        let s = String::from_utf8_lossy(&line);
+        echo(&s);
+        all.push_str(&s);
+        line.clear();
+    }
+    // We don't want the echoed output to be stuck in a buffer if the caller
+    // does something else before the next write.
+    let _ = io::stdout().flush();
+    Ok(all)
+}
+
+fn join(handle: Option<thread::JoinHandle<io::Result<String>>>) -> io::Result<String> {
+    match handle {
+        Some(h) => h
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("the output reader thread panicked"))),
+        None => Ok(String::new()),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn run() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let td_path = fs::canonicalize(td.path())?;
+        let pd = Pushd::new(&td_path)?;
+
+        let output = pd.run(r#"sh -c 'pwd -P; echo "two words" >&2'"#)?;
+        assert_eq!(output.stdout, format!("{}\n", td_path.display()));
+        assert_eq!(output.stderr, "two words\n");
+        assert!(output.status.success());
+
+        assert!(matches!(
+            pd.run("sh -c 'exit 3'"),
+            Err(PushdError::CommandFailed { status, .. }) if status.code() == Some(3),
+        ));
+        assert!(matches!(
+            pd.run("echo 'unterminated"),
+            Err(PushdError::ParseCommand { .. }),
+        ));
+        assert!(matches!(pd.run("  "), Err(PushdError::ParseCommand { .. })));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+// This module contains the `Sandbox` builder, which is only compiled when the
+// `temp` feature is enabled.
+
+use crate::{DropBehavior, NonPanicking, Panicking, Pushd, PushdBuilder, PushdError, ScopedEnv};
+use std::{
+    ffi::{OsStr, OsString},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+use tempfile::TempDir;
+
+/// A builder for an isolated working environment, made up of an optional
+/// temporary directory, a [`Pushd`] for a directory, and a set of
+/// environment variable changes.
+///
+/// Calling [`Sandbox::enter`] sets everything up and returns a
+/// [`SandboxGuard`]. When that guard is dropped, everything is undone in the
+/// reverse order: the environment variables are restored, then the current
+/// directory is changed back, and then the temporary directory is deleted.
+///
+/// This requires the `temp` feature.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Sandbox;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let sandbox = Sandbox::new()
+///     .temp_dir()
+///     .env_dir("HOME")
+///     .env("LANG", "C")
+///     .remove_env("XDG_CONFIG_HOME")
+///     .enter()?;
+/// // The current directory and `$HOME` are now a new temp dir.
+/// # Ok(())
+/// # }
+/// ```
+pub struct Sandbox<B: DropBehavior = Panicking> {
+    dir: Option<Dir>,
+    env: Vec<(OsString, EnvValue)>,
+    behavior: PhantomData<B>,
+}
+
+enum Dir {
+    Temp,
+    Path(PathBuf),
+}
+
+enum EnvValue {
+    Set(OsString),
+    Remove,
+    SandboxDir,
+}
+
+impl Sandbox<Panicking> {
+    /// Returns a new `Sandbox` builder which doesn't change anything yet.
+    #[must_use]
+    pub fn new() -> Sandbox<Panicking> {
+        Sandbox {
+            dir: None,
+            env: Vec::new(),
+            behavior: PhantomData,
+        }
+    }
+}
+
+impl Default for Sandbox<Panicking> {
+    fn default() -> Self {
+        Sandbox::new()
+    }
+}
+
+impl<B: DropBehavior> Sandbox<B> {
+    /// Makes the sandbox use guards that never panic when they are dropped.
+    #[must_use]
+    pub fn no_panic(self) -> Sandbox<NonPanicking> {
+        Sandbox {
+            dir: self.dir,
+            env: self.env,
+            behavior: PhantomData,
+        }
+    }
+
+    /// Makes the sandbox create a new temporary directory and change to it.
+    /// This replaces any directory set with [`Sandbox::dir`].
+    #[must_use]
+    pub fn temp_dir(mut self) -> Self {
+        self.dir = Some(Dir::Temp);
+        self
+    }
+
+    /// Makes the sandbox change to the given directory. This replaces a
+    /// temporary directory requested with [`Sandbox::temp_dir`].
+    #[must_use]
+    pub fn dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.dir = Some(Dir::Path(path.as_ref().to_owned()));
+        self
+    }
+
+    /// Makes the sandbox set an environment variable.
+    #[must_use]
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(mut self, key: K, value: V) -> Self {
+        self.env.push((
+            key.as_ref().to_owned(),
+            EnvValue::Set(value.as_ref().to_owned()),
+        ));
+        self
+    }
+
+    /// Makes the sandbox set an environment variable to the path of the
+    /// sandbox's directory. This is useful for variables like `HOME`. If the
+    /// sandbox has no directory, then the variable is set to the current
+    /// directory.
+    #[must_use]
+    pub fn env_dir<K: AsRef<OsStr>>(mut self, key: K) -> Self {
+        self.env
+            .push((key.as_ref().to_owned(), EnvValue::SandboxDir));
+        self
+    }
+
+    /// Makes the sandbox remove an environment variable.
+    #[must_use]
+    pub fn remove_env<K: AsRef<OsStr>>(mut self, key: K) -> Self {
+        self.env.push((key.as_ref().to_owned(), EnvValue::Remove));
+        self
+    }
+
+    /// Sets up the sandbox and returns a guard that undoes everything when it
+    /// is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the temporary directory cannot be created, if the
+    /// current directory cannot be changed, or if an environment variable has an invalid name or
+    /// value. Anything that was already set up is undone before the error is returned.
+    #[track_caller]
+    pub fn enter(self) -> Result<SandboxGuard<B>, PushdError> {
+        let temp = match &self.dir {
+            Some(Dir::Temp) => {
+                Some(TempDir::new().map_err(|e| PushdError::CreateTempDir { source: e })?)
+            }
+            _ => None,
+        };
+        let path = match (&temp, self.dir) {
+            (Some(t), _) => Some(t.path().to_owned()),
+            (None, Some(Dir::Path(p))) => Some(p),
+            _ => None,
+        };
+
+        let mut guard = SandboxGuard {
+            env: ScopedEnv::empty(),
+            pushd: None,
+            temp,
+        };
+        if let Some(path) = path {
+            guard.pushd = Some(PushdBuilder::new(path).cast().push()?);
+        }
+
+        let needs_dir = self
+            .env
+            .iter()
+            .any(|(_, v)| matches!(v, EnvValue::SandboxDir));
+        let sandbox_dir = if needs_dir {
+            std::env::current_dir()?
+        } else {
+            PathBuf::new()
+        };
+        for (key, value) in self.env {
+            match value {
+                EnvValue::Set(v) => guard.env.set(key, v)?,
+                EnvValue::Remove => guard.env.remove(key)?,
+                EnvValue::SandboxDir => guard.env.set(key, &sandbox_dir)?,
+            };
+        }
+
+        Ok(guard)
+    }
+}
+
+/// The guard returned by [`Sandbox::enter`]. When it is dropped, it restores
+/// the original environment variables, changes back to the original
+/// directory, and deletes the temporary directory if there is one.
+pub struct SandboxGuard<B: DropBehavior = Panicking> {
+    // The fields are dropped in order, which undoes everything in the reverse
+    // order that `Sandbox::enter` did it.
+    env: ScopedEnv<B>,
+    pushd: Option<Pushd<B>>,
+    temp: Option<TempDir>,
+}
+
+impl<B: DropBehavior> SandboxGuard<B> {
+    /// Returns the path of the sandbox's directory, or `None` if the sandbox
+    /// didn't change directories.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match (&self.temp, &self.pushd) {
+            (Some(t), _) => Some(t.path()),
+            (None, Some(p)) => Some(p.path()),
+            _ => None,
+        }
+    }
+
+    /// Returns the [`Pushd`] guard for the sandbox's directory, or `None` if
+    /// the sandbox didn't change directories.
+    #[must_use]
+    pub fn guard(&self) -> Option<&Pushd<B>> {
+        self.pushd.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+
+    #[test]
+    #[serial]
+    fn sandbox() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        env::set_var("PUSHD_TEST_SANDBOX_HOME", "orig");
+        env::set_var("PUSHD_TEST_SANDBOX_REMOVE", "orig");
+
+        let path = {
+            let sandbox = Sandbox::new()
+                .temp_dir()
+                .env_dir("PUSHD_TEST_SANDBOX_HOME")
+                .env("PUSHD_TEST_SANDBOX_SET", "value")
+                .remove_env("PUSHD_TEST_SANDBOX_REMOVE")
+                .enter()?;
+            let path = fs::canonicalize(sandbox.path().unwrap())?;
+            assert_eq!(fs::canonicalize(env::current_dir()?)?, path);
+            assert_eq!(
+                fs::canonicalize(env::var("PUSHD_TEST_SANDBOX_HOME")?)?,
+                path
+            );
+            assert_eq!(env::var("PUSHD_TEST_SANDBOX_SET")?, "value");
+            assert_eq!(env::var_os("PUSHD_TEST_SANDBOX_REMOVE"), None);
+            path
+        };
+        assert_eq!(env::current_dir()?, cwd);
+        assert!(!path.exists());
+        assert_eq!(env::var("PUSHD_TEST_SANDBOX_HOME")?, "orig");
+        assert_eq!(env::var_os("PUSHD_TEST_SANDBOX_SET"), None);
+        assert_eq!(env::var("PUSHD_TEST_SANDBOX_REMOVE")?, "orig");
+
+        // If an env var is invalid, the directory change is undone.
+        let res = Sandbox::new().dir("src").env("BAD=NAME", "value").enter();
+        assert!(matches!(res, Err(PushdError::InvalidEnvVar { .. })));
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
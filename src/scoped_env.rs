@@ -0,0 +1,190 @@
+use crate::{events, DropBehavior, NonPanicking, Panicking, PushdError};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// A `ScopedEnv` sets and removes environment variables, and restores their
+/// original values when it's dropped.
+///
+/// Like [`Pushd`](crate::Pushd), the `B` type parameter records whether the
+/// guard may panic when it is dropped. Restoring a variable only fails if the
+/// OS rejects the change, in which case [`env::set_var`] panics. A
+/// `ScopedEnv<Panicking>` lets that panic happen, while a
+/// `ScopedEnv<NonPanicking>` catches it and logs a warning instead.
+///
+/// Note that changing environment variables affects the whole process, so
+/// this has the same thread safety concerns as [`env::set_var`].
+///
+/// # Examples
+///
+/// ```
+/// use pushd::ScopedEnv;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let mut env = ScopedEnv::new();
+/// env.set("HOME", std::env::temp_dir())?.remove("XDG_CONFIG_HOME")?;
+/// // When `env` is dropped, `HOME` and `XDG_CONFIG_HOME` are restored to
+/// // their original values.
+/// # Ok(())
+/// # }
+/// ```
+pub struct ScopedEnv<B: DropBehavior = Panicking> {
+    // The original value of each variable we've changed, in the order that
+    // we first changed them.
+    saved: Vec<(OsString, Option<OsString>)>,
+    behavior: PhantomData<B>,
+}
+
+impl ScopedEnv<Panicking> {
+    /// Constructs a new `ScopedEnv` which has not changed any variables yet.
+    /// It may panic if it cannot restore a variable when it is dropped.
+    #[must_use]
+    pub fn new() -> ScopedEnv<Panicking> {
+        ScopedEnv::empty()
+    }
+}
+
+impl Default for ScopedEnv<Panicking> {
+    fn default() -> Self {
+        ScopedEnv::new()
+    }
+}
+
+impl ScopedEnv<NonPanicking> {
+    /// Constructs a new `ScopedEnv` which has not changed any variables yet.
+    /// It will never panic when it is dropped.
+    #[must_use]
+    pub fn new_no_panic() -> ScopedEnv<NonPanicking> {
+        ScopedEnv::empty()
+    }
+}
+
+impl<B: DropBehavior> ScopedEnv<B> {
+    pub(crate) fn empty() -> ScopedEnv<B> {
+        ScopedEnv {
+            saved: Vec::new(),
+            behavior: PhantomData,
+        }
+    }
+
+    /// Sets an environment variable. Its original value is restored when the
+    /// guard is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::InvalidEnvVar`] error if the name is empty or
+    /// contains `=` or a NUL character, or if the value contains a NUL character.
+    pub fn set<K, V>(&mut self, key: K, value: V) -> Result<&mut Self, PushdError>
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        check_var(key, Some(value))?;
+        self.save(key);
+        env::set_var(key, value);
+        Ok(self)
+    }
+
+    /// Removes an environment variable. Its original value is restored when
+    /// the guard is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::InvalidEnvVar`] error if the name is empty or
+    /// contains `=` or a NUL character.
+    pub fn remove<K: AsRef<OsStr>>(&mut self, key: K) -> Result<&mut Self, PushdError> {
+        let key = key.as_ref();
+        check_var(key, None)?;
+        self.save(key);
+        env::remove_var(key);
+        Ok(self)
+    }
+
+    /// Restores all of the variables this guard has changed to their original
+    /// values. After this, dropping the guard does nothing, unless more
+    /// variables are changed.
+    pub fn restore(&mut self) {
+        // We restore in the reverse order of the changes, like a stack of
+        // guards would.
+        while let Some((key, value)) = self.saved.pop() {
+            match value {
+                Some(v) => env::set_var(&key, v),
+                None => env::remove_var(&key),
+            }
+        }
+    }
+
+    fn save(&mut self, key: &OsStr) {
+        if !self.saved.iter().any(|(k, _)| k == key) {
+            self.saved.push((key.to_owned(), env::var_os(key)));
+        }
+    }
+}
+
+fn check_var(key: &OsStr, value: Option<&OsStr>) -> Result<(), PushdError> {
+    let bad_key = key.is_empty() || key.as_encoded_bytes().iter().any(|&b| b == b'=' || b == 0);
+    let bad_value = value.is_some_and(|v| v.as_encoded_bytes().contains(&0));
+    if bad_key || bad_value {
+        return Err(PushdError::InvalidEnvVar {
+            name: key.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+impl<B: DropBehavior> Drop for ScopedEnv<B> {
+    /// Restores all of the variables this guard has changed to their original
+    /// values.
+    fn drop(&mut self) {
+        if B::MAY_PANIC {
+            self.restore();
+            return;
+        }
+        if panic::catch_unwind(AssertUnwindSafe(|| self.restore())).is_err() {
+            events::env_restore_failed();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::error::Error as StdError;
+
+    #[test]
+    #[serial]
+    fn set_and_remove() -> Result<(), Box<dyn StdError>> {
+        env::set_var("PUSHD_TEST_SET", "orig");
+        env::set_var("PUSHD_TEST_REMOVE", "orig");
+        env::remove_var("PUSHD_TEST_UNSET");
+
+        {
+            let mut env = ScopedEnv::new();
+            env.set("PUSHD_TEST_SET", "new")?
+                .set("PUSHD_TEST_SET", "newer")?
+                .set("PUSHD_TEST_UNSET", "new")?
+                .remove("PUSHD_TEST_REMOVE")?;
+            assert_eq!(env::var("PUSHD_TEST_SET")?, "newer");
+            assert_eq!(env::var("PUSHD_TEST_UNSET")?, "new");
+            assert_eq!(env::var_os("PUSHD_TEST_REMOVE"), None);
+        }
+        assert_eq!(env::var("PUSHD_TEST_SET")?, "orig");
+        assert_eq!(env::var("PUSHD_TEST_REMOVE")?, "orig");
+        assert_eq!(env::var_os("PUSHD_TEST_UNSET"), None);
+
+        let mut env = ScopedEnv::new_no_panic();
+        assert!(matches!(
+            env.set("BAD=NAME", "value"),
+            Err(PushdError::InvalidEnvVar { name }) if name == "BAD=NAME",
+        ));
+        assert!(env.set("PUSHD_TEST_SET", "a\0b").is_err());
+        assert!(env.remove("").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,505 @@
+// This module contains the checks for `Pushd::new_secure`, which refuses to
+// change to a directory that someone else could swap out from under us.
+
+use crate::{DropBehavior, Panicking, Pushd, PushdBuilder, PushdError};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+#[cfg(unix)]
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+};
+
+/// The checks that a guard built with
+/// [`PushdBuilder::security`](crate::PushdBuilder::security) or
+/// [`Pushd::new_secure`] makes before it changes to its target directory.
+///
+/// The checks apply to the target and to every directory above it. By default
+/// all of them are enabled, so the guard refuses to change to the target if:
+///
+/// * The target is world-writable, or any directory above it is
+///   world-writable without the sticky bit set, like `/tmp` has.
+/// * The target or any directory above it is owned by a user other than the
+///   current user or root.
+/// * The target or any directory above it is a symlink.
+///
+/// Even when symlinks are allowed, a path with a `..` component after a
+/// symlink is rejected, since the `..` leads to the parent of the symlink's
+/// destination, which is never checked. The ownership and permission checks
+/// are only done on Unix.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{Pushd, SecurityPolicy};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// // Make all of the checks except the one for symlinks.
+/// let policy = SecurityPolicy::default().allow_symlinks();
+/// let _pd = Pushd::builder(std::env::current_dir()?)
+///     .security(policy)
+///     .push()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SecurityPolicy {
+    world_writable: bool,
+    other_owners: bool,
+    symlinks: bool,
+}
+
+impl SecurityPolicy {
+    /// Allows the target and the directories above it to be world-writable.
+    #[must_use]
+    pub fn allow_world_writable(mut self) -> SecurityPolicy {
+        self.world_writable = true;
+        self
+    }
+
+    /// Allows the target and the directories above it to be owned by any
+    /// user.
+    #[must_use]
+    pub fn allow_other_owners(mut self) -> SecurityPolicy {
+        self.other_owners = true;
+        self
+    }
+
+    /// Allows the path to the target to go through symlinks.
+    #[must_use]
+    pub fn allow_symlinks(mut self) -> SecurityPolicy {
+        self.symlinks = true;
+        self
+    }
+}
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that refuses to change to a directory
+    /// that someone other than the current user could replace or modify. It
+    /// uses the default [`SecurityPolicy`], which rejects targets that are
+    /// world-writable, owned by another user, or reached through a symlink.
+    ///
+    /// This is meant for tools that run code relative to the current
+    /// directory after changing to it. On Unix, the guard opens the target and
+    /// checks that the open directory is the one it checked before changing
+    /// to it with `fchdir`, so that the target cannot be swapped for another
+    /// directory between the checks and the change. See
+    /// [`PushdBuilder::enter_via_handle`].
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::InsecureDir`] error if the target fails one of the
+    /// checks. Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn new_secure<P: AsRef<Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .security(SecurityPolicy::default())
+            .push()
+    }
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`] check its target against the given
+    /// policy before changing to it, and again each time it is
+    /// [re-entered](Pushd::reenter). See [`Pushd::new_secure`] for details.
+    ///
+    /// The checks are done on the target after it is expanded and, if the
+    /// guard [canonicalizes](PushdBuilder::canonicalize) its paths, after it is
+    /// canonicalized. Since canonicalizing resolves all symlinks, a
+    /// canonicalized target always passes the symlink check.
+    #[must_use]
+    pub fn security(mut self, policy: SecurityPolicy) -> Self {
+        self.opts.security = Some(policy);
+        self
+    }
+}
+
+#[cfg(unix)]
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`] open its target directory and change to
+    /// it with `fchdir`, rather than passing the target's path to
+    /// [`env::set_current_dir`](std::env::set_current_dir). Any checks on the
+    /// target, like the ones set with [`PushdBuilder::expect_owner`], are done
+    /// on the open directory right before changing to it, so the directory
+    /// can't be swapped for another one between the checks and the change.
+    ///
+    /// This is done automatically by guards with a
+    /// [security policy](PushdBuilder::security) or expectations. This is only
+    /// available on Unix.
+    ///
+    /// On Linux, macOS, the BSDs other than OpenBSD, illumos, Solaris, and
+    /// AIX, the target is opened with `O_PATH` or `O_SEARCH`, so this only
+    /// needs the same search permission on the target that changing to it by
+    /// path does. On other Unix platforms, opening the target also needs read
+    /// permission, so a target that only has search permission fails with a
+    /// [`PushdError::PermissionDenied`] error.
+    #[must_use]
+    pub fn enter_via_handle(mut self) -> Self {
+        self.opts.handle.get_or_insert_with(HandleChecks::default);
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] refuse to change to its target unless
+    /// the target is owned by the user with the ID `uid`. This implies
+    /// [`PushdBuilder::enter_via_handle`]. This is only available on Unix.
+    #[must_use]
+    pub fn expect_owner(mut self, uid: u32) -> Self {
+        self.opts
+            .handle
+            .get_or_insert_with(HandleChecks::default)
+            .owner = Some(uid);
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] refuse to change to its target unless
+    /// the target is on the device with the ID `dev`, as returned by
+    /// [`MetadataExt::dev`](std::os::unix::fs::MetadataExt::dev). This implies
+    /// [`PushdBuilder::enter_via_handle`]. This is only available on Unix.
+    #[must_use]
+    pub fn expect_device(mut self, dev: u64) -> Self {
+        self.opts
+            .handle
+            .get_or_insert_with(HandleChecks::default)
+            .device = Some(dev);
+        self
+    }
+}
+
+// The checks that are done on an open target directory.
+#[cfg(unix)]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct HandleChecks {
+    owner: Option<u32>,
+    device: Option<u64>,
+}
+
+// The directory that passed the checks, so that we can make sure it's the one
+// we change to.
+pub(crate) struct Checked {
+    #[cfg(unix)]
+    id: (u64, u64),
+}
+
+// Opens a directory so that we can check it and then change to it. On Linux,
+// and on the platforms with `O_SEARCH`, this only needs search permission on
+// the directory, just like changing to it. Elsewhere it needs read permission
+// too, so a directory that can be changed to by path may fail to open.
+#[cfg(unix)]
+pub(crate) fn open_dir(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(SEARCH_FLAGS)
+        .open(path)
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) const SEARCH_FLAGS: libc::c_int = libc::O_PATH | libc::O_DIRECTORY;
+#[cfg(any(
+    target_vendor = "apple",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "illumos",
+    target_os = "solaris",
+    target_os = "aix",
+))]
+pub(crate) const SEARCH_FLAGS: libc::c_int = libc::O_SEARCH | libc::O_DIRECTORY;
+#[cfg(all(
+    unix,
+    not(any(
+        target_os = "linux",
+        target_vendor = "apple",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "illumos",
+        target_os = "solaris",
+        target_os = "aix",
+    )),
+))]
+pub(crate) const SEARCH_FLAGS: libc::c_int = libc::O_DIRECTORY;
+
+// Checks an open directory and changes to it. `from` and `path` are only used
+// for errors.
+#[cfg(unix)]
+pub(crate) fn enter_dir(
+    dir: &File,
+    checks: Option<HandleChecks>,
+    checked: Option<&Checked>,
+    from: &Path,
+    path: &Path,
+) -> Result<(), PushdError> {
+    use std::os::unix::fs::MetadataExt;
+
+    let target = from.join(path);
+    let md = dir.metadata().map_err(|e| PushdError::Metadata {
+        path: target.clone(),
+        source: e,
+    })?;
+    if !md.is_dir() {
+        return Err(PushdError::NotADirectory { path: target });
+    }
+    if checked.is_some_and(|c| (md.dev(), md.ino()) != c.id) {
+        return Err(insecure(
+            &target,
+            &target,
+            "was replaced while it was being checked",
+        ));
+    }
+    let checks = checks.unwrap_or_default();
+    if checks.owner.is_some_and(|uid| md.uid() != uid) {
+        return Err(insecure(
+            &target,
+            &target,
+            "is not owned by the expected user",
+        ));
+    }
+    if checks.device.is_some_and(|dev| md.dev() != dev) {
+        return Err(insecure(&target, &target, "is not on the expected device"));
+    }
+
+    crate::cwd_cache::clear_cwd_cache();
+    // SAFETY: `dir` is an open file descriptor.
+    if unsafe { libc::fchdir(dir.as_raw_fd()) } == -1 {
+        return Err(crate::chdir_error(
+            from.to_owned(),
+            path.to_owned(),
+            io::Error::last_os_error(),
+        ));
+    }
+    Ok(())
+}
+
+// Checks `target`, which must be absolute, and every directory above it
+// against the policy.
+//
+// The directories are checked in order from the root, so a `..` leads back to
+// a directory that was already checked, as long as nothing before it was a
+// symlink. After a symlink, a `..` leads to the parent of wherever the symlink
+// points, which was never checked, so that is rejected.
+pub(crate) fn check(target: &Path, policy: SecurityPolicy) -> Result<Checked, PushdError> {
+    let mut dir = PathBuf::new();
+    let mut md = None;
+    let mut through_symlink = false;
+    let mut components = target.components().peekable();
+    while let Some(c) = components.next() {
+        let is_target = components.peek().is_none();
+        if c == Component::ParentDir {
+            if through_symlink {
+                return Err(insecure(target, &dir, "is a symlink followed by `..`"));
+            }
+            dir.pop();
+        } else {
+            dir.push(c);
+        }
+        if matches!(c, Component::Prefix(_)) {
+            continue;
+        }
+
+        let metadata_err = |e| PushdError::Metadata {
+            path: dir.clone(),
+            source: e,
+        };
+        let mut m = fs::symlink_metadata(&dir).map_err(metadata_err)?;
+        if m.file_type().is_symlink() {
+            if !policy.symlinks {
+                return Err(insecure(target, &dir, "is a symlink"));
+            }
+            through_symlink = true;
+            m = fs::metadata(&dir).map_err(metadata_err)?;
+        }
+        #[cfg(unix)]
+        check_owner_and_mode(target, &dir, is_target, &m, policy)?;
+        #[cfg(not(unix))]
+        let _ = is_target;
+        md = Some(m);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let md = md.ok_or_else(|| PushdError::NotADirectory {
+            path: target.to_owned(),
+        })?;
+        Ok(Checked {
+            id: (md.dev(), md.ino()),
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = md;
+        Ok(Checked {})
+    }
+}
+
+#[cfg(unix)]
+fn check_owner_and_mode(
+    target: &Path,
+    dir: &Path,
+    is_target: bool,
+    md: &fs::Metadata,
+    policy: SecurityPolicy,
+) -> Result<(), PushdError> {
+    use std::os::unix::fs::MetadataExt;
+
+    let world_writable = md.mode() & 0o002 != 0;
+    let sticky = md.mode() & 0o1000 != 0;
+    // A sticky directory above the target, like `/tmp`, is fine, since only
+    // the owner of an entry in it can rename or remove that entry.
+    if !policy.world_writable && world_writable && (is_target || !sticky) {
+        return Err(insecure(target, dir, "is world-writable"));
+    }
+
+    // SAFETY: `geteuid` has no preconditions and always succeeds.
+    let euid = unsafe { libc::geteuid() };
+    if !policy.other_owners && md.uid() != euid && md.uid() != 0 {
+        return Err(insecure(target, dir, "is owned by another user"));
+    }
+    Ok(())
+}
+
+fn insecure(target: &Path, dir: &Path, reason: &'static str) -> PushdError {
+    PushdError::InsecureDir {
+        target: target.to_owned(),
+        path: dir.to_owned(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    #[cfg(unix)]
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::{env, error::Error as StdError};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn secure_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        // A new temp dir is only writable by its owner.
+        let td = tempdir()?;
+        let policy = SecurityPolicy::default().allow_symlinks();
+        let mut pd = Pushd::builder(td.path()).security(policy).push()?;
+        assert!(pd.is_same_dir(td.path()));
+        pd.pop()?;
+        pd.reenter()?;
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn insecure_dirs() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        // The temp dir itself may be reached through a symlink, like it is on
+        // macOS.
+        let td = tempdir()?;
+        let root = fs::canonicalize(td.path())?;
+
+        let writable = root.join("writable");
+        fs::create_dir(&writable)?;
+        fs::set_permissions(&writable, fs::Permissions::from_mode(0o777))?;
+        let link = root.join("link");
+        symlink(root.join("writable"), &link)?;
+
+        let cwd = env::current_dir()?;
+        for (target, reason) in [(&writable, "is world-writable"), (&link, "is a symlink")] {
+            let res = Pushd::new_secure(target);
+            assert!(
+                matches!(
+                    &res,
+                    Err(PushdError::InsecureDir { path, reason: r, .. })
+                        if path == target && *r == reason,
+                ),
+                "{target:?}: {:?}",
+                res.err(),
+            );
+            assert_eq!(env::current_dir()?, cwd);
+        }
+
+        let policy = SecurityPolicy::default()
+            .allow_world_writable()
+            .allow_symlinks();
+        let pd = Pushd::builder(&link).security(policy).push()?;
+        assert!(pd.is_same_dir(&writable));
+        drop(pd);
+
+        // A `..` is fine unless it follows a symlink, since then it leads
+        // somewhere that wasn't checked.
+        let sub = root.join("sub");
+        fs::create_dir(&sub)?;
+        let pd = Pushd::new_secure(sub.join("../sub"))?;
+        assert!(pd.is_same_dir(&sub));
+        drop(pd);
+        let res = Pushd::builder(link.join("..")).security(policy).push();
+        assert!(
+            matches!(
+                &res,
+                Err(PushdError::InsecureDir { path, reason, .. })
+                    if *path == link && *reason == "is a symlink followed by `..`",
+            ),
+            "{:?}",
+            res.err(),
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn handle_checks() -> Result<(), Box<dyn StdError>> {
+        use std::os::unix::fs::MetadataExt;
+
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let md = fs::metadata(td.path())?;
+
+        let mut pd = Pushd::builder(td.path()).enter_via_handle().push()?;
+        assert!(pd.is_same_dir(td.path()));
+        pd.pop()?;
+        pd.reenter()?;
+        drop(pd);
+
+        let pd = Pushd::builder(td.path())
+            .expect_owner(md.uid())
+            .expect_device(md.dev())
+            .push()?;
+        assert!(pd.is_same_dir(td.path()));
+        drop(pd);
+
+        let cwd = env::current_dir()?;
+        for (builder, reason) in [
+            (
+                Pushd::builder(td.path()).expect_owner(md.uid() + 1),
+                "is not owned by the expected user",
+            ),
+            (
+                Pushd::builder(td.path()).expect_device(md.dev() + 1),
+                "is not on the expected device",
+            ),
+        ] {
+            let res = builder.push();
+            assert!(
+                matches!(
+                    &res,
+                    Err(PushdError::InsecureDir { reason: r, .. }) if *r == reason,
+                ),
+                "{:?}",
+                res.err(),
+            );
+            assert_eq!(env::current_dir()?, cwd);
+        }
+
+        Ok(())
+    }
+}
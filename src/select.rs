@@ -0,0 +1,184 @@
+// This module contains the constructors which pick a target out of several
+// candidate paths, either given explicitly or found with a glob pattern.
+
+use crate::{Panicking, Pushd, PushdBuilder, PushdError};
+use std::path::{Path, PathBuf};
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that changes to the first of the given
+    /// candidates that is an existing directory. Relative paths are resolved
+    /// against the current directory.
+    ///
+    /// This is useful for code that has to work with more than one layout,
+    /// for example when a directory moved between releases of a project.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::NoCandidateDir`] error, which contains all of the
+    /// candidates, if none of them is an existing directory. Otherwise it returns the same errors
+    /// as [`Pushd::new`].
+    #[track_caller]
+    pub fn new_any<I, P>(candidates: I) -> Result<Pushd<Panicking>, PushdError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut tried = Vec::new();
+        for c in candidates {
+            let c = c.as_ref();
+            if c.is_dir() {
+                return PushdBuilder::new(c.to_owned()).push();
+            }
+            tried.push(c.to_owned());
+        }
+        Err(PushdError::NoCandidateDir { candidates: tried })
+    }
+
+    /// Constructs a new `Pushd` struct that changes to the single directory
+    /// matching the given glob pattern, like
+    /// `target/debug/build/mycrate-*/out`. Matches that are not directories
+    /// are ignored. See the [`glob`](https://docs.rs/glob) crate for the
+    /// pattern syntax.
+    ///
+    /// This requires the `glob` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::InvalidGlob`] error if the pattern is invalid, a
+    /// [`PushdError::NoGlobMatch`] error if no directory matches it, and a
+    /// [`PushdError::AmbiguousGlob`] error if more than one directory matches it. Otherwise it
+    /// returns the same errors as [`Pushd::new`].
+    #[cfg(feature = "glob")]
+    #[track_caller]
+    pub fn new_glob(pattern: &str) -> Result<Pushd<Panicking>, PushdError> {
+        let mut matches = glob_dirs(pattern)?;
+        if matches.len() > 1 {
+            return Err(PushdError::AmbiguousGlob {
+                pattern: pattern.to_owned(),
+                matches,
+            });
+        }
+        let target = matches.pop().ok_or_else(|| PushdError::NoGlobMatch {
+            pattern: pattern.to_owned(),
+        })?;
+        PushdBuilder::new(target).push()
+    }
+
+    /// Like [`Pushd::new_glob`], but if more than one directory matches the
+    /// pattern, this changes to the one that was modified most recently
+    /// instead of returning an error.
+    ///
+    /// This requires the `glob` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::InvalidGlob`] error if the pattern is invalid, and
+    /// a [`PushdError::NoGlobMatch`] error if no directory matches it. Otherwise it returns the
+    /// same errors as [`Pushd::new`].
+    #[cfg(feature = "glob")]
+    #[track_caller]
+    pub fn new_glob_newest(pattern: &str) -> Result<Pushd<Panicking>, PushdError> {
+        let target = glob_dirs(pattern)?
+            .into_iter()
+            .max_by_key(|m| m.metadata().and_then(|md| md.modified()).ok())
+            .ok_or_else(|| PushdError::NoGlobMatch {
+                pattern: pattern.to_owned(),
+            })?;
+        PushdBuilder::new(target).push()
+    }
+}
+
+// Returns all of the directories that match a glob pattern.
+#[cfg(feature = "glob")]
+fn glob_dirs(pattern: &str) -> Result<Vec<PathBuf>, PushdError> {
+    let paths = glob::glob(pattern).map_err(|e| PushdError::InvalidGlob {
+        pattern: pattern.to_owned(),
+        message: e.to_string(),
+    })?;
+    // Entries that can't be read are skipped, since we can't change to them
+    // anyway.
+    Ok(paths.flatten().filter(|p| p.is_dir()).collect())
+}
+
+// Formats a list of paths for an error message.
+pub(crate) fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn new_any() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        {
+            let pd = Pushd::new_any(["no-such-dir", "Cargo.toml", "src", "pushd-macros"])?;
+            assert_eq!(pd.path(), Path::new("src"));
+            assert_eq!(env::current_dir()?, cwd.join("src"));
+        }
+
+        let candidates = ["no-such-dir", "Cargo.toml"];
+        let err = Pushd::new_any(candidates).err();
+        assert!(matches!(
+            &err,
+            Some(PushdError::NoCandidateDir { candidates: c }) if c == &candidates.map(PathBuf::from),
+        ));
+        assert_eq!(
+            err.map(|e| e.to_string()),
+            Some("None of the candidate directories exist: no-such-dir, Cargo.toml".to_owned()),
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    #[serial]
+    fn new_glob() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempfile::tempdir()?;
+        let base = td.path().to_str().ok_or("non-UTF-8 temp dir")?;
+        std::fs::create_dir(td.path().join("build-abc"))?;
+        std::fs::write(td.path().join("build-file"), "")?;
+
+        let pattern = format!("{base}/build-*");
+        {
+            let pd = Pushd::new_glob(&pattern)?;
+            assert_eq!(pd.path(), td.path().join("build-abc"));
+        }
+
+        std::fs::create_dir(td.path().join("build-def"))?;
+        assert!(matches!(
+            Pushd::new_glob(&pattern),
+            Err(PushdError::AmbiguousGlob { matches, .. }) if matches.len() == 2,
+        ));
+        std::fs::File::open(td.path().join("build-abc"))?
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_mins(1))?;
+        {
+            let pd = Pushd::new_glob_newest(&pattern)?;
+            assert_eq!(pd.path(), td.path().join("build-abc"));
+        }
+
+        assert!(matches!(
+            Pushd::new_glob(&format!("{base}/nothing-*")),
+            Err(PushdError::NoGlobMatch { .. }),
+        ));
+        assert!(matches!(
+            Pushd::new_glob("a/***"),
+            Err(PushdError::InvalidGlob { .. }),
+        ));
+
+        Ok(())
+    }
+}
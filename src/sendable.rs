@@ -0,0 +1,104 @@
+// This module implements `SendablePushd`, the escape hatch for moving a guard
+// to another thread.
+
+use crate::{DropBehavior, Panicking, Pushd};
+use std::ops::{Deref, DerefMut};
+
+/// A [`Pushd`] that can be sent to another thread. Create one with
+/// [`Pushd::into_sendable`].
+///
+/// A [`Pushd`] is not [`Send`], so that it can't be moved to another thread
+/// by accident and then changed back, or dropped, from there. The current
+/// directory is shared by the whole process, so changing back from a worker
+/// thread that happens to drop the guard can change it out from under the
+/// thread that created the guard. Use this when moving the guard is
+/// intentional.
+///
+/// This derefs to the [`Pushd`] it wraps, so it can be used in the same ways.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let pd = Pushd::new(std::env::temp_dir())?.into_sendable();
+/// std::thread::spawn(move || drop(pd)).join().unwrap();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SendablePushd<B: DropBehavior = Panicking> {
+    guard: Pushd<B>,
+}
+
+// SAFETY: The only field of `Pushd` that isn't `Send` is the marker that
+// exists to make it `!Send`. Everything else is `Send`, and nothing in a
+// guard refers to the thread that created it.
+unsafe impl<B: DropBehavior> Send for SendablePushd<B> {}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Wraps this guard in a [`SendablePushd`], which can be sent to another
+    /// thread.
+    ///
+    /// A `Pushd` itself can't be sent:
+    ///
+    /// ```compile_fail
+    /// use pushd::Pushd;
+    ///
+    /// let pd = Pushd::new(std::env::temp_dir()).unwrap();
+    /// std::thread::spawn(move || drop(pd));
+    /// ```
+    #[must_use]
+    pub fn into_sendable(self) -> SendablePushd<B> {
+        SendablePushd { guard: self }
+    }
+}
+
+impl<B: DropBehavior> SendablePushd<B> {
+    /// Returns the wrapped [`Pushd`], which can't be sent to another thread
+    /// again.
+    #[must_use]
+    pub fn into_inner(self) -> Pushd<B> {
+        self.guard
+    }
+}
+
+impl<B: DropBehavior> Deref for SendablePushd<B> {
+    type Target = Pushd<B>;
+
+    fn deref(&self) -> &Pushd<B> {
+        &self.guard
+    }
+}
+
+impl<B: DropBehavior> DerefMut for SendablePushd<B> {
+    fn deref_mut(&mut self) -> &mut Pushd<B> {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, path::Path, thread};
+
+    #[test]
+    #[serial]
+    fn sendable() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let pd = Pushd::new("src")?.into_sendable();
+        assert_eq!(pd.path(), Path::new("src"));
+        let pd = thread::spawn(move || pd)
+            .join()
+            .map_err(|_| "thread panicked")?;
+        let mut pd = pd.into_inner();
+        pd.pop()?;
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
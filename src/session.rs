@@ -0,0 +1,195 @@
+// This module contains `Session`, which tracks a current directory of its own
+// instead of changing the process's.
+
+use crate::{current_dir, PushdError};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A `Session` carries its own current directory, without ever changing the
+/// process's current directory.
+///
+/// This gives library code the same "change to a directory and work with
+/// relative paths" style as [`Pushd`](crate::Pushd), without the process-wide
+/// side effects, so it is safe to use from any thread. Relative paths passed
+/// to a `Session`'s methods are resolved against the session's directory.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Session;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut session = Session::new()?;
+/// session.cd(std::env::temp_dir())?;
+/// session.write("pushd-session-example", "content")?;
+/// assert_eq!(session.read_to_string("pushd-session-example")?, "content");
+/// session.remove("pushd-session-example")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Session {
+    cwd: PathBuf,
+}
+
+impl Session {
+    /// Creates a new `Session` that starts in the process's current
+    /// directory.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined.
+    pub fn new() -> Result<Session, PushdError> {
+        Ok(Session {
+            cwd: current_dir()?,
+        })
+    }
+
+    /// Creates a new `Session` that starts in the given directory. A relative
+    /// path is resolved against the process's current directory.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`Session::cd`].
+    pub fn at<P: AsRef<Path>>(path: P) -> Result<Session, PushdError> {
+        let mut session = Session::new()?;
+        session.cd(path)?;
+        Ok(session)
+    }
+
+    /// Returns the session's current directory. This is always an absolute
+    /// path with all symlinks resolved.
+    #[must_use]
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// Changes the session's current directory. A relative path is resolved
+    /// against the session's current directory, like a relative path passed
+    /// to [`env::set_current_dir`](std::env::set_current_dir) is resolved
+    /// against the process's current directory.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the target's metadata cannot be read, or if the target
+    /// is not a directory. In that case the session's directory is not changed.
+    pub fn cd<P: AsRef<Path>>(&mut self, path: P) -> Result<(), PushdError> {
+        let path = self.resolve(path);
+        let err = |source| PushdError::Metadata {
+            path: path.clone(),
+            source,
+        };
+        let target = fs::canonicalize(&path).map_err(err)?;
+        if !fs::metadata(&target).map_err(err)?.is_dir() {
+            return Err(PushdError::NotADirectory { path });
+        }
+        #[cfg(windows)]
+        let target = crate::windows::simplify(target);
+        self.cwd = target;
+        Ok(())
+    }
+
+    /// Resolves a path against the session's current directory. An absolute
+    /// path is returned as is. This does not check whether the path exists.
+    #[must_use]
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.cwd.join(path)
+    }
+
+    /// Reads the entire contents of a file, like [`fs::read`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`fs::read`].
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
+        fs::read(self.resolve(path))
+    }
+
+    /// Reads the entire contents of a file into a string, like
+    /// [`fs::read_to_string`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`fs::read_to_string`].
+    pub fn read_to_string<P: AsRef<Path>>(&self, path: P) -> io::Result<String> {
+        fs::read_to_string(self.resolve(path))
+    }
+
+    /// Writes `contents` to a file, like [`fs::write`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`fs::write`].
+    pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(&self, path: P, contents: C) -> io::Result<()> {
+        fs::write(self.resolve(path), contents)
+    }
+
+    /// Creates a new, empty directory, like [`fs::create_dir`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`fs::create_dir`].
+    pub fn create_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::create_dir(self.resolve(path))
+    }
+
+    /// Removes a file, like [`fs::remove_file`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns the same errors as [`fs::remove_file`].
+    pub fn remove<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::remove_file(self.resolve(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn session() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = current_dir()?;
+        let td = tempdir()?;
+        let td_path = fs::canonicalize(td.path())?;
+
+        let mut session = Session::at(td.path())?;
+        assert_eq!(session.cwd(), td_path);
+
+        session.create_dir("dir")?;
+        session.cd("dir")?;
+        assert_eq!(session.cwd(), td_path.join("dir"));
+        session.write("file", "content")?;
+        assert_eq!(session.read_to_string("file")?, "content");
+        assert_eq!(session.read("file")?, b"content");
+        assert_eq!(session.resolve("file"), td_path.join("dir/file"));
+
+        session.cd("..")?;
+        assert_eq!(session.cwd(), td_path);
+        assert!(matches!(
+            session.cd("dir/file"),
+            Err(PushdError::NotADirectory { .. }),
+        ));
+        assert!(matches!(
+            session.cd("missing"),
+            Err(PushdError::Metadata { .. }),
+        ));
+        assert_eq!(session.cwd(), td_path);
+
+        session.remove("dir/file")?;
+        assert!(!td_path.join("dir/file").exists());
+
+        // The process's current directory never changes.
+        assert_eq!(current_dir()?, cwd);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+// This module implements `SharedPushd`, a guard with more than one owner.
+
+use crate::{DirStatus, DropBehavior, Panicking, Pushd, PushdBuilder, PushdError, SendablePushd};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// A [`Pushd`] that can be cloned, so that several owners can keep the
+/// current directory in the guard's target. The guard changes back to its
+/// original directory when the last clone is dropped. Create one with
+/// [`Pushd::new_shared`] or [`Pushd::into_shared`].
+///
+/// The clones can be sent to other threads, but keep in mind that the current
+/// directory is shared by the whole process.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let cwd = std::env::current_dir().unwrap();
+/// let shared = Pushd::new_shared(std::env::temp_dir())?;
+/// let other = shared.clone();
+/// drop(shared);
+/// assert_ne!(std::env::current_dir().unwrap(), cwd);
+/// drop(other);
+/// assert_eq!(std::env::current_dir().unwrap(), cwd);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SharedPushd<B: DropBehavior = Panicking> {
+    guard: Arc<Mutex<SendablePushd<B>>>,
+}
+
+impl Pushd<Panicking> {
+    /// Constructs a new [`SharedPushd`]. This is the same as calling
+    /// [`Pushd::into_shared`] on the guard returned by [`Pushd::new`].
+    ///
+    /// # Errors
+    ///
+    /// This returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn new_shared<P: AsRef<Path>>(path: P) -> Result<SharedPushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .push()
+            .map(Pushd::into_shared)
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Turns this guard into a [`SharedPushd`], which changes back to the
+    /// original directory when its last clone is dropped.
+    #[must_use]
+    pub fn into_shared(self) -> SharedPushd<B> {
+        SharedPushd {
+            guard: Arc::new(Mutex::new(self.into_sendable())),
+        }
+    }
+}
+
+impl<B: DropBehavior> SharedPushd<B> {
+    /// Returns the path that the guard changed to. See [`Pushd::path`].
+    #[must_use]
+    pub fn path(&self) -> PathBuf {
+        self.lock().path().to_owned()
+    }
+
+    /// Returns the original directory that the guard will change back to.
+    /// See [`Pushd::original`].
+    #[must_use]
+    pub fn original(&self) -> PathBuf {
+        self.lock().original().to_owned()
+    }
+
+    /// Returns the guard's [`DirStatus`]. See [`Pushd::status`].
+    #[must_use]
+    pub fn status(&self) -> DirStatus {
+        self.lock().status()
+    }
+
+    /// Returns the number of clones of this `SharedPushd` that exist,
+    /// including this one.
+    #[must_use]
+    pub fn holders(&self) -> usize {
+        Arc::strong_count(&self.guard)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SendablePushd<B>> {
+        self.guard.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// This isn't derived so that `B` doesn't need to implement `Clone`.
+impl<B: DropBehavior> Clone for SharedPushd<B> {
+    fn clone(&self) -> SharedPushd<B> {
+        SharedPushd {
+            guard: Arc::clone(&self.guard),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, thread};
+
+    #[test]
+    #[serial]
+    fn shared() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let shared = Pushd::new_shared("src")?;
+        assert_eq!(shared.path(), Path::new("src"));
+        assert_eq!(shared.original(), cwd);
+
+        let other = shared.clone();
+        assert_eq!(shared.holders(), 2);
+        thread::spawn(move || drop(other))
+            .join()
+            .map_err(|_| "thread panicked")?;
+        assert_eq!(shared.holders(), 1);
+        assert!(shared.status().is_current);
+
+        drop(shared);
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
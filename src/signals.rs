@@ -0,0 +1,210 @@
+// This module contains `restore_on_signal`, which is only available with the
+// `signals` feature.
+//
+// On Unix the handler can't take locks or allocate, so the nesting stack
+// keeps a C string copy of the directory to restore up to date, which the
+// handler reads with a single atomic load. On Windows the handler runs on its
+// own thread, so it can restore the directory the same way the panic hook
+// does.
+
+use crate::PushdError;
+#[cfg(unix)]
+use std::{
+    ffi::{c_char, c_int, CString},
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering},
+};
+use std::{
+    io,
+    sync::{Mutex, PoisonError},
+};
+
+/// Installs handlers for the signals that terminate a process, which change
+/// back to the original directory of the oldest [`Pushd`](crate::Pushd) that
+/// is still in its target before the process terminates as it normally
+/// would.
+///
+/// Terminating a process by a signal doesn't run any destructors, so
+/// without this a process that is interrupted while a guard is alive
+/// terminates in the guard's target. That matters when something else looks
+/// at the directory the process was in when it exited, like a shell wrapper
+/// that changes to it.
+///
+/// On Unix, this handles `SIGINT`, `SIGTERM`, and `SIGHUP`. A signal that
+/// already has a handler, or that is ignored, is left alone. On Windows, this
+/// handles all of the console control events, like Ctrl-C and closing the
+/// console window.
+///
+/// Guards created with [`Pushd::new_thread_scoped`](crate::Pushd::new_thread_scoped)
+/// and guards that use a [`CwdBackend`](crate::CwdBackend) don't change the
+/// process's directory, so they are ignored.
+///
+/// Calling this more than once does nothing after the first call. This
+/// requires the `signals` feature.
+///
+/// # Errors
+///
+/// This returns a [`PushdError::Signal`] error if a handler cannot be
+/// installed. This is only returned by the first call.
+pub fn restore_on_signal() -> Result<(), PushdError> {
+    let mut installed = INSTALLED.lock().unwrap_or_else(PoisonError::into_inner);
+    if *installed {
+        return Ok(());
+    }
+    install().map_err(|source| PushdError::Signal { source })?;
+    *installed = true;
+    Ok(())
+}
+
+static INSTALLED: Mutex<bool> = Mutex::new(false);
+
+// The directory for the Unix handler to change to, or null if no guard is in
+// its target. This is only kept up to date once the handlers are installed.
+#[cfg(unix)]
+static TARGET: AtomicPtr<c_char> = AtomicPtr::new(ptr::null_mut());
+// The ID of the guard whose original directory is in `TARGET`.
+#[cfg(unix)]
+static TARGET_ID: AtomicU64 = AtomicU64::new(u64::MAX);
+// Set by the handler before it reads `TARGET`, so that `TARGET` is never
+// freed while the handler might be using it.
+#[cfg(unix)]
+static IN_HANDLER: AtomicBool = AtomicBool::new(false);
+// Set once the handlers are being installed, so that `TARGET` isn't kept up
+// to date until it's needed.
+#[cfg(unix)]
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+const SIGNALS: [c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+#[cfg(unix)]
+fn install() -> io::Result<()> {
+    ACTIVE.store(true, Ordering::SeqCst);
+    crate::nesting::update_signal_target();
+    SIGNALS.into_iter().try_for_each(install_one)
+}
+
+#[cfg(unix)]
+fn install_one(sig: c_int) -> io::Result<()> {
+    // SAFETY: An all-zero `sigaction` is a valid value, and `sigaction` only
+    // writes to `old`.
+    let mut old: libc::sigaction = unsafe { std::mem::zeroed() };
+    if unsafe { libc::sigaction(sig, ptr::null(), ptr::addr_of_mut!(old)) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    if old.sa_sigaction != libc::SIG_DFL {
+        return Ok(());
+    }
+    // SAFETY: See above.
+    let mut new: libc::sigaction = unsafe { std::mem::zeroed() };
+    new.sa_sigaction = handle as extern "C" fn(c_int) as libc::sighandler_t;
+    // SAFETY: `new` is a valid `sigaction` and `handle` only calls functions
+    // that are async-signal-safe.
+    if unsafe { libc::sigaction(sig, ptr::addr_of!(new), ptr::null_mut()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+extern "C" fn handle(sig: c_int) {
+    IN_HANDLER.store(true, Ordering::SeqCst);
+    let dir = TARGET.load(Ordering::SeqCst);
+    // SAFETY: `dir` is either null or a C string that is never freed once
+    // `IN_HANDLER` is set. `chdir`, `signal`, and `raise` are all
+    // async-signal-safe. The signal is blocked while this handler runs, so the
+    // raised signal is delivered with its default action once it returns.
+    unsafe {
+        if !dir.is_null() {
+            libc::chdir(dir);
+        }
+        libc::signal(sig, libc::SIG_DFL);
+        libc::raise(sig);
+    }
+}
+
+// Tells the Unix handler which directory to change to. This is called by the
+// nesting stack with its lock held whenever it changes, with the ID and
+// original directory of the oldest guard that changes the process's
+// directory.
+#[cfg(unix)]
+pub(crate) fn set_target(outermost: Option<(u64, &Path)>) {
+    if !ACTIVE.load(Ordering::SeqCst) {
+        return;
+    }
+    let id = outermost.map_or(u64::MAX, |(id, _)| id);
+    if TARGET_ID.swap(id, Ordering::SeqCst) == id {
+        return;
+    }
+    let new = outermost
+        .and_then(|(_, dir)| CString::new(dir.as_os_str().as_bytes()).ok())
+        .map_or(ptr::null_mut(), CString::into_raw);
+    let old = TARGET.swap(new, Ordering::SeqCst);
+    // If the handler is running, it may be using the old string. The process
+    // is about to terminate anyway, so it's fine to leak it.
+    if old.is_null() || IN_HANDLER.load(Ordering::SeqCst) {
+        return;
+    }
+    // SAFETY: `old` came from `CString::into_raw`, and the handler isn't
+    // using it.
+    drop(unsafe { CString::from_raw(old) });
+}
+
+#[cfg(windows)]
+fn install() -> io::Result<()> {
+    use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+
+    // SAFETY: `handle` is a valid handler routine for as long as the process
+    // runs.
+    if unsafe { SetConsoleCtrlHandler(Some(handle), 1) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn handle(_ctrl: u32) -> windows_sys::Win32::Foundation::BOOL {
+    crate::nesting::restore_outermost();
+    // Returning false lets the next handler, which is eventually the default
+    // one that terminates the process, handle the event.
+    0
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, ffi::CStr};
+
+    fn target() -> Option<String> {
+        let dir = TARGET.load(Ordering::SeqCst);
+        // SAFETY: Nothing frees the target while the tests are serialized.
+        (!dir.is_null()).then(|| {
+            unsafe { CStr::from_ptr(dir) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    #[test]
+    #[serial]
+    fn restore_on_signal() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        super::restore_on_signal()?;
+        let cwd = env::current_dir()?;
+        assert_eq!(target(), None);
+        {
+            let _outer = Pushd::new("src")?;
+            assert_eq!(target().as_deref(), cwd.to_str());
+            let _inner = Pushd::new("..")?;
+            assert_eq!(target().as_deref(), cwd.to_str());
+        }
+        assert_eq!(target(), None);
+
+        Ok(())
+    }
+}
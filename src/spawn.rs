@@ -0,0 +1,91 @@
+// This module contains `spawn_in_dir`, which runs a closure on a new thread
+// with the current directory changed.
+
+use crate::{Pushd, PushdError};
+use std::{panic, path::Path, thread};
+
+/// Runs `f` on a new thread with the current directory changed to `path`,
+/// waits for it to finish, and returns its result. A relative `path` is
+/// resolved against the current directory.
+///
+/// On Linux, the new thread calls `unshare(CLONE_FS)` before it changes
+/// directories, like [`Pushd::new_thread_scoped`], so the change is never
+/// visible to any other thread. On other platforms, the thread holds a
+/// process-wide lock for as long as it is in `path`, so concurrent calls to
+/// this function, and tests that use the `in_dir` and `in_temp_dir` macros,
+/// run one at a time. Code that changes the current directory without this
+/// function can still see the change on those platforms.
+///
+/// If `f` panics, the panic is resumed on the calling thread.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let tmp = std::env::temp_dir();
+/// let cwd = pushd::spawn_in_dir(&tmp, || std::env::current_dir())?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This returns the same errors as [`Pushd::new`] if the thread cannot change
+/// to `path` or cannot change back afterwards. On Linux, it also returns an
+/// error if `unshare` fails.
+pub fn spawn_in_dir<P, F, T>(path: P, f: F) -> Result<T, PushdError>
+where
+    P: AsRef<Path>,
+    F: FnOnce() -> T + Send,
+    T: Send,
+{
+    let path = path.as_ref();
+    thread::scope(|s| {
+        let thread = s.spawn(move || {
+            #[cfg(not(target_os = "linux"))]
+            let _lock = crate::__private::serial_lock();
+            let builder = Pushd::builder(path);
+            #[cfg(target_os = "linux")]
+            let builder = builder.thread_scoped();
+            let mut pd = builder.push()?;
+            let res = f();
+            pd.pop()?;
+            Ok(res)
+        });
+        thread
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+
+    #[test]
+    #[serial]
+    fn spawn_in_dir() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let inside = super::spawn_in_dir("src", env::current_dir)??;
+        assert_eq!(
+            fs::canonicalize(inside)?,
+            fs::canonicalize(cwd.join("src"))?
+        );
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert!(matches!(
+            super::spawn_in_dir("no-such-dir", || ()),
+            Err(PushdError::TargetNotFound { .. }),
+        ));
+
+        let res = panic::catch_unwind(|| super::spawn_in_dir("src", || panic!("oops")));
+        assert!(res.is_err());
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
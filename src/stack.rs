@@ -0,0 +1,505 @@
+//! A directory stack, like the one used by the `pushd`, `popd`, and `dirs`
+//! shell builtins.
+//!
+//! The [`Pushd`](crate::Pushd) guard is the best fit for library code, since
+//! it always changes back when it goes out of scope. For script-like programs,
+//! especially ports of shell scripts, an explicit stack can be more natural.
+//! The [`pushd`], [`popd`], and [`dirs`] functions in this module work with a
+//! process-wide [`DirStack`] owned by this crate. A [`DirStack`] can also be
+//! created and used on its own.
+//!
+//! A stack can be converted to and from the format that shells use to list
+//! their directory stacks, so a program can pick up a stack from the shell
+//! that ran it, or hand its stack back. See [`DirStack::to_shell_format`] and
+//! [`DirStack::from_shell_format`].
+//!
+//! # Examples
+//!
+//! ```
+//! use pushd::stack;
+//!
+//! # fn main() -> Result<(), pushd::PushdError> {
+//! let orig = std::env::current_dir().unwrap();
+//! stack::pushd(std::env::temp_dir())?;
+//! assert_eq!(stack::popd()?, orig);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{chdir, chdir_error, current_dir, expand, history, report, HistoryAction, PushdError};
+use std::{
+    env,
+    panic::Location,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// The separator between the directories in a shell directory stack listing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum ShellFormat {
+    /// The directories are separated by spaces. This is the format of the
+    /// `dirs` builtin's output, and of `${DIRSTACK[*]}` in bash or
+    /// `$dirstack` in zsh.
+    Spaces,
+    /// The directories are separated by colons, like `PATH`. This format
+    /// allows spaces in directory names.
+    Colons,
+}
+
+impl ShellFormat {
+    fn separator(self) -> char {
+        match self {
+            ShellFormat::Spaces => ' ',
+            ShellFormat::Colons => ':',
+        }
+    }
+}
+
+/// A stack of directories. Each call to [`DirStack::pushd`] saves the
+/// current directory on the stack before changing directories, and each call
+/// to [`DirStack::popd`] changes back to the most recently saved directory.
+///
+/// With the `serde` feature, a stack can be serialized and deserialized, so a
+/// program can save its stack and pick it up again in a later run.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct DirStack {
+    // The saved directories, with the top of the stack at the end.
+    saved: Vec<PathBuf>,
+}
+
+impl DirStack {
+    /// Creates a new, empty stack.
+    #[must_use]
+    pub fn new() -> DirStack {
+        DirStack::default()
+    }
+
+    /// Saves the current directory on the stack and changes to `path`,
+    /// returning the new current directory.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined or
+    /// changed. In that case the stack is not changed, unless the error came from looking up the
+    /// new current directory after changing to `path`. Then the old directory is still saved on
+    /// the stack, so that [`DirStack::popd`] can change back to it.
+    #[track_caller]
+    pub fn pushd<P: AsRef<Path>>(&mut self, path: P) -> Result<PathBuf, PushdError> {
+        let path = path.as_ref();
+        let cwd = current_dir()?;
+        let caller = Location::caller();
+        if let Err(e) = chdir(path) {
+            let e = chdir_error(cwd.clone(), path.to_owned(), e);
+            report(HistoryAction::Push, &cwd, &cwd.join(path), caller, Err(&e));
+            return Err(e);
+        }
+        report(HistoryAction::Push, &cwd, &cwd.join(path), caller, Ok(()));
+        history::record_change(&cwd);
+        // The directory is saved before anything else can fail, since we've
+        // already left it.
+        self.saved.push(cwd);
+        let new = current_dir()?;
+        history::record_visit(&new);
+        Ok(new)
+    }
+
+    /// Removes the top directory from the stack and changes to it, returning
+    /// the directory that was changed to.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::EmptyStack`] error if the stack is empty. It will
+    /// also return an error if the current directory cannot be changed, in which case the
+    /// directory is left on the stack.
+    #[track_caller]
+    pub fn popd(&mut self) -> Result<PathBuf, PushdError> {
+        let top = self.saved.last().ok_or(PushdError::EmptyStack)?;
+        let cwd = current_dir()?;
+        let caller = Location::caller();
+        if let Err(e) = chdir(top) {
+            let e = chdir_error(cwd.clone(), top.clone(), e);
+            report(HistoryAction::Pop, &cwd, top, caller, Err(&e));
+            return Err(e);
+        }
+        report(HistoryAction::Pop, &cwd, top, caller, Ok(()));
+        history::record_change(&cwd);
+        history::record_visit(top);
+        Ok(self.saved.pop().unwrap_or_default())
+    }
+
+    /// Rotates the stack so that entry `n` of the listing returned by
+    /// [`DirStack::dirs`] is first, and changes to it, returning the new
+    /// current directory. Entry 0 is the current directory, so `goto(0)` does
+    /// nothing. This is what `pushd +n` does in a shell.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::NoStackEntry`] error if there is no entry `n`. It
+    /// will also return an error if the current directory cannot be determined or changed, in
+    /// which case the stack is not changed.
+    #[track_caller]
+    pub fn goto(&mut self, n: usize) -> Result<PathBuf, PushdError> {
+        let mut dirs = self.entries(n)?;
+        if n == 0 {
+            return Ok(dirs.swap_remove(0));
+        }
+        change(&dirs[0], &dirs[n], HistoryAction::Push, Location::caller())?;
+        dirs.rotate_left(n);
+        let new = dirs.remove(0);
+        dirs.reverse();
+        self.saved = dirs;
+        Ok(new)
+    }
+
+    /// Removes the first `n` entries of the listing returned by
+    /// [`DirStack::dirs`] and changes to the entry after them, returning the
+    /// new current directory. This is like calling [`DirStack::popd`] `n`
+    /// times, except that it only changes directories once. Entry 0 is the
+    /// current directory, so `pop_to(0)` does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::NoStackEntry`] error if there is no entry `n`. It
+    /// will also return an error if the current directory cannot be determined or changed, in
+    /// which case the stack is not changed.
+    #[track_caller]
+    pub fn pop_to(&mut self, n: usize) -> Result<PathBuf, PushdError> {
+        let mut dirs = self.entries(n)?;
+        if n > 0 {
+            change(&dirs[0], &dirs[n], HistoryAction::Pop, Location::caller())?;
+            self.saved.truncate(self.saved.len() - n);
+        }
+        Ok(dirs.swap_remove(n))
+    }
+
+    /// Removes entry `n` of the listing returned by [`DirStack::dirs`] from the
+    /// stack without changing directories, and returns it. Entry 0 is the
+    /// current directory, so `remove(0)` is the same as [`DirStack::popd`].
+    /// This is what `popd +n` does in a shell.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::NoStackEntry`] error if there is no entry `n`. For
+    /// `remove(0)`, it returns the same errors as [`DirStack::popd`].
+    #[track_caller]
+    pub fn remove(&mut self, n: usize) -> Result<PathBuf, PushdError> {
+        if n == 0 {
+            return self.popd();
+        }
+        let len = self.saved.len();
+        if n > len {
+            return Err(PushdError::NoStackEntry { n, len: len + 1 });
+        }
+        Ok(self.saved.remove(len - n))
+    }
+
+    // Returns the listing from `dirs`, or an error if it has no entry `n`.
+    fn entries(&self, n: usize) -> Result<Vec<PathBuf>, PushdError> {
+        if n > self.saved.len() {
+            return Err(PushdError::NoStackEntry {
+                n,
+                len: self.saved.len() + 1,
+            });
+        }
+        self.dirs()
+    }
+
+    /// Returns the saved directories, starting from the top of the stack.
+    /// This does not include the current directory. See [`DirStack::dirs`]
+    /// for a listing like the `dirs` builtin.
+    pub fn iter(&self) -> impl Iterator<Item = &Path> {
+        self.saved.iter().rev().map(PathBuf::as_path)
+    }
+
+    /// Returns the current directory followed by the saved directories,
+    /// starting from the top of the stack, like the `dirs` shell builtin.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined.
+    pub fn dirs(&self) -> Result<Vec<PathBuf>, PushdError> {
+        let mut dirs = vec![current_dir()?];
+        dirs.extend(self.iter().map(Path::to_path_buf));
+        Ok(dirs)
+    }
+
+    /// Returns the number of saved directories.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.saved.len()
+    }
+
+    /// Returns true if there are no saved directories.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.saved.is_empty()
+    }
+
+    /// Removes all of the saved directories without changing the current
+    /// directory.
+    pub fn clear(&mut self) {
+        self.saved.clear();
+    }
+
+    /// Returns a listing of the stack like the `dirs` shell builtin prints,
+    /// which is the current directory followed by the saved directories,
+    /// starting from the top of the stack. Directories under the user's home
+    /// directory are abbreviated with `~`.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined. It will
+    /// return a [`PushdError::NonUtf8Path`] error if a directory is not valid UTF-8, and a
+    /// [`PushdError::ShellFormat`] error if a directory contains the format's separator.
+    pub fn to_shell_format(&self, format: ShellFormat) -> Result<String, PushdError> {
+        let home = env::home_dir().filter(|h| h.is_absolute());
+        let mut entries = Vec::new();
+        for dir in self.dirs()? {
+            let abbreviated = match home.as_deref().map(|h| dir.strip_prefix(h)) {
+                Some(Ok(rest)) if rest.as_os_str().is_empty() => PathBuf::from("~"),
+                Some(Ok(rest)) => Path::new("~").join(rest),
+                _ => dir.clone(),
+            };
+            let Some(entry) = abbreviated.to_str() else {
+                return Err(PushdError::NonUtf8Path { path: dir });
+            };
+            if entry.contains(format.separator()) {
+                return Err(PushdError::ShellFormat { path: dir });
+            }
+            entries.push(entry.to_owned());
+        }
+        Ok(entries.join(&format.separator().to_string()))
+    }
+
+    /// Parses a listing in the format returned by
+    /// [`DirStack::to_shell_format`]. The first directory in the listing is
+    /// the shell's current directory, so it is not included in the stack. A
+    /// leading `~` is expanded to the user's home directory.
+    ///
+    /// The current directory is not changed.
+    #[must_use]
+    pub fn from_shell_format(listing: &str, format: ShellFormat) -> DirStack {
+        let mut saved = listing
+            .split(format.separator())
+            .filter(|e| !e.is_empty())
+            .skip(1)
+            .map(|e| expand::expand_tilde(PathBuf::from(e)).unwrap_or_else(|_| PathBuf::from(e)))
+            .collect::<Vec<_>>();
+        // The listing starts from the top of the stack.
+        saved.reverse();
+        DirStack { saved }
+    }
+}
+
+// Changes from one entry on the stack to another.
+fn change(
+    cwd: &Path,
+    to: &Path,
+    action: HistoryAction,
+    caller: &'static Location<'static>,
+) -> Result<(), PushdError> {
+    if let Err(e) = chdir(to) {
+        let e = chdir_error(cwd.to_owned(), to.to_owned(), e);
+        report(action, cwd, to, caller, Err(&e));
+        return Err(e);
+    }
+    report(action, cwd, to, caller, Ok(()));
+    history::record_change(cwd);
+    history::record_visit(to);
+    Ok(())
+}
+
+static STACK: Mutex<DirStack> = Mutex::new(DirStack { saved: Vec::new() });
+
+fn stack() -> MutexGuard<'static, DirStack> {
+    STACK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Calls [`DirStack::pushd`] on the process-wide stack.
+///
+/// # Errors
+///
+/// This function returns the same errors as [`DirStack::pushd`].
+#[track_caller]
+pub fn pushd<P: AsRef<Path>>(path: P) -> Result<PathBuf, PushdError> {
+    stack().pushd(path)
+}
+
+/// Calls [`DirStack::popd`] on the process-wide stack.
+///
+/// # Errors
+///
+/// This function returns the same errors as [`DirStack::popd`].
+#[track_caller]
+pub fn popd() -> Result<PathBuf, PushdError> {
+    stack().popd()
+}
+
+/// Calls [`DirStack::dirs`] on the process-wide stack.
+///
+/// # Errors
+///
+/// This function returns the same errors as [`DirStack::dirs`].
+pub fn dirs() -> Result<Vec<PathBuf>, PushdError> {
+    stack().dirs()
+}
+
+/// Returns a copy of the process-wide stack.
+#[must_use]
+pub fn global() -> DirStack {
+    stack().clone()
+}
+
+/// Replaces the process-wide stack, returning the stack it replaced. This
+/// does not change the current directory.
+#[allow(clippy::must_use_candidate)]
+pub fn set_global(new: DirStack) -> DirStack {
+    std::mem::replace(&mut *stack(), new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+
+    #[test]
+    #[serial]
+    fn dir_stack() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = current_dir()?;
+        let mut stack = DirStack::new();
+        assert_eq!(stack.pushd("src")?, cwd.join("src"));
+        assert_eq!(stack.pushd("..")?, cwd);
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.dirs()?, [cwd.clone(), cwd.join("src"), cwd.clone()]);
+
+        assert!(stack.pushd("no-such-dir").is_err());
+        assert_eq!(stack.len(), 2);
+
+        assert_eq!(stack.popd()?, cwd.join("src"));
+        assert_eq!(env::current_dir()?, cwd.join("src"));
+        assert_eq!(stack.popd()?, cwd);
+        assert!(matches!(stack.popd(), Err(PushdError::EmptyStack)));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn indexed() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = current_dir()?;
+        let (src, macros) = (cwd.join("src"), cwd.join("pushd-macros"));
+        let mut stack = DirStack::new();
+        stack.pushd("src")?;
+        stack.pushd(&macros)?;
+        assert_eq!(stack.dirs()?, [macros.clone(), src.clone(), cwd.clone()]);
+
+        assert_eq!(stack.goto(0)?, macros);
+        assert_eq!(stack.goto(2)?, cwd);
+        assert_eq!(env::current_dir()?, cwd);
+        assert_eq!(stack.dirs()?, [cwd.clone(), macros.clone(), src.clone()]);
+        assert!(matches!(
+            stack.goto(3),
+            Err(PushdError::NoStackEntry { n: 3, len: 3 }),
+        ));
+
+        assert_eq!(stack.remove(1)?, macros);
+        assert_eq!(stack.dirs()?, [cwd.clone(), src.clone()]);
+        assert!(stack.remove(2).is_err());
+
+        stack.pushd(&macros)?;
+        assert_eq!(stack.pop_to(2)?, src);
+        assert_eq!(env::current_dir()?, src);
+        assert!(stack.is_empty());
+        assert_eq!(stack.pop_to(0)?, src);
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn shell_format() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempfile::tempdir()?;
+        let home = fs::canonicalize(td.path())?;
+        fs::create_dir_all(home.join("a b/c"))?;
+        let mut env = crate::ScopedEnv::new();
+        env.set("HOME", &home)?;
+        #[cfg(windows)]
+        env.set("USERPROFILE", &home)?;
+
+        let _pd = crate::Pushd::new(&home)?;
+        let mut stack = DirStack::new();
+        stack.pushd("a b")?;
+        stack.pushd("c")?;
+
+        let colons = stack.to_shell_format(ShellFormat::Colons)?;
+        let expect = [
+            Path::new("~").join("a b/c"),
+            Path::new("~").join("a b"),
+            PathBuf::from("~"),
+        ]
+        .map(|p| p.to_string_lossy().into_owned())
+        .join(":");
+        assert_eq!(colons, expect);
+        assert!(matches!(
+            stack.to_shell_format(ShellFormat::Spaces),
+            Err(PushdError::ShellFormat { .. }),
+        ));
+
+        let parsed = DirStack::from_shell_format(&colons, ShellFormat::Colons);
+        assert_eq!(parsed, stack);
+        assert_eq!(
+            DirStack::from_shell_format("~/x ~ /tmp", ShellFormat::Spaces)
+                .iter()
+                .collect::<Vec<_>>(),
+            [home.as_path(), Path::new("/tmp")],
+        );
+
+        stack.popd()?;
+        stack.popd()?;
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn global_stack() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = current_dir()?;
+        pushd("src")?;
+        assert_eq!(dirs()?, [cwd.join("src"), cwd.clone()]);
+        assert_eq!(global().len(), 1);
+        assert_eq!(popd()?, cwd);
+        assert!(global().is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    #[serial]
+    fn serde_round_trip() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = current_dir()?;
+        let mut stack = DirStack::new();
+        stack.pushd("src")?;
+        let json = serde_json::to_string(&stack)?;
+        stack.popd()?;
+
+        let prev = set_global(serde_json::from_str(&json)?);
+        assert_eq!(popd()?, cwd);
+        set_global(prev);
+
+        Ok(())
+    }
+}
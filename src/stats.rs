@@ -0,0 +1,149 @@
+// This module keeps the counters returned by `pushd::stats`.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(0);
+static PUSHES: AtomicU64 = AtomicU64::new(0);
+static POPS: AtomicU64 = AtomicU64::new(0);
+static RESTORE_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+/// Counters for the [`Pushd`](crate::Pushd) guards in this process, as
+/// returned by [`stats`].
+///
+/// Only guards are counted. Directory changes made with a
+/// [`DirStack`](crate::DirStack) are not included.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// The number of guards that have changed directories and not yet changed
+    /// back.
+    pub active: usize,
+    /// The largest value that `active` has had.
+    pub max_depth: usize,
+    /// The number of times a guard changed into its target directory,
+    /// including when it was [re-entered](crate::Pushd::reenter).
+    pub pushes: u64,
+    /// The number of times a guard changed back to its original directory, or
+    /// to one of its [fallback directories](crate::PushdBuilder::fallback_dirs).
+    pub pops: u64,
+    /// The number of guards that could not change back to their original
+    /// directory when they were dropped.
+    pub restore_failures: u64,
+}
+
+/// Returns the current values of the counters for the [`Pushd`](crate::Pushd)
+/// guards in this process.
+///
+/// A long-running program can check this periodically, for example to alert
+/// when `restore_failures` goes up.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::Pushd;
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let _pd = Pushd::new(std::env::temp_dir())?;
+/// let stats = pushd::stats();
+/// assert_eq!(stats.active, 1);
+/// assert_eq!(stats.restore_failures, 0);
+/// # Ok(())
+/// # }
+/// ```
+#[must_use]
+pub fn stats() -> Stats {
+    Stats {
+        active: ACTIVE.load(Ordering::Relaxed),
+        max_depth: MAX_DEPTH.load(Ordering::Relaxed),
+        pushes: PUSHES.load(Ordering::Relaxed),
+        pops: POPS.load(Ordering::Relaxed),
+        restore_failures: RESTORE_FAILURES.load(Ordering::Relaxed),
+    }
+}
+
+pub(crate) fn entered() {
+    PUSHES.fetch_add(1, Ordering::Relaxed);
+    let active = ACTIVE.fetch_add(1, Ordering::Relaxed) + 1;
+    MAX_DEPTH.fetch_max(active, Ordering::Relaxed);
+}
+
+// Returns the number of guards that are in their targets.
+pub(crate) fn active() -> usize {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+pub(crate) fn popped() {
+    POPS.fetch_add(1, Ordering::Relaxed);
+}
+
+// Called when a guard stops being active, whether or not it changed back.
+pub(crate) fn exited() {
+    ACTIVE.fetch_sub(1, Ordering::Relaxed);
+}
+
+pub(crate) fn restore_failed() {
+    RESTORE_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+    #[cfg(not(target_os = "windows"))]
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn counters() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let before = stats();
+        {
+            let _pd1 = Pushd::new("src")?;
+            let mut pd2 = Pushd::new("..")?;
+            pd2.pop()?;
+            pd2.reenter()?;
+            let during = stats();
+            assert_eq!(during.active, before.active + 2);
+            assert!(during.max_depth >= before.active + 2);
+            assert_eq!(during.pushes, before.pushes + 3);
+            assert_eq!(during.pops, before.pops + 1);
+        }
+        Pushd::new("src")?.forget();
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let after = stats();
+        assert_eq!(after.active, before.active);
+        assert_eq!(after.pushes, before.pushes + 4);
+        assert_eq!(after.pops, before.pops + 3);
+        assert_eq!(after.restore_failures, before.restore_failures);
+
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn restore_failure() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let before = stats();
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        std::fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+        {
+            let _pd = Pushd::new_no_panic(td.path())?;
+            std::fs::remove_dir(&orig)?;
+        }
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let after = stats();
+        assert_eq!(after.active, before.active);
+        assert_eq!(after.restore_failures, before.restore_failures + 1);
+
+        Ok(())
+    }
+}
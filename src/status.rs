@@ -0,0 +1,171 @@
+// This module implements `Pushd::status` and `Pushd::assert_current`, which
+// check that a guard's target is still the current directory.
+
+use crate::{compare, current_dir, files::Anchor, DropBehavior, Pushd, PushdError};
+use std::path::Path;
+
+/// Whether a guard's target directory is still the current directory, as
+/// returned by [`Pushd::status`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DirStatus {
+    /// True if the current directory is the guard's target directory.
+    pub is_current: bool,
+    /// True if the target directory still exists. This is false if the
+    /// directory was deleted, even if it is still the current directory.
+    pub exists: bool,
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Returns whether the current directory is still this guard's target,
+    /// and whether the target still exists.
+    ///
+    /// On Unix, the guard opens the target the first time this or another
+    /// method needs it, and keeps it open for as long as it is alive. This
+    /// compares the device and inode numbers of the open directory with those
+    /// of the current directory. That means that once the target is open, it
+    /// is still current even if it was renamed, but not if it was replaced by
+    /// another directory at the same path. On other platforms, the
+    /// canonicalized paths are compared instead.
+    ///
+    /// This only makes a couple of `stat` calls, so it is cheap enough to call
+    /// before doing any IO with relative paths. After the guard is popped, the
+    /// target is not current, unless it is the same as the original
+    /// directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// let pd = Pushd::new(std::env::temp_dir())?;
+    /// let status = pd.status();
+    /// assert!(status.is_current && status.exists);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn status(&self) -> DirStatus {
+        dir_status(&self.anchor)
+    }
+
+    /// Checks that the current directory is still this guard's target, using
+    /// [`Pushd::status`].
+    ///
+    /// # Errors
+    ///
+    /// This returns a [`PushdError::TargetRemoved`] error if the target was
+    /// deleted, and a [`PushdError::CwdChangedExternally`] error if the
+    /// current directory is some other directory.
+    pub fn assert_current(&self) -> Result<(), PushdError> {
+        let status = self.status();
+        let path = self.anchor.path();
+        if !status.exists {
+            return Err(self.with_context(PushdError::TargetRemoved {
+                path: path.to_owned(),
+            }));
+        }
+        if !status.is_current {
+            let e = match current_dir() {
+                Ok(found) => PushdError::CwdChangedExternally {
+                    expected: path.to_owned(),
+                    found,
+                },
+                Err(e) => PushdError::GetCurrentDir { source: e },
+            };
+            return Err(self.with_context(e));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn dir_status(anchor: &Anchor) -> DirStatus {
+    #[cfg(unix)]
+    if let Some(Ok((id, exists))) = anchor.identity() {
+        use std::os::unix::fs::MetadataExt;
+
+        let is_current = std::fs::metadata(".").is_ok_and(|md| (md.dev(), md.ino()) == id);
+        return DirStatus { is_current, exists };
+    }
+    let target = anchor.path();
+    DirStatus {
+        is_current: compare::same_dir(Path::new("."), target),
+        exists: target.is_dir(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn status() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let target = td.path().join("target");
+        fs::create_dir(&target)?;
+
+        let mut pd = Pushd::new(&target)?;
+        assert_eq!(
+            pd.status(),
+            DirStatus {
+                is_current: true,
+                exists: true
+            },
+        );
+        pd.assert_current()?;
+
+        env::set_current_dir(td.path())?;
+        assert!(!pd.status().is_current);
+        assert!(matches!(
+            pd.assert_current(),
+            Err(PushdError::CwdChangedExternally { .. }),
+        ));
+        env::set_current_dir(&target)?;
+        pd.assert_current()?;
+
+        pd.pop()?;
+        assert!(!pd.status().is_current);
+
+        Ok(())
+    }
+
+    // Windows does not allow deleting the current directory.
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    #[serial]
+    fn removed() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let target = td.path().join("target");
+        fs::create_dir(&target)?;
+
+        let pd = Pushd::new(&target)?;
+        // This opens the target, so the guard can tell that it was removed.
+        pd.assert_current()?;
+        fs::remove_dir(&target)?;
+        let status = pd.status();
+        // Replacing the directory with a new one at the same path doesn't
+        // make the guard's target exist again.
+        fs::create_dir(&target)?;
+        let res = pd.assert_current();
+        drop(pd);
+
+        assert_eq!(
+            status,
+            DirStatus {
+                is_current: true,
+                exists: false
+            },
+        );
+        assert!(matches!(res, Err(PushdError::TargetRemoved { path }) if path == target));
+
+        Ok(())
+    }
+}
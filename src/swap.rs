@@ -0,0 +1,134 @@
+// This module contains `Pushd::swap`, which changes a live guard to a new
+// target.
+
+use crate::{
+    enter, events, history, lock, pwd, report, secure, DropBehavior, HistoryAction, Pushd,
+    PushdError, PwdMode,
+};
+use std::path::Path;
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Changes directly to a new target, without changing back to the
+    /// original directory first. The guard keeps its original directory, so
+    /// it still changes back there when it is popped or dropped. A relative
+    /// `path` is resolved against the current directory.
+    ///
+    /// This does everything that creating the guard did for its first target,
+    /// including the guard's security checks, lock, and
+    /// [`on_enter`](crate::PushdBuilder::on_enter) hook, and it is logged and
+    /// recorded in the [`history`](crate::history) like any other directory
+    /// change. Afterwards, [`Pushd::path`] returns the new target as an
+    /// absolute path. A [change tracker](crate::PushdBuilder::track_changes)
+    /// keeps tracking the guard's first target.
+    ///
+    /// If the guard has been popped, this only changes the target that
+    /// [`Pushd::reenter`] changes to, and a relative `path` is resolved
+    /// against the original directory. If the guard
+    /// [is a no-op](Pushd::is_noop), this does nothing.
+    ///
+    /// # Errors
+    ///
+    /// This returns the same errors as creating a guard for `path`. If it
+    /// returns an error, the guard is still in its old target.
+    pub fn swap<P: AsRef<Path>>(&mut self, path: P) -> Result<(), PushdError> {
+        self.swap_inner(path.as_ref())
+            .map_err(|e| self.with_context(e))
+    }
+
+    fn swap_inner(&mut self, path: &Path) -> Result<(), PushdError> {
+        self.sync_timeout(false);
+        if self.noop {
+            return Ok(());
+        }
+        if self.popped {
+            self.target = self.orig.join(path);
+            if let Some(pwd) = &mut self.pwd {
+                pwd.set_logical(None);
+            }
+            return Ok(());
+        }
+
+        let from = self.opts.current_dir()?;
+        let target = from.join(path);
+        let logical = match self.opts.pwd {
+            Some(PwdMode::Logical) => Some(pwd::Pwd::logical_target(&from, path)),
+            _ => None,
+        };
+        let chdir_to = logical.clone().unwrap_or_else(|| target.clone());
+        #[cfg(windows)]
+        let mapped = crate::windows::map_drive(&self.opts, &target)?;
+        #[cfg(windows)]
+        let chdir_to = mapped.as_ref().map_or(chdir_to, |m| m.path().to_owned());
+
+        let checked = match self.opts.security {
+            Some(policy) => Some(secure::check(&target, policy)?),
+            None => None,
+        };
+        let lock = lock::acquire(self.opts.lock, &target)?;
+        if let Some(hook) = &mut self.opts.on_enter {
+            hook(&target);
+        }
+        // The threads that watch the target would see the new directory as a
+        // problem until they are told about it.
+        self.set_threads_active(false);
+        if let Err(e) = enter(&self.opts, checked.as_ref(), &from, &target, &chdir_to) {
+            self.set_threads_active(true);
+            report(HistoryAction::Push, &from, &target, self.caller, Err(&e));
+            return Err(e);
+        }
+        report(HistoryAction::Push, &from, &target, self.caller, Ok(()));
+        events::swapped(&self.span, &from, &target);
+        history::record_change(&from);
+        history::record_visit(&target);
+
+        #[cfg(windows)]
+        {
+            // This unmaps the old drive, if any.
+            self.mapped = mapped;
+        }
+        if let Some(pwd) = &mut self.pwd {
+            pwd.swap(logical);
+        }
+        self.lock = lock;
+        self.anchor = self.opts.anchor(chdir_to);
+        self.target = target;
+        self.retarget_threads()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Pushd, PushdError};
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn swap() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let mut pd = Pushd::new("src")?;
+        pd.swap("../pushd-macros")?;
+        assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
+        assert_eq!(pd.path(), cwd.join("src/../pushd-macros"));
+        assert_eq!(pd.original(), cwd);
+
+        assert!(matches!(
+            pd.swap("no-such-dir"),
+            Err(PushdError::TargetNotFound { .. }),
+        ));
+        assert_eq!(env::current_dir()?, cwd.join("pushd-macros"));
+
+        pd.pop()?;
+        assert_eq!(env::current_dir()?, cwd);
+        pd.swap("src")?;
+        assert_eq!(env::current_dir()?, cwd);
+        pd.reenter()?;
+        assert_eq!(env::current_dir()?, cwd.join("src"));
+        drop(pd);
+        assert_eq!(env::current_dir()?, cwd);
+
+        Ok(())
+    }
+}
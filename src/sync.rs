@@ -0,0 +1,32 @@
+// This module contains the mutex used for the crate's process-wide lock and
+// its registry of guards. With the `parking_lot` feature it is
+// `parking_lot`'s mutex, which doesn't poison. Otherwise it is the std mutex,
+// and a poisoned lock is used anyway, since nothing that holds either lock
+// can leave the data in an inconsistent state.
+
+#[cfg(feature = "parking_lot")]
+pub(crate) use parking_lot::{Mutex, MutexGuard};
+#[cfg(not(feature = "parking_lot"))]
+pub(crate) use std::sync::{Mutex, MutexGuard};
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::{PoisonError, TryLockError};
+
+// Locks the mutex, waiting for it if necessary.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    #[cfg(feature = "parking_lot")]
+    return mutex.lock();
+    #[cfg(not(feature = "parking_lot"))]
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Locks the mutex if nothing else holds it.
+pub(crate) fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    #[cfg(feature = "parking_lot")]
+    return mutex.try_lock();
+    #[cfg(not(feature = "parking_lot"))]
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
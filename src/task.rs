@@ -0,0 +1,154 @@
+//! A task-local "virtual" current directory for async code.
+//!
+//! The process's current directory is shared by every task running in the
+//! process, so changing it in a multi-task async program is almost never what
+//! you want. This module instead tracks a current directory per task. Code
+//! running inside [`with_dir`] can call [`current_dir`] to find out which
+//! directory it should be working in and [`resolve`] to turn relative paths
+//! into absolute paths against that directory.
+//!
+//! Nothing in this module ever calls [`std::env::set_current_dir`]. Code
+//! which uses the process's current directory directly, such as
+//! [`std::fs::File::open`] with a relative path, is not affected.
+//!
+//! This module requires the `tokio` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), pushd::PushdError> {
+//! use pushd::task;
+//! use std::env;
+//!
+//! let tmp = env::temp_dir();
+//! task::with_dir(&tmp, async {
+//!     assert_eq!(task::current_dir()?, tmp);
+//!     assert_eq!(task::resolve("scratch")?, tmp.join("scratch"));
+//!     Ok::<(), pushd::PushdError>(())
+//! })
+//! .await??;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::PushdError;
+use std::{
+    env,
+    future::Future,
+    path::{Path, PathBuf},
+};
+
+tokio::task_local! {
+    static CURRENT_DIR: PathBuf;
+}
+
+/// Runs the given future with its virtual current directory set to `path`.
+///
+/// If `path` is relative, it is resolved against the virtual current
+/// directory of the calling task, as returned by [`current_dir`]. Calls to
+/// `with_dir` can be nested, and the outer directory is restored when the
+/// inner future completes.
+///
+/// The path is not checked for existence. This function never touches the
+/// filesystem unless it needs to call [`env::current_dir`] to resolve a
+/// relative path.
+///
+/// # Errors
+///
+/// This function will return an error if `path` is relative, there is no enclosing `with_dir`
+/// scope, and the process's current directory cannot be determined.
+pub async fn with_dir<P, F>(path: P, f: F) -> Result<F::Output, PushdError>
+where
+    P: AsRef<Path>,
+    F: Future,
+{
+    let dir = resolve(path)?;
+    Ok(CURRENT_DIR.scope(dir, f).await)
+}
+
+/// Returns the virtual current directory for the current task.
+///
+/// When called outside of any [`with_dir`] scope, this falls back to the
+/// process's current directory.
+///
+/// # Errors
+///
+/// This function will return an error if there is no enclosing `with_dir` scope and the
+/// process's current directory cannot be determined.
+pub fn current_dir() -> Result<PathBuf, PushdError> {
+    match CURRENT_DIR.try_with(Clone::clone) {
+        Ok(dir) => Ok(dir),
+        Err(_) => Ok(env::current_dir()?),
+    }
+}
+
+/// Resolves `path` against the virtual current directory for the current
+/// task.
+///
+/// Absolute paths are returned as-is. Relative paths are joined to the value
+/// returned by [`current_dir`]. This is a purely lexical operation, so `.` and
+/// `..` components are left in place.
+///
+/// # Errors
+///
+/// This function will return an error if `path` is relative, there is no enclosing `with_dir`
+/// scope, and the process's current directory cannot be determined.
+pub fn resolve<P: AsRef<Path>>(path: P) -> Result<PathBuf, PushdError> {
+    let path = path.as_ref();
+    if path.is_absolute() {
+        return Ok(path.to_owned());
+    }
+    Ok(current_dir()?.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::error::Error as StdError;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn nested_dirs() -> Result<(), Box<dyn StdError>> {
+        let td = tempdir()?;
+        let root = td.path().to_owned();
+
+        let (outer, inner) = with_dir(&root, async {
+            let inner = with_dir("a/b", async { current_dir() }).await;
+            (current_dir(), inner)
+        })
+        .await?;
+
+        assert_eq!(outer?, root);
+        assert_eq!(inner??, root.join("a/b"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tasks_are_independent() -> Result<(), Box<dyn StdError>> {
+        let td = tempdir()?;
+        let a = td.path().join("a");
+        let b = td.path().join("b");
+
+        let ta = tokio::spawn(with_dir(a.clone(), async { resolve("file") }));
+        let tb = tokio::spawn(with_dir(b.clone(), async { resolve("file") }));
+
+        assert_eq!(ta.await???, a.join("file"));
+        assert_eq!(tb.await???, b.join("file"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn falls_back_to_process_cwd() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        assert_eq!(current_dir()?, env::current_dir()?);
+        assert_eq!(resolve("src")?, env::current_dir()?.join("src"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+// This module sends metrics through the `metrics` facade when the `metrics`
+// feature is enabled. Without that feature, these functions do nothing.
+// See the crate docs for the list of metrics.
+
+use std::time::Instant;
+
+#[cfg(feature = "metrics")]
+pub(crate) fn pushed() {
+    metrics::counter!("pushd_pushes_total").increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn pushed() {}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn popped(entered_at: Instant) {
+    metrics::counter!("pushd_pops_total").increment(1);
+    metrics::histogram!("pushd_guard_hold_seconds").record(entered_at.elapsed());
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn popped(_entered_at: Instant) {}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn restore_failed() {
+    metrics::counter!("pushd_restore_failures_total").increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn restore_failed() {}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use crate::Pushd;
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use serial_test::serial;
+    use std::{
+        env,
+        error::Error as StdError,
+        sync::{Arc, Mutex},
+    };
+
+    // A recorder that records the name of each metric every time it is
+    // updated.
+    #[derive(Default)]
+    struct TestRecorder {
+        updates: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct Handle {
+        name: String,
+        updates: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Handle {
+        fn update(&self) {
+            self.updates.lock().unwrap().push(self.name.clone());
+        }
+    }
+
+    impl CounterFn for Handle {
+        fn increment(&self, _value: u64) {
+            self.update();
+        }
+
+        fn absolute(&self, _value: u64) {
+            self.update();
+        }
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, _value: f64) {
+            self.update();
+        }
+    }
+
+    impl TestRecorder {
+        fn handle(&self, key: &Key) -> Arc<Handle> {
+            Arc::new(Handle {
+                name: key.name().to_owned(),
+                updates: self.updates.clone(),
+            })
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.handle(key))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.handle(key))
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn emits_metrics() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let recorder = TestRecorder::default();
+        metrics::with_local_recorder(&recorder, || -> Result<(), Box<dyn StdError>> {
+            let _pd = Pushd::new("src")?;
+            Ok(())
+        })?;
+        assert_eq!(
+            *recorder.updates.lock().unwrap(),
+            [
+                "pushd_pushes_total",
+                "pushd_pops_total",
+                "pushd_guard_hold_seconds",
+            ],
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+// This module contains guards for temporary directories, which are only
+// compiled when the `temp` feature is enabled.
+
+use crate::{DropBehavior, Panicking, Pushd, PushdBuilder, PushdError};
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+use tempfile::TempDir;
+
+/// A `TempPushd` is a [`Pushd`] for a new temporary directory. When it is
+/// dropped, it changes back to the original directory and then deletes the
+/// temporary directory and everything in it.
+///
+/// Create one with [`Pushd::new_temp`], [`Pushd::new_temp_from`], or
+/// [`Pushd::temp_with`]. This requires the `temp` feature.
+#[derive(Debug)]
+pub struct TempPushd<B: DropBehavior = Panicking> {
+    // The fields are dropped in order, so we change back to the original
+    // directory before the temporary directory is deleted.
+    pushd: Pushd<B>,
+    dir: TempDir,
+}
+
+impl Pushd<Panicking> {
+    /// Creates a new temporary directory and returns a [`TempPushd`] that has
+    /// changed to it. The directory is deleted when the guard is dropped.
+    ///
+    /// This requires the `temp` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the temporary directory cannot be created. Otherwise it
+    /// returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn new_temp() -> Result<TempPushd<Panicking>, PushdError> {
+        let dir = TempDir::new().map_err(|e| PushdError::CreateTempDir { source: e })?;
+        let pushd = PushdBuilder::new(dir.path().to_owned()).push()?;
+        Ok(TempPushd { pushd, dir })
+    }
+
+    /// Creates a new temporary directory, recursively copies the contents of
+    /// the `fixture` directory into it, and returns a [`TempPushd`] that has
+    /// changed to it. The copy is deleted when the guard is dropped, so tests
+    /// can change the files in it freely without touching the fixture.
+    ///
+    /// On Unix, symlinks in the fixture are copied as symlinks, with the same
+    /// target. On other platforms, the file or directory that a symlink points
+    /// to is copied instead.
+    ///
+    /// This requires the `temp` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the temporary directory cannot be created or if
+    /// anything in the fixture cannot be copied. Otherwise it returns the same errors as
+    /// [`Pushd::new`].
+    #[track_caller]
+    pub fn new_temp_from<P: AsRef<Path>>(fixture: P) -> Result<TempPushd<Panicking>, PushdError> {
+        let dir = TempDir::new().map_err(|e| PushdError::CreateTempDir { source: e })?;
+        copy_tree(fixture.as_ref(), dir.path())?;
+        let pushd = PushdBuilder::new(dir.path().to_owned()).push()?;
+        Ok(TempPushd { pushd, dir })
+    }
+
+    /// Creates a new temporary directory, fills it with the files and
+    /// directories described by a [`Layout`], and returns a [`TempPushd`] that
+    /// has changed to it. The directory is deleted when the guard is dropped.
+    ///
+    /// The closure is passed an empty `Layout` and should return the layout
+    /// to create.
+    ///
+    /// This requires the `temp` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pushd::Pushd;
+    ///
+    /// # fn main() -> Result<(), pushd::PushdError> {
+    /// let _td = Pushd::temp_with(|l| {
+    ///     l.file("Cargo.toml", "[package]\nname = \"example\"\n")
+    ///         .dir("tests")
+    ///         .file("src/main.rs", "fn main() {}\n")
+    /// })?;
+    /// assert!(std::path::Path::new("src/main.rs").exists());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the temporary directory cannot be created or if
+    /// anything in the layout cannot be created. Otherwise it returns the same errors as
+    /// [`Pushd::new`].
+    #[track_caller]
+    pub fn temp_with<F>(layout: F) -> Result<TempPushd<Panicking>, PushdError>
+    where
+        F: FnOnce(Layout) -> Layout,
+    {
+        let dir = TempDir::new().map_err(|e| PushdError::CreateTempDir { source: e })?;
+        layout(Layout::default()).create(dir.path())?;
+        let pushd = PushdBuilder::new(dir.path().to_owned()).push()?;
+        Ok(TempPushd { pushd, dir })
+    }
+}
+
+/// A description of files and directories to create in a temporary
+/// directory, for use with [`Pushd::temp_with`].
+///
+/// All paths are relative to the temporary directory, and the parent
+/// directories of each entry are created as needed. Entries are created in
+/// the order they were added.
+///
+/// This requires the `temp` feature.
+#[derive(Debug, Default)]
+pub struct Layout {
+    entries: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl Layout {
+    /// Adds a file with the given contents.
+    #[must_use]
+    pub fn file<P: AsRef<Path>, C: AsRef<[u8]>>(mut self, path: P, contents: C) -> Self {
+        self.entries
+            .push((path.as_ref().to_owned(), Some(contents.as_ref().to_owned())));
+        self
+    }
+
+    /// Adds an empty directory.
+    #[must_use]
+    pub fn dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.entries.push((path.as_ref().to_owned(), None));
+        self
+    }
+
+    fn create(self, root: &Path) -> Result<(), PushdError> {
+        for (path, contents) in self.entries {
+            let err = |source| PushdError::CreateLayout {
+                path: path.clone(),
+                source,
+            };
+            // An absolute path or a `..` would let an entry escape the
+            // temporary directory.
+            if !path
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "layout paths must be relative and cannot contain `..`",
+                )));
+            }
+
+            let full = root.join(&path);
+            match contents {
+                Some(contents) => {
+                    if let Some(parent) = full.parent() {
+                        fs::create_dir_all(parent).map_err(err)?;
+                    }
+                    fs::write(&full, contents).map_err(err)?;
+                }
+                None => fs::create_dir_all(&full).map_err(err)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Copies the contents of the `from` directory into the `to` directory, which
+// must already exist.
+fn copy_tree(from: &Path, to: &Path) -> Result<(), PushdError> {
+    let err = |path: &Path, dest: &Path| {
+        let (path, dest) = (path.to_owned(), dest.to_owned());
+        move |source| PushdError::CopyFixture { path, dest, source }
+    };
+
+    for entry in fs::read_dir(from).map_err(err(from, to))? {
+        let entry = entry.map_err(err(from, to))?;
+        let src = entry.path();
+        let dest = to.join(entry.file_name());
+        let file_type = entry.file_type().map_err(err(&src, &dest))?;
+
+        #[cfg(unix)]
+        if file_type.is_symlink() {
+            let target = fs::read_link(&src).map_err(err(&src, &dest))?;
+            std::os::unix::fs::symlink(target, &dest).map_err(err(&src, &dest))?;
+            continue;
+        }
+
+        if is_dir(&src, file_type).map_err(err(&src, &dest))? {
+            fs::create_dir(&dest).map_err(err(&src, &dest))?;
+            copy_tree(&src, &dest)?;
+        } else {
+            fs::copy(&src, &dest).map_err(err(&src, &dest))?;
+        }
+    }
+    Ok(())
+}
+
+// Returns true if the entry is a directory or a symlink to one.
+fn is_dir(path: &Path, file_type: fs::FileType) -> io::Result<bool> {
+    if file_type.is_symlink() {
+        return Ok(fs::metadata(path)?.is_dir());
+    }
+    Ok(file_type.is_dir())
+}
+
+impl<B: DropBehavior> TempPushd<B> {
+    /// Returns the path of the temporary directory.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Returns the [`Pushd`] guard for the temporary directory.
+    #[must_use]
+    pub fn guard(&self) -> &Pushd<B> {
+        &self.pushd
+    }
+
+    /// Returns a mutable reference to the [`Pushd`] guard for the temporary
+    /// directory. You can use this to call [`Pushd::pop`] while keeping the
+    /// temporary directory around.
+    #[must_use]
+    pub fn guard_mut(&mut self) -> &mut Pushd<B> {
+        &mut self.pushd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs};
+
+    #[test]
+    #[serial]
+    fn new_temp() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let path = {
+            let td = Pushd::new_temp()?;
+            assert_eq!(
+                fs::canonicalize(env::current_dir()?)?,
+                fs::canonicalize(td.path())?,
+            );
+            td.path().to_owned()
+        };
+        assert_eq!(env::current_dir()?, cwd);
+        assert!(!path.exists());
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn new_temp_from() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let fixture = tempfile::tempdir()?;
+        fs::create_dir_all(fixture.path().join("a/b"))?;
+        fs::write(fixture.path().join("top"), "top")?;
+        fs::write(fixture.path().join("a/b/nested"), "nested")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("top", fixture.path().join("link"))?;
+
+        {
+            let td = Pushd::new_temp_from(fixture.path())?;
+            assert_ne!(td.path(), fixture.path());
+            assert_eq!(fs::read_to_string("top")?, "top");
+            assert_eq!(fs::read_to_string("a/b/nested")?, "nested");
+            #[cfg(unix)]
+            assert_eq!(fs::read_link("link")?, Path::new("top"));
+
+            fs::write("top", "changed")?;
+        }
+        assert_eq!(fs::read_to_string(fixture.path().join("top"))?, "top");
+
+        assert!(matches!(
+            Pushd::new_temp_from(fixture.path().join("missing")),
+            Err(PushdError::CopyFixture { .. }),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn temp_with() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        {
+            let _td = Pushd::temp_with(|l| {
+                l.file("Cargo.toml", "[package]")
+                    .dir("empty/dir")
+                    .file("src/main.rs", b"fn main() {}")
+            })?;
+            assert_eq!(fs::read_to_string("Cargo.toml")?, "[package]");
+            assert_eq!(fs::read_to_string("src/main.rs")?, "fn main() {}");
+            assert!(Path::new("empty/dir").is_dir());
+        }
+
+        assert!(matches!(
+            Pushd::temp_with(|l| l.file("../escape", "")),
+            Err(PushdError::CreateLayout { path, .. }) if path == Path::new("../escape"),
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+//! Helpers for testing code that changes directories.
+//!
+//! The [`FakeCwd`] backend models a directory tree and a current directory
+//! entirely in memory. Give it to the code under test with
+//! [`Pushd::with_backend`](crate::Pushd::with_backend) or
+//! [`PushdBuilder::backend`](crate::PushdBuilder::backend), and then check
+//! what the code did with [`FakeCwd::assert_visited`] and
+//! [`FakeCwd::assert_restored`]. Since the process's current directory is
+//! never changed, tests that use a `FakeCwd` don't touch the real filesystem
+//! and don't need to be run one at a time.
+//!
+//! # Examples
+//!
+//! ```
+//! use pushd::{testing::FakeCwd, Pushd};
+//!
+//! # fn main() -> Result<(), pushd::PushdError> {
+//! let fake = FakeCwd::new().with_dir("build/debug").with_dir("src");
+//! {
+//!     let _build = Pushd::with_backend(fake.clone(), "build/debug")?;
+//!     let _src = Pushd::with_backend(fake.clone(), "/src")?;
+//! }
+//! fake.assert_visited(["build/debug", "src"]);
+//! fake.assert_restored();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{normalize::normalize, CwdBackend};
+use std::{
+    collections::BTreeSet,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// A [`CwdBackend`] with an in-memory directory tree and current directory.
+///
+/// A new `FakeCwd` only has the root directory, `/`, which is also its
+/// current directory. Add directories with [`FakeCwd::with_dir`] or
+/// [`FakeCwd::create_dir_all`]. Changing to a directory that isn't in the
+/// tree returns an [`io::ErrorKind::NotFound`] error, just like the real
+/// filesystem would. Paths are resolved without following symlinks, since
+/// there are none, so `a/../b` is the same as `b`.
+///
+/// All clones of a `FakeCwd` share the same tree and current directory, so
+/// keep a clone to check what a guard did with it.
+#[derive(Clone, Debug)]
+pub struct FakeCwd {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug)]
+struct State {
+    dirs: BTreeSet<PathBuf>,
+    start: PathBuf,
+    cwd: PathBuf,
+    // The directories that something changed into, and the directories they
+    // were changed into from, for the changes that haven't been changed back.
+    open: Vec<(PathBuf, PathBuf)>,
+    visited: Vec<PathBuf>,
+}
+
+impl Default for FakeCwd {
+    fn default() -> FakeCwd {
+        FakeCwd::new()
+    }
+}
+
+impl FakeCwd {
+    /// Constructs a new `FakeCwd` whose tree only has the root directory.
+    #[must_use]
+    pub fn new() -> FakeCwd {
+        let root = PathBuf::from("/");
+        FakeCwd {
+            state: Arc::new(Mutex::new(State {
+                dirs: BTreeSet::from([root.clone()]),
+                start: root.clone(),
+                cwd: root,
+                open: Vec::new(),
+                visited: Vec::new(),
+            })),
+        }
+    }
+
+    /// Adds a directory, and every directory above it, to the tree. A
+    /// relative path is resolved against the root.
+    #[must_use]
+    pub fn with_dir<P: AsRef<Path>>(self, path: P) -> FakeCwd {
+        self.create_dir_all(path);
+        self
+    }
+
+    /// Adds a directory, and every directory above it, to the tree, and makes
+    /// it the current directory. This is the directory that
+    /// [`FakeCwd::assert_restored`] expects to end up in, and that relative
+    /// paths passed to [`FakeCwd::assert_visited`] are resolved against.
+    #[must_use]
+    pub fn starting_in<P: AsRef<Path>>(self, path: P) -> FakeCwd {
+        let path = self.create_dir_all(path);
+        let mut state = self.state();
+        state.start.clone_from(&path);
+        state.cwd = path;
+        drop(state);
+        self
+    }
+
+    /// Adds a directory, and every directory above it, to the tree, returning
+    /// its absolute path. A relative path is resolved against the root.
+    pub fn create_dir_all<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = normalize(&Path::new("/").join(path));
+        let mut state = self.state();
+        for dir in path.ancestors() {
+            state.dirs.insert(dir.to_owned());
+        }
+        path
+    }
+
+    /// Removes a directory and everything below it from the tree, returning
+    /// true if it was there. A relative path is resolved against the current
+    /// directory. This is useful for testing what happens when a guard's
+    /// original directory is deleted. The root can't be removed.
+    pub fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> bool {
+        let mut state = self.state();
+        let path = normalize(&state.cwd.join(path));
+        if path.parent().is_none() {
+            return false;
+        }
+        let before = state.dirs.len();
+        state.dirs.retain(|d| !d.starts_with(&path));
+        state.dirs.len() != before
+    }
+
+    /// Returns true if the directory is in the tree. A relative path is
+    /// resolved against the current directory.
+    #[must_use]
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        let state = self.state();
+        state.dirs.contains(&normalize(&state.cwd.join(path)))
+    }
+
+    /// Returns the current directory.
+    #[must_use]
+    pub fn cwd(&self) -> PathBuf {
+        self.state().cwd.clone()
+    }
+
+    /// Returns the directories that were changed into, as absolute paths, in
+    /// the order they were changed into. Changing back to the directory that
+    /// the newest of these was changed into from, which is what a guard does
+    /// when it is popped, is not included.
+    #[must_use]
+    pub fn visited(&self) -> Vec<PathBuf> {
+        self.state().visited.clone()
+    }
+
+    /// Panics unless the directories returned by [`FakeCwd::visited`] are
+    /// `expected`. Relative paths in `expected` are resolved against the
+    /// starting directory, which is the root unless the `FakeCwd` was created
+    /// with [`FakeCwd::starting_in`].
+    ///
+    /// # Panics
+    ///
+    /// This panics if the visited directories are different.
+    #[track_caller]
+    pub fn assert_visited<I, P>(&self, expected: I)
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let state = self.state();
+        let expected = expected
+            .into_iter()
+            .map(|p| normalize(&state.start.join(p)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            state.visited, expected,
+            "the fake current directory visited {:?}, not {expected:?}",
+            state.visited,
+        );
+    }
+
+    /// Panics unless every directory change has been changed back, so that
+    /// the current directory is the starting directory again.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the current directory isn't the starting directory, or
+    /// if something changed into a directory and never changed back.
+    #[track_caller]
+    pub fn assert_restored(&self) {
+        let state = self.state();
+        if let Some((_, to)) = state.open.last() {
+            panic!(
+                "the fake current directory was never changed back from {}",
+                to.display(),
+            );
+        }
+        assert_eq!(
+            state.cwd,
+            state.start,
+            "the fake current directory is {}, not {}",
+            state.cwd.display(),
+            state.start.display(),
+        );
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl CwdBackend for FakeCwd {
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        Ok(self.cwd())
+    }
+
+    fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.state();
+        let new = normalize(&state.cwd.join(path));
+        if !state.dirs.contains(&new) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        if state.open.last().is_some_and(|(from, _)| *from == new) {
+            state.open.pop();
+        } else {
+            let from = state.cwd.clone();
+            state.open.push((from, new.clone()));
+            state.visited.push(new.clone());
+        }
+        state.cwd = new;
+        Ok(())
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.exists(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pushd, PushdError};
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, panic};
+
+    #[test]
+    #[serial]
+    fn fake_cwd() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let fake = FakeCwd::new().with_dir("a/b").starting_in("home");
+        assert!(fake.exists("/a"));
+        assert!(!fake.exists("b"));
+
+        let mut outer = Pushd::with_backend(fake.clone(), "../a")?;
+        assert_eq!(outer.original(), Path::new("/home"));
+        {
+            let _inner = Pushd::with_backend(fake.clone(), "b/.")?;
+            assert_eq!(fake.cwd(), Path::new("/a/b"));
+            assert_eq!(env::current_dir()?, cwd);
+        }
+        assert_eq!(fake.cwd(), Path::new("/a"));
+        assert!(panic::catch_unwind(|| fake.assert_restored()).is_err());
+        outer.pop()?;
+        fake.assert_visited(["/a", "/a/b"]);
+        fake.assert_restored();
+        assert!(panic::catch_unwind(|| fake.assert_visited(["a"])).is_err());
+
+        let res = Pushd::with_backend(fake.clone(), "missing");
+        assert!(matches!(res, Err(PushdError::TargetNotFound { .. })));
+
+        let pd = Pushd::builder("/a/b")
+            .backend(fake.clone())
+            .no_panic()
+            .push()?;
+        assert!(fake.remove_dir_all("/home"));
+        assert!(!fake.remove_dir_all("/"));
+        assert!(pd.into_pop().is_err());
+        assert_eq!(fake.cwd(), Path::new("/a/b"));
+        assert!(panic::catch_unwind(|| fake.assert_restored()).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! A per-thread emulated current directory.
+//!
+//! The process's current directory is shared by every thread, so changing it
+//! in threaded code is a race. This module gives each thread its own current
+//! directory instead, which is only used to resolve relative paths. It never
+//! changes the process's current directory, so it is safe to use from as many
+//! threads as you like.
+//!
+//! A thread's directory starts out as the process's current directory the
+//! first time the thread uses this module. Change it with [`push`], which
+//! returns a guard that changes it back when it is dropped, and use it with
+//! [`current`] and [`resolve`].
+//!
+//! # Examples
+//!
+//! ```
+//! use pushd::thread_local;
+//!
+//! # fn main() -> Result<(), pushd::PushdError> {
+//! let tmp = std::env::temp_dir();
+//! let _pd = thread_local::push(&tmp)?;
+//! assert_eq!(thread_local::resolve("file")?, tmp.join("file"));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{chdir_error, current_dir, PushdError};
+use std::{
+    cell::RefCell,
+    fs,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+thread_local! {
+    static CWD: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// A guard that changes the calling thread's emulated current directory,
+/// returned by [`push`]. When it is dropped it changes the thread's directory
+/// back to what it was when the guard was created.
+///
+/// This guard is `!Send`, because it only makes sense on the thread that
+/// created it.
+#[derive(Debug)]
+#[must_use = "the thread's directory is changed back as soon as the guard is dropped"]
+pub struct ThreadPushd {
+    orig: PathBuf,
+    target: PathBuf,
+    popped: bool,
+    not_send: PhantomData<*const ()>,
+}
+
+/// Changes the calling thread's emulated current directory to `path`, which
+/// is resolved relative to the thread's current directory. The process's
+/// current directory is not changed.
+///
+/// # Errors
+///
+/// This returns the same errors that [`Pushd::new`](crate::Pushd::new) would
+/// if `path` doesn't exist, isn't a directory, or can't be read, and an error
+/// if the thread's directory has not been set yet and the process's current
+/// directory cannot be determined.
+pub fn push<P: AsRef<Path>>(path: P) -> Result<ThreadPushd, PushdError> {
+    let orig = current()?;
+    let target = orig.join(path.as_ref());
+    match fs::metadata(&target) {
+        Ok(m) if m.is_dir() => (),
+        Ok(_) => return Err(PushdError::NotADirectory { path: target }),
+        Err(e) => return Err(chdir_error(orig, path.as_ref().to_owned(), e)),
+    }
+    set(target.clone());
+    Ok(ThreadPushd {
+        orig,
+        target,
+        popped: false,
+        not_send: PhantomData,
+    })
+}
+
+/// Returns the calling thread's emulated current directory.
+///
+/// # Errors
+///
+/// This returns an error if the thread's directory has not been set yet and
+/// the process's current directory cannot be determined.
+pub fn current() -> Result<PathBuf, PushdError> {
+    if let Some(cwd) = CWD.with_borrow(Clone::clone) {
+        return Ok(cwd);
+    }
+    let cwd = current_dir()?;
+    set(cwd.clone());
+    Ok(cwd)
+}
+
+/// Resolves `path` relative to the calling thread's emulated current
+/// directory. An absolute `path` is returned as is.
+///
+/// # Errors
+///
+/// This returns an error if the thread's directory has not been set yet and
+/// the process's current directory cannot be determined.
+pub fn resolve<P: AsRef<Path>>(path: P) -> Result<PathBuf, PushdError> {
+    Ok(current()?.join(path))
+}
+
+fn set(cwd: PathBuf) {
+    CWD.set(Some(cwd));
+}
+
+impl ThreadPushd {
+    /// Returns the thread's directory from when this guard was created.
+    #[must_use]
+    pub fn original(&self) -> &Path {
+        &self.orig
+    }
+
+    /// Returns the directory this guard changed the thread's directory to.
+    #[must_use]
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Changes the thread's directory back to what it was when this guard
+    /// was created. After this is called, dropping the guard does nothing.
+    pub fn pop(&mut self) {
+        if !self.popped {
+            set(self.orig.clone());
+            self.popped = true;
+        }
+    }
+}
+
+impl Drop for ThreadPushd {
+    fn drop(&mut self) {
+        self.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, thread};
+
+    #[test]
+    #[serial]
+    fn per_thread() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let handles = ["src", "pushd-macros"].map(|dir| {
+            thread::spawn(move || -> Result<_, PushdError> {
+                let start = current()?;
+                let pd = push(dir)?;
+                let inside = resolve("Cargo.toml")?;
+                drop(pd);
+                Ok((start, inside, current()?))
+            })
+        });
+        for (handle, dir) in handles.into_iter().zip(["src", "pushd-macros"]) {
+            let (start, inside, end) = handle.join().unwrap()?;
+            assert_eq!(start, cwd);
+            assert_eq!(inside, cwd.join(dir).join("Cargo.toml"));
+            assert_eq!(end, cwd);
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert!(matches!(
+            push("no-such-dir"),
+            Err(PushdError::TargetNotFound { .. }),
+        ));
+        assert!(matches!(
+            push("Cargo.toml"),
+            Err(PushdError::NotADirectory { .. }),
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+// This module implements guards with a timeout. A guard with a timeout has a
+// thread that changes back to the original directory if the guard is still in
+// its target once the timeout is up. The guard finds out that this happened
+// the next time it is used.
+
+use crate::{chdir, events, DropBehavior, Panicking, Pushd, PushdBuilder, PushdError};
+use std::{
+    panic::Location,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` that changes back to its original directory
+    /// on its own if it is still in its target after `timeout`. See
+    /// [`PushdBuilder::timeout`] for details.
+    ///
+    /// # Errors
+    ///
+    /// This returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn new_with_timeout<P: AsRef<Path>>(
+        path: P,
+        timeout: Duration,
+    ) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .timeout(timeout)
+            .push()
+    }
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`] start a thread that changes back to the
+    /// original directory if the guard is still in its target `timeout` after
+    /// it was created, and logs a warning when it does. After that, the guard
+    /// is [popped](Pushd::is_popped), so dropping it does nothing.
+    ///
+    /// This bounds the damage done by a guard that is leaked, or held by code
+    /// that is stuck, in a long-running process. The thread only gets one
+    /// chance, so if the guard is popped and [re-entered](Pushd::reenter), it
+    /// is only changed back if the timeout hasn't been reached yet.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.opts.timeout = Some(timeout);
+        self
+    }
+}
+
+struct State {
+    // Whether the guard is in its target.
+    active: bool,
+    // Whether the thread changed back to the original directory.
+    fired: bool,
+}
+
+pub(crate) struct Timer {
+    // The thread exits when this is dropped.
+    tx: Option<mpsc::Sender<()>>,
+    state: Arc<Mutex<State>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Timer {
+    pub(crate) fn start(
+        orig: PathBuf,
+        caller: &'static Location<'static>,
+        timeout: Duration,
+        span: events::Span,
+    ) -> Timer {
+        let (tx, rx) = mpsc::channel::<()>();
+        let state = Arc::new(Mutex::new(State {
+            active: true,
+            fired: false,
+        }));
+        let thread = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                if rx.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout) {
+                    return;
+                }
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                if !state.active {
+                    return;
+                }
+                match chdir(&orig) {
+                    Ok(()) => {
+                        state.fired = true;
+                        events::timed_out(&span, &orig, caller, timeout);
+                    }
+                    Err(e) => events::timeout_failed(&span, &orig, caller, &e),
+                }
+            })
+        };
+        Timer {
+            tx: Some(tx),
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    // Tells the thread whether the guard is in its target, and returns true
+    // if the thread has already changed back.
+    pub(crate) fn set_active(&self, active: bool) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.active = active;
+        state.fired
+    }
+
+    pub(crate) fn fired(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .fired
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    // If the guard's timer changed back to the original directory, this
+    // finishes popping the guard. If `leaving` is true, the guard is about to
+    // change back itself, so this also makes sure that the timer won't.
+    pub(crate) fn sync_timeout(&mut self, leaving: bool) {
+        let Some(timer) = &mut self.timer else {
+            return;
+        };
+        let fired = if leaving {
+            timer.set_active(false)
+        } else {
+            timer.fired()
+        };
+        if !fired || self.popped {
+            return;
+        }
+        // The timer only fires once.
+        self.timer = None;
+        self.set_threads_active(false);
+        if let Some(pwd) = &self.pwd {
+            pwd.restore();
+        }
+        if let Some(hook) = &mut self.opts.on_exit {
+            hook(&self.orig);
+        }
+        self.finish_pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, time::Instant};
+
+    #[test]
+    #[serial]
+    fn timeout() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let mut pd = Pushd::new_with_timeout("src", Duration::from_millis(10))?;
+        let start = Instant::now();
+        while env::current_dir()? != cwd {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "the guard did not change back",
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(pd.is_popped());
+        pd.pop()?;
+
+        // After a pop, the timer does nothing.
+        let mut pd = Pushd::new_with_timeout("src", Duration::from_millis(10))?;
+        pd.pop()?;
+        env::set_current_dir("src")?;
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(env::current_dir()?, cwd.join("src"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+// This module contains the `typed-path` integration, which is only compiled
+// when the `typed-path` feature is enabled.
+
+use crate::{Panicking, Pushd, PushdBuilder, PushdError};
+use std::path::PathBuf;
+use typed_path::{Utf8Component, Utf8Encoding, Utf8NativeEncoding, Utf8Path};
+
+/// Converts a [`typed_path::Utf8Path`], like a
+/// [`Utf8UnixPath`](typed_path::Utf8UnixPath) or a
+/// [`Utf8WindowsPath`](typed_path::Utf8WindowsPath), to a native path.
+///
+/// A path with this platform's syntax is returned as is. A path with another
+/// platform's syntax is converted, but only if it is relative, since an
+/// absolute path for another platform doesn't refer to anything on this one.
+/// For example, on Linux, `src\bin` is converted to `src/bin`, but
+/// `C:\src\bin` and `\src\bin` are rejected.
+///
+/// This requires the `typed-path` feature.
+///
+/// # Examples
+///
+/// ```
+/// use typed_path::{Utf8UnixPath, Utf8WindowsPath};
+///
+/// # fn main() -> Result<(), pushd::PushdError> {
+/// let path = pushd::to_native_path(Utf8WindowsPath::new(r"src\bin"))?;
+/// assert_eq!(path, std::path::Path::new("src").join("bin"));
+///
+/// #[cfg(unix)]
+/// assert!(pushd::to_native_path(Utf8WindowsPath::new(r"C:\src")).is_err());
+/// #[cfg(windows)]
+/// assert!(pushd::to_native_path(Utf8UnixPath::new("/src")).is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// This returns a [`PushdError::ForeignPath`] error if `path` uses another
+/// platform's syntax and is absolute, or has a root or a prefix like a drive
+/// letter, or has a file name that is not valid on this platform.
+pub fn to_native_path<T: Utf8Encoding>(path: &Utf8Path<T>) -> Result<PathBuf, PushdError> {
+    if T::label() == Utf8NativeEncoding::label() {
+        return Ok(PathBuf::from(path.as_str()));
+    }
+    let foreign = |reason: String| PushdError::ForeignPath {
+        path: path.as_str().to_owned(),
+        encoding: T::label(),
+        reason,
+    };
+    if !path
+        .components()
+        .all(|c| c.is_normal() || c.is_current() || c.is_parent())
+    {
+        return Err(foreign(
+            "only relative paths can be converted from another platform".to_string(),
+        ));
+    }
+    let native = path
+        .with_encoding_checked::<Utf8NativeEncoding>()
+        .map_err(|e| foreign(e.to_string()))?;
+    Ok(PathBuf::from(native.as_str()))
+}
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct from a [`typed_path::Utf8Path`]. The
+    /// path is converted to a native path with [`to_native_path`] first.
+    ///
+    /// Other than the conversion, this behaves exactly like [`Pushd::new`].
+    /// This requires the `typed-path` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return a [`PushdError::ForeignPath`] error if the path cannot be
+    /// converted. Otherwise it returns the same errors as [`Pushd::new`].
+    #[track_caller]
+    pub fn new_typed<T: Utf8Encoding>(path: &Utf8Path<T>) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(to_native_path(path)?).push()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+    use typed_path::{Utf8UnixPath, Utf8WindowsPath};
+
+    #[test]
+    #[serial]
+    fn new_typed() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        for path in [r"pushd-macros\src", r".\pushd-macros\..\pushd-macros\src"] {
+            let _pd = Pushd::new_typed(Utf8WindowsPath::new(path))?;
+            assert_eq!(env::current_dir()?, cwd.join("pushd-macros").join("src"));
+        }
+        {
+            let _pd = Pushd::new_typed(Utf8UnixPath::new("pushd-macros/src"))?;
+            assert_eq!(env::current_dir()?, cwd.join("pushd-macros").join("src"));
+        }
+        assert_eq!(env::current_dir()?, cwd);
+
+        #[cfg(unix)]
+        for path in [r"C:\Windows", r"\Windows", r"C:Windows", r"\\server\share"] {
+            assert!(
+                matches!(
+                    Pushd::new_typed(Utf8WindowsPath::new(path)),
+                    Err(PushdError::ForeignPath {
+                        encoding: "windows",
+                        ..
+                    }),
+                ),
+                "{path}",
+            );
+        }
+        #[cfg(unix)]
+        assert!(matches!(
+            to_native_path(Utf8WindowsPath::new("a\0b")),
+            Err(PushdError::ForeignPath { .. }),
+        ));
+        #[cfg(windows)]
+        for path in ["/tmp", "a|b"] {
+            assert!(
+                matches!(
+                    Pushd::new_typed(Utf8UnixPath::new(path)),
+                    Err(PushdError::ForeignPath {
+                        encoding: "unix",
+                        ..
+                    }),
+                ),
+                "{path}",
+            );
+        }
+
+        Ok(())
+    }
+}
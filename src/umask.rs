@@ -0,0 +1,131 @@
+// This module contains the `ScopedUmask` guard, which is only available on
+// Unix.
+
+use crate::{DropBehavior, NonPanicking, Panicking};
+use std::marker::PhantomData;
+
+/// A `ScopedUmask` sets the process's file mode creation mask when it's
+/// created and restores the previous mask when it's dropped.
+///
+/// Like [`Pushd`](crate::Pushd), the `B` type parameter records whether the
+/// guard may panic when it is dropped. Restoring the mask cannot fail, so in
+/// practice a `ScopedUmask` never panics, but the parameter lets it be used
+/// anywhere that requires a [`NonPanicking`] guard.
+///
+/// Note that the umask is shared by every thread in the process.
+///
+/// This is only available on Unix.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::ScopedUmask;
+///
+/// let _umask = ScopedUmask::new(0o077);
+/// // Files created here are only accessible by their owner.
+/// ```
+pub struct ScopedUmask<B: DropBehavior = Panicking> {
+    previous: u32,
+    mask: u32,
+    restored: bool,
+    behavior: PhantomData<B>,
+}
+
+impl ScopedUmask<Panicking> {
+    /// Sets the umask to the given value and returns a guard that restores the
+    /// previous value when it is dropped. Only the permission bits of the
+    /// mask, `0o777`, are used.
+    #[must_use]
+    pub fn new(mask: u32) -> ScopedUmask<Panicking> {
+        ScopedUmask::set(mask)
+    }
+}
+
+impl ScopedUmask<NonPanicking> {
+    /// Sets the umask to the given value and returns a guard that restores the
+    /// previous value when it is dropped, and that will never panic.
+    #[must_use]
+    pub fn new_no_panic(mask: u32) -> ScopedUmask<NonPanicking> {
+        ScopedUmask::set(mask)
+    }
+}
+
+impl<B: DropBehavior> ScopedUmask<B> {
+    fn set(mask: u32) -> ScopedUmask<B> {
+        let mask = mask & 0o777;
+        ScopedUmask {
+            previous: umask(mask),
+            mask,
+            restored: false,
+            behavior: PhantomData,
+        }
+    }
+
+    /// Returns the umask that this guard set.
+    #[must_use]
+    pub fn mask(&self) -> u32 {
+        self.mask
+    }
+
+    /// Returns the umask that was in effect when this guard was created, which
+    /// it will restore.
+    #[must_use]
+    pub fn previous(&self) -> u32 {
+        self.previous
+    }
+
+    /// Restores the previous umask the first time it is called. If this method
+    /// is called repeatedly it will not do anything on subsequent calls.
+    pub fn restore(&mut self) {
+        if !self.restored {
+            umask(self.previous);
+            self.restored = true;
+        }
+    }
+}
+
+impl<B: DropBehavior> Drop for ScopedUmask<B> {
+    /// Restores the previous umask.
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+// Sets the umask, returning the previous value.
+#[allow(clippy::cast_possible_truncation, clippy::useless_conversion)]
+fn umask(mask: u32) -> u32 {
+    // SAFETY: `umask` always succeeds and has no memory safety requirements.
+    u32::from(unsafe { libc::umask(mask as libc::mode_t) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{error::Error as StdError, fs, os::unix::fs::PermissionsExt};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn scoped_umask() -> Result<(), Box<dyn StdError>> {
+        let orig = umask(0o022);
+        let td = tempdir()?;
+
+        {
+            let umask = ScopedUmask::new(0o077);
+            assert_eq!(umask.previous(), 0o022);
+            fs::write(td.path().join("private"), "")?;
+        }
+        fs::write(td.path().join("public"), "")?;
+
+        let mode = |name| -> Result<u32, Box<dyn StdError>> {
+            Ok(fs::metadata(td.path().join(name))?.permissions().mode() & 0o777)
+        };
+        assert_eq!(mode("private")?, 0o600);
+        assert_eq!(mode("public")?, 0o644);
+
+        umask(orig);
+
+        Ok(())
+    }
+}
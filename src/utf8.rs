@@ -0,0 +1,155 @@
+// This module contains the `camino` integration, which is only compiled when
+// the `camino` feature is enabled.
+
+use crate::{DropBehavior, NonPanicking, Panicking, Pushd, PushdBuilder, PushdError};
+use camino::Utf8Path;
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct from a UTF-8 path.
+    ///
+    /// This is the equivalent of [`Pushd::new`], except that it also checks
+    /// that the original directory is valid UTF-8. That guarantees that
+    /// [`Pushd::utf8_path`] and [`Pushd::utf8_original`] return `Some`.
+    ///
+    /// This requires the `camino` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined or if it is
+    /// not valid UTF-8. It will also return an error if the current directory cannot be changed.
+    #[track_caller]
+    pub fn new_utf8<P: AsRef<Utf8Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().as_std_path().to_owned())
+            .require_utf8()
+            .push()
+    }
+}
+
+impl Pushd<NonPanicking> {
+    /// Constructs a new `Pushd` struct from a UTF-8 path that will never
+    /// panic.
+    ///
+    /// This is the equivalent of [`Pushd::new_no_panic`], except that it also
+    /// checks that the original directory is valid UTF-8. That guarantees that
+    /// [`Pushd::utf8_path`] and [`Pushd::utf8_original`] return `Some`.
+    ///
+    /// This requires the `camino` feature.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the current directory cannot be determined or if it is
+    /// not valid UTF-8. It will also return an error if the current directory cannot be changed.
+    #[track_caller]
+    pub fn new_utf8_no_panic<P: AsRef<Utf8Path>>(
+        path: P,
+    ) -> Result<Pushd<NonPanicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().as_std_path().to_owned())
+            .require_utf8()
+            .no_panic()
+            .push()
+    }
+}
+
+impl<B: DropBehavior> Pushd<B> {
+    /// Returns the path that this `Pushd` changed to as a [`Utf8Path`], or
+    /// `None` if it is not valid UTF-8.
+    ///
+    /// This requires the `camino` feature.
+    #[must_use]
+    pub fn utf8_path(&self) -> Option<&Utf8Path> {
+        Utf8Path::from_path(self.path())
+    }
+
+    /// Returns the original directory that this `Pushd` will change back to
+    /// as a [`Utf8Path`], or `None` if it is not valid UTF-8.
+    ///
+    /// This requires the `camino` feature.
+    #[must_use]
+    pub fn utf8_original(&self) -> Option<&Utf8Path> {
+        Utf8Path::from_path(self.original())
+    }
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Requires the original directory to be valid UTF-8. If it is not, then
+    /// [`PushdBuilder::push`] returns a [`PushdError::NonUtf8Path`] error
+    /// without changing directories.
+    ///
+    /// This requires the `camino` feature.
+    #[must_use]
+    pub fn require_utf8(mut self) -> Self {
+        self.opts.require_utf8 = true;
+        self
+    }
+}
+
+impl PushdError {
+    /// Returns the path associated with this error as a [`Utf8Path`]. This
+    /// returns `None` if the error does not have an associated path or if the
+    /// path is not valid UTF-8.
+    ///
+    /// This requires the `camino` feature.
+    #[must_use]
+    pub fn utf8_path(&self) -> Option<&Utf8Path> {
+        let path = match self {
+            PushdError::SetCurrentDir { path, .. }
+            | PushdError::TargetNotFound { path, .. }
+            | PushdError::PermissionDenied { path, .. }
+            | PushdError::RestoreFailed { path, .. }
+            | PushdError::Metadata { path, .. }
+            | PushdError::NotADirectory { path }
+            | PushdError::TargetRemoved { path }
+            | PushdError::Locked { path }
+            | PushdError::Lock { path, .. }
+            | PushdError::OpenDir { path, .. }
+            | PushdError::NonUtf8Path { path }
+            | PushdError::RelativeTarget { path }
+            | PushdError::ParentDir { path } => path,
+            #[cfg(unix)]
+            PushdError::Chroot { path, .. } | PushdError::RestoreRoot { path, .. } => path,
+            PushdError::CwdChangedExternally { found, .. } => found,
+            _ => return None,
+        };
+        Utf8Path::from_path(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camino::Utf8PathBuf;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn utf8_accessors() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = Utf8PathBuf::try_from(env::current_dir()?)?;
+
+        let td = tempdir()?;
+        let path = Utf8PathBuf::try_from(td.path().to_owned())?;
+        let pd = Pushd::new_utf8(&path)?;
+        assert_eq!(pd.utf8_path(), Some(path.as_path()));
+        assert_eq!(pd.utf8_original(), Some(cwd.as_path()));
+
+        Ok(())
+    }
+
+    #[test]
+    #[serial]
+    fn utf8_error_path() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let res = Pushd::new_utf8_no_panic(Utf8Path::new("does-not-exist"));
+        assert_eq!(
+            res.err()
+                .and_then(|e| e.utf8_path().map(Utf8Path::to_owned)),
+            Some(Utf8PathBuf::from("does-not-exist")),
+        );
+
+        Ok(())
+    }
+}
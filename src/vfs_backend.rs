@@ -0,0 +1,152 @@
+// This module contains the `vfs` crate integration, which is only compiled
+// when the `vfs` feature is enabled.
+
+use crate::CwdBackend;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+use vfs::{error::VfsErrorKind, VfsError, VfsPath};
+
+/// A [`CwdBackend`] with a current directory in a [`vfs`] filesystem, like a
+/// [`vfs::MemoryFS`] or [`vfs::OverlayFS`].
+///
+/// The filesystems in the `vfs` crate don't have a current directory, so this
+/// keeps track of one. It starts at the filesystem's root. All clones of a
+/// `VfsBackend` share the same current directory, so you can keep a clone to
+/// see where a guard is. This requires the `vfs` feature.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::{Pushd, VfsBackend};
+/// use vfs::{MemoryFS, VfsPath};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let root = VfsPath::new(MemoryFS::new());
+/// root.join("src")?.create_dir()?;
+///
+/// let backend = VfsBackend::new(&root);
+/// let pd = Pushd::with_backend(backend.clone(), "/src")?;
+/// assert_eq!(backend.cwd().as_str(), "/src");
+/// drop(pd);
+/// assert!(backend.cwd().is_root());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct VfsBackend {
+    cwd: Arc<Mutex<VfsPath>>,
+}
+
+impl VfsBackend {
+    /// Constructs a new `VfsBackend` whose current directory is the root of
+    /// the filesystem that `path` belongs to.
+    #[must_use]
+    pub fn new(path: &VfsPath) -> Self {
+        Self {
+            cwd: Arc::new(Mutex::new(path.root())),
+        }
+    }
+
+    /// Returns the current directory.
+    #[must_use]
+    pub fn cwd(&self) -> VfsPath {
+        self.cwd
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl CwdBackend for VfsBackend {
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        let cwd = self.cwd();
+        Ok(if cwd.is_root() {
+            PathBuf::from("/")
+        } else {
+            PathBuf::from(cwd.as_str())
+        })
+    }
+
+    fn set_current_dir(&self, path: &Path) -> io::Result<()> {
+        let Some(path) = path.to_str() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "vfs paths must be UTF-8",
+            ));
+        };
+        let mut cwd = self.cwd.lock().unwrap_or_else(PoisonError::into_inner);
+        let new = cwd.join(path).map_err(io_error)?;
+        if !new.exists().map_err(io_error)? {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        if !new.is_dir().map_err(io_error)? {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        *cwd = new;
+        Ok(())
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        let Some(path) = path.to_str() else {
+            return false;
+        };
+        self.cwd()
+            .join(path)
+            .and_then(|p| p.is_dir())
+            .unwrap_or(false)
+    }
+}
+
+fn io_error(e: VfsError) -> io::Error {
+    let kind = match e.kind() {
+        VfsErrorKind::IoError(e) => e.kind(),
+        VfsErrorKind::FileNotFound => io::ErrorKind::NotFound,
+        VfsErrorKind::InvalidPath => io::ErrorKind::InvalidInput,
+        VfsErrorKind::NotSupported => io::ErrorKind::Unsupported,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pushd, PushdError};
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+    use vfs::MemoryFS;
+
+    #[test]
+    #[serial]
+    fn memory_fs() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let root = VfsPath::new(MemoryFS::new());
+        root.join("a/b")?.create_dir_all()?;
+        root.join("file")?.create_file()?;
+        let backend = VfsBackend::new(&root);
+
+        let mut outer = Pushd::with_backend(backend.clone(), "/a")?;
+        assert_eq!(outer.original(), Path::new("/"));
+        {
+            let _inner = Pushd::with_backend(backend.clone(), "b")?;
+            assert_eq!(backend.cwd().as_str(), "/a/b");
+            assert_eq!(env::current_dir()?, cwd);
+        }
+        assert_eq!(backend.cwd().as_str(), "/a");
+        outer.pop()?;
+        assert!(backend.cwd().is_root());
+
+        let res = Pushd::with_backend(backend.clone(), "missing");
+        assert!(matches!(res, Err(PushdError::TargetNotFound { .. })));
+        let res = Pushd::with_backend(backend.clone(), "file");
+        assert!(res.is_err_and(|e| e.is_not_a_directory()));
+        assert!(backend.cwd().is_root());
+
+        Ok(())
+    }
+}
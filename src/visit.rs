@@ -0,0 +1,200 @@
+// This module contains the helpers which run a closure in each of several
+// directories in turn, either from a list or, with the `walkdir` feature, from
+// a recursive walk of a directory tree.
+
+use crate::{Pushd, PushdError};
+use std::path::{Path, PathBuf};
+
+/// Runs `f` once in each of the given directories, in order, and returns the
+/// result for each directory.
+///
+/// For each directory, this changes to it, calls `f` with the path as it was
+/// given, and changes back before moving on to the next one, even if `f`
+/// returns an error or panics. If changing to a directory or back fails, the
+/// [`PushdError`] is converted to `E` and recorded as the result for that
+/// directory, and this moves on to the next one.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::process::Command;
+///
+/// let results = pushd::visit_each(["checkouts/a", "checkouts/b"], |_| {
+///     Command::new("git").arg("pull").status()?;
+///     Ok::<_, Box<dyn std::error::Error>>(())
+/// });
+/// for (dir, res) in results {
+///     if let Err(e) = res {
+///         eprintln!("{}: {e}", dir.display());
+///     }
+/// }
+/// ```
+pub fn visit_each<I, P, F, T, E>(dirs: I, mut f: F) -> Vec<(PathBuf, Result<T, E>)>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+    F: FnMut(&Path) -> Result<T, E>,
+    E: From<PushdError>,
+{
+    dirs.into_iter()
+        .map(|dir| {
+            let dir = dir.as_ref();
+            (dir.to_owned(), visit(dir, &mut f))
+        })
+        .collect()
+}
+
+/// Recursively walks the tree under `root`, including `root` itself, and runs
+/// `f` in each directory for which `filter` returns true. This returns the
+/// result for each directory that `f` ran in, in the order they were visited.
+///
+/// Directories are visited in depth-first order, with the entries of each
+/// directory sorted by name. Symlinks are not followed. The paths passed to
+/// `filter` and `f` are `root` joined with the path of the directory under it.
+/// Each directory is visited like it is by [`visit_each`], so the current
+/// directory is restored after each call to `f`.
+///
+/// If part of the tree cannot be read, a [`PushdError::WalkTree`] error
+/// converted to `E` is recorded for that path, and the walk continues.
+///
+/// This requires the `walkdir` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::process::Command;
+///
+/// // Run `cargo fmt` in every directory under `crates` with a `Cargo.toml`.
+/// let results = pushd::visit_tree(
+///     "crates",
+///     |dir| dir.join("Cargo.toml").is_file(),
+///     |_| {
+///         Command::new("cargo").arg("fmt").status()?;
+///         Ok::<_, Box<dyn std::error::Error>>(())
+///     },
+/// );
+/// ```
+#[cfg(feature = "walkdir")]
+pub fn visit_tree<P, G, F, T, E>(root: P, mut filter: G, mut f: F) -> Vec<(PathBuf, Result<T, E>)>
+where
+    P: AsRef<Path>,
+    G: FnMut(&Path) -> bool,
+    F: FnMut(&Path) -> Result<T, E>,
+    E: From<PushdError>,
+{
+    let walk = walkdir::WalkDir::new(root.as_ref())
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Ok(e) if e.file_type().is_dir() => Some(Ok(e.into_path())),
+            Ok(_) => None,
+            Err(e) => Some(Err(e)),
+        });
+
+    let mut results = Vec::new();
+    for entry in walk {
+        match entry {
+            Ok(dir) if filter(&dir) => {
+                let res = visit(&dir, &mut f);
+                results.push((dir, res));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                let path = e.path().unwrap_or(root.as_ref()).to_owned();
+                let err = PushdError::WalkTree {
+                    path: path.clone(),
+                    source: e.into(),
+                };
+                results.push((path, Err(err.into())));
+            }
+        }
+    }
+    results
+}
+
+// Runs `f` in a single directory. The guard doesn't panic on drop, so a panic
+// in `f` can't turn into a double panic if changing back fails too.
+fn visit<F, T, E>(dir: &Path, f: &mut F) -> Result<T, E>
+where
+    F: FnMut(&Path) -> Result<T, E>,
+    E: From<PushdError>,
+{
+    let mut pd = Pushd::new_no_panic(dir)?;
+    let res = f(dir);
+    pd.pop()?;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError};
+
+    #[test]
+    #[serial]
+    fn visit_each() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let results = super::visit_each(["src", "no-such-dir", "pushd-macros"], |dir| {
+            assert_eq!(env::current_dir()?, cwd.join(dir));
+            if dir == Path::new("pushd-macros") {
+                return Err("failed".into());
+            }
+            Ok::<_, Box<dyn StdError>>(dir.to_owned())
+        });
+        assert_eq!(env::current_dir()?, cwd);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, Path::new("src"));
+        assert_eq!(results[0].1.as_deref().ok(), Some(Path::new("src")));
+        assert!(results[1]
+            .1
+            .as_ref()
+            .is_err_and(|e| e.downcast_ref::<PushdError>().is_some()));
+        assert_eq!(
+            results[2].1.as_ref().map_err(ToString::to_string).err(),
+            Some("failed".to_owned()),
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "walkdir")]
+    #[test]
+    #[serial]
+    fn visit_tree() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let td = tempfile::tempdir()?;
+        for dir in ["a/x", "b", "c/y/z"] {
+            std::fs::create_dir_all(td.path().join(dir))?;
+        }
+        for file in ["Cargo.toml", "a/x/Cargo.toml", "c/y/Cargo.toml"] {
+            std::fs::write(td.path().join(file), "")?;
+        }
+
+        let results = super::visit_tree(
+            td.path(),
+            |dir| dir.join("Cargo.toml").is_file(),
+            |dir| {
+                assert_eq!(
+                    std::fs::canonicalize(env::current_dir()?)?,
+                    std::fs::canonicalize(dir)?,
+                );
+                Ok::<_, Box<dyn StdError>>(dir.strip_prefix(td.path())?.to_owned())
+            },
+        );
+        assert_eq!(env::current_dir()?, cwd);
+
+        let visited = results
+            .into_iter()
+            .map(|(_, res)| res)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(visited, [Path::new(""), Path::new("a/x"), Path::new("c/y")],);
+
+        Ok(())
+    }
+}
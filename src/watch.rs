@@ -0,0 +1,311 @@
+// This module implements the watchdog for the `notify` feature. The watchdog
+// is a thread that checks a guard's target each time `notify` reports that
+// something happened to it. There's no way to be notified when the current
+// directory changes, so the thread also checks the target every
+// `POLL_INTERVAL`.
+
+use crate::{current_dir, events, files::Anchor, status, DropBehavior, PushdBuilder, PushdError};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::Duration,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A problem with a guard's target directory that was found by its
+/// [watchdog](PushdBuilder::watchdog).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WatchEvent {
+    /// The target directory was deleted.
+    Removed { path: PathBuf },
+    /// The target directory was renamed or moved, so `path` no longer refers
+    /// to it. This is only reported on Unix.
+    Moved { path: PathBuf },
+    /// Something other than the guard changed the current directory from the
+    /// target to `found`.
+    CwdChanged { expected: PathBuf, found: PathBuf },
+}
+
+impl fmt::Display for WatchEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchEvent::Removed { path } => {
+                write!(f, "The directory {} was deleted", path.display())
+            }
+            WatchEvent::Moved { path } => {
+                write!(f, "The directory {} was moved or renamed", path.display())
+            }
+            WatchEvent::CwdChanged { expected, found } => write!(
+                f,
+                "The current directory was changed externally: expected {} but found {}",
+                expected.display(),
+                found.display(),
+            ),
+        }
+    }
+}
+
+pub(crate) type WatchCallback = Box<dyn FnMut(WatchEvent) + Send>;
+
+// What the watchdog does with each event.
+pub(crate) enum Watch {
+    Log,
+    Callback(WatchCallback),
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// Makes the resulting [`Pushd`](crate::Pushd) start a watchdog thread
+    /// that logs a warning as soon as the guard's target is deleted or moved,
+    /// or something else changes the current directory, while the guard is
+    /// in its target. Without this, these problems usually only show up later
+    /// as a confusing "not found" error from unrelated code.
+    ///
+    /// The thread is notified of changes to the target with the
+    /// [`notify`](https://docs.rs/notify) crate, and it also checks the
+    /// current directory four times a second. A change made by another
+    /// guard is reported like any other change, so this is best used with a
+    /// guard that nothing else nests inside of. The thread is stopped when the
+    /// guard is dropped.
+    ///
+    /// This requires the `notify` feature.
+    #[must_use]
+    pub fn watchdog(mut self) -> Self {
+        self.opts.watch = Some(Watch::Log);
+        self
+    }
+
+    /// Like [`PushdBuilder::watchdog`], but the watchdog calls the closure
+    /// with each [`WatchEvent`] instead of logging it. The closure is called
+    /// on the watchdog's thread. Each problem is only reported once, until it
+    /// goes away.
+    ///
+    /// This requires the `notify` feature.
+    #[must_use]
+    pub fn watchdog_callback<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(WatchEvent) + Send + 'static,
+    {
+        self.opts.watch = Some(Watch::Callback(Box::new(callback)));
+        self
+    }
+}
+
+enum Msg {
+    Changed,
+    Stop,
+}
+
+pub(crate) struct Watchdog {
+    // The watch is removed when this is dropped.
+    _watcher: RecommendedWatcher,
+    tx: mpsc::Sender<Msg>,
+    // Whether the guard is in its target. The thread holds the lock while it
+    // checks the target and reports any problem, so once a guard has set this
+    // to false, it won't get any more events.
+    active: Arc<Mutex<bool>>,
+    // The thread returns the watch when it stops, so it can be restarted.
+    thread: Option<thread::JoinHandle<Watch>>,
+}
+
+impl Watchdog {
+    // Starts watching the anchored directory, which must be the current
+    // directory.
+    pub(crate) fn start(
+        watch: Watch,
+        anchor: &Anchor,
+        span: events::Span,
+    ) -> Result<Watchdog, PushdError> {
+        let path = anchor.path();
+        let err = |e| PushdError::Watch {
+            path: path.to_owned(),
+            source: e,
+        };
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = {
+            let tx = tx.clone();
+            notify::recommended_watcher(move |_: notify::Result<notify::Event>| {
+                // The thread only exits after the watcher is dropped, so this
+                // can't fail.
+                let _ = tx.send(Msg::Changed);
+            })
+            .map_err(err)?
+        };
+        watcher
+            .watch(path, RecursiveMode::NonRecursive)
+            .map_err(err)?;
+
+        let active = Arc::new(Mutex::new(true));
+        let thread = {
+            let anchor = anchor.duplicate();
+            let active = Arc::clone(&active);
+            thread::spawn(move || run(&rx, &anchor, watch, &active, &span))
+        };
+        Ok(Watchdog {
+            _watcher: watcher,
+            tx,
+            active,
+            thread: Some(thread),
+        })
+    }
+
+    // Tells the watchdog whether the guard is in its target. It doesn't check
+    // anything while the guard is popped.
+    pub(crate) fn set_active(&self, active: bool) {
+        *self.active.lock().unwrap_or_else(PoisonError::into_inner) = active;
+    }
+
+    // Stops this watchdog and starts a new one for the anchored directory
+    // with the same watch. If a callback panicked, the watch is gone, so this
+    // returns `None`.
+    pub(crate) fn retarget(
+        mut self,
+        anchor: &Anchor,
+        span: events::Span,
+    ) -> Result<Option<Watchdog>, PushdError> {
+        let _ = self.tx.send(Msg::Stop);
+        let Some(Ok(watch)) = self.thread.take().map(thread::JoinHandle::join) else {
+            return Ok(None);
+        };
+        Watchdog::start(watch, anchor, span).map(Some)
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let _ = self.tx.send(Msg::Stop);
+        if let Some(thread) = self.thread.take() {
+            // If the callback panicked, the thread has already exited.
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(
+    rx: &mpsc::Receiver<Msg>,
+    anchor: &Anchor,
+    mut watch: Watch,
+    active: &Mutex<bool>,
+    span: &events::Span,
+) -> Watch {
+    let mut last = None;
+    loop {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Msg::Stop) | Err(RecvTimeoutError::Disconnected) => return watch,
+            Ok(Msg::Changed) | Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        let active = active.lock().unwrap_or_else(PoisonError::into_inner);
+        let event = if *active { check(anchor) } else { None };
+        if let Some(event) = event.as_ref().filter(|e| last.as_ref() != Some(*e)) {
+            match &mut watch {
+                Watch::Log => events::watchdog(span, event),
+                Watch::Callback(callback) => callback(event.clone()),
+            }
+        }
+        last = event;
+    }
+}
+
+fn check(anchor: &Anchor) -> Option<WatchEvent> {
+    let path = anchor.path();
+    let status = status::dir_status(anchor);
+    if !status.exists {
+        return Some(WatchEvent::Removed {
+            path: path.to_owned(),
+        });
+    }
+    #[cfg(unix)]
+    if let Some(Ok((id, _))) = anchor.identity() {
+        use std::os::unix::fs::MetadataExt;
+
+        if !std::fs::metadata(path).is_ok_and(|md| (md.dev(), md.ino()) == id) {
+            return Some(WatchEvent::Moved {
+                path: path.to_owned(),
+            });
+        }
+    }
+    if !status.is_current {
+        return Some(WatchEvent::CwdChanged {
+            expected: path.to_owned(),
+            found: current_dir().ok()?,
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pushd;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs, time::Instant};
+    use tempfile::tempdir;
+
+    // Waits for the watchdog to report `n` events.
+    fn wait_for(events: &Mutex<Vec<WatchEvent>>, n: usize) -> Vec<WatchEvent> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(10) {
+            let events = events.lock().unwrap();
+            if events.len() >= n {
+                return events.clone();
+            }
+            drop(events);
+            thread::sleep(Duration::from_millis(10));
+        }
+        events.lock().unwrap().clone()
+    }
+
+    #[test]
+    #[serial]
+    fn watchdog() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let td = tempdir()?;
+        let target = fs::canonicalize(td.path())?.join("target");
+        fs::create_dir(&target)?;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut pd = {
+            let events = Arc::clone(&events);
+            Pushd::builder(&target)
+                .watchdog_callback(move |e| events.lock().unwrap().push(e))
+                .push()?
+        };
+
+        env::set_current_dir(td.path())?;
+        let seen = wait_for(&events, 1);
+        env::set_current_dir(&target)?;
+        assert_eq!(
+            seen,
+            [WatchEvent::CwdChanged {
+                expected: target.clone(),
+                found: env::current_dir()?.parent().ok_or("no parent")?.to_owned(),
+            }],
+        );
+
+        // Nothing is reported while the guard is popped.
+        pd.pop()?;
+        thread::sleep(POLL_INTERVAL * 2);
+        assert_eq!(events.lock().unwrap().len(), 1);
+        pd.reenter()?;
+
+        // Windows does not allow deleting the current directory.
+        #[cfg(not(target_os = "windows"))]
+        {
+            fs::remove_dir(&target)?;
+            let seen = wait_for(&events, 2);
+            assert_eq!(seen[1], WatchEvent::Removed { path: target });
+        }
+        pd.forget();
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,703 @@
+// This module contains Windows-specific functionality.
+
+use crate::{builder::Options, events, DropBehavior, Panicking, Pushd, PushdBuilder, PushdError};
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    fs::OpenOptions,
+    io, mem,
+    os::windows::{ffi::OsStrExt, fs::OpenOptionsExt, io::AsRawHandle},
+    path::{Component, Path, PathBuf, Prefix},
+    ptr,
+};
+use windows_sys::Win32::{
+    Foundation::NO_ERROR,
+    NetworkManagement::WNet::{
+        WNetAddConnection2W, WNetCancelConnection2W, CONNECT_TEMPORARY, NETRESOURCEW,
+        RESOURCETYPE_DISK,
+    },
+    Storage::FileSystem::{
+        DefineDosDeviceW, GetFileInformationByHandle, GetLogicalDrives, BY_HANDLE_FILE_INFORMATION,
+        DDD_EXACT_MATCH_ON_REMOVE, DDD_REMOVE_DEFINITION, FILE_FLAG_BACKUP_SEMANTICS,
+    },
+    System::Environment::{GetEnvironmentVariableW, SetEnvironmentVariableW},
+};
+
+// `SetCurrentDirectoryW` only accepts paths shorter than `MAX_PATH`, including
+// the trailing backslash that it adds and the terminating NUL.
+const MAX_CHDIR_LEN: usize = 258;
+
+impl Pushd<Panicking> {
+    /// Constructs a new `Pushd` struct that maps `path` to a free drive letter,
+    /// like the `subst` command, and changes to the root of that drive. This
+    /// is the equivalent of using [`PushdBuilder::subst_to_drive`].
+    ///
+    /// This is only available on Windows.
+    ///
+    /// # Errors
+    ///
+    /// This returns the same errors as [`Pushd::new`], and an error if there
+    /// is no free drive letter or if the drive cannot be mapped.
+    #[track_caller]
+    pub fn new_subst<P: AsRef<Path>>(path: P) -> Result<Pushd<Panicking>, PushdError> {
+        PushdBuilder::new(path.as_ref().to_owned())
+            .subst_to_drive()
+            .push()
+    }
+}
+
+impl<B: DropBehavior> PushdBuilder<B> {
+    /// If the target is a UNC path like `\\server\share\dir`, maps the share
+    /// to a free drive letter and changes to the equivalent path on that
+    /// drive, like `Z:\dir`. This is what `pushd` does in `cmd.exe`, and it
+    /// helps with tools that cannot handle a UNC path as the current
+    /// directory.
+    ///
+    /// The drive is unmapped when the guard is popped or dropped. If the
+    /// target is not a UNC path, this option does nothing.
+    ///
+    /// This is only available on Windows.
+    #[must_use]
+    pub fn map_unc_to_drive(mut self) -> Self {
+        self.opts.drives.unc = true;
+        self
+    }
+
+    /// Maps the target to a free drive letter, like the `subst` command, and
+    /// changes to the root of that drive, like `Z:\`. This keeps the current
+    /// directory short, which helps with tools that cannot handle paths
+    /// longer than `MAX_PATH`.
+    ///
+    /// The drive is removed when the guard is popped or dropped. Like a drive
+    /// created by `subst`, it is visible to every process for the current
+    /// user while it exists. If this is combined with
+    /// [`PushdBuilder::map_unc_to_drive`] and the target is a UNC path, the
+    /// share is mapped as a network drive instead.
+    ///
+    /// This is only available on Windows.
+    #[must_use]
+    pub fn subst_to_drive(mut self) -> Self {
+        self.opts.drives.subst = true;
+        self
+    }
+
+    /// Makes the resulting [`Pushd`] keep track of the current directory on
+    /// each drive the way `cmd.exe` does, and restore it when the guard is
+    /// popped or dropped.
+    ///
+    /// Windows has a separate current directory for each drive, which is used
+    /// to resolve drive-relative paths like `D:` or `D:dir`. These are stored
+    /// in hidden environment variables like `=D:`, which `cmd.exe` updates
+    /// when it changes directories, but changing directories with
+    /// [`std::env::set_current_dir`] does not. With this option, the guard
+    /// saves all of these variables when it changes directories, sets the
+    /// variables for the drives of the original directory and the target, and
+    /// puts the saved values back when it changes back. This way, a
+    /// drive-relative path in a child process means the same thing that it
+    /// would mean in `cmd.exe`.
+    ///
+    /// This is only available on Windows.
+    #[must_use]
+    pub fn restore_drive_dirs(mut self) -> Self {
+        self.opts.drives.dirs = true;
+        self
+    }
+}
+
+// Which kinds of drive mapping the options turn on.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct DriveOptions {
+    pub(crate) unc: bool,
+    pub(crate) subst: bool,
+    pub(crate) dirs: bool,
+}
+
+// The values of the `=A:` through `=Z:` environment variables, which hold the
+// current directory for each drive, from before a guard changed them.
+pub(crate) struct DriveDirs {
+    saved: Vec<(Vec<u16>, Option<Vec<u16>>)>,
+}
+
+impl DriveDirs {
+    // Saves the current directory variables and then sets them for the drives
+    // of `from` and `to`, if the options ask for it.
+    pub(crate) fn save(opts: &Options, from: &Path, to: &Path) -> Option<DriveDirs> {
+        if !opts.drives.dirs {
+            return None;
+        }
+        let saved = (b'A'..=b'Z')
+            .map(|l| {
+                let name = drive_var(char::from(l));
+                let value = get_var(&name);
+                (name, value)
+            })
+            .collect();
+        for dir in [from, to] {
+            if let Some(letter) = drive_letter(dir) {
+                let value = strip_verbatim_disk(dir).unwrap_or_else(|| dir.as_os_str().to_owned());
+                set_var(&drive_var(letter), Some(&wide(&value)));
+            }
+        }
+        Some(DriveDirs { saved })
+    }
+
+    pub(crate) fn restore(self) {
+        for (name, value) in self.saved {
+            set_var(&name, value.as_deref());
+        }
+    }
+}
+
+fn drive_var(letter: char) -> Vec<u16> {
+    wide(OsStr::new(&format!("={letter}:")))
+}
+
+fn drive_letter(path: &Path) -> Option<char> {
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return None;
+    };
+    match prefix.kind() {
+        Prefix::Disk(l) | Prefix::VerbatimDisk(l) => Some(char::from(l.to_ascii_uppercase())),
+        _ => None,
+    }
+}
+
+// Returns the NUL-terminated value of an environment variable, or `None` if it
+// is not set.
+fn get_var(name: &[u16]) -> Option<Vec<u16>> {
+    // SAFETY: `name` is a NUL-terminated UTF-16 string. With an empty buffer,
+    // this returns the size of buffer needed, including the NUL.
+    let len = unsafe { GetEnvironmentVariableW(name.as_ptr(), ptr::null_mut(), 0) };
+    if len == 0 {
+        return None;
+    }
+    let mut value = vec![0; len as usize];
+    // SAFETY: `value` has room for `len` UTF-16 code units.
+    let written = unsafe { GetEnvironmentVariableW(name.as_ptr(), value.as_mut_ptr(), len) };
+    // The variable could have changed between the two calls.
+    if written == 0 || written >= len {
+        return None;
+    }
+    value.truncate(written as usize + 1);
+    Some(value)
+}
+
+// Sets an environment variable, or removes it if `value` is `None`. Both the
+// name and value must be NUL-terminated.
+fn set_var(name: &[u16], value: Option<&[u16]>) {
+    let value = value.map_or(ptr::null(), <[u16]>::as_ptr);
+    // SAFETY: `name` and `value` are NUL-terminated UTF-16 strings, or
+    // `value` is null, which removes the variable. This can only fail if
+    // we're out of memory, in which case there's nothing useful to do.
+    unsafe { SetEnvironmentVariableW(name.as_ptr(), value) };
+}
+
+// Resolves a drive-relative path like `D:` or `D:dir` against the current
+// directory for that drive, so that the guard's target is an absolute path.
+// Any other path is returned as is.
+pub(crate) fn resolve_drive_relative(path: PathBuf) -> PathBuf {
+    let mut components = path.components();
+    let relative = matches!(
+        components.next(),
+        Some(Component::Prefix(prefix)) if matches!(prefix.kind(), Prefix::Disk(_)),
+    ) && !matches!(components.next(), Some(Component::RootDir));
+    if !relative {
+        return path;
+    }
+    // `absolute` uses `GetFullPathNameW`, which resolves the path against the
+    // drive's current directory.
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+// A network share or directory that we mapped to a drive letter. The mapping
+// is removed when this is dropped, if it hasn't been removed already.
+pub(crate) struct MappedDrive {
+    local: Vec<u16>,
+    letter: char,
+    path: PathBuf,
+    kind: DriveKind,
+    mapped: bool,
+}
+
+enum DriveKind {
+    Network,
+    // The target that the drive was defined as, which must be passed again
+    // to remove the definition.
+    Subst(Vec<u16>),
+}
+
+impl MappedDrive {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn unmap(&mut self) -> Result<(), PushdError> {
+        if !self.mapped {
+            return Ok(());
+        }
+
+        match &self.kind {
+            DriveKind::Network => {
+                // SAFETY: `self.local` is a NUL-terminated UTF-16 string.
+                let err = unsafe { WNetCancelConnection2W(self.local.as_ptr(), 0, 1) };
+                if err != NO_ERROR {
+                    return Err(PushdError::UnmapNetworkDrive {
+                        drive: self.letter,
+                        source: os_error(err),
+                    });
+                }
+            }
+            DriveKind::Subst(target) => {
+                // SAFETY: `self.local` and `target` are NUL-terminated UTF-16
+                // strings.
+                let ok = unsafe {
+                    DefineDosDeviceW(
+                        DDD_REMOVE_DEFINITION | DDD_EXACT_MATCH_ON_REMOVE,
+                        self.local.as_ptr(),
+                        target.as_ptr(),
+                    )
+                };
+                if ok == 0 {
+                    return Err(PushdError::UnsubstDrive {
+                        drive: self.letter,
+                        source: io::Error::last_os_error(),
+                    });
+                }
+            }
+        }
+        self.mapped = false;
+        Ok(())
+    }
+}
+
+impl Drop for MappedDrive {
+    fn drop(&mut self) {
+        if let Err(e) = self.unmap() {
+            events::unmap_failed(&e);
+        }
+    }
+}
+
+// Maps the target to a drive letter if the options ask for it. The `path` must
+// be absolute.
+pub(crate) fn map_drive(opts: &Options, path: &Path) -> Result<Option<MappedDrive>, PushdError> {
+    if opts.drives.unc {
+        if let Some(mapped) = map_unc(path)? {
+            return Ok(Some(mapped));
+        }
+    }
+    if opts.drives.subst {
+        return subst(path).map(Some);
+    }
+    Ok(None)
+}
+
+// Maps the share for the given path to a free drive letter. If the path is not
+// a UNC path, this returns `None`.
+fn map_unc(path: &Path) -> Result<Option<MappedDrive>, PushdError> {
+    let Some((remote, rest)) = split_unc(path) else {
+        return Ok(None);
+    };
+
+    let letter = free_drive_letter().ok_or_else(|| PushdError::NoFreeDriveLetter {
+        path: path.to_owned(),
+    })?;
+    let mut local = wide(OsStr::new(&format!("{letter}:")));
+    let mut remote = wide(&remote);
+
+    let resource = NETRESOURCEW {
+        dwScope: 0,
+        dwType: RESOURCETYPE_DISK,
+        dwDisplayType: 0,
+        dwUsage: 0,
+        lpLocalName: local.as_mut_ptr(),
+        lpRemoteName: remote.as_mut_ptr(),
+        lpComment: ptr::null_mut(),
+        lpProvider: ptr::null_mut(),
+    };
+    // SAFETY: All of the strings in `resource` are NUL-terminated UTF-16
+    // strings which outlive this call. Null pointers for the password and
+    // user name mean that the current user's credentials are used.
+    let err = unsafe {
+        WNetAddConnection2W(
+            &raw const resource,
+            ptr::null(),
+            ptr::null(),
+            CONNECT_TEMPORARY,
+        )
+    };
+    if err != NO_ERROR {
+        return Err(PushdError::MapNetworkDrive {
+            path: path.to_owned(),
+            source: os_error(err),
+        });
+    }
+
+    let mut mapped = PathBuf::from(format!(r"{letter}:\"));
+    mapped.push(rest);
+    Ok(Some(MappedDrive {
+        local,
+        letter,
+        path: mapped,
+        kind: DriveKind::Network,
+        mapped: true,
+    }))
+}
+
+// Defines a free drive letter as the given directory, like `subst` does.
+fn subst(path: &Path) -> Result<MappedDrive, PushdError> {
+    let letter = free_drive_letter().ok_or_else(|| PushdError::NoFreeDriveLetter {
+        path: path.to_owned(),
+    })?;
+    let local = wide(OsStr::new(&format!("{letter}:")));
+    // `DefineDosDeviceW` doesn't accept the `\\?\` prefix for a path on a
+    // drive, but it doesn't need it for long paths either.
+    let target = match strip_verbatim_disk(path) {
+        Some(stripped) => wide(&stripped),
+        None => wide(path.as_os_str()),
+    };
+    // SAFETY: `local` and `target` are NUL-terminated UTF-16 strings.
+    if unsafe { DefineDosDeviceW(0, local.as_ptr(), target.as_ptr()) } == 0 {
+        return Err(PushdError::SubstDrive {
+            path: path.to_owned(),
+            source: io::Error::last_os_error(),
+        });
+    }
+
+    Ok(MappedDrive {
+        local,
+        letter,
+        path: PathBuf::from(format!(r"{letter}:\")),
+        kind: DriveKind::Subst(target),
+        mapped: true,
+    })
+}
+
+// Turns a path like `\\?\C:\a\b` into `C:\a\b`, or returns `None` if the path
+// doesn't start with a verbatim disk prefix.
+fn strip_verbatim_disk(path: &Path) -> Option<OsString> {
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return None;
+    };
+    let Prefix::VerbatimDisk(letter) = prefix.kind() else {
+        return None;
+    };
+    let mut stripped = OsString::from(format!("{}:", char::from(letter)));
+    for c in components {
+        if let Component::Normal(name) = c {
+            stripped.push(r"\");
+            stripped.push(name);
+        }
+    }
+    if path.components().count() <= 2 {
+        stripped.push(r"\");
+    }
+    Some(stripped)
+}
+
+// Splits a UNC path into the `\\server\share` part and the rest of the path.
+fn split_unc(path: &Path) -> Option<(OsString, PathBuf)> {
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return None;
+    };
+    let (Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share)) = prefix.kind() else {
+        return None;
+    };
+
+    let mut remote = OsString::from(r"\\");
+    remote.push(server);
+    remote.push(r"\");
+    remote.push(share);
+
+    let rest = components
+        .filter(|c| !matches!(c, Component::RootDir))
+        .collect();
+    Some((remote, rest))
+}
+
+fn free_drive_letter() -> Option<char> {
+    // SAFETY: `GetLogicalDrives` takes no arguments and just returns a
+    // bitmask.
+    let used = unsafe { GetLogicalDrives() };
+    // Like `cmd.exe`, we start from the end of the alphabet. We never use A,
+    // B, or C.
+    (b'D'..=b'Z')
+        .rev()
+        .find(|l| used & (1 << (l - b'A')) == 0)
+        .map(char::from)
+}
+
+fn wide(s: &OsStr) -> Vec<u16> {
+    s.encode_wide().chain([0]).collect()
+}
+
+fn os_error(err: u32) -> io::Error {
+    #[allow(clippy::cast_possible_wrap)]
+    io::Error::from_raw_os_error(err as i32)
+}
+
+// Removes the `\\?\` prefix from a path when the path means the same thing
+// without it, so `\\?\C:\dir` becomes `C:\dir` and `\\?\UNC\server\share`
+// becomes `\\server\share`. Paths which need the prefix, because they are too
+// long or contain components that Windows would otherwise mangle, are returned
+// as is. This is the same approach as the `dunce` crate.
+pub(crate) fn simplify(path: PathBuf) -> PathBuf {
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return path;
+    };
+    let mut simple = match prefix.kind() {
+        Prefix::VerbatimDisk(letter) => OsString::from(format!("{}:", char::from(letter))),
+        Prefix::VerbatimUNC(server, share) => {
+            let mut s = OsString::from(r"\\");
+            s.push(server);
+            s.push(r"\");
+            s.push(share);
+            s
+        }
+        _ => return path,
+    };
+
+    for c in components {
+        match c {
+            Component::RootDir => {}
+            Component::Normal(name) if is_safe_component(name) => {
+                simple.push(r"\");
+                simple.push(name);
+            }
+            _ => return path,
+        }
+    }
+    // A bare drive like `C:` means the current directory on that drive, so
+    // we need to keep the root.
+    if matches!(prefix.kind(), Prefix::VerbatimDisk(_)) && path.components().count() <= 2 {
+        simple.push(r"\");
+    }
+
+    if simple.encode_wide().count() > MAX_CHDIR_LEN {
+        return path;
+    }
+    PathBuf::from(simple)
+}
+
+// Returns true if a path component means the same thing with or without the
+// `\\?\` prefix.
+fn is_safe_component(name: &OsStr) -> bool {
+    let Some(name) = name.to_str() else {
+        return false;
+    };
+    if name.ends_with(['.', ' '])
+        || name
+            .chars()
+            .any(|c| c.is_ascii_control() || r#"<>:"/\|?*"#.contains(c))
+    {
+        return false;
+    }
+
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    let reserved = ["CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$"];
+    if reserved.iter().any(|r| stem.eq_ignore_ascii_case(r)) {
+        return false;
+    }
+    // `COM1` through `COM9` and `LPT1` through `LPT9`.
+    let bytes = stem.as_bytes();
+    !(bytes.len() == 4
+        && (bytes[..3].eq_ignore_ascii_case(b"COM") || bytes[..3].eq_ignore_ascii_case(b"LPT"))
+        && (b'1'..=b'9').contains(&bytes[3]))
+}
+
+// Returns true if the two paths are the same when compared the way Windows
+// compares them, ignoring case, the difference between `/` and `\`, and a
+// `\\?\` prefix that doesn't change what the path means.
+pub(crate) fn same_path(a: &Path, b: &Path) -> bool {
+    fn fold(path: &Path) -> Option<Vec<String>> {
+        simplify(path.to_owned())
+            .components()
+            .map(|c| c.as_os_str().to_str().map(str::to_lowercase))
+            .collect()
+    }
+    fold(a).is_some_and(|a| Some(a) == fold(b))
+}
+
+// Returns whether the two paths refer to the same file or directory, by
+// comparing their volume serial numbers and file indexes. This sees through
+// 8.3 short names, symlinks, and junctions. It returns `None` if either path
+// cannot be opened.
+pub(crate) fn same_file(a: &Path, b: &Path) -> Option<bool> {
+    Some(file_id(a)? == file_id(b)?)
+}
+
+fn file_id(path: &Path) -> Option<(u32, u32, u32)> {
+    // Opening a directory requires `FILE_FLAG_BACKUP_SEMANTICS`. No access
+    // rights are needed to read its file information.
+    let file = OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(path)
+        .ok()?;
+    // SAFETY: `BY_HANDLE_FILE_INFORMATION` is plain data, so all zeroes is a
+    // valid value.
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { mem::zeroed() };
+    // SAFETY: The handle is open for as long as `file` is alive, and `info`
+    // is a valid place for the call to write to.
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), ptr::addr_of_mut!(info)) } == 0 {
+        return None;
+    }
+    Some((
+        info.dwVolumeSerialNumber,
+        info.nFileIndexHigh,
+        info.nFileIndexLow,
+    ))
+}
+
+// Adds the `\\?\` prefix to a path that is too long to pass to
+// `SetCurrentDirectoryW` without it. Shorter paths, and paths that already
+// have a verbatim or device prefix, are returned as is.
+pub(crate) fn extend_long_path(path: &Path) -> Cow<'_, Path> {
+    if path.as_os_str().encode_wide().count() <= MAX_CHDIR_LEN {
+        return Cow::Borrowed(path);
+    }
+    // The `\\?\` prefix turns off all normalization, so the path must be
+    // absolute with no `.` or `..` components.
+    let Ok(abs) = std::path::absolute(path) else {
+        return Cow::Borrowed(path);
+    };
+    let Some(Component::Prefix(prefix)) = abs.components().next() else {
+        return Cow::Borrowed(path);
+    };
+
+    let mut extended = match prefix.kind() {
+        Prefix::Disk(letter) => OsString::from(format!(r"\\?\{}:", char::from(letter))),
+        Prefix::UNC(server, share) => {
+            let mut s = OsString::from(r"\\?\UNC\");
+            s.push(server);
+            s.push(r"\");
+            s.push(share);
+            s
+        }
+        _ => return Cow::Borrowed(path),
+    };
+    for c in abs.components() {
+        if let Component::Normal(name) = c {
+            extended.push(r"\");
+            extended.push(name);
+        }
+    }
+    Cow::Owned(PathBuf::from(extended))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_paths() {
+        let same = [
+            (r"C:\Users\Me", r"c:\users\me"),
+            (r"C:\Users\Me", "C:/Users/Me/"),
+            (r"\\?\C:\Users\Me", r"C:\users\me"),
+            (r"\\server\share\Dir", r"\\?\UNC\SERVER\share\dir"),
+        ];
+        for (a, b) in same {
+            assert!(same_path(Path::new(a), Path::new(b)), "{a} and {b}");
+        }
+        assert!(!same_path(Path::new(r"C:\a"), Path::new(r"D:\a")));
+        assert!(!same_path(Path::new(r"C:\a\b"), Path::new(r"C:\a")));
+    }
+
+    #[test]
+    fn split_unc_paths() {
+        assert_eq!(
+            split_unc(Path::new(r"\\server\share\a\b")),
+            Some((OsString::from(r"\\server\share"), PathBuf::from(r"a\b"))),
+        );
+        assert_eq!(
+            split_unc(Path::new(r"\\?\UNC\server\share\a")),
+            Some((OsString::from(r"\\server\share"), PathBuf::from("a"))),
+        );
+        assert_eq!(split_unc(Path::new(r"C:\a\b")), None);
+        assert_eq!(split_unc(Path::new(r"a\b")), None);
+    }
+
+    #[test]
+    fn drive_relative_paths() {
+        for path in ["C:", "C:dir"] {
+            let resolved = resolve_drive_relative(PathBuf::from(path));
+            assert!(resolved.is_absolute(), "{path}");
+            assert_eq!(drive_letter(&resolved), Some('C'), "{path}");
+        }
+        assert_eq!(
+            resolve_drive_relative(PathBuf::from(r"C:\dir")),
+            Path::new(r"C:\dir"),
+        );
+        assert_eq!(
+            resolve_drive_relative(PathBuf::from("dir")),
+            Path::new("dir")
+        );
+    }
+
+    #[test]
+    fn strip_verbatim_disk_paths() {
+        assert_eq!(
+            strip_verbatim_disk(Path::new(r"\\?\C:\a\b")),
+            Some(OsString::from(r"C:\a\b")),
+        );
+        assert_eq!(
+            strip_verbatim_disk(Path::new(r"\\?\C:\")),
+            Some(OsString::from(r"C:\")),
+        );
+        assert_eq!(strip_verbatim_disk(Path::new(r"C:\a")), None);
+        assert_eq!(
+            strip_verbatim_disk(Path::new(r"\\?\UNC\server\share")),
+            None
+        );
+    }
+
+    #[test]
+    fn simplify_paths() {
+        let cases = [
+            (r"\\?\C:\a\b", r"C:\a\b"),
+            (r"\\?\C:\", r"C:\"),
+            (r"\\?\UNC\server\share\a", r"\\server\share\a"),
+            (r"C:\a\b", r"C:\a\b"),
+            (r"\\?\C:\a\con", r"\\?\C:\a\con"),
+            (r"\\?\C:\a\COM1.txt", r"\\?\C:\a\COM1.txt"),
+            (r"\\?\C:\a.", r"\\?\C:\a."),
+            (r"\\?\C:\a\..\b", r"\\?\C:\a\..\b"),
+            (r"\\?\C:\a/b", r"\\?\C:\a/b"),
+            (r"\\.\pipe\foo", r"\\.\pipe\foo"),
+        ];
+        for (path, expect) in cases {
+            assert_eq!(simplify(PathBuf::from(path)), Path::new(expect), "{path}");
+        }
+
+        let long = format!(r"\\?\C:\{}", "a".repeat(300));
+        assert_eq!(simplify(PathBuf::from(&long)), Path::new(&long));
+    }
+
+    #[test]
+    fn extend_long_paths() {
+        let short = Path::new(r"C:\a\b");
+        assert!(matches!(extend_long_path(short), Cow::Borrowed(p) if p == short));
+
+        let name = "a".repeat(200);
+        let long = format!(r"C:\{name}\{name}");
+        assert_eq!(
+            extend_long_path(Path::new(&long)),
+            Path::new(&format!(r"\\?\C:\{name}\{name}")),
+        );
+        assert_eq!(
+            extend_long_path(Path::new(&format!(r"C:\{name}\x\..\{name}"))),
+            Path::new(&format!(r"\\?\C:\{name}\{name}")),
+        );
+        assert_eq!(
+            extend_long_path(Path::new(&format!(r"\\server\share\{name}\{name}"))),
+            Path::new(&format!(r"\\?\UNC\server\share\{name}\{name}")),
+        );
+
+        let verbatim = format!(r"\\?\C:\{name}\{name}");
+        assert_eq!(extend_long_path(Path::new(&verbatim)), Path::new(&verbatim));
+    }
+}
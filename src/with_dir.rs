@@ -0,0 +1,124 @@
+// This module contains `with_dir_checked`, which runs a closure in a
+// directory and returns any failure to change back as a value.
+
+use crate::{DropPolicy, Pushd, PushdError};
+use std::path::Path;
+use thiserror::Error;
+
+/// The error returned by [`with_dir_checked`].
+#[derive(Debug, Error)]
+pub enum WithDirError<T, E> {
+    /// The current directory could not be changed to the target, so the
+    /// closure was not called.
+    #[error(transparent)]
+    Enter(PushdError),
+    /// The closure returned an error, and the original directory was
+    /// restored.
+    #[error(transparent)]
+    Body(E),
+    /// The closure was called, but the original directory could not be
+    /// restored afterwards. `outcome` is what the closure returned.
+    #[error("Could not change back to the original directory after running the closure: {error}")]
+    RestoreFailed {
+        outcome: Result<T, E>,
+        #[source]
+        error: PushdError,
+    },
+}
+
+/// Changes to `path`, calls `f`, and then changes back to the original
+/// directory. Unlike a [`Pushd`], this never panics when it cannot change
+/// back. Instead, the failure is returned as a
+/// [`WithDirError::RestoreFailed`] error, along with whatever `f` returned.
+///
+/// This is useful for programs built with `panic = "abort"`, which cannot
+/// recover from a panic while a guard is dropped.
+///
+/// If `f` panics, the guard changes back while the panic unwinds, and logs a
+/// warning instead of panicking if it cannot.
+///
+/// # Examples
+///
+/// ```
+/// use pushd::WithDirError;
+/// use std::{env, fs};
+///
+/// let res = pushd::with_dir_checked(env::temp_dir(), || fs::read_dir(".").map(Iterator::count));
+/// match res {
+///     Ok(count) => println!("{count} entries"),
+///     Err(WithDirError::RestoreFailed { outcome, error }) => {
+///         eprintln!("stuck in the temp dir after {outcome:?}: {error}");
+///     }
+///     Err(e) => eprintln!("{e}"),
+/// }
+/// ```
+///
+/// # Errors
+///
+/// This returns a [`WithDirError::Enter`] error with the same errors as
+/// [`Pushd::new`] if it cannot change to `path`, a [`WithDirError::Body`]
+/// error if `f` returns an error, and a [`WithDirError::RestoreFailed`] error
+/// if it cannot change back.
+#[track_caller]
+pub fn with_dir_checked<P, F, T, E>(path: P, f: F) -> Result<T, WithDirError<T, E>>
+where
+    P: AsRef<Path>,
+    F: FnOnce() -> Result<T, E>,
+{
+    let mut pd = Pushd::builder(path)
+        .no_panic()
+        .drop_policy(DropPolicy::Ignore)
+        .push()
+        .map_err(WithDirError::Enter)?;
+    let outcome = f();
+    if let Err(error) = pd.pop() {
+        return Err(WithDirError::RestoreFailed { outcome, error });
+    }
+    outcome.map_err(WithDirError::Body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::{env, error::Error as StdError, fs, io};
+    use tempfile::tempdir;
+
+    #[test]
+    #[serial]
+    fn with_dir_checked() -> Result<(), Box<dyn StdError>> {
+        env::set_current_dir(env::var("CARGO_MANIFEST_DIR")?)?;
+
+        let cwd = env::current_dir()?;
+        let inside = super::with_dir_checked("src", env::current_dir)?;
+        assert_eq!(inside, cwd.join("src"));
+        assert_eq!(env::current_dir()?, cwd);
+
+        let res = super::with_dir_checked("src", || Err::<(), _>(io::Error::other("body")));
+        assert!(matches!(res, Err(WithDirError::Body(e)) if e.to_string() == "body"));
+        assert_eq!(env::current_dir()?, cwd);
+
+        let res = super::with_dir_checked("no-such-dir", || Ok::<_, io::Error>(()));
+        assert!(matches!(
+            res,
+            Err(WithDirError::Enter(PushdError::TargetNotFound { .. })),
+        ));
+
+        let td = tempdir()?;
+        let orig = td.path().join("orig");
+        fs::create_dir(&orig)?;
+        env::set_current_dir(&orig)?;
+        let res = super::with_dir_checked(&cwd, || {
+            fs::remove_dir(&orig)?;
+            Ok::<_, io::Error>(3)
+        });
+        env::set_current_dir(&cwd)?;
+        let Err(WithDirError::RestoreFailed { outcome, error }) = res else {
+            return Err("changed back to a directory that was removed".into());
+        };
+        assert_eq!(outcome?, 3);
+        assert!(matches!(error, PushdError::RestoreFailed { .. }));
+
+        Ok(())
+    }
+}